@@ -1,11 +1,16 @@
-﻿use super::{args::Meta, AddRows, Args};
-use crate::{common_cpu::Cpu, get_static, ByteOf, LaunchError, QueueAlloc, SchemeError, Unsigned};
+﻿use super::{args::Meta, AddRows, Args, BoundsCheck};
+use crate::{
+    args_not_support, common_cpu::Cpu, get_static, type_not_support, ByteOf, LaunchError,
+    QueueAlloc, SchemeError, Unsigned,
+};
 use digit_layout::types as ty;
 use half::f16;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use std::ops::AddAssign;
 
-pub struct Operator;
+pub struct Operator {
+    bounds: BoundsCheck,
+}
 
 impl AddRows<Cpu> for Operator {}
 
@@ -15,7 +20,9 @@ impl crate::Operator for Operator {
     type Args = Args<Cpu>;
 
     fn new(_node: &Self::TopoNode) -> Self {
-        Self
+        Self {
+            bounds: BoundsCheck::None,
+        }
     }
 
     fn scheme(
@@ -68,6 +75,22 @@ impl crate::Operator for Operator {
             bsi msi nss kss
         }
 
+        if self.bounds == BoundsCheck::Checked {
+            macro_rules! check {
+                ($i:ty) => {
+                    check_idx::<$i>(*idx_base, [b, m], [bsi, msi], k)
+                };
+            }
+            match dt_idx {
+                ty::U32 => check!(u32)?,
+                ty::U64 => check!(u64)?,
+                _ => Err(type_not_support(format!(
+                    "add_rows: index type {dt_idx} is not supported"
+                )))?,
+            }
+        }
+        let skip = self.bounds == BoundsCheck::Skip;
+
         let dst = *dst_base as usize;
         let src = *src_base as usize;
         let idx = *idx_base as usize;
@@ -89,6 +112,7 @@ impl crate::Operator for Operator {
                         nss,
                         bsi,
                         msi,
+                        skip,
                     }
                     .calculate(bm)
                 })
@@ -102,12 +126,45 @@ impl crate::Operator for Operator {
             (ty::F16, ty::U64) => calculate!(f16, u64),
             (ty::F32, ty::U64) => calculate!(f32, u64),
             (ty::F64, ty::U64) => calculate!(f64, u64),
-            (_, _) => todo!(),
+            (_, _) => Err(type_not_support(format!(
+                "add_rows: {dt} with index type {dt_idx} is not supported"
+            )))?,
         }
         Ok(())
     }
 }
 
+impl Operator {
+    /// 设置索引越界时的处理方式，默认为 [`BoundsCheck::None`]。
+    pub fn set_bounds_check(&mut self, bounds: BoundsCheck) {
+        self.bounds = bounds
+    }
+}
+
+/// 检查全部索引在 `[0, k)` 内，返回第一个越界的索引。
+fn check_idx<I: Unsigned + Copy>(
+    idx: *const u8,
+    [b, m]: [usize; 2],
+    [bsi, msi]: [isize; 2],
+    k: usize,
+) -> Result<(), LaunchError> {
+    for i in 0..b {
+        for j in 0..m {
+            let val = unsafe {
+                *idx.byte_offset(i as isize * bsi + j as isize * msi)
+                    .cast::<I>()
+            }
+            .val();
+            if val >= k {
+                Err(args_not_support(format!(
+                    "add_rows: idx[{i}, {j}] = {val} out of range [0, {k})"
+                )))?
+            }
+        }
+    }
+    Ok(())
+}
+
 struct Scheme<T, I> {
     dst: *mut T,
     src: *const T,
@@ -122,6 +179,8 @@ struct Scheme<T, I> {
     nss: isize,
     bsi: isize,
     msi: isize,
+    /// 跳过越界的索引。
+    skip: bool,
 }
 
 impl<T, I> Scheme<T, I>
@@ -134,6 +193,9 @@ where
         let m = (bm % self.m) as isize;
         let dst = unsafe { self.dst.byte_offset(b * self.bsd + m * self.msd) };
         let idx = unsafe { *self.idx.byte_offset(b * self.bsi + m * self.msi) }.val();
+        if idx >= self.k && self.skip {
+            return;
+        }
         assert!(idx < self.k);
 
         let src = unsafe { self.src.byte_offset(idx as isize * self.kss) };
@@ -146,3 +208,90 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Args, BoundsCheck, Operator};
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        Operator as _, TensorLayout,
+    };
+    use digit_layout::types as ty;
+
+    #[test]
+    fn test_strided_dst() {
+        const B: usize = 2;
+        const M: usize = 3;
+        let n = 4;
+        let k = 5;
+
+        let src = (0..k * n).map(|i| i as f64).collect::<Vec<_>>();
+        let idx: [u32; B * M] = [4, 0, 2, 1, 1, 3];
+        // 目标为 [B, 2M, n] 的缓冲区，只写入奇数行
+        let mut dst = vec![-1.0f64; B * 2 * M * n];
+
+        let unit = size_of::<f64>() as isize;
+        let n_ = n as isize;
+        Operator::new(&Cpu)
+            .launch(
+                &Args::<Cpu> {
+                    dst_layout: TensorLayout::new(
+                        ty::F64,
+                        &[B, M, n],
+                        &[2 * M as isize * n_ * unit, 2 * n_ * unit, unit],
+                    ),
+                    dst_base: dst[n..].as_mut_ptr().cast(),
+                    src_layout: TensorLayout::new_contiguous(ty::F64, &[k, n]),
+                    src_base: src.as_ptr().cast(),
+                    idx_layout: TensorLayout::new_contiguous(ty::U32, &[B, M]),
+                    idx_base: idx.as_ptr().cast(),
+                },
+                &mut [],
+                &ThisThread,
+            )
+            .unwrap();
+
+        for (r, row) in dst.chunks(n).enumerate() {
+            if r % 2 == 0 {
+                assert!(row.iter().all(|&x| x == -1.));
+            } else {
+                let i = idx[r / 2] as usize;
+                let expected = src[i * n..][..n].iter().map(|x| x - 1.);
+                assert!(row.iter().copied().eq(expected));
+            }
+        }
+    }
+
+    #[test]
+    fn test_bounds_check() {
+        use crate::LaunchErrorKind;
+
+        let (b, m, n, k) = (1, 3, 2, 4);
+        let src = (0..k * n).map(|i| i as f64).collect::<Vec<_>>();
+        let idx: [u32; 3] = [1, 4, 9];
+        let mut dst = vec![0.0f64; b * m * n];
+
+        let mut op = Operator::new(&Cpu);
+        op.set_bounds_check(BoundsCheck::Checked);
+        let args = Args::<Cpu> {
+            dst_layout: TensorLayout::new_contiguous(ty::F64, &[b, m, n]),
+            dst_base: dst.as_mut_ptr().cast(),
+            src_layout: TensorLayout::new_contiguous(ty::F64, &[k, n]),
+            src_base: src.as_ptr().cast(),
+            idx_layout: TensorLayout::new_contiguous(ty::U32, &[b, m]),
+            idx_base: idx.as_ptr().cast(),
+        };
+        let err = op.launch(&args, &mut [], &ThisThread).unwrap_err();
+        assert_eq!(
+            err.kind,
+            LaunchErrorKind::Scheme(crate::SchemeErrorKind::ArgsNotSupport)
+        );
+        assert_eq!(err.info, "add_rows: idx[0, 1] = 4 out of range [0, 4)");
+        // 检查失败时不写入
+        assert!(dst.iter().all(|&x| x == 0.));
+
+        op.set_bounds_check(BoundsCheck::Skip);
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+        assert_eq!(dst, [2., 3., 0., 0., 0., 0.]);
+    }
+}
@@ -1,6 +1,7 @@
-use super::{AddRows, Args};
+use super::{AddRows, Args, BoundsCheck};
 use crate::{
     add_rows::args::Meta,
+    args_not_support,
     cuda::{dt_name, Gpu, Handle, ModuleBox},
     get_static, strides_not_support,
     utils::gcd,
@@ -16,6 +17,7 @@ use std::{
 pub struct Operator {
     handle: Arc<Handle>,
     max_threads_block: usize,
+    bounds: BoundsCheck,
     schemes: Mutex<LruCache<SchemeKey, Scheme>>,
 }
 
@@ -30,6 +32,7 @@ impl crate::Operator for Operator {
         Self {
             handle: node.0.clone(),
             max_threads_block: node.0.device().block_limit().max_threads,
+            bounds: BoundsCheck::None,
             schemes: node.0.scheme_cache(SchemeDiversity::Low),
         }
     }
@@ -59,7 +62,14 @@ impl crate::Operator for Operator {
     where
         QA: QueueAlloc<Hardware = Self::Hardware>,
     {
-        let Meta { batch: b, n, m, .. } = args.meta()?;
+        let Meta {
+            batch: b, n, m, k, ..
+        } = args.meta()?;
+        if self.bounds == BoundsCheck::Checked {
+            Err(args_not_support(
+                "cuda: add_rows can not check indices before launch",
+            ))?;
+        }
 
         let Args {
             dst_layout,
@@ -80,7 +90,7 @@ impl crate::Operator for Operator {
         };
 
         get_static! {
-            b   n   m
+            b   n   m   k
             bsd msd nsd
             bsi msi nss kss
         }
@@ -98,7 +108,8 @@ impl crate::Operator for Operator {
         let &[bsi] = cast(&[bsi], unit_idx as usize).as_slice() else {
             todo!()
         };
-        let params = cuda::params![dst_base, src_base, idx_base, bsd, msd, kss, bsi];
+        let k = k as u32;
+        let params = cuda::params![dst_base, src_base, idx_base, k, bsd, msd, kss, bsi];
         let block = gcd(self.max_threads_block, n);
         let dimx = n.div_ceil(block);
         let key = SchemeKey {
@@ -122,6 +133,16 @@ impl crate::Operator for Operator {
     }
 }
 
+impl Operator {
+    /// 设置索引越界时的处理方式，默认为 [`BoundsCheck::None`]。
+    ///
+    /// 核函数总是跳过越界的索引，`None` 与 `Skip` 相同。
+    /// 不支持在发射前检查设备上的索引，`Checked` 在发射时报错。
+    pub fn set_bounds_check(&mut self, bounds: BoundsCheck) {
+        self.bounds = bounds
+    }
+}
+
 #[derive(Clone)]
 struct Scheme {
     module: Arc<ModuleBox>,
@@ -149,12 +170,13 @@ extern "C" __global__ void {name}(
     {type_name} *__restrict__ dst,
     {type_name} const *__restrict__ src,
     unsigned int const *__restrict__ idx,
+    unsigned int const k,
     int const stride_d_b,
     int const stride_d_m,
     int const stride_s,
     int const stride_i
 ){{
-    add_rows(dst, src, idx,stride_d_b, stride_d_m, stride_s, stride_i);
+    add_rows(dst, src, idx, k, stride_d_b, stride_d_m, stride_s, stride_i);
 }}"#
             )
         });
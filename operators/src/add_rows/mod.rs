@@ -13,3 +13,15 @@ mod args;
 pub use args::Args;
 
 crate::op_trait!(AddRows);
+
+/// 索引越界时的处理方式。
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum BoundsCheck {
+    /// 不预先检查，越界时 panic。
+    #[default]
+    None,
+    /// 发射前检查全部索引，存在越界时返回错误并报告第一个越界的索引。
+    Checked,
+    /// 跳过越界的索引，对应的行保持不变。
+    Skip,
+}
@@ -0,0 +1,91 @@
+use crate::{
+    rank_mismatch, shape_not_support, type_not_support,
+    utils::{dim_distinct, rank_error, type_distinct},
+    ConstPtr, Hardware, MutPtr, PairLayout, SchemeError, TensorLayout,
+};
+use digit_layout::{types as ty, DigitLayout};
+use std::{
+    iter::zip,
+    ptr::{null, null_mut},
+};
+
+pub struct Args<H: Hardware> {
+    /// 超出容差的元素数量，u32 类型的标量。
+    pub count_layout: TensorLayout,
+    pub count_base: MutPtr<H>,
+    pub a_layout: TensorLayout,
+    pub a_base: ConstPtr<H>,
+    /// 相对容差以 `b` 为基准。
+    pub b_layout: TensorLayout,
+    pub b_base: ConstPtr<H>,
+    pub atol: f32,
+    pub rtol: f32,
+}
+
+pub(super) struct Meta {
+    pub dt: DigitLayout,
+}
+
+impl<H: Hardware> Args<H> {
+    pub fn new_null(a_layout: TensorLayout, b_layout: TensorLayout, atol: f32, rtol: f32) -> Self {
+        Self {
+            count_layout: TensorLayout::new(ty::U32, &[], &[]),
+            count_base: null_mut(),
+            a_layout,
+            a_base: null(),
+            b_layout,
+            b_base: null(),
+            atol,
+            rtol,
+        }
+    }
+
+    pub(super) fn meta(&self) -> Result<Meta, SchemeError> {
+        let Self {
+            count_layout: count,
+            a_layout: a,
+            b_layout: b,
+            ..
+        } = self;
+
+        if count.dt() != ty::U32 {
+            return Err(type_not_support(format!(
+                "count must be u32, but {} is given",
+                count.dt()
+            )));
+        }
+        if count.ndim() != 0 {
+            return Err(rank_error("count", 0, count.ndim()));
+        }
+
+        let dt = type_distinct(&[a.dt(), b.dt()])?;
+        use digit_layout::LayoutContent::Real;
+        if !matches!(dt.decode(), Real { exponent: 1.., .. }) {
+            return Err(type_not_support(format!(
+                "data type {dt} is not supported, must be floating-point numbers",
+            )));
+        }
+        if a.ndim() != b.ndim() {
+            return Err(rank_mismatch(format!(
+                "a.ndim = {}, b.ndim = {}",
+                a.ndim(),
+                b.ndim(),
+            )));
+        }
+        for (&da, &db) in zip(a.shape(), b.shape()) {
+            dim_distinct(&[da, db])?;
+        }
+
+        Ok(Meta { dt })
+    }
+
+    /// 合并连续维度后的执行方案，依次为 `a` 和 `b` 的步长。统计与顺序无关，按 a 的步长排序以便合并。
+    pub(super) fn scheme(&self) -> Result<PairLayout, SchemeError> {
+        let scheme = PairLayout::new(&self.a_layout, &self.b_layout)?;
+        let [sa, sb] = &scheme.strides;
+        if zip(sa, sb).any(|(&sa, &sb)| sa == 0 && sb == 0) {
+            return Err(shape_not_support("all_close: both inputs are broadcast"));
+        }
+        Ok(scheme)
+    }
+}
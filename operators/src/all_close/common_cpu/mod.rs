@@ -0,0 +1,135 @@
+use super::{args::Meta, AllClose, Args};
+use crate::{
+    common_cpu::Cpu, shape_not_support, type_not_support, ByteOf, LaunchError, PairLayout,
+    QueueAlloc, SchemeError,
+};
+use half::{bf16, f16};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::cmp::Ordering;
+
+pub struct Operator;
+
+impl AllClose<Cpu> for Operator {}
+
+impl crate::Operator for Operator {
+    type Hardware = Cpu;
+    type TopoNode = Cpu;
+    type Args = Args<Cpu>;
+
+    #[inline]
+    fn new(_node: &Self::TopoNode) -> Self {
+        Self
+    }
+
+    fn scheme(
+        &mut self,
+        args: &Self::Args,
+        _max_workspace_size: usize,
+    ) -> Result<usize, SchemeError> {
+        let _meta = args.meta()?;
+        Ok(0)
+    }
+
+    fn launch<QA>(
+        &self,
+        args: &Self::Args,
+        _workspace: &mut [ByteOf<Self::Hardware>],
+        _queue_alloc: &QA,
+    ) -> Result<(), LaunchError>
+    where
+        QA: QueueAlloc<Hardware = Self::Hardware>,
+    {
+        let Meta { dt } = args.meta()?;
+        let scheme = args.scheme()?;
+
+        use digit_layout::types as ty;
+        let count = match dt {
+            ty::F16 => count::<f16>(&scheme, args, f16::to_f64),
+            ty::BF16 => count::<bf16>(&scheme, args, bf16::to_f64),
+            ty::F32 => count::<f32>(&scheme, args, |x| x as _),
+            ty::F64 => count::<f64>(&scheme, args, |x| x),
+            _ => Err(type_not_support(format!("cpu: all_close of {dt}")))?,
+        };
+        let count = u32::try_from(count).map_err(|_| {
+            shape_not_support(format!(
+                "all_close: {count} mismatches overflow the u32 count"
+            ))
+        })?;
+        unsafe { *args.count_base.cast::<u32>() = count };
+        Ok(())
+    }
+}
+
+fn count<T: Copy>(scheme: &PairLayout, args: &Args<Cpu>, load: impl Sync + Fn(T) -> f64) -> usize {
+    let atol = args.atol as f64;
+    let rtol = args.rtol as f64;
+    let a = args.a_base as isize;
+    let b = args.b_base as isize;
+    (0..scheme.count())
+        .into_par_iter()
+        .filter(|&i| {
+            let [sa, sb] = scheme.offsets(i);
+            let a = load(unsafe { ((a + sa) as *const T).read() });
+            let b = load(unsafe { ((b + sb) as *const T).read() });
+            // 无法比较（存在 NaN）时计为超出容差
+            !(a - b)
+                .abs()
+                .partial_cmp(&(atol + rtol * b.abs()))
+                .is_some_and(Ordering::is_le)
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Args, Operator};
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        Operator as _, TensorLayout,
+    };
+    use digit_layout::types as ty;
+
+    #[test]
+    fn test_count() {
+        const M: usize = 64;
+        const N: usize = 300;
+
+        let a = (0..M * N)
+            .map(|i| (i as f32 * 0.37).sin())
+            .collect::<Vec<_>>();
+        let mut b = a.clone();
+        let mut count = u32::MAX;
+
+        let mut op = Operator::new(&Cpu);
+        let layout = TensorLayout::new_contiguous(ty::F32, &[M, N]);
+        let mut args = Args::<Cpu>::new_null(layout.clone(), layout, 1e-5, 1e-3);
+        args.count_base = (&raw mut count).cast();
+        args.a_base = a.as_ptr().cast();
+        args.b_base = b.as_ptr().cast();
+        op.scheme(&args, 0).unwrap();
+
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+        assert_eq!(count, 0);
+
+        // 容差内的扰动不计数
+        for x in b.iter_mut().step_by(7) {
+            *x *= 1. + 5e-4;
+        }
+        // 超出容差的扰动和 NaN 计数
+        b[3] += 0.1;
+        b[1000] -= 0.1;
+        b[M * N - 1] = f32::NAN;
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+        assert_eq!(count, 3);
+
+        // 转置视图逐元素对应时结果相同
+        let unit = size_of::<f32>() as isize;
+        args.a_layout = TensorLayout::new(ty::F32, &[N, M], &[unit, N as isize * unit]);
+        args.b_layout = args.a_layout.clone();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+        assert_eq!(count, 3);
+
+        args.count_layout = TensorLayout::new(ty::U64, &[], &[]);
+        assert!(op.scheme(&args, 0).is_err());
+    }
+}
@@ -0,0 +1,13 @@
+//! count = Σ [!(|a - b| <= atol + rtol · |b|)]
+//!
+//! 统计两个张量中超出容差的元素数量，用于在设备上直接校验计算结果。NaN 总是计为超出容差。
+
+#[cfg(any(use_cpu, test))]
+pub mod common_cpu;
+#[cfg(use_cl)]
+pub mod opencl;
+
+mod args;
+pub use args::Args;
+
+crate::op_trait!(AllClose);
@@ -0,0 +1,54 @@
+#define CL_TARGET_OPENCL_VERSION 200
+#pragma OPENCL EXTENSION cl_khr_fp16 : enable
+
+#ifndef Tval
+#define Tval float
+#endif
+
+// 每个工作组处理最内维一行中的一段，组内规约后原子累加到计数
+// outer 依次存放外层各维的长度、a 步长和 b 步长，从外到内
+kernel void all_close(
+    global unsigned int *count,
+    global Tval const *a,
+    global Tval const *b,
+    global long const *outer,
+    int const outer_ndim,
+    long const cols,
+    long const chunk,
+    long const a_stride_col,
+    long const b_stride_col,
+    float const atol,
+    float const rtol) {
+
+    long const
+        g_idx = get_group_id(0),
+        chunks = (cols + chunk - 1) / chunk,
+        begin = g_idx % chunks * chunk,
+        end = min(begin + chunk, cols);
+
+    long rem = g_idx / chunks, a_offset = 0, b_offset = 0;
+    for (int k = outer_ndim - 1; k >= 0; --k) {
+        long const d = outer[3 * k], i = rem % d;
+        a_offset += i * outer[3 * k + 1];
+        b_offset += i * outer[3 * k + 2];
+        rem /= d;
+    }
+
+    long const
+        l_idx = get_local_id(0),
+        l_len = get_local_size(0);
+
+    unsigned int n = 0;
+    for (long c = begin + l_idx; c < end; c += l_len) {
+        float const
+            x = (float) a[a_offset + c * a_stride_col],
+            y = (float) b[b_offset + c * b_stride_col];
+        // 写成否定形式使 NaN 计为超出容差
+        if (!(fabs(x - y) <= atol + rtol * fabs(y)))
+            ++n;
+    }
+    n = work_group_reduce_add(n);
+
+    if (l_idx == 0 && n != 0)
+        atomic_add((volatile global unsigned int *) count, n);
+}
@@ -0,0 +1,291 @@
+use super::{args::Meta, AllClose, Args};
+use crate::{
+    execution_failed,
+    opencl::{write_from_slice, ClDevice, CodeGen, KernelCache, CL2_0},
+    shape_not_support, type_not_support, ByteOf, LaunchError, PairLayout, QueueAlloc,
+    SchemeDiversity::Low as LowDiversity,
+    SchemeError,
+};
+use clrt::{
+    bindings::{cl_int, cl_long},
+    Context,
+};
+use digit_layout::{types as Ty, DigitLayout};
+use lru::LruCache;
+use std::{iter::zip, slice::from_raw_parts_mut, sync::Mutex};
+
+pub struct Operator {
+    ctx: Context,
+    max_group_size: usize,
+    schemes: Mutex<LruCache<SchemeKey, KernelCache>>,
+}
+
+impl AllClose<ClDevice> for Operator {}
+
+impl crate::Operator for Operator {
+    type Hardware = ClDevice;
+    type TopoNode = ClDevice;
+    type Args = Args<ClDevice>;
+
+    fn new(node: &Self::TopoNode) -> Self {
+        let ctx = node.context().clone();
+        let max_group_size = ctx
+            .devices()
+            .iter()
+            .map(|d| d.max_group_size())
+            .min()
+            .unwrap()
+            / 2;
+        Self {
+            ctx,
+            max_group_size,
+            schemes: node.new_cache(LowDiversity),
+        }
+    }
+
+    fn scheme(
+        &mut self,
+        args: &Self::Args,
+        _max_workspace_size: usize,
+    ) -> Result<usize, SchemeError> {
+        let Meta { dt } = args.meta()?;
+        self.cache_kernel(dt)?;
+        Ok(0)
+    }
+
+    fn launch<QA>(
+        &self,
+        args: &Self::Args,
+        _workspace: &mut [ByteOf<Self::Hardware>],
+        queue_alloc: &QA,
+    ) -> Result<(), LaunchError>
+    where
+        QA: QueueAlloc<Hardware = Self::Hardware>,
+    {
+        let Meta { dt } = args.meta()?;
+        let PairLayout {
+            shape,
+            strides: [a_strides, b_strides],
+        } = args.scheme()?;
+
+        let unit = dt.nbytes() as isize;
+        // 核函数以 u32 计数
+        let total = shape.iter().product::<usize>();
+        if u32::try_from(total).is_err() {
+            Err(shape_not_support(format!(
+                "opencl: all_close of {total} elements overflows the u32 count"
+            )))?
+        }
+        // 最内维分段由工作组内并行遍历，其余各维和段号展开为工作组编号
+        let (cols, sac, sbc) = match shape.len() {
+            0 => (1, 0, 0),
+            n => (shape[n - 1], a_strides[n - 1], b_strides[n - 1]),
+        };
+        let outer_ndim = shape.len().saturating_sub(1);
+        let rows = shape[..outer_ndim].iter().product::<usize>();
+        // 末尾的 0 用于清零计数
+        let mut host = Vec::<cl_long>::with_capacity(outer_ndim * 3 + 1);
+        for ((&d, &sa), &sb) in zip(zip(&shape[..outer_ndim], &a_strides), &b_strides) {
+            host.extend([d as cl_long, (sa / unit) as cl_long, (sb / unit) as cl_long])
+        }
+        host.push(0);
+
+        let key = self.cache_kernel(dt)?;
+        let mut all_close = self
+            .schemes
+            .lock()
+            .unwrap()
+            .get(&key)
+            .unwrap()
+            .take_guard("all_close")
+            .ok_or_else(|| execution_failed("opencl: kernel all_close not found"))?;
+
+        let queue = queue_alloc.queue();
+        let mut outer = queue_alloc.alloc(size_of_val(&host[..]));
+        write_from_slice(&mut outer, &host, queue);
+        let zero = &outer[outer_ndim * 3 * size_of::<cl_long>()..][..size_of::<u32>()];
+        let count = unsafe { from_raw_parts_mut(args.count_base, size_of::<u32>()) };
+        queue.memcpy(count, zero, None);
+
+        if total != 0 {
+            let group_size = cols.min(self.max_group_size);
+            let chunk = group_size * ITEMS_PER_THREAD;
+            let groups = rows * cols.div_ceil(chunk);
+            all_close
+                .set_arg(0, &args.count_base)
+                .set_arg(1, &args.a_base)
+                .set_arg(2, &args.b_base)
+                .set_arg(3, outer.as_ptr())
+                .set_arg(4, outer_ndim as cl_int)
+                .set_arg(5, cols as cl_long)
+                .set_arg(6, chunk as cl_long)
+                .set_arg(7, (sac / unit) as cl_long)
+                .set_arg(8, (sbc / unit) as cl_long)
+                .set_arg(9, args.atol)
+                .set_arg(10, args.rtol)
+                .launch(&[0], &[groups * group_size], &[group_size], queue, None);
+        }
+
+        queue_alloc.free(outer);
+        Ok(())
+    }
+}
+
+impl Operator {
+    fn cache_kernel(&self, dt: DigitLayout) -> Result<SchemeKey, SchemeError> {
+        let tval = match dt {
+            Ty::F32 => "float",
+            Ty::F16 => "half",
+            _ => Err(type_not_support(format!(
+                "opencl: all_close does not support {dt}"
+            )))?,
+        };
+        let key = SchemeKey { dt };
+        self.schemes.lock().unwrap().get_or_insert(key, || {
+            let src = CodeGen::new(include_str!("all_close.cl"))
+                .define("Tval", tval)
+                .to_string();
+            KernelCache::new(&self.ctx, &src, CL2_0)
+        });
+        Ok(key)
+    }
+}
+
+/// 每个工作项在一段中最多处理的元素数。
+const ITEMS_PER_THREAD: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+struct SchemeKey {
+    dt: DigitLayout,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Args, Operator};
+    use crate::{
+        opencl::{read_to_vec, write_from_slice, ClDevice},
+        Operator as _, TensorLayout,
+    };
+    use clrt::Platform;
+    use digit_layout::types as ty;
+
+    #[test]
+    fn test_count() {
+        const M: usize = 64;
+        const N: usize = 300;
+
+        let a = (0..M * N)
+            .map(|i| (i as f32 * 0.37).sin())
+            .collect::<Vec<_>>();
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let mut cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+
+                let mut a_svm = context.malloc::<f32>(M * N);
+                let mut b_svm = context.malloc::<f32>(M * N);
+                let mut count_svm = context.malloc::<u32>(1);
+                for (svm, perturb) in [(&mut a_svm, false), (&mut b_svm, true)] {
+                    let mut map = queue.map_mut(svm, false);
+                    let ([], mem, []) = (unsafe { map.align_to_mut::<f32>() }) else {
+                        panic!()
+                    };
+                    mem.copy_from_slice(&a);
+                    if perturb {
+                        mem[3] += 0.1;
+                        mem[1000] -= 0.1;
+                        mem[M * N - 1] = f32::NAN;
+                    }
+                    queue.unmap(map);
+                }
+
+                let layout = TensorLayout::new_contiguous(ty::F32, &[M, N]);
+                let mut args = Args::<ClDevice>::new_null(layout.clone(), layout, 1e-5, 1e-3);
+                args.count_base = count_svm.as_mut_ptr().cast();
+                args.a_base = a_svm.as_ptr().cast();
+                cl_op.scheme(&args, 0).unwrap();
+
+                let mut count = |b_base| {
+                    args.b_base = b_base;
+                    cl_op.launch(&args, &mut [], &queue).unwrap();
+                    queue.finish();
+                    let map = queue.map(&mut count_svm);
+                    let ([], count, []) = (unsafe { map.align_to::<u32>() }) else {
+                        panic!()
+                    };
+                    let count = count[0];
+                    queue.unmap(map);
+                    count
+                };
+                assert_eq!(count(a_svm.as_ptr().cast()), 0);
+                assert_eq!(count(b_svm.as_ptr().cast()), 3);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rank() {
+        // a 连续，b 按相反的维度顺序存放，布局无法合并，保持 3 维
+        const SHAPE: [usize; 3] = [6, 7, 50];
+        const LEN: usize = SHAPE[0] * SHAPE[1] * SHAPE[2];
+
+        let a = (0..LEN)
+            .map(|i| (i as f32 * 0.37).sin())
+            .collect::<Vec<_>>();
+        let mut b = vec![0.0f32; LEN];
+        for i in 0..SHAPE[0] {
+            for j in 0..SHAPE[1] {
+                for k in 0..SHAPE[2] {
+                    b[(k * SHAPE[1] + j) * SHAPE[0] + i] = a[(i * SHAPE[1] + j) * SHAPE[2] + k];
+                }
+            }
+        }
+        b[5] += 0.1;
+        b[LEN - 2] = f32::NAN;
+
+        let unit = size_of::<f32>() as isize;
+        let a_layout = TensorLayout::new_contiguous(ty::F32, &SHAPE);
+        let b_layout = TensorLayout::new(
+            ty::F32,
+            &SHAPE,
+            &[
+                unit,
+                unit * SHAPE[0] as isize,
+                unit * (SHAPE[0] * SHAPE[1]) as isize,
+            ],
+        );
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let mut cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+
+                let mut a_svm = context.malloc::<f32>(LEN);
+                let mut b_svm = context.malloc::<f32>(LEN);
+                let mut count_svm = context.malloc::<u32>(1);
+                write_from_slice(&mut a_svm, &a, &queue);
+                write_from_slice(&mut b_svm, &b, &queue);
+
+                let mut args =
+                    Args::<ClDevice>::new_null(a_layout.clone(), b_layout.clone(), 1e-5, 1e-3);
+                args.count_base = count_svm.as_mut_ptr().cast();
+                args.a_base = a_svm.as_ptr().cast();
+                args.b_base = b_svm.as_ptr().cast();
+                cl_op.scheme(&args, 0).unwrap();
+
+                // 重复发射，计数每次从 0 开始
+                for _ in 0..2 {
+                    cl_op.launch(&args, &mut [], &queue).unwrap();
+                    assert_eq!(read_to_vec::<u32>(&mut count_svm, &queue), [2]);
+                }
+            }
+        }
+    }
+}
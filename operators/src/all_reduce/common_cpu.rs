@@ -162,6 +162,8 @@ fn test_comm() {
                             dst_base: buf.as_mut_ptr().cast(),
                             src_layout: TensorLayout::new_contiguous(U32, &[8]),
                             src_base: buf.as_ptr().cast(),
+                            scale: 1.,
+                            bias: 0.,
                         },
                         op: ReduceOp::Sum,
                     },
@@ -4,7 +4,7 @@ use crate::{
     SchemeError, TensorLayout, Workspace, WorkspaceCollector,
 };
 use ndarray_layout::ArrayLayout;
-use std::marker::PhantomData;
+use std::{marker::PhantomData, ptr::null_mut};
 
 pub struct Operator<Hardware, MatMul, Softmax, Rearrange> {
     mat_mul: MatMul,
@@ -202,6 +202,8 @@ where
                         dst_base: q_base,
                         src_layout: args.q_layout.clone(),
                         src_base: args.q_base,
+                        scale: 1.,
+                        bias: 0.,
                     },
                     workspace,
                     queue_alloc,
@@ -239,8 +241,11 @@ where
         self.softmax.launch(
             &fuesd_softmax::Args {
                 att_mask: *mask,
+                window: None,
                 att_layout: att_softmax,
                 att_base: att_buf.as_mut_ptr(),
+                out_layout: None,
+                out_base: null_mut(),
             },
             workspace,
             queue_alloc,
@@ -268,6 +273,8 @@ where
                     dst_base: *o_base,
                     src_layout: q_layout.clone(),
                     src_base: q_base,
+                    scale: 1.,
+                    bias: 0.,
                 },
                 workspace,
                 queue_alloc,
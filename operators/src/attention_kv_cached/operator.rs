@@ -146,6 +146,8 @@ where
                 dst_base: unsafe { k_cache_base.byte_add(k_cat.offset() as _) },
                 src_layout: k_layout.clone(),
                 src_base: *k_base,
+                scale: 1.,
+                bias: 0.,
             },
             workspace,
             queue_alloc,
@@ -156,6 +158,8 @@ where
                 dst_base: unsafe { v_cache_base.byte_add(k_cat.offset() as _) },
                 src_layout: v_layout.clone(),
                 src_base: *v_base,
+                scale: 1.,
+                bias: 0.,
             },
             workspace,
             queue_alloc,
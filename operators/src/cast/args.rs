@@ -0,0 +1,149 @@
+use crate::{
+    rank_mismatch, shape_not_support, type_not_support, utils::dim_distinct, ConstPtr, Hardware,
+    MutPtr, PairLayout, SchemeError, TensorLayout,
+};
+use digit_layout::{types as ty, DigitLayout};
+use std::{
+    iter::zip,
+    ptr::{null, null_mut},
+};
+
+pub struct Args<H: Hardware> {
+    pub y_layout: TensorLayout,
+    pub y_base: MutPtr<H>,
+    pub x_layout: TensorLayout,
+    pub x_base: ConstPtr<H>,
+    pub rounding: Rounding,
+}
+
+/// 窄化时的舍入方式。
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum Rounding {
+    /// 舍入到最近偶数。
+    #[default]
+    NearestEven,
+    /// 随机舍入，只支持 f32 到 bf16。
+    ///
+    /// 按到两侧 bf16 的距离成比例地随机选择其一，结果的期望等于原值。
+    /// 随机数由 `seed` 和元素在输出中的逻辑位置决定，相同的种子总是得到相同的结果。
+    Stochastic { seed: u64 },
+}
+
+pub(super) struct Meta {
+    pub dt_y: DigitLayout,
+    pub dt_x: DigitLayout,
+}
+
+impl<H: Hardware> Args<H> {
+    pub fn new_null(y_layout: TensorLayout, x_layout: TensorLayout) -> Self {
+        Self {
+            y_layout,
+            y_base: null_mut(),
+            x_layout,
+            x_base: null(),
+            rounding: Rounding::NearestEven,
+        }
+    }
+
+    pub(super) fn meta(&self) -> Result<Meta, SchemeError> {
+        let Self {
+            y_layout: y,
+            x_layout: x,
+            ..
+        } = self;
+
+        let is_float = |dt: DigitLayout| {
+            use digit_layout::LayoutContent::Real;
+            matches!(dt.decode(), Real { exponent: 1.., .. })
+        };
+        for dt in [y.dt(), x.dt()] {
+            if !is_float(dt) {
+                return Err(type_not_support(format!(
+                    "data type {dt} is not supported, must be floating-point numbers",
+                )));
+            }
+        }
+        if y.ndim() != x.ndim() {
+            return Err(rank_mismatch(format!(
+                "y.ndim = {}, x.ndim = {}",
+                y.ndim(),
+                x.ndim(),
+            )));
+        }
+        for (&dy, &dx) in zip(y.shape(), x.shape()) {
+            dim_distinct(&[dy, dx])?;
+        }
+        if matches!(self.rounding, Rounding::Stochastic { .. })
+            && (x.dt() != ty::F32 || y.dt() != ty::BF16)
+        {
+            return Err(type_not_support(format!(
+                "stochastic rounding from {} to {}, only f32 to bf16 is supported",
+                x.dt(),
+                y.dt(),
+            )));
+        }
+
+        Ok(Meta {
+            dt_y: y.dt(),
+            dt_x: x.dt(),
+        })
+    }
+
+    /// 合并连续维度后的执行方案，依次为 `y` 和 `x` 的步长。
+    pub(super) fn scheme(&self) -> Result<PairLayout, SchemeError> {
+        let scheme = PairLayout::new(&self.y_layout, &self.x_layout)?;
+        if scheme.strides[0].contains(&0) {
+            return Err(shape_not_support("cast: output cannot be broadcast"));
+        }
+        Ok(scheme)
+    }
+}
+
+#[test]
+fn test_scheme() {
+    use digit_layout::types::{F16, F32};
+
+    // [2, 3, 4] 的 f32 转置为 [4, 3, 2] 后输出到连续的 f16
+    let args = Args::<crate::common_cpu::Cpu>::new_null(
+        TensorLayout::new_contiguous(F16, &[4, 3, 2]),
+        TensorLayout::new(F32, &[4, 3, 2], &[4, 16, 48]),
+    );
+    args.meta().unwrap();
+    let scheme = args.scheme().unwrap();
+    assert_eq!(scheme.shape, [4, 3, 2]);
+    assert_eq!(scheme.strides, [[12, 4, 2], [4, 16, 48]]);
+    assert_eq!(scheme.count(), 24);
+
+    // 两侧都连续时合并为一维
+    let args = Args::<crate::common_cpu::Cpu>::new_null(
+        TensorLayout::new_contiguous(F16, &[4, 1, 6]),
+        TensorLayout::new_contiguous(F32, &[4, 1, 6]),
+    );
+    let scheme = args.scheme().unwrap();
+    assert_eq!(scheme.shape, [24]);
+    assert_eq!(scheme.strides, [[2], [4]]);
+
+    // 输出不能广播
+    let args = Args::<crate::common_cpu::Cpu>::new_null(
+        TensorLayout::new(F16, &[4, 6], &[0, 2]),
+        TensorLayout::new_contiguous(F32, &[4, 6]),
+    );
+    assert!(args.scheme().is_err());
+
+    // 整数类型不支持
+    let args = Args::<crate::common_cpu::Cpu>::new_null(
+        TensorLayout::new_contiguous(F16, &[4]),
+        TensorLayout::new_contiguous(digit_layout::types::U32, &[4]),
+    );
+    assert!(args.meta().is_err());
+
+    // 随机舍入只支持 f32 到 bf16
+    let mut args = Args::<crate::common_cpu::Cpu>::new_null(
+        TensorLayout::new_contiguous(F16, &[4]),
+        TensorLayout::new_contiguous(F32, &[4]),
+    );
+    args.rounding = Rounding::Stochastic { seed: 0 };
+    assert!(args.meta().is_err());
+    args.y_layout = TensorLayout::new_contiguous(digit_layout::types::BF16, &[4]);
+    assert!(args.meta().is_ok());
+}
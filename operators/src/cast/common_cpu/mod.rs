@@ -0,0 +1,278 @@
+use super::{args::Meta, Args, Cast, Rounding};
+use crate::{
+    common_cpu::{Cpu, Float},
+    type_not_support, ByteOf, LaunchError, PairLayout, QueueAlloc, SchemeError,
+};
+use half::{bf16, f16};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+pub struct Operator;
+
+impl Cast<Cpu> for Operator {}
+
+impl crate::Operator for Operator {
+    type Hardware = Cpu;
+    type TopoNode = Cpu;
+    type Args = Args<Cpu>;
+
+    #[inline]
+    fn new(_node: &Self::TopoNode) -> Self {
+        Self
+    }
+
+    fn scheme(
+        &mut self,
+        args: &Self::Args,
+        _max_workspace_size: usize,
+    ) -> Result<usize, SchemeError> {
+        let _meta = args.meta()?;
+        Ok(0)
+    }
+
+    fn launch<QA>(
+        &self,
+        args: &Self::Args,
+        _workspace: &mut [ByteOf<Self::Hardware>],
+        _queue_alloc: &QA,
+    ) -> Result<(), LaunchError>
+    where
+        QA: QueueAlloc<Hardware = Self::Hardware>,
+    {
+        let Meta { dt_y, dt_x } = args.meta()?;
+        let scheme = args.scheme()?;
+        // meta 已保证随机舍入时为 f32 到 bf16
+        if let Rounding::Stochastic { seed } = args.rounding {
+            calculate_stochastic(&scheme, args, seed);
+            return Ok(());
+        }
+
+        use digit_layout::types as ty;
+        macro_rules! calculate {
+            ($x:ty => $( $dt:ident: $y:ty ),+) => {
+                match dt_y {
+                    $( ty::$dt => calculate::<$y, $x>(&scheme, args), )+
+                    _ => Err(type_not_support(format!("cpu: cast to {dt_y}")))?,
+                }
+            };
+        }
+        macro_rules! dispatch {
+            ($( $dt:ident: $x:ty ),+) => {
+                match dt_x {
+                    $( ty::$dt => calculate!($x => F16: f16, BF16: bf16, F32: f32, F64: f64), )+
+                    _ => Err(type_not_support(format!("cpu: cast from {dt_x}")))?,
+                }
+            };
+        }
+        dispatch!(F16: f16, BF16: bf16, F32: f32, F64: f64);
+        Ok(())
+    }
+}
+
+fn calculate<Y: Float, X: Float>(scheme: &PairLayout, args: &Args<Cpu>) {
+    for_each(scheme, args, |_, y, x| unsafe {
+        *(y as *mut Y) = Y::from_f64((x as *const X).read().to_f64())
+    })
+}
+
+fn calculate_stochastic(scheme: &PairLayout, args: &Args<Cpu>, seed: u64) {
+    for_each(scheme, args, |i, y, x| unsafe {
+        *(y as *mut bf16) = stochastic_bf16((x as *const f32).read(), random(seed, i as _))
+    })
+}
+
+/// 并行遍历所有元素，传入元素在执行方案中的逻辑下标和两侧的地址。
+fn for_each(scheme: &PairLayout, args: &Args<Cpu>, f: impl Fn(usize, isize, isize) + Sync) {
+    let y = args.y_base as isize;
+    let x = args.x_base as isize;
+    (0..scheme.count()).into_par_iter().for_each(|i| {
+        let [sy, sx] = scheme.offsets(i);
+        f(i, y + sy, x + sx)
+    })
+}
+
+/// 由种子和元素下标生成 32 位随机数（SplitMix64），与 OpenCL 实现一致。
+fn random(seed: u64, i: u64) -> u32 {
+    let mut z = seed.wrapping_add(i.wrapping_add(1).wrapping_mul(0x9e3779b97f4a7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    (z ^ (z >> 31)) as u32
+}
+
+/// 在将被截断的低 16 位上加一个均匀的随机数再截断，进位的概率正比于被截断的部分。
+///
+/// 无穷和 NaN 按最近偶数转换，避免进位改变其编码。
+fn stochastic_bf16(val: f32, rand: u32) -> bf16 {
+    if !val.is_finite() {
+        return bf16::from_f32(val);
+    }
+    bf16::from_bits(((val.to_bits() + (rand & 0xffff)) >> 16) as u16)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Args, Operator, Rounding};
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        Operator as _, TensorLayout,
+    };
+    use digit_layout::types as ty;
+    use half::f16;
+
+    #[test]
+    fn test_round_trip() {
+        const M: usize = 37;
+        const N: usize = 53;
+
+        let x = (0..M * N)
+            .map(|i| (i as f32 * 0.37).sin() * 100.)
+            .collect::<Vec<_>>();
+        let mut h = vec![f16::ZERO; M * N];
+        let mut y = vec![0.0f32; M * N];
+
+        let mut op = Operator::new(&Cpu);
+        // 以转置视图读入，测试任意步长
+        let unit = size_of::<f32>() as isize;
+        let args = Args::<Cpu> {
+            y_layout: TensorLayout::new_contiguous(ty::F16, &[N, M]),
+            y_base: h.as_mut_ptr().cast(),
+            x_layout: TensorLayout::new(ty::F32, &[N, M], &[unit, N as isize * unit]),
+            x_base: x.as_ptr().cast(),
+            rounding: Rounding::NearestEven,
+        };
+        op.scheme(&args, 0).unwrap();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        let args = Args::<Cpu> {
+            y_layout: TensorLayout::new_contiguous(ty::F32, &[N, M]),
+            y_base: y.as_mut_ptr().cast(),
+            x_layout: TensorLayout::new_contiguous(ty::F16, &[N, M]),
+            x_base: h.as_ptr().cast(),
+            rounding: Rounding::NearestEven,
+        };
+        op.scheme(&args, 0).unwrap();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        for i in 0..M {
+            for j in 0..N {
+                let a = x[i * N + j];
+                let b = y[j * M + i];
+                // f16 有 11 位有效数字，舍入误差不超过半个最低位
+                assert!((a - b).abs() <= a.abs() * 2f32.powi(-11), "{a} vs {b}");
+                assert_eq!(h[j * M + i], f16::from_f32(a));
+            }
+        }
+
+        // f16 -> f32 -> f16 逐位还原
+        let mut back = vec![f16::ZERO; M * N];
+        let args = Args::<Cpu> {
+            y_layout: TensorLayout::new_contiguous(ty::F16, &[N, M]),
+            y_base: back.as_mut_ptr().cast(),
+            x_layout: TensorLayout::new_contiguous(ty::F32, &[N, M]),
+            x_base: y.as_ptr().cast(),
+            rounding: Rounding::NearestEven,
+        };
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+        assert_eq!(back, h);
+    }
+
+    #[test]
+    fn test_narrowing() {
+        use half::bf16;
+
+        let x = [
+            70000.0f32,
+            -70000.,
+            f32::NAN,
+            1. + 2f32.powi(-11),
+            1. + 3. * 2f32.powi(-11),
+        ];
+        let mut h = [f16::ZERO; 5];
+        let mut b = [bf16::ZERO; 5];
+
+        let mut op = Operator::new(&Cpu);
+        let x_layout = TensorLayout::new_contiguous(ty::F32, &[5]);
+        let args = Args::<Cpu> {
+            y_layout: TensorLayout::new_contiguous(ty::F16, &[5]),
+            y_base: h.as_mut_ptr().cast(),
+            x_layout: x_layout.clone(),
+            x_base: x.as_ptr().cast(),
+            rounding: Rounding::NearestEven,
+        };
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+        let args = Args::<Cpu> {
+            y_layout: TensorLayout::new_contiguous(ty::BF16, &[5]),
+            y_base: b.as_mut_ptr().cast(),
+            x_layout,
+            x_base: x.as_ptr().cast(),
+            rounding: Rounding::NearestEven,
+        };
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        // 超出范围变为无穷，NaN 保持
+        assert_eq!(h[0], f16::INFINITY);
+        assert_eq!(h[1], f16::NEG_INFINITY);
+        assert!(h[2].is_nan());
+        // 恰在两个 f16 中间时舍入到偶数
+        assert_eq!(h[3], f16::ONE);
+        assert_eq!(h[4].to_f32(), 1. + 2f32.powi(-9));
+        // bf16 的范围与 f32 相同，不溢出
+        assert_eq!(b[0], bf16::from_f32(70000.));
+        assert!(b[2].is_nan());
+    }
+
+    #[test]
+    fn test_stochastic() {
+        use half::bf16;
+
+        const N: usize = 1 << 16;
+        // 1 + 2^-10 位于 1 和 1 + 2^-7 之间八分之一处
+        let val = 1. + 2f32.powi(-10);
+        let x = vec![val; N];
+
+        let op = Operator::new(&Cpu);
+        let cast = |seed| {
+            let mut y = vec![bf16::ZERO; N];
+            let args = Args::<Cpu> {
+                y_layout: TensorLayout::new_contiguous(ty::BF16, &[N]),
+                y_base: y.as_mut_ptr().cast(),
+                x_layout: TensorLayout::new_contiguous(ty::F32, &[N]),
+                x_base: x.as_ptr().cast(),
+                rounding: Rounding::Stochastic { seed },
+            };
+            op.launch(&args, &mut [], &ThisThread).unwrap();
+            y
+        };
+
+        let y = cast(42);
+        // 只会舍入到两侧相邻的 bf16
+        let lo = bf16::ONE;
+        let hi = bf16::from_f32(1. + 2f32.powi(-7));
+        assert!(y.iter().all(|&y| y == lo || y == hi));
+        // 均值无偏，标准差约为 2^-7 * sqrt(7/64) / sqrt(N) ≈ 1.0e-5
+        let mean = y.iter().map(|y| y.to_f64()).sum::<f64>() / N as f64;
+        assert!((mean - val as f64).abs() < 1e-4, "{mean} vs {val}");
+        // 而最近偶数舍入总是得到 1
+        assert_eq!(bf16::from_f32(val), lo);
+
+        // 相同的种子结果相同，不同的种子结果不同
+        assert_eq!(cast(42), y);
+        assert_ne!(cast(7), y);
+
+        // 无穷、NaN 和可表示的值保持不变
+        let x = [f32::INFINITY, f32::NEG_INFINITY, f32::NAN, 1.5, -0.];
+        let mut y = [bf16::ZERO; 5];
+        let args = Args::<Cpu> {
+            y_layout: TensorLayout::new_contiguous(ty::BF16, &[5]),
+            y_base: y.as_mut_ptr().cast(),
+            x_layout: TensorLayout::new_contiguous(ty::F32, &[5]),
+            x_base: x.as_ptr().cast(),
+            rounding: Rounding::Stochastic { seed: 1 },
+        };
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+        assert_eq!(y[0], bf16::INFINITY);
+        assert_eq!(y[1], bf16::NEG_INFINITY);
+        assert!(y[2].is_nan());
+        assert_eq!(y[3], bf16::from_f32(1.5));
+        assert_eq!(y[4].to_bits(), 0x8000);
+    }
+}
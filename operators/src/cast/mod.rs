@@ -0,0 +1,14 @@
+//! y = cast(x)
+//!
+//! 浮点类型之间的转换。窄化时默认舍入到最近偶数，超出目标类型范围的值变为同号的无穷，NaN 保持为 NaN。
+//! f32 到 bf16 还支持可复现的随机舍入，见 [`Rounding`]。
+
+#[cfg(any(use_cpu, test))]
+pub mod common_cpu;
+#[cfg(use_cl)]
+pub mod opencl;
+
+mod args;
+pub use args::{Args, Rounding};
+
+crate::op_trait!(Cast);
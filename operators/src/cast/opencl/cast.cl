@@ -0,0 +1,74 @@
+#define CL_TARGET_OPENCL_VERSION 200
+#pragma OPENCL EXTENSION cl_khr_fp16 : enable
+
+#ifndef Ty
+#define Ty float
+#endif
+
+#ifndef Tx
+#define Tx float
+#endif
+
+// bf16 以 ushort 存储，取 f32 的高 16 位
+inline ushort bf16_rne(float val) {
+    uint bits = as_uint(val);
+    if (isnan(val)) return (ushort) ((bits >> 16) | 0x40);
+    return (ushort) ((bits + 0x7fff + ((bits >> 16) & 1)) >> 16);
+}
+
+// 在将被截断的低 16 位上加一个均匀的随机数再截断，无穷和 NaN 按最近偶数转换
+inline ushort bf16_stochastic(float val, uint rand) {
+    if (!isfinite(val)) return bf16_rne(val);
+    return (ushort) ((as_uint(val) + (rand & 0xffff)) >> 16);
+}
+
+// 由种子和元素下标生成 32 位随机数（SplitMix64），与 CPU 实现一致
+inline uint random(ulong seed, ulong i) {
+    ulong z = seed + (i + 1) * 0x9e3779b97f4a7c15UL;
+    z = (z ^ (z >> 30)) * 0xbf58476d1ce4e5b9UL;
+    z = (z ^ (z >> 27)) * 0x94d049bb133111ebUL;
+    return (uint) (z ^ (z >> 31));
+}
+
+// half 经由 vload_half/vstore_half 读写，窄化时舍入到最近偶数
+#ifdef X_HALF
+#define LOAD(ptr) vload_half(0, (__global half const *) (ptr))
+#elif defined(X_BF16)
+#define LOAD(ptr) as_float((uint) *(ptr) << 16)
+#else
+#define LOAD(ptr) ((float) *(ptr))
+#endif
+
+#ifdef Y_HALF
+#define STORE(ptr, val) vstore_half_rte(val, 0, (__global half *) (ptr))
+#elif defined(Y_BF16)
+#define STORE(ptr, val) (*(ptr) = bf16_rne(val))
+#else
+#define STORE(ptr, val) (*(ptr) = (Ty) (val))
+#endif
+
+__kernel void cast(
+    __global Ty *y,
+    long const y_stride_row,
+    long const y_stride_col,
+    __global Tx const *x,
+    long const x_stride_row,
+    long const x_stride_col,
+    // 随机舍入的种子和本次发射首个元素的逻辑下标
+    ulong const seed,
+    ulong const offset) {
+
+    // 以 64 位计算下标，元素数可以超过 2^31
+    long const
+        r = get_global_id(0),
+        c = get_global_id(1);
+
+    __global Ty *dst = y + r * y_stride_row + c * y_stride_col;
+    float val = LOAD(x + r * x_stride_row + c * x_stride_col);
+#ifdef STOCHASTIC
+    ulong i = offset + (ulong) r * get_global_size(1) + (ulong) c;
+    *dst = bf16_stochastic(val, random(seed, i));
+#else
+    STORE(dst, val);
+#endif
+}
@@ -0,0 +1,367 @@
+use super::{args::Meta, Args, Cast, Rounding};
+use crate::{
+    execution_failed,
+    opencl::{ClDevice, CodeGen, KernelCache, CL2_0},
+    type_not_support,
+    utils::gcd,
+    ByteOf, LaunchError, PairLayout, QueueAlloc,
+    SchemeDiversity::Low as LowDiversity,
+    SchemeError,
+};
+use clrt::{
+    bindings::{cl_long, cl_ulong},
+    Context,
+};
+use digit_layout::{types as Ty, DigitLayout};
+use lru::LruCache;
+use std::{iter::zip, sync::Mutex};
+
+pub struct Operator {
+    ctx: Context,
+    max_group_size: usize,
+    schemes: Mutex<LruCache<SchemeKey, KernelCache>>,
+}
+
+impl Cast<ClDevice> for Operator {}
+
+impl crate::Operator for Operator {
+    type Hardware = ClDevice;
+    type TopoNode = ClDevice;
+    type Args = Args<ClDevice>;
+
+    fn new(node: &Self::TopoNode) -> Self {
+        let ctx = node.context().clone();
+        let max_group_size = ctx
+            .devices()
+            .iter()
+            .map(|d| d.max_group_size())
+            .min()
+            .unwrap()
+            / 2;
+        Self {
+            ctx,
+            max_group_size,
+            schemes: node.new_cache(LowDiversity),
+        }
+    }
+
+    fn scheme(
+        &mut self,
+        args: &Self::Args,
+        _max_workspace_size: usize,
+    ) -> Result<usize, SchemeError> {
+        let Meta { dt_y, dt_x } = args.meta()?;
+        self.cache_kernel(dt_y, dt_x, args.rounding)?;
+        Ok(0)
+    }
+
+    fn launch<QA>(
+        &self,
+        args: &Self::Args,
+        _workspace: &mut [ByteOf<Self::Hardware>],
+        queue_alloc: &QA,
+    ) -> Result<(), LaunchError>
+    where
+        QA: QueueAlloc<Hardware = Self::Hardware>,
+    {
+        let Meta { dt_y, dt_x } = args.meta()?;
+        let PairLayout {
+            mut shape,
+            strides: [mut y_strides, mut x_strides],
+        } = args.scheme()?;
+
+        // 最后两维由核函数处理，不足两维时补齐
+        while shape.len() < 2 {
+            shape.insert(0, 1);
+            y_strides.insert(0, 0);
+            x_strides.insert(0, 0);
+        }
+        let n = shape.len();
+        let (outer, [r, c]) = (&shape[..n - 2], [shape[n - 2], shape[n - 1]]);
+        let uy = dt_y.nbytes() as isize;
+        let ux = dt_x.nbytes() as isize;
+        let [syr, syc] = [y_strides[n - 2] / uy, y_strides[n - 1] / uy];
+        let [sxr, sxc] = [x_strides[n - 2] / ux, x_strides[n - 1] / ux];
+
+        let key = self.cache_kernel(dt_y, dt_x, args.rounding)?;
+        let seed = match args.rounding {
+            Rounding::NearestEven => 0,
+            Rounding::Stochastic { seed } => seed,
+        };
+        let mut cast = self
+            .schemes
+            .lock()
+            .unwrap()
+            .get(&key)
+            .unwrap()
+            .take_guard("cast")
+            .ok_or_else(|| execution_failed("opencl: kernel cast not found"))?;
+
+        // 更高的维度逐个发射
+        let group_size = gcd(self.max_group_size, c);
+        for i in 0..outer.iter().product::<usize>() {
+            let mut rem = i;
+            let mut y = args.y_base;
+            let mut x = args.x_base;
+            for ((&d, &sy), &sx) in zip(zip(outer, &y_strides[..n - 2]), &x_strides[..n - 2]).rev()
+            {
+                let k = (rem % d) as isize;
+                y = unsafe { y.byte_offset(k * sy) };
+                x = unsafe { x.byte_offset(k * sx) };
+                rem /= d;
+            }
+            cast.set_arg(0, &y)
+                .set_arg(1, syr as cl_long)
+                .set_arg(2, syc as cl_long)
+                .set_arg(3, &x)
+                .set_arg(4, sxr as cl_long)
+                .set_arg(5, sxc as cl_long)
+                .set_arg(6, seed as cl_ulong)
+                .set_arg(7, (i * r * c) as cl_ulong)
+                .launch(
+                    &[0, 0],
+                    &[r, c],
+                    &[1, group_size],
+                    queue_alloc.queue(),
+                    None,
+                );
+        }
+
+        Ok(())
+    }
+}
+
+impl Operator {
+    fn cache_kernel(
+        &self,
+        dt_y: DigitLayout,
+        dt_x: DigitLayout,
+        rounding: Rounding,
+    ) -> Result<SchemeKey, SchemeError> {
+        let ty = |dt| match dt {
+            Ty::F32 => Ok("float"),
+            Ty::F16 => Ok("half"),
+            Ty::BF16 => Ok("ushort"),
+            _ => Err(type_not_support(format!(
+                "opencl: cast does not support {dt}"
+            ))),
+        };
+        let (y, x) = (ty(dt_y)?, ty(dt_x)?);
+
+        let key = SchemeKey {
+            dt_y,
+            dt_x,
+            stochastic: matches!(rounding, Rounding::Stochastic { .. }),
+        };
+        self.schemes.lock().unwrap().get_or_insert(key, || {
+            let mut code = CodeGen::new(include_str!("cast.cl"));
+            code.define("Ty", y).define("Tx", x);
+            match dt_y {
+                Ty::F16 => {
+                    code.define("Y_HALF", true);
+                }
+                Ty::BF16 => {
+                    code.define("Y_BF16", true);
+                }
+                _ => {}
+            }
+            match dt_x {
+                Ty::F16 => {
+                    code.define("X_HALF", true);
+                }
+                Ty::BF16 => {
+                    code.define("X_BF16", true);
+                }
+                _ => {}
+            }
+            if key.stochastic {
+                code.define("STOCHASTIC", true);
+            }
+            KernelCache::new(&self.ctx, &code.to_string(), CL2_0)
+        });
+        Ok(key)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+struct SchemeKey {
+    dt_y: DigitLayout,
+    dt_x: DigitLayout,
+    stochastic: bool,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Args, Operator, Rounding};
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        opencl::ClDevice,
+        Operator as _, TensorLayout,
+    };
+    use clrt::Platform;
+    use digit_layout::types as ty;
+    use half::f16;
+    use std::iter::zip;
+
+    #[test]
+    fn test_compute() {
+        use super::super::common_cpu::Operator as RefOp;
+
+        const B: usize = 3;
+        const M: usize = 17;
+        const N: usize = 64;
+        let len = B * M * N;
+
+        let x = (0..len)
+            .map(|i| (i as f32 * 0.37).sin() * 100.)
+            .collect::<Vec<_>>();
+        // [B, M, N] 的后两维转置为 [B, N, M]
+        let unit = size_of::<f32>() as isize;
+        let x_layout = TensorLayout::new(
+            ty::F32,
+            &[B, N, M],
+            &[(M * N) as isize * unit, unit, N as isize * unit],
+        );
+
+        let mut cpu_op = RefOp::new(&Cpu);
+        let mut h_ref = vec![f16::ZERO; len];
+        cpu_op
+            .launch(
+                &Args::<Cpu> {
+                    y_layout: TensorLayout::new_contiguous(ty::F16, &[B, N, M]),
+                    y_base: h_ref.as_mut_ptr().cast(),
+                    x_layout: x_layout.clone(),
+                    x_base: x.as_ptr().cast(),
+                    rounding: Rounding::NearestEven,
+                },
+                &mut [],
+                &ThisThread,
+            )
+            .unwrap();
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let mut cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+
+                let mut x_svm = context.malloc::<f32>(len);
+                let mut h_svm = context.malloc::<f16>(len);
+                let mut y_svm = context.malloc::<f32>(len);
+
+                let mut map = queue.map_mut(&mut x_svm, false);
+                let ([], mem, []) = (unsafe { map.align_to_mut::<f32>() }) else {
+                    panic!()
+                };
+                mem.copy_from_slice(&x);
+                queue.unmap(map);
+
+                // f32 -> f16
+                let args = Args::<ClDevice> {
+                    y_layout: TensorLayout::new_contiguous(ty::F16, &[B, N, M]),
+                    y_base: h_svm.as_mut_ptr().cast(),
+                    x_layout: x_layout.clone(),
+                    x_base: x_svm.as_ptr().cast(),
+                    rounding: Rounding::NearestEven,
+                };
+                cl_op.scheme(&args, 0).unwrap();
+                cl_op.launch(&args, &mut [], &queue).unwrap();
+                // f16 -> f32
+                let args = Args::<ClDevice> {
+                    y_layout: TensorLayout::new_contiguous(ty::F32, &[B, N, M]),
+                    y_base: y_svm.as_mut_ptr().cast(),
+                    x_layout: TensorLayout::new_contiguous(ty::F16, &[B, N, M]),
+                    x_base: h_svm.as_ptr().cast(),
+                    rounding: Rounding::NearestEven,
+                };
+                cl_op.scheme(&args, 0).unwrap();
+                cl_op.launch(&args, &mut [], &queue).unwrap();
+                queue.finish();
+
+                let map = queue.map(&mut h_svm);
+                let ([], h_ans, []) = (unsafe { map.align_to::<f16>() }) else {
+                    panic!()
+                };
+                assert_eq!(h_ans, h_ref);
+                queue.unmap(map);
+
+                let map = queue.map(&mut y_svm);
+                let ([], y_ans, []) = (unsafe { map.align_to::<f32>() }) else {
+                    panic!()
+                };
+                for (a, b) in zip(&h_ref, y_ans) {
+                    assert_eq!(a.to_f32(), *b);
+                }
+                queue.unmap(map);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stochastic() {
+        use super::super::common_cpu::Operator as RefOp;
+        use crate::opencl::read_to_vec;
+        use half::bf16;
+
+        const M: usize = 64;
+        const N: usize = 256;
+        let x = (0..M * N)
+            .map(|i| (i as f32 * 0.37).sin() * 100.)
+            .collect::<Vec<_>>();
+        let y_layout = TensorLayout::new_contiguous(ty::BF16, &[M, N]);
+        let x_layout = TensorLayout::new_contiguous(ty::F32, &[M, N]);
+
+        for rounding in [Rounding::NearestEven, Rounding::Stochastic { seed: 42 }] {
+            let mut y_ref = vec![bf16::ZERO; M * N];
+            RefOp::new(&Cpu)
+                .launch(
+                    &Args::<Cpu> {
+                        y_layout: y_layout.clone(),
+                        y_base: y_ref.as_mut_ptr().cast(),
+                        x_layout: x_layout.clone(),
+                        x_base: x.as_ptr().cast(),
+                        rounding,
+                    },
+                    &mut [],
+                    &ThisThread,
+                )
+                .unwrap();
+
+            for platform in Platform::all() {
+                for device in platform.devices() {
+                    println!("device: {}", device.name());
+
+                    let context = device.context();
+                    let queue = context.queue();
+                    let mut cl_op =
+                        Operator::new(&ClDevice::new(context.clone(), Default::default()));
+
+                    let mut x_svm = context.malloc::<f32>(M * N);
+                    let mut y_svm = context.malloc::<bf16>(M * N);
+                    let mut map = queue.map_mut(&mut x_svm, false);
+                    let ([], mem, []) = (unsafe { map.align_to_mut::<f32>() }) else {
+                        panic!()
+                    };
+                    mem.copy_from_slice(&x);
+                    queue.unmap(map);
+
+                    let args = Args::<ClDevice> {
+                        y_layout: y_layout.clone(),
+                        y_base: y_svm.as_mut_ptr().cast(),
+                        x_layout: x_layout.clone(),
+                        x_base: x_svm.as_ptr().cast(),
+                        rounding,
+                    };
+                    cl_op.scheme(&args, 0).unwrap();
+                    cl_op.launch(&args, &mut [], &queue).unwrap();
+
+                    // 随机数只取决于种子和逻辑位置，与 CPU 逐位一致
+                    let y_ans = read_to_vec::<bf16>(&mut y_svm, &queue);
+                    assert_eq!(y_ans, y_ref, "{rounding:?}");
+                }
+            }
+        }
+    }
+}
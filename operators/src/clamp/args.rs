@@ -0,0 +1,111 @@
+use crate::{
+    args_not_support, rank_mismatch, shape_not_support, type_not_support,
+    utils::{dim_distinct, type_distinct},
+    ConstPtr, Hardware, MutPtr, PairLayout, SchemeError, TensorLayout,
+};
+use digit_layout::DigitLayout;
+use std::{
+    iter::zip,
+    ptr::{null, null_mut},
+};
+
+pub struct Args<H: Hardware> {
+    pub y_layout: TensorLayout,
+    pub y_base: MutPtr<H>,
+    pub x_layout: TensorLayout,
+    pub x_base: ConstPtr<H>,
+    /// 下界，不能大于 `hi`。
+    pub lo: f32,
+    /// 上界。
+    pub hi: f32,
+}
+
+pub(super) struct Meta {
+    pub dt: DigitLayout,
+}
+
+impl<H: Hardware> Args<H> {
+    pub fn new_null(y_layout: TensorLayout, x_layout: TensorLayout, lo: f32, hi: f32) -> Self {
+        Self {
+            y_layout,
+            y_base: null_mut(),
+            x_layout,
+            x_base: null(),
+            lo,
+            hi,
+        }
+    }
+
+    pub(super) fn meta(&self) -> Result<Meta, SchemeError> {
+        let Self {
+            y_layout: y,
+            x_layout: x,
+            lo,
+            hi,
+            ..
+        } = self;
+
+        let dt = type_distinct(&[y.dt(), x.dt()])?;
+        use digit_layout::LayoutContent::Real;
+        if !matches!(dt.decode(), Real { exponent: 1.., .. }) {
+            return Err(type_not_support(format!(
+                "data type {dt} is not supported, must be floating-point numbers",
+            )));
+        }
+        if lo.is_nan() || hi.is_nan() || lo > hi {
+            return Err(args_not_support(format!(
+                "clamp: invalid range [{lo}, {hi}]"
+            )));
+        }
+        if y.ndim() != x.ndim() {
+            return Err(rank_mismatch(format!(
+                "y.ndim = {}, x.ndim = {}",
+                y.ndim(),
+                x.ndim(),
+            )));
+        }
+        for (&dy, &dx) in zip(y.shape(), x.shape()) {
+            dim_distinct(&[dy, dx])?;
+        }
+
+        Ok(Meta { dt })
+    }
+
+    /// 合并连续维度后的执行方案，依次为 `y` 和 `x` 的步长。
+    pub(super) fn scheme(&self) -> Result<PairLayout, SchemeError> {
+        let scheme = PairLayout::new(&self.y_layout, &self.x_layout)?;
+        if scheme.strides[0].contains(&0) {
+            return Err(shape_not_support("clamp: output cannot be broadcast"));
+        }
+        Ok(scheme)
+    }
+}
+
+#[test]
+fn test_meta() {
+    use digit_layout::types::{F16, F32, U32};
+
+    let layout = TensorLayout::new_contiguous(F32, &[4, 6]);
+    let args = Args::<crate::common_cpu::Cpu>::new_null(layout.clone(), layout.clone(), -1., 1.);
+    args.meta().unwrap();
+    let scheme = args.scheme().unwrap();
+    assert_eq!(scheme.shape, [24]);
+    assert_eq!(scheme.count(), 24);
+
+    // 边界必须有序且不是 NaN
+    for (lo, hi) in [(1., -1.), (f32::NAN, 1.), (0., f32::NAN)] {
+        let args = Args::<crate::common_cpu::Cpu>::new_null(layout.clone(), layout.clone(), lo, hi);
+        assert!(args.meta().is_err());
+    }
+    // 输入输出类型必须相同，且为浮点数
+    let args = Args::<crate::common_cpu::Cpu>::new_null(
+        TensorLayout::new_contiguous(F16, &[4, 6]),
+        layout,
+        -1.,
+        1.,
+    );
+    assert!(args.meta().is_err());
+    let layout = TensorLayout::new_contiguous(U32, &[4]);
+    let args = Args::<crate::common_cpu::Cpu>::new_null(layout.clone(), layout, 0., 1.);
+    assert!(args.meta().is_err());
+}
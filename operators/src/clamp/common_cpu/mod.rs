@@ -0,0 +1,148 @@
+use super::{args::Meta, Args, Clamp};
+use crate::{
+    common_cpu::{Cpu, Float},
+    type_not_support, ByteOf, LaunchError, PairLayout, QueueAlloc, SchemeError,
+};
+use half::{bf16, f16};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+pub struct Operator;
+
+impl Clamp<Cpu> for Operator {}
+
+impl crate::Operator for Operator {
+    type Hardware = Cpu;
+    type TopoNode = Cpu;
+    type Args = Args<Cpu>;
+
+    #[inline]
+    fn new(_node: &Self::TopoNode) -> Self {
+        Self
+    }
+
+    fn scheme(
+        &mut self,
+        args: &Self::Args,
+        _max_workspace_size: usize,
+    ) -> Result<usize, SchemeError> {
+        let _meta = args.meta()?;
+        Ok(0)
+    }
+
+    fn launch<QA>(
+        &self,
+        args: &Self::Args,
+        _workspace: &mut [ByteOf<Self::Hardware>],
+        _queue_alloc: &QA,
+    ) -> Result<(), LaunchError>
+    where
+        QA: QueueAlloc<Hardware = Self::Hardware>,
+    {
+        let Meta { dt } = args.meta()?;
+        let scheme = args.scheme()?;
+
+        use digit_layout::types as ty;
+        match dt {
+            ty::F16 => calculate::<f16>(&scheme, args),
+            ty::BF16 => calculate::<bf16>(&scheme, args),
+            ty::F32 => calculate::<f32>(&scheme, args),
+            ty::F64 => calculate::<f64>(&scheme, args),
+            _ => Err(type_not_support(format!("cpu: clamp {dt}")))?,
+        }
+        Ok(())
+    }
+}
+
+/// 经由 f64 比较，范围内的值经 f64 往返不变，被截断的值为边界舍入到目标类型的结果。
+fn calculate<T: Float>(scheme: &PairLayout, args: &Args<Cpu>) {
+    let lo = args.lo as f64;
+    let hi = args.hi as f64;
+    let y = args.y_base as isize;
+    let x = args.x_base as isize;
+    (0..scheme.count()).into_par_iter().for_each(|i| {
+        let [sy, sx] = scheme.offsets(i);
+        let (y, x) = (y + sy, x + sx);
+        // f64::clamp 对 NaN 返回 NaN
+        unsafe {
+            let val = (x as *const T).read().to_f64();
+            *(y as *mut T) = T::from_f64(val.clamp(lo, hi))
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Args, Operator};
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        Operator as _, TensorLayout,
+    };
+    use digit_layout::types as ty;
+    use half::f16;
+
+    #[test]
+    fn test_compute() {
+        const M: usize = 3;
+        const N: usize = 4;
+
+        let x = [
+            -5.0f32,
+            -1.,
+            -0.5,
+            0.,
+            0.5,
+            1.,
+            5.,
+            f32::NAN,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            1e30,
+            -0.,
+        ];
+        let mut y = [0.0f32; M * N];
+
+        // 以转置视图读入，测试任意步长
+        let unit = size_of::<f32>() as isize;
+        let args = Args::<Cpu> {
+            y_layout: TensorLayout::new_contiguous(ty::F32, &[N, M]),
+            y_base: y.as_mut_ptr().cast(),
+            x_layout: TensorLayout::new(ty::F32, &[N, M], &[unit, N as isize * unit]),
+            x_base: x.as_ptr().cast(),
+            lo: -1.,
+            hi: 2.,
+        };
+        let mut op = Operator::new(&Cpu);
+        op.scheme(&args, 0).unwrap();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        for i in 0..M {
+            for j in 0..N {
+                let a = x[i * N + j];
+                let b = y[j * M + i];
+                if a.is_nan() {
+                    // NaN 原样传递
+                    assert!(b.is_nan());
+                } else {
+                    assert_eq!(b, a.clamp(-1., 2.));
+                }
+            }
+        }
+
+        // f16 原地计算，边界舍入到 f16
+        let mut h = x.map(f16::from_f32);
+        let layout = TensorLayout::new_contiguous(ty::F16, &[M * N]);
+        let args = Args::<Cpu> {
+            y_layout: layout.clone(),
+            y_base: h.as_mut_ptr().cast(),
+            x_layout: layout,
+            x_base: h.as_ptr().cast(),
+            lo: 0.1,
+            hi: 1e5,
+        };
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+        assert_eq!(h[0], f16::from_f32(0.1));
+        assert_eq!(h[4], f16::from_f32(0.5));
+        assert_eq!(h[8], f16::INFINITY);
+        assert!(h[7].is_nan());
+    }
+}
@@ -0,0 +1,13 @@
+//! y = clamp(x, lo, hi)
+//!
+//! 逐元素将 `x` 限制在 `[lo, hi]` 内，NaN 原样传递。`y` 可以与 `x` 相同以原地计算。
+
+#[cfg(any(use_cpu, test))]
+pub mod common_cpu;
+#[cfg(use_cl)]
+pub mod opencl;
+
+mod args;
+pub use args::Args;
+
+crate::op_trait!(Clamp);
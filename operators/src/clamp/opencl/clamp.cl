@@ -0,0 +1,36 @@
+#define CL_TARGET_OPENCL_VERSION 200
+#pragma OPENCL EXTENSION cl_khr_fp16 : enable
+
+#ifndef Tval
+#define Tval float
+#endif
+
+// half 经由 vload_half/vstore_half 读写，被截断的值舍入到最近偶数
+#ifdef HALF
+#define LOAD(ptr) vload_half(0, (__global half const *) (ptr))
+#define STORE(ptr, val) vstore_half_rte(val, 0, (__global half *) (ptr))
+#else
+#define LOAD(ptr) (*(ptr))
+#define STORE(ptr, val) (*(ptr) = (val))
+#endif
+
+// 内置的 clamp 对 NaN 的结果未定义，因此单独处理 NaN
+__kernel void clamp_elements(
+    __global Tval *y,
+    long const y_stride_row,
+    long const y_stride_col,
+    __global Tval const *x,
+    long const x_stride_row,
+    long const x_stride_col,
+    float const lo,
+    float const hi) {
+
+    // 以 64 位计算下标，元素数可以超过 2^31
+    long const
+        r = get_global_id(0),
+        c = get_global_id(1);
+
+    float val = LOAD(x + r * x_stride_row + c * x_stride_col);
+    if (!isnan(val)) val = fmin(fmax(val, lo), hi);
+    STORE(y + r * y_stride_row + c * y_stride_col, val);
+}
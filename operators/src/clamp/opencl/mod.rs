@@ -0,0 +1,265 @@
+use super::{args::Meta, Args, Clamp};
+use crate::{
+    execution_failed,
+    opencl::{ClDevice, CodeGen, KernelCache, CL2_0},
+    type_not_support,
+    utils::gcd,
+    ByteOf, LaunchError, PairLayout, QueueAlloc,
+    SchemeDiversity::Low as LowDiversity,
+    SchemeError,
+};
+use clrt::{bindings::cl_long, Context};
+use digit_layout::{types as Ty, DigitLayout};
+use lru::LruCache;
+use std::{iter::zip, sync::Mutex};
+
+pub struct Operator {
+    ctx: Context,
+    max_group_size: usize,
+    schemes: Mutex<LruCache<DigitLayout, KernelCache>>,
+}
+
+impl Clamp<ClDevice> for Operator {}
+
+impl crate::Operator for Operator {
+    type Hardware = ClDevice;
+    type TopoNode = ClDevice;
+    type Args = Args<ClDevice>;
+
+    fn new(node: &Self::TopoNode) -> Self {
+        let ctx = node.context().clone();
+        let max_group_size = ctx
+            .devices()
+            .iter()
+            .map(|d| d.max_group_size())
+            .min()
+            .unwrap()
+            / 2;
+        Self {
+            ctx,
+            max_group_size,
+            schemes: node.new_cache(LowDiversity),
+        }
+    }
+
+    fn scheme(
+        &mut self,
+        args: &Self::Args,
+        _max_workspace_size: usize,
+    ) -> Result<usize, SchemeError> {
+        let Meta { dt } = args.meta()?;
+        self.cache_kernel(dt)?;
+        Ok(0)
+    }
+
+    fn launch<QA>(
+        &self,
+        args: &Self::Args,
+        _workspace: &mut [ByteOf<Self::Hardware>],
+        queue_alloc: &QA,
+    ) -> Result<(), LaunchError>
+    where
+        QA: QueueAlloc<Hardware = Self::Hardware>,
+    {
+        let Meta { dt } = args.meta()?;
+        let PairLayout {
+            mut shape,
+            strides: [mut y_strides, mut x_strides],
+        } = args.scheme()?;
+
+        // 最后两维由核函数处理，不足两维时补齐
+        while shape.len() < 2 {
+            shape.insert(0, 1);
+            y_strides.insert(0, 0);
+            x_strides.insert(0, 0);
+        }
+        let n = shape.len();
+        let (outer, [r, c]) = (&shape[..n - 2], [shape[n - 2], shape[n - 1]]);
+        let unit = dt.nbytes() as isize;
+        let [syr, syc] = [y_strides[n - 2] / unit, y_strides[n - 1] / unit];
+        let [sxr, sxc] = [x_strides[n - 2] / unit, x_strides[n - 1] / unit];
+
+        self.cache_kernel(dt)?;
+        let mut kernel = self
+            .schemes
+            .lock()
+            .unwrap()
+            .get(&dt)
+            .unwrap()
+            .take_guard("clamp_elements")
+            .ok_or_else(|| execution_failed("opencl: kernel clamp_elements not found"))?;
+
+        // 更高的维度逐个发射
+        let group_size = gcd(self.max_group_size, c);
+        for i in 0..outer.iter().product::<usize>() {
+            let mut rem = i;
+            let mut y = args.y_base;
+            let mut x = args.x_base;
+            for ((&d, &sy), &sx) in zip(zip(outer, &y_strides[..n - 2]), &x_strides[..n - 2]).rev()
+            {
+                let k = (rem % d) as isize;
+                y = unsafe { y.byte_offset(k * sy) };
+                x = unsafe { x.byte_offset(k * sx) };
+                rem /= d;
+            }
+            kernel
+                .set_arg(0, &y)
+                .set_arg(1, syr as cl_long)
+                .set_arg(2, syc as cl_long)
+                .set_arg(3, &x)
+                .set_arg(4, sxr as cl_long)
+                .set_arg(5, sxc as cl_long)
+                .set_arg(6, args.lo)
+                .set_arg(7, args.hi)
+                .launch(
+                    &[0, 0],
+                    &[r, c],
+                    &[1, group_size],
+                    queue_alloc.queue(),
+                    None,
+                );
+        }
+        Ok(())
+    }
+}
+
+impl Operator {
+    fn cache_kernel(&self, dt: DigitLayout) -> Result<(), SchemeError> {
+        let ty = match dt {
+            Ty::F32 => "float",
+            Ty::F16 => "half",
+            _ => Err(type_not_support(format!(
+                "opencl: clamp does not support {dt}"
+            )))?,
+        };
+        self.schemes.lock().unwrap().get_or_insert(dt, || {
+            let mut code = CodeGen::new(include_str!("clamp.cl"));
+            code.define("Tval", ty);
+            if dt == Ty::F16 {
+                code.define("HALF", true);
+            }
+            KernelCache::new(&self.ctx, &code.to_string(), CL2_0)
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Args, Operator};
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        opencl::{read_to_vec, ClDevice},
+        Operator as _, TensorLayout,
+    };
+    use clrt::Platform;
+    use digit_layout::types as ty;
+    use half::f16;
+
+    #[test]
+    fn test_compute() {
+        use super::super::common_cpu::Operator as RefOp;
+
+        const B: usize = 3;
+        const M: usize = 17;
+        const N: usize = 64;
+        let len = B * M * N;
+
+        let mut x = (0..len)
+            .map(|i| (i as f32 * 0.37).sin() * 4.)
+            .collect::<Vec<_>>();
+        x[5] = f32::NAN;
+        x[6] = f32::INFINITY;
+        // [B, M, N] 的后两维转置为 [B, N, M]
+        let unit = size_of::<f32>() as isize;
+        let x_layout = TensorLayout::new(
+            ty::F32,
+            &[B, N, M],
+            &[(M * N) as isize * unit, unit, N as isize * unit],
+        );
+        let y_layout = TensorLayout::new_contiguous(ty::F32, &[B, N, M]);
+        let (lo, hi) = (-1.5, 2.);
+
+        let mut y_ref = vec![0.0f32; len];
+        RefOp::new(&Cpu)
+            .launch(
+                &Args::<Cpu> {
+                    y_layout: y_layout.clone(),
+                    y_base: y_ref.as_mut_ptr().cast(),
+                    x_layout: x_layout.clone(),
+                    x_base: x.as_ptr().cast(),
+                    lo,
+                    hi,
+                },
+                &mut [],
+                &ThisThread,
+            )
+            .unwrap();
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let mut cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+
+                let mut x_svm = context.malloc::<f32>(len);
+                let mut y_svm = context.malloc::<f32>(len);
+                let mut map = queue.map_mut(&mut x_svm, false);
+                let ([], mem, []) = (unsafe { map.align_to_mut::<f32>() }) else {
+                    panic!()
+                };
+                mem.copy_from_slice(&x);
+                queue.unmap(map);
+
+                let args = Args::<ClDevice> {
+                    y_layout: y_layout.clone(),
+                    y_base: y_svm.as_mut_ptr().cast(),
+                    x_layout: x_layout.clone(),
+                    x_base: x_svm.as_ptr().cast(),
+                    lo,
+                    hi,
+                };
+                cl_op.scheme(&args, 0).unwrap();
+                cl_op.launch(&args, &mut [], &queue).unwrap();
+
+                let y_ans = read_to_vec::<f32>(&mut y_svm, &queue);
+                for (a, b) in y_ans.iter().zip(&y_ref) {
+                    assert!(a == b || (a.is_nan() && b.is_nan()), "{a} vs {b}");
+                }
+
+                // f16 原地计算
+                let h = x.iter().map(|&x| f16::from_f32(x)).collect::<Vec<_>>();
+                let mut h_svm = context.malloc::<f16>(len);
+                let mut map = queue.map_mut(&mut h_svm, false);
+                let ([], mem, []) = (unsafe { map.align_to_mut::<f16>() }) else {
+                    panic!()
+                };
+                mem.copy_from_slice(&h);
+                queue.unmap(map);
+
+                let layout = TensorLayout::new_contiguous(ty::F16, &[B, M, N]);
+                let args = Args::<ClDevice> {
+                    y_layout: layout.clone(),
+                    y_base: h_svm.as_mut_ptr().cast(),
+                    x_layout: layout,
+                    x_base: h_svm.as_ptr().cast(),
+                    lo,
+                    hi,
+                };
+                cl_op.scheme(&args, 0).unwrap();
+                cl_op.launch(&args, &mut [], &queue).unwrap();
+
+                let h_ans = read_to_vec::<f16>(&mut h_svm, &queue);
+                for (a, b) in h_ans.iter().zip(&h) {
+                    if b.is_nan() {
+                        assert!(a.is_nan());
+                    } else {
+                        assert_eq!(*a, f16::from_f32(b.to_f32().clamp(lo, hi)));
+                    }
+                }
+            }
+        }
+    }
+}
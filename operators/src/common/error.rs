@@ -32,12 +32,46 @@ pub struct LaunchError {
 impl From<SchemeError> for LaunchError {
     fn from(SchemeError { kind, info }: SchemeError) -> Self {
         Self {
-            kind: LaunchErrorKind::Scheme(kind),
+            kind: kind.into(),
             info,
         }
     }
 }
 
+impl From<SchemeErrorKind> for LaunchErrorKind {
+    #[inline]
+    fn from(kind: SchemeErrorKind) -> Self {
+        Self::Scheme(kind)
+    }
+}
+
+/// 发射错误的类别，调用者据此决定换用其他后端重试还是中止。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LaunchErrorCategory {
+    /// 参数不被当前后端支持或互相矛盾，本应在 scheme 时发现，可以换用其他后端重试。
+    Validation,
+    /// 设备执行失败，重试没有意义，应当中止。
+    Runtime,
+}
+
+impl LaunchErrorKind {
+    #[inline]
+    pub fn category(&self) -> LaunchErrorCategory {
+        match self {
+            Self::Scheme(_) => LaunchErrorCategory::Validation,
+            Self::ExecutionFailed => LaunchErrorCategory::Runtime,
+        }
+    }
+}
+
+impl LaunchError {
+    /// 错误的类别，见 [`LaunchErrorCategory`]。
+    #[inline]
+    pub fn category(&self) -> LaunchErrorCategory {
+        self.kind.category()
+    }
+}
+
 pub(super) mod functions {
     use super::{LaunchError, LaunchErrorKind::*, SchemeError, SchemeErrorKind::*};
 
@@ -65,3 +99,46 @@ pub(super) mod functions {
 
     builder!(LaunchError: execution_failed    ExecutionFailed  );
 }
+
+#[test]
+fn test_question_mark() {
+    use functions::*;
+
+    fn scheme(e: SchemeError) -> Result<(), SchemeError> {
+        Err(e)?;
+        Ok(())
+    }
+
+    fn launch(e: SchemeError) -> Result<(), LaunchError> {
+        Err(e)?;
+        Ok(())
+    }
+
+    for (builder, kind) in [
+        (
+            type_not_support as fn(&'static str) -> SchemeError,
+            SchemeErrorKind::TypeNotSupport,
+        ),
+        (type_mismatch, SchemeErrorKind::TypeMismatch),
+        (rank_mismatch, SchemeErrorKind::RankMismatch),
+        (rank_not_support, SchemeErrorKind::RankNotSupport),
+        (shape_not_support, SchemeErrorKind::ShapeNotSupport),
+        (shape_mismatch, SchemeErrorKind::ShapeMismatch),
+        (strides_not_support, SchemeErrorKind::StridesNotSupport),
+        (args_not_support, SchemeErrorKind::ArgsNotSupport),
+        (dyn_not_support, SchemeErrorKind::DynamicNotSupport),
+    ] {
+        let e = scheme(builder("info")).unwrap_err();
+        assert_eq!(e.kind, kind);
+        assert_eq!(e.info, "info");
+
+        let e = launch(builder("info")).unwrap_err();
+        assert_eq!(e.kind, LaunchErrorKind::Scheme(kind));
+        assert_eq!(e.info, "info");
+        assert_eq!(e.category(), LaunchErrorCategory::Validation);
+    }
+
+    let e = execution_failed("info");
+    assert_eq!(e.kind, LaunchErrorKind::ExecutionFailed);
+    assert_eq!(e.category(), LaunchErrorCategory::Runtime);
+}
@@ -3,26 +3,37 @@ mod calculator;
 mod diversity;
 mod error;
 mod maybe_dyn;
+mod pair_layout;
+mod plan;
 mod pool;
+mod registry;
 mod tensor;
 mod unsigned;
 mod workspace;
 
 pub use blob::Blob;
 pub use calculator::OffsetCalculator;
-pub use error::{functions::*, LaunchError, LaunchErrorKind, SchemeError, SchemeErrorKind};
+pub use error::{
+    functions::*, LaunchError, LaunchErrorCategory, LaunchErrorKind, SchemeError, SchemeErrorKind,
+};
 pub use maybe_dyn::{dyn_, DynVal, MaybeDyn};
+pub use plan::SchemePlan;
 pub use pool::Pool;
-pub use tensor::TensorLayout;
+pub use registry::{DynOperator, Registry};
+pub use tensor::{contiguous, contiguous_mut, empty_like, zeros_like, TensorLayout};
 pub use unsigned::Unsigned;
 pub use workspace::Workspace;
 
 pub(crate) use diversity::{SchemeCacheSize, SchemeDiversity};
 pub(crate) use maybe_dyn::{get_static, static_from};
+pub(crate) use pair_layout::PairLayout;
 pub(crate) use workspace::WorkspaceCollector;
 
 pub mod utils {
-    use super::{rank_not_support, shape_mismatch, type_mismatch, MaybeDyn, SchemeError};
+    use super::{
+        rank_not_support, shape_mismatch, strides_not_support, type_mismatch, MaybeDyn,
+        SchemeError, TensorLayout,
+    };
     use digit_layout::DigitLayout;
 
     #[cfg(any(use_cuda, use_cl))]
@@ -59,12 +70,72 @@ pub mod utils {
             .copied()
             .map_err(|_| shape_mismatch(format!("{args:?} are not distinct")))
     }
+
+    /// 检查逐行共享的向量（例如归一化的权重），返回向量长度。
+    ///
+    /// 接受 [d]，或者行数为 1、或以步长 0 广播到 `n` 行的 [n, d]，后者的每一行都读取同一个向量。
+    pub(crate) fn row_vector(
+        arg: &str,
+        layout: &TensorLayout,
+        n: MaybeDyn<usize>,
+    ) -> Result<MaybeDyn<usize>, SchemeError> {
+        match (layout.shape(), layout.strides()) {
+            (&[d], _) => Ok(d),
+            (&[nw, d], &[sn, _]) => {
+                if nw.get_static() != Some(&1) {
+                    if sn.get_static().is_some_and(|&sn| sn != 0) {
+                        return Err(strides_not_support(format!(
+                            "{arg} must be broadcast across rows with stride 0"
+                        )));
+                    }
+                    dim_distinct(&[nw, n])?;
+                }
+                Ok(d)
+            }
+            _ => Err(rank_error(arg, 1, layout.ndim())),
+        }
+    }
+
+    /// 发射前检查张量基址非空，且按形状和步长计算的偏移不溢出。
+    ///
+    /// 只在启用 `debug-assertions` 特性时生效，否则不产生任何代码。
+    #[inline]
+    #[allow(unused_variables)]
+    pub(crate) fn debug_check_tensor<T>(name: &str, layout: &super::TensorLayout, base: *const T) {
+        #[cfg(feature = "debug-assertions")]
+        {
+            let (Some(shape), Some(strides)) = (
+                MaybeDyn::get_all(layout.shape()),
+                MaybeDyn::get_all(layout.strides()),
+            ) else {
+                panic!("{name}: dynamic layout at launch")
+            };
+            if shape.contains(&0) {
+                return;
+            }
+            assert!(!base.is_null(), "{name}: base pointer is null");
+            std::iter::zip(shape, strides).fold(0isize, |acc, (&d, &s)| {
+                isize::try_from(d - 1)
+                    .ok()
+                    .and_then(|d| d.checked_mul(s))
+                    .and_then(|offset| acc.checked_add(offset.abs()))
+                    .unwrap_or_else(|| {
+                        panic!("{name}: offset of {shape:?} x {strides:?} overflows")
+                    })
+            });
+        }
+    }
 }
 
 #[cfg(test)]
 #[allow(dead_code)]
 pub(crate) mod test_utils {
-    use std::fmt;
+    use std::{
+        fmt,
+        fs::File,
+        io::{self, BufWriter, Write},
+        path::Path,
+    };
 
     pub struct Diff {
         pub abs: f64,
@@ -79,11 +150,31 @@ pub(crate) mod test_utils {
         }
     }
 
+    /// 将 f32 的非规格化数刷为同号的 0，模拟设备上的 flush-to-zero 行为。
+    pub fn flush_denormal(x: f64) -> f64 {
+        if x.abs() < f32::MIN_POSITIVE as f64 {
+            0f64.copysign(x)
+        } else {
+            x
+        }
+    }
+
     pub struct ErrorCollector {
         threshold: Diff,
         max_diff: Diff,
         outliers: Vec<usize>,
         count: usize,
+        flush_denormals: bool,
+        denormal_only: usize,
+        records: Option<Vec<Record>>,
+    }
+
+    /// 一个离群元素的记录，只通过 [push](ErrorCollector::push) 传入的元素没有值。
+    struct Record {
+        index: usize,
+        values: Option<[f64; 2]>,
+        abs: f64,
+        rel: f64,
     }
 
     impl ErrorCollector {
@@ -93,20 +184,88 @@ pub(crate) mod test_utils {
                 max_diff: Diff { abs: 0., rel: 0. },
                 outliers: vec![],
                 count: 0,
+                flush_denormals: false,
+                denormal_only: 0,
+                records: None,
+            }
+        }
+
+        /// 记录每个离群元素的序号、值和误差，用于 [write_csv](Self::write_csv)。
+        pub fn record_outliers(mut self) -> Self {
+            self.records = Some(vec![]);
+            self
+        }
+
+        /// 比较前将两侧的 f32 非规格化数刷为 0，用于对比会 flush-to-zero 的设备。
+        pub fn flush_denormals(mut self) -> Self {
+            self.flush_denormals = true;
+            self
+        }
+
+        /// 比较参考值 `a` 和待测值 `b`。
+        ///
+        /// 启用 [flush_denormals](Self::flush_denormals) 时，仅因非规格化数处理不同而超出阈值的元素不计为离群，
+        /// 而是计入 [denormal_only](Self::denormal_only)。
+        pub fn push_pair(&mut self, a: f64, b: f64) {
+            let diff = Diff::new(a, b);
+            if self.flush_denormals && self.is_outlier(&diff) {
+                let flushed = Diff::new(flush_denormal(a), flush_denormal(b));
+                if !self.is_outlier(&flushed) {
+                    self.denormal_only += 1;
+                    return self.push(flushed);
+                }
             }
+            self.push_with(diff, Some([a, b]))
+        }
+
+        /// 仅因非规格化数处理不同而产生差异的元素数量。
+        pub fn denormal_only(&self) -> usize {
+            self.denormal_only
+        }
+
+        fn is_outlier(&self, diff: &Diff) -> bool {
+            diff.abs > self.threshold.abs && diff.rel > self.threshold.rel
         }
 
         pub fn push(&mut self, diff: Diff) {
+            self.push_with(diff, None)
+        }
+
+        fn push_with(&mut self, diff: Diff, values: Option<[f64; 2]>) {
             self.max_diff.abs = f64::max(self.max_diff.abs, diff.abs);
             self.max_diff.rel = f64::max(self.max_diff.rel, diff.rel);
 
-            if diff.abs > self.threshold.abs && diff.rel > self.threshold.rel {
+            if self.is_outlier(&diff) {
                 self.outliers.push(self.count);
+                if let Some(records) = &mut self.records {
+                    records.push(Record {
+                        index: self.count,
+                        values,
+                        abs: diff.abs,
+                        rel: diff.rel,
+                    })
+                }
             }
 
             self.count += 1;
         }
 
+        /// 将记录的离群元素写入 CSV 文件，列为 `index,expected,actual,abs,rel`。
+        ///
+        /// 需要先启用 [record_outliers](Self::record_outliers)，否则只写表头。
+        /// 没有值的元素 `expected` 和 `actual` 两列为空。
+        pub fn write_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+            let mut file = BufWriter::new(File::create(path)?);
+            writeln!(file, "index,expected,actual,abs,rel")?;
+            for r in self.records.iter().flatten() {
+                match r.values {
+                    Some([a, b]) => writeln!(file, "{},{a},{b},{},{}", r.index, r.abs, r.rel)?,
+                    None => writeln!(file, "{},,,{},{}", r.index, r.abs, r.rel)?,
+                }
+            }
+            file.flush()
+        }
+
         pub fn summary(self) -> (usize, usize) {
             (self.outliers.len(), self.count)
         }
@@ -116,6 +275,28 @@ pub(crate) mod test_utils {
         }
     }
 
+    /// 检查每行元素之和与 1 的偏差不超过 `eps`，不满足时返回第一个不满足的行号。
+    pub fn rows_sum_to_one<'a>(
+        rows: impl IntoIterator<Item = &'a [f64]>,
+        eps: f64,
+    ) -> Result<(), usize> {
+        match rows
+            .into_iter()
+            .position(|row| !(1. - eps..=1. + eps).contains(&row.iter().sum::<f64>()))
+        {
+            Some(i) => Err(i),
+            None => Ok(()),
+        }
+    }
+
+    /// 检查所有值都在 `[lo, hi]` 之间，不满足时返回第一个不满足的值的序号。
+    pub fn all_within(vals: impl IntoIterator<Item = f64>, lo: f64, hi: f64) -> Result<(), usize> {
+        match vals.into_iter().position(|x| !(lo..=hi).contains(&x)) {
+            Some(i) => Err(i),
+            None => Ok(()),
+        }
+    }
+
     impl fmt::Display for ErrorCollector {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             write!(
@@ -125,7 +306,227 @@ pub(crate) mod test_utils {
                 self.max_diff.rel,
                 self.outliers.len(),
                 self.count,
+            )?;
+            if self.flush_denormals {
+                write!(f, ", denormal only: {}", self.denormal_only)?
+            }
+            Ok(())
+        }
+    }
+
+    /// 计算两个向量的余弦相似度，两者都为零向量时视为完全相似。
+    pub fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+        assert_eq!(a.len(), b.len());
+        let (dot, aa, bb) = a
+            .iter()
+            .zip(b)
+            .fold((0f64, 0f64, 0f64), |(dot, aa, bb), (a, b)| {
+                (dot + a * b, aa + a * a, bb + b * b)
+            });
+        if aa == 0. && bb == 0. {
+            1.
+        } else {
+            dot / (aa.sqrt() * bb.sqrt())
+        }
+    }
+
+    /// 按行收集参考值与待测值的余弦相似度，低于阈值的行记为离群行。
+    pub struct CosineCollector {
+        threshold: f64,
+        min_sim: f64,
+        outliers: Vec<usize>,
+        count: usize,
+    }
+
+    impl CosineCollector {
+        pub fn new(threshold: f64) -> Self {
+            Self {
+                threshold,
+                min_sim: 1.,
+                outliers: vec![],
+                count: 0,
+            }
+        }
+
+        pub fn push(&mut self, a: &[f64], b: &[f64]) {
+            let sim = cosine_similarity(a, b);
+            self.min_sim = f64::min(self.min_sim, sim);
+
+            if sim.is_nan() || sim < self.threshold {
+                self.outliers.push(self.count);
+            }
+
+            self.count += 1;
+        }
+
+        pub fn summary(self) -> (usize, usize) {
+            (self.outliers.len(), self.count)
+        }
+
+        pub fn outliers(&self) -> &[usize] {
+            &self.outliers
+        }
+    }
+
+    impl fmt::Display for CosineCollector {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(
+                f,
+                "min cos: {:.6}, outliers: {}/{}",
+                self.min_sim,
+                self.outliers.len(),
+                self.count,
             )
         }
     }
+
+    /// 分别在待测硬件和 CPU 上发射算子，用 `ec` 比较两侧输出，离群值不得超过千分之一。
+    ///
+    /// `args` 和 `cpu_args` 构造两侧的参数，`output` 和 `cpu_output` 在发射后读出两侧的输出。
+    #[allow(clippy::too_many_arguments)]
+    pub fn assert_matches_cpu<Op, Ref, QA>(
+        op: &Op,
+        args: impl FnOnce() -> Op::Args,
+        queue_alloc: &QA,
+        output: impl FnOnce() -> Vec<f64>,
+        cpu_op: &Ref,
+        cpu_args: impl FnOnce() -> Ref::Args,
+        cpu_output: impl FnOnce() -> Vec<f64>,
+        mut ec: ErrorCollector,
+    ) where
+        Op: crate::Operator,
+        Ref: crate::Operator<Hardware = crate::common_cpu::Cpu>,
+        QA: crate::QueueAlloc<Hardware = Op::Hardware>,
+    {
+        use crate::common_cpu::ThisThread;
+
+        op.launch(&args(), &mut [], queue_alloc).unwrap();
+        let ans = output();
+        cpu_op.launch(&cpu_args(), &mut [], &ThisThread).unwrap();
+        let expected = cpu_output();
+
+        assert_eq!(ans.len(), expected.len());
+        expected
+            .into_iter()
+            .zip(ans)
+            .for_each(|(a, b)| ec.push(Diff::new(a, b)));
+        println!("{ec}");
+
+        let (out, count) = ec.summary();
+        assert!(out * 1000 <= count);
+    }
+
+    #[test]
+    fn test_assert_matches_cpu() {
+        use crate::{
+            common_cpu::{Cpu, ThisThread},
+            rope::{common_cpu::Operator as Rope, Args},
+            Operator as _, TensorLayout,
+        };
+        use digit_layout::types as ty;
+
+        const NT: usize = 3;
+        let (nh, dh) = (2, 8);
+        let p = [0u32, 2, 5];
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f64).sin())
+            .collect::<Vec<_>>();
+        let args = |dt, t_base| {
+            Args::<Cpu>::builder(
+                TensorLayout::new_contiguous(dt, &[NT, nh, dh]),
+                t_base,
+                TensorLayout::new_contiguous(ty::U32, &[NT]),
+                p.as_ptr().cast(),
+                1e4,
+            )
+            .build()
+        };
+
+        // f32 与 f64 参考结果对比
+        let mut t_ans = t.iter().map(|&x| x as f32).collect::<Vec<_>>();
+        let mut t_ref = t;
+        let ans = t_ans.as_mut_ptr();
+        let expected = t_ref.as_mut_ptr();
+        let op = Rope::new(&Cpu);
+        assert_matches_cpu(
+            &op,
+            || args(ty::F32, ans.cast()),
+            &ThisThread,
+            || t_ans.iter().map(|&x| x as f64).collect(),
+            &op,
+            || args(ty::F64, expected.cast()),
+            || t_ref.clone(),
+            ErrorCollector::new(f32::EPSILON as f64, 1e-3),
+        );
+    }
+
+    #[test]
+    fn test_rows_sum_to_one() {
+        let data = [0.25, 0.75, 0.5, 0.5, 0.1, 0.2];
+        assert_eq!(rows_sum_to_one(data.chunks(2).take(2), 1e-12), Ok(()));
+        assert_eq!(rows_sum_to_one(data.chunks(2), 1e-12), Err(2));
+        assert_eq!(rows_sum_to_one([&[f64::NAN][..]], 1e-12), Err(0));
+    }
+
+    #[test]
+    fn test_all_within() {
+        assert_eq!(all_within([0., 0.5, 1.], 0., 1.), Ok(()));
+        assert_eq!(all_within([0., 1.5, -1.], 0., 1.), Err(1));
+        assert_eq!(all_within([0., f64::NAN], 0., 1.), Err(1));
+    }
+    #[test]
+    fn test_cosine_collector() {
+        let mut cc = CosineCollector::new(0.999);
+        cc.push(&[1., 2., 3.], &[1.001, 2., 2.999]);
+        cc.push(&[1., 0.], &[0., 1.]);
+        cc.push(&[0., 0.], &[0., 0.]);
+        assert_eq!(cc.outliers(), [1]);
+        assert_eq!(cc.summary(), (1, 3));
+    }
+    #[test]
+    fn test_flush_denormals() {
+        let denormal = f32::MIN_POSITIVE as f64 / 4.;
+        let pairs = [(denormal, 0.), (-denormal, 0.), (1., 1.), (0.5, 0.25)];
+
+        let mut ec = ErrorCollector::new(0., 1e-3);
+        pairs.iter().for_each(|&(a, b)| ec.push_pair(a, b));
+        assert_eq!(ec.outliers(), [0, 1, 3]);
+
+        let mut ec = ErrorCollector::new(0., 1e-3).flush_denormals();
+        pairs.iter().for_each(|&(a, b)| ec.push_pair(a, b));
+        assert_eq!(ec.outliers(), [3]);
+        assert_eq!(ec.denormal_only(), 2);
+        assert_eq!(flush_denormal(-denormal).to_bits(), (-0f64).to_bits());
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let path = std::env::temp_dir().join(format!("operators-diff-{}.csv", std::process::id()));
+
+        let mut ec = ErrorCollector::new(1e-3, 1e-3).record_outliers();
+        ec.push_pair(1., 1.);
+        ec.push_pair(2., 3.);
+        ec.push_pair(0.5, 0.5005);
+        ec.push(Diff { abs: 1., rel: 0.5 });
+        ec.push_pair(-4., 4.);
+        ec.write_csv(&path).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let expected = [(1, 2., 3.), (4, -4., 4.)].map(|(i, a, b)| {
+            let diff = Diff::new(a, b);
+            format!("{i},{a},{b},{},{}", diff.abs, diff.rel)
+        });
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "index,expected,actual,abs,rel",
+                expected[0].as_str(),
+                "3,,,1,0.5",
+                expected[1].as_str(),
+            ]
+        );
+        assert_eq!(ec.outliers(), [1, 3, 4]);
+    }
 }
@@ -0,0 +1,118 @@
+use super::{static_from, SchemeError, TensorLayout};
+use std::iter::zip;
+
+/// 两个形状相同的张量逐元素对应时合并连续维度后的布局，步长以字节为单位，按第一个张量的步长从大到小排列。
+///
+/// 长度为 1 的维度被丢弃，两侧步长都恰好是后一维整数倍的相邻维度合并为一维。
+/// 步长为 0 的维度只会与同侧步长也为 0 的维度合并，因此合并后仍能检查广播。
+pub(crate) struct PairLayout {
+    pub shape: Vec<usize>,
+    pub strides: [Vec<isize>; 2],
+}
+
+impl PairLayout {
+    /// 调用者须保证 `a` 和 `b` 的形状相同。
+    pub fn new(a: &TensorLayout, b: &TensorLayout) -> Result<Self, SchemeError> {
+        let mut dims = Vec::with_capacity(a.ndim());
+        for ((d, sa), sb) in zip(zip(a.shape(), a.strides()), b.strides()) {
+            let d = *static_from(d)?;
+            let sa = *static_from(sa)?;
+            let sb = *static_from(sb)?;
+            if d != 1 {
+                dims.push((d, sa, sb))
+            }
+        }
+        dims.sort_by_key(|&(_, sa, _)| std::cmp::Reverse(sa.abs()));
+
+        let mut shape = Vec::<usize>::with_capacity(dims.len());
+        let mut strides_a = Vec::<isize>::with_capacity(dims.len());
+        let mut strides_b = Vec::<isize>::with_capacity(dims.len());
+        for (d, sa, sb) in dims {
+            match (shape.last_mut(), strides_a.last_mut(), strides_b.last_mut()) {
+                // 前一维恰好是这一维的整数倍，合并为一维
+                (Some(d_), Some(sa_), Some(sb_))
+                    if *sa_ == sa * d as isize && *sb_ == sb * d as isize =>
+                {
+                    *d_ *= d;
+                    *sa_ = sa;
+                    *sb_ = sb;
+                }
+                _ => {
+                    shape.push(d);
+                    strides_a.push(sa);
+                    strides_b.push(sb);
+                }
+            }
+        }
+        Ok(Self {
+            shape,
+            strides: [strides_a, strides_b],
+        })
+    }
+
+    /// 元素数量。
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.shape.iter().product()
+    }
+
+    /// 按合并后的形状行优先编号的第 `i` 个元素在两侧的字节偏移。
+    pub fn offsets(&self, i: usize) -> [isize; 2] {
+        let [sa, sb] = &self.strides;
+        let mut rem = i;
+        let mut ans = [0; 2];
+        for ((&d, &sa), &sb) in zip(zip(&self.shape, sa), sb).rev() {
+            let k = (rem % d) as isize;
+            ans[0] += k * sa;
+            ans[1] += k * sb;
+            rem /= d;
+        }
+        ans
+    }
+}
+
+#[test]
+fn test_pair_layout() {
+    use digit_layout::types::{F16, F32};
+
+    // [2, 3, 4] 的 f32 转置为 [4, 3, 2] 后对应连续的 f16
+    let layout = PairLayout::new(
+        &TensorLayout::new_contiguous(F16, &[4, 3, 2]),
+        &TensorLayout::new(F32, &[4, 3, 2], &[4, 16, 48]),
+    )
+    .unwrap();
+    assert_eq!(layout.shape, [4, 3, 2]);
+    assert_eq!(layout.strides, [[12, 4, 2], [4, 16, 48]]);
+    assert_eq!(layout.count(), 24);
+    assert_eq!(layout.offsets(0), [0, 0]);
+    assert_eq!(layout.offsets(7), [14, 52]);
+
+    // 两侧都连续时合并为一维，长度为 1 的维度被丢弃
+    let layout = PairLayout::new(
+        &TensorLayout::new_contiguous(F16, &[4, 1, 6]),
+        &TensorLayout::new_contiguous(F32, &[4, 1, 6]),
+    )
+    .unwrap();
+    assert_eq!(layout.shape, [24]);
+    assert_eq!(layout.strides, [[2], [4]]);
+    assert_eq!(layout.offsets(5), [10, 20]);
+
+    // 广播的维度保留步长 0
+    let layout = PairLayout::new(
+        &TensorLayout::new_contiguous(F32, &[4, 6]),
+        &TensorLayout::new(F32, &[4, 6], &[0, 4]),
+    )
+    .unwrap();
+    assert_eq!(layout.shape, [4, 6]);
+    assert_eq!(layout.strides, [[24, 4], [0, 4]]);
+
+    // 标量
+    let layout = PairLayout::new(
+        &TensorLayout::new_contiguous(F32, &[]),
+        &TensorLayout::new_contiguous(F32, &[]),
+    )
+    .unwrap();
+    assert!(layout.shape.is_empty());
+    assert_eq!(layout.count(), 1);
+    assert_eq!(layout.offsets(0), [0, 0]);
+}
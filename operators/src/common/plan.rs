@@ -0,0 +1,22 @@
+/// 算子规划出的执行方案描述。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SchemePlan {
+    /// 工作空间需求，保证不大于规划时传入的最大工作空间容量。
+    pub workspace_size: usize,
+    /// 工作空间基址的对齐要求。
+    pub align: usize,
+    /// 后端选用的算法标签，仅用于诊断和调度统计。
+    pub algorithm: &'static str,
+}
+
+impl SchemePlan {
+    /// 不声明具体算法的方案，用于只提供工作空间需求的算子。
+    #[inline]
+    pub const fn from_size(workspace_size: usize) -> Self {
+        Self {
+            workspace_size,
+            align: 1,
+            algorithm: "default",
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{args_not_support, ByteOf, LaunchError, Operator, QueueAlloc, SchemeError};
+use std::{any::Any, collections::HashMap};
+
+/// 擦除了参数类型的算子，可以装箱为 trait 对象，按名称动态分发。
+///
+/// 参数以 [`Any`] 传入，类型与算子的 [`Operator::Args`] 不符时返回错误。
+pub trait DynOperator<QA: QueueAlloc> {
+    /// 见 [`Operator::scheme`]。
+    fn scheme(&mut self, args: &dyn Any, max_workspace_size: usize) -> Result<usize, SchemeError>;
+    /// 见 [`Operator::launch`]。
+    fn launch(
+        &self,
+        args: &dyn Any,
+        workspace: &mut [ByteOf<QA::Hardware>],
+        queue_alloc: &QA,
+    ) -> Result<(), LaunchError>;
+}
+
+impl<Op, QA> DynOperator<QA> for Op
+where
+    Op: Operator,
+    Op::Args: 'static,
+    QA: QueueAlloc<Hardware = Op::Hardware>,
+{
+    fn scheme(&mut self, args: &dyn Any, max_workspace_size: usize) -> Result<usize, SchemeError> {
+        Operator::scheme(self, downcast::<Op>(args)?, max_workspace_size)
+    }
+
+    fn launch(
+        &self,
+        args: &dyn Any,
+        workspace: &mut [ByteOf<QA::Hardware>],
+        queue_alloc: &QA,
+    ) -> Result<(), LaunchError> {
+        Operator::launch(self, downcast::<Op>(args)?, workspace, queue_alloc)
+    }
+}
+
+fn downcast<Op: Operator>(args: &dyn Any) -> Result<&Op::Args, SchemeError>
+where
+    Op::Args: 'static,
+{
+    args.downcast_ref().ok_or_else(|| {
+        args_not_support(format!(
+            "args of {} expected",
+            std::any::type_name::<Op::Args>()
+        ))
+    })
+}
+
+type Constructor<QA, N> = Box<dyn Fn(&N) -> Box<dyn DynOperator<QA>>>;
+
+/// 算子注册表，将算子名称映射到在拓扑节点 `N` 上构造算子的函数。
+///
+/// 每种硬件（由队列分配器 `QA` 决定）使用独立的注册表，供按计算图中的名称构造算子。
+pub struct Registry<QA, N> {
+    constructors: HashMap<String, Constructor<QA, N>>,
+}
+
+impl<QA, N> Default for Registry<QA, N> {
+    fn default() -> Self {
+        Self {
+            constructors: HashMap::new(),
+        }
+    }
+}
+
+impl<QA: QueueAlloc, N> Registry<QA, N> {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 以 `name` 注册算子 `Op`，名称已存在时替换并返回 `false`。
+    pub fn register<Op>(&mut self, name: impl Into<String>) -> bool
+    where
+        Op: Operator<Hardware = QA::Hardware, TopoNode = N> + 'static,
+        Op::Args: 'static,
+    {
+        let constructor: Constructor<QA, N> =
+            Box::new(|node: &N| -> Box<dyn DynOperator<QA>> { Box::new(Op::new(node)) });
+        self.constructors.insert(name.into(), constructor).is_none()
+    }
+
+    /// 在 `node` 上构造名为 `name` 的算子，未注册时返回 `None`。
+    pub fn build(&self, name: &str, node: &N) -> Option<Box<dyn DynOperator<QA>>> {
+        self.constructors.get(name).map(|f| f(node))
+    }
+
+    /// 已注册的算子名称，顺序不定。
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.constructors.keys().map(String::as_str)
+    }
+}
+
+#[test]
+fn test_registry() {
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        rearrange, rope, TensorLayout,
+    };
+    use digit_layout::types as ty;
+
+    let mut registry = Registry::<ThisThread, Cpu>::new();
+    assert!(registry.register::<rope::common_cpu::Operator>("rope"));
+    assert!(registry.register::<rearrange::common_cpu::Operator>("rearrange"));
+    assert!(!registry.register::<rearrange::common_cpu::Operator>("rearrange"));
+    let mut names = registry.names().collect::<Vec<_>>();
+    names.sort_unstable();
+    assert_eq!(names, ["rearrange", "rope"]);
+    assert!(registry.build("gelu", &Cpu).is_none());
+
+    // 按名称构造 rearrange，转置 [2, 3]
+    let src = (0..6u32).collect::<Vec<_>>();
+    let mut dst = vec![0u32; 6];
+    let unit = size_of::<u32>() as isize;
+    let args = rearrange::Args::<Cpu> {
+        dst_layout: TensorLayout::new_contiguous(ty::U32, &[2, 3]),
+        dst_base: dst.as_mut_ptr().cast(),
+        src_layout: TensorLayout::new(ty::U32, &[2, 3], &[unit, 2 * unit]),
+        src_base: src.as_ptr().cast(),
+        scale: 1.,
+        bias: 0.,
+    };
+    let mut op = registry.build("rearrange", &Cpu).unwrap();
+    op.scheme(&args, 0).unwrap();
+    op.launch(&args, &mut [], &ThisThread).unwrap();
+    assert_eq!(dst, [0, 2, 4, 1, 3, 5]);
+
+    // 按名称构造 rope，位置 0 不旋转
+    let mut t = vec![1.0f32; 2 * 4];
+    let pos = [0u32, 1];
+    let args = rope::Args::<Cpu>::builder(
+        TensorLayout::new_contiguous(ty::F32, &[2, 1, 4]),
+        t.as_mut_ptr().cast(),
+        TensorLayout::new_contiguous(ty::U32, &[2]),
+        pos.as_ptr().cast(),
+        1e4,
+    )
+    .build();
+    let op = registry.build("rope", &Cpu).unwrap();
+    op.launch(&args, &mut [], &ThisThread).unwrap();
+    assert_eq!(t[..4], [1.; 4]);
+    assert_ne!(t[4..], [1.; 4]);
+
+    // 参数类型不符时报错
+    let err = op.launch(&0u32, &mut [], &ThisThread).unwrap_err();
+    assert_eq!(
+        err.kind,
+        crate::LaunchErrorKind::Scheme(crate::SchemeErrorKind::ArgsNotSupport)
+    );
+}
@@ -1,8 +1,13 @@
-﻿use crate::MaybeDyn;
+﻿use crate::{
+    dyn_not_support, rank_mismatch, shape_mismatch, static_from, ByteOf, ConstPtr, Hardware,
+    MaybeDyn, MutPtr, QueueAlloc, SchemeError,
+};
 use digit_layout::DigitLayout;
 use ndarray_layout::ArrayLayout;
 use std::{
     alloc::{alloc, dealloc, Layout},
+    iter::zip,
+    ops::Range,
     ptr::{copy_nonoverlapping, NonNull},
     slice::from_raw_parts,
 };
@@ -29,7 +34,18 @@ impl TensorLayout {
 
     pub fn new(dt: DigitLayout, shape: &[usize], strides: &[isize]) -> Self {
         assert_eq!(shape.len(), strides.len());
+        unsafe { Self::from_raw(dt, shape, strides) }
+    }
 
+    /// 直接由其他框架的形状和步长数组构造布局，不做任何检查。
+    ///
+    /// # Safety
+    ///
+    /// - `shape` 和 `strides` 长度相同；
+    /// - `strides` 以字节为单位；
+    /// - 其中的值都被视作静态值，不能与动态值的标记冲突。
+    pub unsafe fn from_raw(dt: DigitLayout, shape: &[usize], strides: &[isize]) -> Self {
+        debug_assert_eq!(shape.len(), strides.len());
         unsafe {
             let ptr = alloc(Self::layout(shape.len()));
 
@@ -91,6 +107,57 @@ impl TensorLayout {
         unsafe { from_raw_parts(ptr.add(2 + len), len) }
     }
 
+    /// 张量访问的字节范围，相对于基址。
+    ///
+    /// 形状或步长中存在动态值时返回 `None`。
+    pub fn byte_range(&self) -> Option<Range<isize>> {
+        let shape = MaybeDyn::get_all(self.shape())?;
+        let strides = MaybeDyn::get_all(self.strides())?;
+        let mut range = 0..self.dt().nbytes() as isize;
+        for (&d, &s) in zip(shape, strides) {
+            if d == 0 {
+                return Some(0..0);
+            }
+            let offset = (d - 1) as isize * s;
+            if offset < 0 {
+                range.start += offset
+            } else {
+                range.end += offset
+            }
+        }
+        Some(range)
+    }
+
+    /// 按广播规则将布局扩展到 `shape`，扩展的维度步长为 0，不复制数据。
+    ///
+    /// 与 numpy 相同，从末维开始对齐，新增的前导维度和长度为 1 的维度可以扩展，
+    /// 其余维度的长度必须相同。形状或步长为动态值时报错。
+    pub fn broadcast_to(&self, shape: &[usize]) -> Result<Self, SchemeError> {
+        let ndim = self.ndim();
+        if shape.len() < ndim {
+            return Err(rank_mismatch(format!(
+                "cannot broadcast rank {ndim} to rank {}",
+                shape.len()
+            )));
+        }
+        let lead = shape.len() - ndim;
+        let mut strides = vec![0isize; shape.len()];
+        for (i, (d, s)) in zip(self.shape(), self.strides()).enumerate() {
+            let (d, s) = (*static_from(d)?, *static_from(s)?);
+            let target = shape[lead + i];
+            strides[lead + i] = match d {
+                _ if d == target => s,
+                1 => 0,
+                _ => {
+                    return Err(shape_mismatch(format!(
+                        "cannot broadcast dim {i} of length {d} to {target}"
+                    )))
+                }
+            };
+        }
+        Ok(Self::new(self.dt(), shape, &strides))
+    }
+
     #[inline(always)]
     fn layout(ndim: usize) -> Layout {
         Layout::array::<usize>(2 + ndim * 2).unwrap()
@@ -118,3 +185,150 @@ impl Drop for TensorLayout {
         unsafe { dealloc(ptr, layout) }
     }
 }
+
+/// 分配足以容纳 `layout` 访问的全部元素的存储，内容未初始化。
+///
+/// 存储大小为 [`TensorLayout::byte_range`] 的长度。步长中存在负值时，
+/// 张量基址应位于存储起点之后 `-byte_range().start` 字节处。
+pub fn empty_like<QA: QueueAlloc>(
+    layout: &TensorLayout,
+    queue_alloc: &QA,
+) -> Result<QA::DevMem, SchemeError> {
+    let range = layout
+        .byte_range()
+        .ok_or_else(|| dyn_not_support("empty_like: dynamic layout"))?;
+    Ok(queue_alloc.alloc((range.end - range.start) as _))
+}
+
+/// 与 [`empty_like`] 相同，但将存储清零。
+///
+/// 只适用于主机可直接访问的存储，设备存储应由对应后端的队列清零。
+pub fn zeros_like<QA>(layout: &TensorLayout, queue_alloc: &QA) -> Result<QA::DevMem, SchemeError>
+where
+    QA: QueueAlloc,
+    QA::Hardware: Hardware<Byte = u8>,
+{
+    let mut mem = empty_like(layout, queue_alloc)?;
+    mem.fill(0);
+    Ok(mem)
+}
+
+/// 将 `mem` 视作形状为 `shape` 的连续张量，返回其布局和基址。
+///
+/// `mem` 不足以容纳张量时返回错误，多余的部分被忽略。
+pub fn contiguous<H: Hardware>(
+    dt: DigitLayout,
+    shape: &[usize],
+    mem: &[ByteOf<H>],
+) -> Result<(TensorLayout, ConstPtr<H>), SchemeError> {
+    check_size(dt, shape, mem.len())?;
+    Ok((TensorLayout::new_contiguous(dt, shape), mem.as_ptr()))
+}
+
+/// 与 [`contiguous`] 相同，但返回可变的基址。
+pub fn contiguous_mut<H: Hardware>(
+    dt: DigitLayout,
+    shape: &[usize],
+    mem: &mut [ByteOf<H>],
+) -> Result<(TensorLayout, MutPtr<H>), SchemeError> {
+    check_size(dt, shape, mem.len())?;
+    Ok((TensorLayout::new_contiguous(dt, shape), mem.as_mut_ptr()))
+}
+
+fn check_size(dt: DigitLayout, shape: &[usize], len: usize) -> Result<(), SchemeError> {
+    // 含长度为 0 的维度时不需要存储，不论其他维度多大
+    let size = if shape.contains(&0) {
+        0
+    } else {
+        shape
+            .iter()
+            .try_fold(dt.nbytes(), |acc, &d| acc.checked_mul(d))
+            .ok_or_else(|| {
+                shape_mismatch(format!(
+                    "tensor {shape:?} of {dt} overflows the address space"
+                ))
+            })?
+    };
+    if size > len {
+        return Err(shape_mismatch(format!(
+            "tensor {shape:?} of {dt} needs {size} bytes, but memory has {len}"
+        )));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_broadcast_to() {
+    use crate::SchemeErrorKind;
+    use digit_layout::types::F32;
+
+    let (n, d) = (5, 8);
+    let row = TensorLayout::new_contiguous(F32, &[1, d]);
+    let layout = row.broadcast_to(&[n, d]).unwrap();
+    assert_eq!(MaybeDyn::get_all(layout.shape()), Some(&[n, d][..]));
+    assert_eq!(MaybeDyn::get_all(layout.strides()), Some(&[0, 4][..]));
+    // 所有行共享同一块存储
+    assert_eq!(layout.byte_range(), Some(0..d as isize * 4));
+
+    // 新增前导维度
+    let vec = TensorLayout::new_contiguous(F32, &[d]);
+    let layout = vec.broadcast_to(&[2, n, d]).unwrap();
+    assert_eq!(MaybeDyn::get_all(layout.strides()), Some(&[0, 0, 4][..]));
+
+    let err = row.broadcast_to(&[n, d + 1]).unwrap_err();
+    assert_eq!(err.kind, SchemeErrorKind::ShapeMismatch);
+    let err = row.broadcast_to(&[d]).unwrap_err();
+    assert_eq!(err.kind, SchemeErrorKind::RankMismatch);
+}
+
+#[test]
+fn test_contiguous() {
+    use crate::common_cpu::Cpu;
+    use digit_layout::types::F32;
+
+    let mut mem = vec![0u8; 6 * size_of::<f32>() + 3];
+    let (layout, base) = contiguous_mut::<Cpu>(F32, &[2, 3], &mut mem).unwrap();
+    assert_eq!(base, mem.as_mut_ptr());
+    assert_eq!(layout.byte_range(), Some(0..24));
+
+    let err = contiguous::<Cpu>(F32, &[2, 4], &mem).unwrap_err();
+    assert_eq!(err.kind, crate::SchemeErrorKind::ShapeMismatch);
+
+    // 字节数溢出时报错，而不是回绕后通过检查
+    let err = contiguous::<Cpu>(F32, &[usize::MAX / 2, 3], &mem).unwrap_err();
+    assert_eq!(err.kind, crate::SchemeErrorKind::ShapeMismatch);
+}
+
+#[test]
+fn test_empty_like() {
+    use crate::common_cpu::ThisThread;
+    use digit_layout::types::F32;
+
+    let layout = TensorLayout::new_contiguous(F32, &[7, 32, 64]);
+    let mem = empty_like(&layout, &ThisThread).unwrap();
+    assert_eq!(mem.len(), 7 * 32 * 64 * size_of::<f32>());
+
+    // 负步长与非连续布局只需覆盖实际访问的范围
+    let layout = TensorLayout::new(F32, &[7, 32], &[-512, 4]);
+    let mem = zeros_like(&layout, &ThisThread).unwrap();
+    assert_eq!(mem.len(), 6 * 512 + 32 * 4);
+    assert!(mem.iter().all(|&b| b == 0));
+
+    let layout = TensorLayout::new_dyn(F32, &[crate::dyn_(); 2], &[crate::dyn_(); 2]);
+    assert!(empty_like(&layout, &ThisThread).is_err());
+}
+
+#[test]
+fn test_from_raw() {
+    use digit_layout::types::F16;
+
+    // 例如来自其他张量库的 [2, 3, 4] 转置视图
+    let shape = [2usize, 4, 3];
+    let strides = [24isize, 2, 8];
+    let layout = unsafe { TensorLayout::from_raw(F16, &shape, &strides) };
+    assert_eq!(layout.dt(), F16);
+    assert_eq!(layout.ndim(), 3);
+    assert_eq!(MaybeDyn::get_all(layout.shape()), Some(&shape[..]));
+    assert_eq!(MaybeDyn::get_all(layout.strides()), Some(&strides[..]));
+    assert_eq!(layout.byte_range(), Some(0..24 + 3 * 2 + 2 * 8 + 2));
+}
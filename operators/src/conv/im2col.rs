@@ -204,6 +204,8 @@ where
                 dst_base: *y_base,
                 src_layout: TensorLayout::new(dt, b.shape(), b.strides()),
                 src_base: *b_base,
+                scale: 1.,
+                bias: 0.,
             },
             workspace,
             queue_alloc,
@@ -220,6 +222,8 @@ where
                 dst_base: b_mem.as_mut_ptr(),
                 src_layout: b_src,
                 src_base: *x_base,
+                scale: 1.,
+                bias: 0.,
             },
             workspace,
             queue_alloc,
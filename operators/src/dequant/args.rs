@@ -0,0 +1,177 @@
+use crate::{
+    args_not_support, shape_mismatch, type_not_support,
+    utils::{dim_distinct, rank_error},
+    ConstPtr, Hardware, MaybeDyn, MutPtr, SchemeError, TensorLayout,
+};
+use digit_layout::{types as ty, DigitLayout};
+use std::ptr::{null, null_mut};
+
+pub struct Args<H: Hardware> {
+    /// 输出 [n, d]，浮点类型。
+    pub y_layout: TensorLayout,
+    pub y_base: MutPtr<H>,
+    /// 逐行打包的量化值 [n, ⌈d · bits / 8⌉]，`u8` 类型。
+    ///
+    /// 4 位量化时每个字节的低 4 位存储偶数下标的值，高 4 位存储奇数下标的值。
+    pub q_layout: TensorLayout,
+    pub q_base: ConstPtr<H>,
+    /// 每个块的缩放 [n, ⌈d / block_size⌉]，浮点类型。
+    pub scale_layout: TensorLayout,
+    pub scale_base: ConstPtr<H>,
+    /// 每个块的零点 [n, ⌈d / block_size⌉]，`u8` 类型。
+    ///
+    /// 为 `None` 时为对称量化，零点取 2^(bits - 1)。
+    pub zero: Option<(TensorLayout, ConstPtr<H>)>,
+    /// 每个量化值的位数，支持 4 和 8。
+    pub bits: usize,
+    /// 每个块的量化值数量。
+    pub block_size: usize,
+}
+
+pub(super) struct Meta {
+    pub dt_y: DigitLayout,
+    pub dt_s: DigitLayout,
+    pub n: MaybeDyn<usize>,
+    pub d: MaybeDyn<usize>,
+}
+
+impl<H: Hardware> Args<H> {
+    pub fn new_null(
+        y_layout: TensorLayout,
+        q_layout: TensorLayout,
+        scale_layout: TensorLayout,
+        zero_layout: Option<TensorLayout>,
+        bits: usize,
+        block_size: usize,
+    ) -> Self {
+        Self {
+            y_layout,
+            y_base: null_mut(),
+            q_layout,
+            q_base: null(),
+            scale_layout,
+            scale_base: null(),
+            zero: zero_layout.map(|layout| (layout, null())),
+            bits,
+            block_size,
+        }
+    }
+
+    pub(super) fn meta(&self) -> Result<Meta, SchemeError> {
+        let Self {
+            y_layout: y,
+            q_layout: q,
+            scale_layout: scale,
+            zero,
+            bits,
+            block_size,
+            ..
+        } = self;
+
+        if !matches!(bits, 4 | 8) {
+            return Err(args_not_support(format!("{bits}-bit quantization")));
+        }
+        if *block_size == 0 {
+            return Err(args_not_support("block size must be positive"));
+        }
+
+        let &[ny, d] = y.shape() else {
+            return Err(rank_error("y", 2, y.ndim()));
+        };
+        let &[nq, dq] = q.shape() else {
+            return Err(rank_error("q", 2, q.ndim()));
+        };
+        let &[ns, bs] = scale.shape() else {
+            return Err(rank_error("scale", 2, scale.ndim()));
+        };
+
+        let is_float = |dt: DigitLayout| {
+            use digit_layout::LayoutContent::Real;
+            matches!(dt.decode(), Real { exponent: 1.., .. })
+        };
+        for (arg, dt) in [("y", y.dt()), ("scale", scale.dt())] {
+            if !is_float(dt) {
+                return Err(type_not_support(format!(
+                    "{arg}: data type {dt} is not supported, must be floating-point numbers"
+                )));
+            }
+        }
+        if q.dt() != ty::U8 {
+            return Err(type_not_support(format!("q: {}, u8 expected", q.dt())));
+        }
+
+        // 列数由 d 决定，最后一个块和最后一个字节可以不满
+        let columns = |arg: &str, actual: MaybeDyn<usize>, expected: Option<usize>| match (
+            actual.get_static(),
+            expected,
+        ) {
+            (Some(&actual), Some(expected)) if actual != expected => Err(shape_mismatch(format!(
+                "{arg} has {actual} columns, {expected} expected"
+            ))),
+            _ => Ok(()),
+        };
+        let d_ = d.get_static().copied();
+        let blocks = d_.map(|d| d.div_ceil(*block_size));
+        columns("q", dq, d_.map(|d| (d * bits).div_ceil(8)))?;
+        columns("scale", bs, blocks)?;
+
+        let mut rows = vec![ny, nq, ns];
+        if let Some((zero, _)) = zero {
+            let &[nz, bz] = zero.shape() else {
+                return Err(rank_error("zero", 2, zero.ndim()));
+            };
+            if zero.dt() != ty::U8 {
+                return Err(type_not_support(format!(
+                    "zero: {}, u8 expected",
+                    zero.dt()
+                )));
+            }
+            columns("zero", bz, blocks)?;
+            rows.push(nz);
+        }
+
+        Ok(Meta {
+            dt_y: y.dt(),
+            dt_s: scale.dt(),
+            n: dim_distinct(&rows)?,
+            d,
+        })
+    }
+}
+
+#[test]
+fn test_meta() {
+    use digit_layout::types::{F16, F32, U8};
+    type Args = super::Args<crate::common_cpu::Cpu>;
+
+    // 10 个 4 位量化值占 5 个字节，每块 4 个值共 3 块，最后一块只有 2 个值
+    let args = Args::new_null(
+        TensorLayout::new_contiguous(F16, &[2, 10]),
+        TensorLayout::new_contiguous(U8, &[2, 5]),
+        TensorLayout::new_contiguous(F32, &[2, 3]),
+        Some(TensorLayout::new_contiguous(U8, &[2, 3])),
+        4,
+        4,
+    );
+    let meta = args.meta().unwrap();
+    assert_eq!(meta.n.get_static(), Some(&2));
+    assert_eq!(meta.d.get_static(), Some(&10));
+
+    let new = |dq: usize, bs: usize, bits: usize, block_size: usize| {
+        Args::new_null(
+            TensorLayout::new_contiguous(F16, &[2, 9]),
+            TensorLayout::new_contiguous(U8, &[2, dq]),
+            TensorLayout::new_contiguous(F32, &[2, bs]),
+            None,
+            bits,
+            block_size,
+        )
+    };
+    // 9 个 4 位量化值向上取整为 5 个字节
+    assert!(new(5, 3, 4, 4).meta().is_ok());
+    assert!(new(4, 3, 4, 4).meta().is_err());
+    assert!(new(9, 3, 8, 4).meta().is_ok());
+    assert!(new(9, 2, 8, 4).meta().is_err());
+    assert!(new(9, 3, 3, 4).meta().is_err());
+    assert!(new(9, 3, 8, 0).meta().is_err());
+}
@@ -0,0 +1,234 @@
+use super::{args::Meta, Args, Dequant};
+use crate::{
+    common_cpu::Cpu, get_static, strides_not_support, type_not_support, ByteOf, LaunchError,
+    QueueAlloc, SchemeError,
+};
+use digit_layout::types as ty;
+use half::f16;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+pub struct Operator;
+
+impl Dequant<Cpu> for Operator {}
+
+impl crate::Operator for Operator {
+    type Hardware = Cpu;
+    type TopoNode = Cpu;
+    type Args = Args<Cpu>;
+
+    #[inline]
+    fn new(_node: &Self::TopoNode) -> Self {
+        Self
+    }
+
+    fn scheme(
+        &mut self,
+        args: &Self::Args,
+        _max_workspace_size: usize,
+    ) -> Result<usize, SchemeError> {
+        let _meta = args.meta()?;
+        Ok(0)
+    }
+
+    fn launch<QA>(
+        &self,
+        args: &Self::Args,
+        _workspace: &mut [ByteOf<Self::Hardware>],
+        _queue_alloc: &QA,
+    ) -> Result<(), LaunchError>
+    where
+        QA: QueueAlloc<Hardware = Self::Hardware>,
+    {
+        let Meta { dt_y, dt_s, n, d } = args.meta()?;
+        let Args {
+            y_layout,
+            y_base,
+            q_layout,
+            q_base,
+            scale_layout,
+            scale_base,
+            zero,
+            bits,
+            block_size,
+        } = args;
+        let &[syn, syd] = y_layout.strides() else {
+            unreachable!()
+        };
+        let &[sqn, sqd] = q_layout.strides() else {
+            unreachable!()
+        };
+        let &[ssn, ssb] = scale_layout.strides() else {
+            unreachable!()
+        };
+
+        get_static! {
+            n   d
+            syn syd
+            sqn sqd
+            ssn ssb
+        }
+        // 量化值按字节打包，同一行的字节必须连续
+        if sqd != 1 {
+            Err(strides_not_support(
+                "cpu: packed q must be contiguous in a row",
+            ))?;
+        }
+
+        let zero = match zero {
+            Some((layout, base)) => {
+                let &[szn, szb] = layout.strides() else {
+                    unreachable!()
+                };
+                get_static!(szn szb);
+                Some((base.cast(), [szn, szb]))
+            }
+            None => None,
+        };
+
+        let load: unsafe fn(*const u8) -> f32 = match dt_s {
+            ty::F16 => |ptr| unsafe { ptr.cast::<f16>().read_unaligned() }.to_f32(),
+            ty::F32 => |ptr| unsafe { ptr.cast::<f32>().read_unaligned() },
+            _ => Err(type_not_support(format!("cpu: scale of {dt_s}")))?,
+        };
+        let store: unsafe fn(*mut u8, f32) = match dt_y {
+            ty::F16 => |ptr, val| unsafe { ptr.cast::<f16>().write_unaligned(f16::from_f32(val)) },
+            ty::F32 => |ptr, val| unsafe { ptr.cast::<f32>().write_unaligned(val) },
+            _ => Err(type_not_support(format!("cpu: dequantize to {dt_y}")))?,
+        };
+
+        Scheme {
+            d,
+            bits: *bits,
+            block_size: *block_size,
+            y: (y_base.cast(), [syn, syd]),
+            q: (q_base.cast(), sqn),
+            scale: (scale_base.cast(), [ssn, ssb]),
+            zero,
+            load,
+            store,
+        }
+        .calculate(n);
+        Ok(())
+    }
+}
+
+struct Scheme {
+    d: usize,
+    bits: usize,
+    block_size: usize,
+    y: (*mut u8, [isize; 2]),
+    q: (*const u8, isize),
+    scale: (*const u8, [isize; 2]),
+    zero: Option<(*const u8, [isize; 2])>,
+    load: unsafe fn(*const u8) -> f32,
+    store: unsafe fn(*mut u8, f32),
+}
+
+unsafe impl Send for Scheme {}
+unsafe impl Sync for Scheme {}
+
+impl Scheme {
+    fn calculate(&self, n: usize) {
+        (0..n as isize).into_par_iter().for_each(|i| self.row(i))
+    }
+
+    fn row(&self, i: isize) {
+        let &Self {
+            d,
+            bits,
+            block_size,
+            y: (y, [syn, syd]),
+            q: (q, sqn),
+            scale: (scale, [ssn, ssb]),
+            zero,
+            load,
+            store,
+        } = self;
+        let q = unsafe { q.byte_offset(i * sqn) };
+        for j in 0..d {
+            let b = (j / block_size) as isize;
+            let q = unsafe { *q.add(j * bits / 8) };
+            let q = match bits {
+                8 => q,
+                4 => (q >> (j % 2 * 4)) & 0xf,
+                _ => unreachable!(),
+            };
+            let z = match zero {
+                Some((zero, [szn, szb])) => unsafe { *zero.byte_offset(i * szn + b * szb) },
+                None => 1 << (bits - 1),
+            };
+            let s = unsafe { load(scale.byte_offset(i * ssn + b * ssb)) };
+            let val = (q as i32 - z as i32) as f32 * s;
+            unsafe { store(y.byte_offset(i * syn + j as isize * syd), val) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Args, Operator};
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        Operator as _, TensorLayout,
+    };
+    use digit_layout::types as ty;
+    use half::f16;
+
+    #[test]
+    fn test_q8() {
+        // 6 个值，每块 4 个，第二块只有 2 个值
+        let q = [128u8, 130, 126, 255, 0, 129];
+        let scale = [0.5f32, 2.];
+        let zero = [100u8, 1];
+
+        let dequant = |zero: Option<&[u8]>| {
+            let mut y = [0f32; 6];
+            let args = Args::<Cpu> {
+                y_layout: TensorLayout::new_contiguous(ty::F32, &[1, 6]),
+                y_base: y.as_mut_ptr().cast(),
+                q_layout: TensorLayout::new_contiguous(ty::U8, &[1, 6]),
+                q_base: q.as_ptr(),
+                scale_layout: TensorLayout::new_contiguous(ty::F32, &[1, 2]),
+                scale_base: scale.as_ptr().cast(),
+                zero: zero
+                    .map(|zero| (TensorLayout::new_contiguous(ty::U8, &[1, 2]), zero.as_ptr())),
+                bits: 8,
+                block_size: 4,
+            };
+            Operator::new(&Cpu)
+                .launch(&args, &mut [], &ThisThread)
+                .unwrap();
+            y
+        };
+
+        // 对称量化，零点为 128
+        assert_eq!(dequant(None), [0., 1., -1., 63.5, -256., 2.]);
+        assert_eq!(dequant(Some(&zero)), [14., 15., 13., 77.5, -2., 256.]);
+    }
+
+    #[test]
+    fn test_q4() {
+        // 两行，每行 5 个 4 位值打包为 3 个字节，最后一个字节只用低 4 位
+        let q = [0x98u8, 0xf0, 0x03, 0x21, 0x43, 0x05];
+        let scale = [f16::ONE, f16::from_f32(0.25), f16::from_f32(2.), f16::ONE];
+        let mut y = [f16::ZERO; 10];
+        let args = Args::<Cpu> {
+            y_layout: TensorLayout::new_contiguous(ty::F16, &[2, 5]),
+            y_base: y.as_mut_ptr().cast(),
+            q_layout: TensorLayout::new_contiguous(ty::U8, &[2, 3]),
+            q_base: q.as_ptr(),
+            scale_layout: TensorLayout::new_contiguous(ty::F16, &[2, 2]),
+            scale_base: scale.as_ptr().cast(),
+            zero: None,
+            bits: 4,
+            block_size: 4,
+        };
+        Operator::new(&Cpu)
+            .launch(&args, &mut [], &ThisThread)
+            .unwrap();
+
+        // 零点为 8
+        let y = y.map(f16::to_f32);
+        assert_eq!(y, [0., 1., -8., 7., -1.25, -14., -12., -10., -8., -3.]);
+    }
+}
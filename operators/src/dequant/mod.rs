@@ -0,0 +1,14 @@
+//! y = (q - zero) · scale
+//!
+//! 反量化按块量化的权重。每行的 `d` 个量化值依次打包存储，每 `block_size` 个值共享一个缩放和零点，
+//! 每行最后一个块可以不满。
+
+#[cfg(any(use_cpu, test))]
+pub mod common_cpu;
+#[cfg(use_cl)]
+pub mod opencl;
+
+mod args;
+pub use args::Args;
+
+crate::op_trait!(Dequant);
@@ -0,0 +1,66 @@
+#define CL_TARGET_OPENCL_VERSION 200
+#pragma OPENCL EXTENSION cl_khr_fp16 : enable
+
+#ifndef Ty
+#define Ty float
+#endif
+
+#ifndef Ts
+#define Ts float
+#endif
+
+#ifndef BITS
+#define BITS 8
+#endif
+
+#ifdef S_HALF
+#define LOAD_SCALE(ptr) vload_half(0, (__global half const *) (ptr))
+#else
+#define LOAD_SCALE(ptr) ((float) *(ptr))
+#endif
+
+#ifdef Y_HALF
+#define STORE(ptr, val) vstore_half_rte(val, 0, (__global half *) (ptr))
+#else
+#define STORE(ptr, val) (*(ptr) = (Ty) (val))
+#endif
+
+// 没有零点时 zero 参数不会被读取
+__kernel void dequant(
+    __global Ty *y,
+    int const y_stride_row,
+    int const y_stride_col,
+    __global uchar const *q,
+    int const q_stride_row,
+    __global Ts const *scale,
+    int const s_stride_row,
+    int const s_stride_col,
+    __global uchar const *zero,
+    int const z_stride_row,
+    int const z_stride_col,
+    int const d,
+    int const block_size) {
+
+    int const
+        r = get_global_id(0),
+        c = get_global_id(1);
+    // 工作项数量向上对齐到工作组，多出的工作项直接返回
+    if (c >= d) return;
+
+    int const b = c / block_size;
+    uchar const byte = q[r * q_stride_row + c * BITS / 8];
+#if BITS == 4
+    int const val = (byte >> (c % 2 * 4)) & 0xf;
+#else
+    int const val = byte;
+#endif
+
+#ifdef HAS_ZERO
+    int const z = zero[r * z_stride_row + b * z_stride_col];
+#else
+    int const z = 1 << (BITS - 1);
+#endif
+
+    float const s = LOAD_SCALE(scale + r * s_stride_row + b * s_stride_col);
+    STORE(y + r * y_stride_row + c * y_stride_col, (float) (val - z) * s);
+}
@@ -0,0 +1,310 @@
+use super::{args::Meta, Args, Dequant};
+use crate::{
+    execution_failed, get_static,
+    opencl::{ClDevice, CodeGen, KernelCache, CL2_0},
+    strides_not_support, type_not_support, ByteOf, LaunchError, QueueAlloc,
+    SchemeDiversity::Low as LowDiversity,
+    SchemeError,
+};
+use clrt::{bindings::cl_int, Context};
+use digit_layout::{types as Ty, DigitLayout};
+use lru::LruCache;
+use std::sync::Mutex;
+
+pub struct Operator {
+    ctx: Context,
+    max_group_size: usize,
+    schemes: Mutex<LruCache<SchemeKey, KernelCache>>,
+}
+
+impl Dequant<ClDevice> for Operator {}
+
+impl crate::Operator for Operator {
+    type Hardware = ClDevice;
+    type TopoNode = ClDevice;
+    type Args = Args<ClDevice>;
+
+    fn new(node: &Self::TopoNode) -> Self {
+        let ctx = node.context().clone();
+        let max_group_size = ctx
+            .devices()
+            .iter()
+            .map(|d| d.max_group_size())
+            .min()
+            .unwrap()
+            / 2;
+        Self {
+            ctx,
+            max_group_size,
+            schemes: node.new_cache(LowDiversity),
+        }
+    }
+
+    fn scheme(
+        &mut self,
+        args: &Self::Args,
+        _max_workspace_size: usize,
+    ) -> Result<usize, SchemeError> {
+        let Meta { dt_y, dt_s, .. } = args.meta()?;
+        self.cache_kernel(SchemeKey {
+            dt_y,
+            dt_s,
+            bits: args.bits,
+            zero: args.zero.is_some(),
+        })?;
+        Ok(0)
+    }
+
+    fn launch<QA>(
+        &self,
+        args: &Self::Args,
+        _workspace: &mut [ByteOf<Self::Hardware>],
+        queue_alloc: &QA,
+    ) -> Result<(), LaunchError>
+    where
+        QA: QueueAlloc<Hardware = Self::Hardware>,
+    {
+        let Meta { dt_y, dt_s, n, d } = args.meta()?;
+        let Args {
+            y_layout,
+            y_base,
+            q_layout,
+            q_base,
+            scale_layout,
+            scale_base,
+            zero,
+            bits,
+            block_size,
+        } = args;
+        let &[syn, syd] = y_layout.strides() else {
+            unreachable!()
+        };
+        let &[sqn, sqd] = q_layout.strides() else {
+            unreachable!()
+        };
+        let &[ssn, ssb] = scale_layout.strides() else {
+            unreachable!()
+        };
+
+        get_static! {
+            n   d
+            syn syd
+            sqn sqd
+            ssn ssb
+        }
+        if sqd != 1 {
+            Err(strides_not_support(
+                "opencl: packed q must be contiguous in a row",
+            ))?;
+        }
+        if n == 0 || d == 0 {
+            return Ok(());
+        }
+
+        // 没有零点时传入 q 占位，核函数不会读取
+        let (zero_base, [szn, szb]) = match zero {
+            Some((layout, base)) => {
+                let &[szn, szb] = layout.strides() else {
+                    unreachable!()
+                };
+                get_static!(szn szb);
+                (*base, [szn, szb])
+            }
+            None => (*q_base, [0, 0]),
+        };
+
+        let uy = dt_y.nbytes() as isize;
+        let us = dt_s.nbytes() as isize;
+        let key = self.cache_kernel(SchemeKey {
+            dt_y,
+            dt_s,
+            bits: *bits,
+            zero: zero.is_some(),
+        })?;
+        let mut dequant = self
+            .schemes
+            .lock()
+            .unwrap()
+            .get(&key)
+            .unwrap()
+            .take_guard("dequant")
+            .ok_or_else(|| execution_failed("opencl: kernel dequant not found"))?;
+
+        // 每行的工作项向上对齐到工作组大小
+        let group_size = d.next_power_of_two().min(self.max_group_size);
+        dequant
+            .set_arg(0, y_base)
+            .set_arg(1, (syn / uy) as cl_int)
+            .set_arg(2, (syd / uy) as cl_int)
+            .set_arg(3, q_base)
+            .set_arg(4, sqn as cl_int)
+            .set_arg(5, scale_base)
+            .set_arg(6, (ssn / us) as cl_int)
+            .set_arg(7, (ssb / us) as cl_int)
+            .set_arg(8, &zero_base)
+            .set_arg(9, szn as cl_int)
+            .set_arg(10, szb as cl_int)
+            .set_arg(11, d as cl_int)
+            .set_arg(12, *block_size as cl_int)
+            .launch(
+                &[0, 0],
+                &[n, d.div_ceil(group_size) * group_size],
+                &[1, group_size],
+                queue_alloc.queue(),
+                None,
+            );
+
+        Ok(())
+    }
+}
+
+impl Operator {
+    fn cache_kernel(&self, key: SchemeKey) -> Result<SchemeKey, SchemeError> {
+        let ty = |dt| match dt {
+            Ty::F32 => Ok("float"),
+            Ty::F16 => Ok("half"),
+            _ => Err(type_not_support(format!(
+                "opencl: dequant does not support {dt}"
+            ))),
+        };
+        let (y, s) = (ty(key.dt_y)?, ty(key.dt_s)?);
+
+        self.schemes.lock().unwrap().get_or_insert(key, || {
+            let mut code = CodeGen::new(include_str!("dequant.cl"));
+            code.define("Ty", y)
+                .define("Ts", s)
+                .define("BITS", key.bits);
+            if key.dt_y == Ty::F16 {
+                code.define("Y_HALF", true);
+            }
+            if key.dt_s == Ty::F16 {
+                code.define("S_HALF", true);
+            }
+            if key.zero {
+                code.define("HAS_ZERO", true);
+            }
+            KernelCache::new(&self.ctx, &code.to_string(), CL2_0)
+        });
+        Ok(key)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+struct SchemeKey {
+    dt_y: DigitLayout,
+    dt_s: DigitLayout,
+    bits: usize,
+    zero: bool,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Args, Operator};
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        opencl::{read_to_vec, ClDevice},
+        Operator as _, TensorLayout,
+    };
+    use clrt::Platform;
+    use digit_layout::types as ty;
+    use half::f16;
+
+    #[test]
+    fn test_compute() {
+        use super::super::common_cpu::Operator as RefOp;
+
+        // 每行 100 个值，每块 32 个，最后一块只有 4 个值
+        const N: usize = 3;
+        const D: usize = 100;
+        const BLOCK: usize = 32;
+        let nb = D.div_ceil(BLOCK);
+
+        for (bits, with_zero) in [(8, false), (8, true), (4, false), (4, true)] {
+            let dq = (D * bits).div_ceil(8);
+            let q = (0..N * dq)
+                .map(|i| (i * 37 % 256) as u8)
+                .collect::<Vec<_>>();
+            let scale = (0..N * nb)
+                .map(|i| f16::from_f32(0.01 * (i + 1) as f32))
+                .collect::<Vec<_>>();
+            let zero = (0..N * nb)
+                .map(|i| (i * 5 % (1 << bits)) as u8)
+                .collect::<Vec<_>>();
+
+            let y_layout = TensorLayout::new_contiguous(ty::F16, &[N, D]);
+            let q_layout = TensorLayout::new_contiguous(ty::U8, &[N, dq]);
+            let s_layout = TensorLayout::new_contiguous(ty::F16, &[N, nb]);
+            let z_layout = TensorLayout::new_contiguous(ty::U8, &[N, nb]);
+
+            let mut y_ref = vec![f16::ZERO; N * D];
+            RefOp::new(&Cpu)
+                .launch(
+                    &Args::<Cpu> {
+                        y_layout: y_layout.clone(),
+                        y_base: y_ref.as_mut_ptr().cast(),
+                        q_layout: q_layout.clone(),
+                        q_base: q.as_ptr(),
+                        scale_layout: s_layout.clone(),
+                        scale_base: scale.as_ptr().cast(),
+                        zero: with_zero.then(|| (z_layout.clone(), zero.as_ptr())),
+                        bits,
+                        block_size: BLOCK,
+                    },
+                    &mut [],
+                    &ThisThread,
+                )
+                .unwrap();
+
+            for platform in Platform::all() {
+                for device in platform.devices() {
+                    println!("device: {}", device.name());
+
+                    let context = device.context();
+                    let queue = context.queue();
+                    let mut cl_op =
+                        Operator::new(&ClDevice::new(context.clone(), Default::default()));
+
+                    let mut q_svm = context.malloc::<u8>(q.len());
+                    let mut s_svm = context.malloc::<f16>(scale.len());
+                    let mut z_svm = context.malloc::<u8>(zero.len());
+                    let mut y_svm = context.malloc::<f16>(N * D);
+                    let mut map = queue.map_mut(&mut q_svm, false);
+                    let ([], mem, []) = (unsafe { map.align_to_mut::<u8>() }) else {
+                        panic!()
+                    };
+                    mem.copy_from_slice(&q);
+                    queue.unmap(map);
+                    let mut map = queue.map_mut(&mut s_svm, false);
+                    let ([], mem, []) = (unsafe { map.align_to_mut::<f16>() }) else {
+                        panic!()
+                    };
+                    mem.copy_from_slice(&scale);
+                    queue.unmap(map);
+                    let mut map = queue.map_mut(&mut z_svm, false);
+                    let ([], mem, []) = (unsafe { map.align_to_mut::<u8>() }) else {
+                        panic!()
+                    };
+                    mem.copy_from_slice(&zero);
+                    queue.unmap(map);
+
+                    let args = Args::<ClDevice> {
+                        y_layout: y_layout.clone(),
+                        y_base: y_svm.as_mut_ptr(),
+                        q_layout: q_layout.clone(),
+                        q_base: q_svm.as_ptr(),
+                        scale_layout: s_layout.clone(),
+                        scale_base: s_svm.as_ptr(),
+                        zero: with_zero.then(|| (z_layout.clone(), z_svm.as_ptr())),
+                        bits,
+                        block_size: BLOCK,
+                    };
+                    cl_op.scheme(&args, 0).unwrap();
+                    cl_op.launch(&args, &mut [], &queue).unwrap();
+
+                    let y_ans = read_to_vec::<f16>(&mut y_svm, &queue);
+                    assert_eq!(y_ans, y_ref, "{bits}-bit, zero: {with_zero}");
+                }
+            }
+        }
+    }
+}
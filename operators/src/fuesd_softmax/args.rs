@@ -1,11 +1,20 @@
-﻿use crate::{rank_not_support, Hardware, MutPtr, SchemeError, TensorLayout};
-use digit_layout::DigitLayout;
+﻿use crate::{
+    args_not_support, rank_not_support, type_not_support, utils::dim_distinct, Hardware, MutPtr,
+    SchemeError, TensorLayout,
+};
+use digit_layout::{types as ty, DigitLayout};
 use std::ptr::null_mut;
 
 pub struct Args<H: Hardware> {
     pub att_mask: AttnMask,
+    /// 滑动窗口大小，与因果掩码配合使用，第 `i` 行只保留 `[i - w, i]` 范围内的键。
+    pub window: Option<usize>,
     pub att_layout: TensorLayout,
     pub att_base: MutPtr<H>,
+    /// 独立的输出张量，形状与 `att` 相同，允许比输入精度更低（f32 输入、f16 输出）。
+    /// 为 `None` 时结果原地写回 `att`。
+    pub out_layout: Option<TensorLayout>,
+    pub out_base: MutPtr<H>,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
@@ -17,22 +26,55 @@ pub enum AttnMask {
 
 pub(super) struct Meta {
     pub dt: DigitLayout,
+    pub dt_out: DigitLayout,
 }
 
 impl<H: Hardware> Args<H> {
     pub fn new_null(att_mask: AttnMask, att_layout: TensorLayout) -> Self {
         Self {
             att_mask,
+            window: None,
             att_layout,
             att_base: null_mut(),
+            out_layout: None,
+            out_base: null_mut(),
         }
     }
 
     pub(super) fn meta(&self) -> Result<Meta, SchemeError> {
         let dt = self.att_layout.dt();
+        if !matches!(dt, ty::F16 | ty::F32 | ty::F64) {
+            return Err(type_not_support(format!(
+                "softmax: data type {dt} is not supported, must be f16, f32 or f64"
+            )));
+        }
         if self.att_layout.ndim() != 3 {
             return Err(rank_not_support(""));
         }
-        Ok(Meta { dt })
+        if self.window.is_some() && self.att_mask != AttnMask::Causal {
+            return Err(args_not_support("sliding window requires causal mask"));
+        }
+        let dt_out = match &self.out_layout {
+            Some(out) => {
+                if out.ndim() != 3 {
+                    return Err(rank_not_support("softmax output"));
+                }
+                for (a, b) in self.att_layout.shape().iter().zip(out.shape()) {
+                    dim_distinct(&[*a, *b])?;
+                }
+                out.dt()
+            }
+            None => dt,
+        };
+        match (dt, dt_out) {
+            (a, b) if a == b => {}
+            (ty::F32, ty::F16) => {}
+            (a, b) => {
+                return Err(type_not_support(format!(
+                    "softmax: {a} input with {b} output"
+                )))
+            }
+        }
+        Ok(Meta { dt, dt_out })
     }
 }
@@ -2,11 +2,50 @@
     args::{AttnMask, Meta},
     Args, FusedSoftmax,
 };
-use crate::{common_cpu::Cpu, get_static, ByteOf, LaunchError, QueueAlloc, SchemeError};
+use crate::{
+    common_cpu::Cpu, get_static, shape_not_support, type_not_support, ByteOf, LaunchError,
+    QueueAlloc, SchemeError,
+};
+use digit_layout::DigitLayout;
 use half::f16;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::sync::{Arc, Mutex};
+
+pub struct Operator {
+    parallel: bool,
+    acc: Option<DigitLayout>,
+    cache_mask: bool,
+    mask: Mutex<Option<CausalMask>>,
+}
 
-pub struct Operator;
+/// 预先计算的加性因果掩码，形状为 `[seq_len, seq_len + offset]`，保留的位置为 0，屏蔽的位置为 -inf。
+struct CausalMask {
+    seq_len: usize,
+    offset: usize,
+    window: Option<usize>,
+    buf: Arc<[f32]>,
+}
+
+impl CausalMask {
+    fn new(seq_len: usize, offset: usize, window: Option<usize>) -> Self {
+        let att_len = seq_len + offset;
+        let mut buf = vec![f32::NEG_INFINITY; seq_len * att_len];
+        for (k, row) in buf.chunks_exact_mut(att_len).enumerate() {
+            let causal = offset + k + 1;
+            let start = match window {
+                Some(w) => causal.saturating_sub(w + 1),
+                None => 0,
+            };
+            row[start..causal].fill(0.)
+        }
+        Self {
+            seq_len,
+            offset,
+            window,
+            buf: buf.into(),
+        }
+    }
+}
 
 impl FusedSoftmax<Cpu> for Operator {}
 
@@ -17,7 +56,12 @@ impl crate::Operator for Operator {
 
     #[inline]
     fn new(_node: &Self::TopoNode) -> Self {
-        Self
+        Self {
+            parallel: true,
+            acc: None,
+            cache_mask: false,
+            mask: Mutex::new(None),
+        }
     }
 
     fn scheme(
@@ -38,11 +82,14 @@ impl crate::Operator for Operator {
     where
         QA: QueueAlloc<Hardware = Self::Hardware>,
     {
-        let Meta { dt } = args.meta()?;
+        let Meta { dt, dt_out } = args.meta()?;
         let Args {
             att_mask,
+            window,
             att_layout,
             att_base,
+            out_layout,
+            out_base,
         } = args;
         let &[nh, seq_len, att_len] = att_layout.shape() else {
             unreachable!()
@@ -56,7 +103,7 @@ impl crate::Operator for Operator {
             sh ss      sa
         }
 
-        macro_rules! calculate {
+        macro_rules! scheme {
             ($ty:ty) => {
                 Scheme::<$ty> {
                     nh,
@@ -66,22 +113,174 @@ impl crate::Operator for Operator {
                     ss,
                     sa,
                     att_base: att_base.cast(),
+                    parallel: self.parallel,
                 }
-                .calculate(*att_mask)
             };
         }
 
         use digit_layout::types as ty;
-        match dt {
-            ty::F16 => calculate!(f16),
-            ty::F32 => calculate!(f32),
-            ty::F64 => calculate!(f64),
-            _ => todo!(),
+        let mask = match att_mask {
+            AttnMask::Causal if self.cache_mask => {
+                if att_len < seq_len {
+                    return Err(shape_not_support(format!(
+                        "causal mask: att_len {att_len} < seq_len {seq_len}"
+                    ))
+                    .into());
+                }
+                Some(self.mask(seq_len, att_len - seq_len, *window))
+            }
+            _ => None,
+        };
+        // 累加精度与默认不同时统一写入独立输出，原地计算时输出即输入
+        let native = if dt == ty::F64 { ty::F64 } else { ty::F32 };
+        if self.acc.is_some_and(|acc| acc != native) {
+            let (osh, oss, osa, out_base, dt_out) = match out_layout {
+                Some(out_layout) => {
+                    let &[osh, oss, osa] = out_layout.strides() else {
+                        unreachable!()
+                    };
+                    get_static! {
+                        osh oss osa
+                    }
+                    (osh, oss, osa, *out_base, dt_out)
+                }
+                None => (sh, ss, sa, *att_base, dt),
+            };
+
+            macro_rules! calculate_acc {
+                ($t:ty => $u:ty, $store:expr) => {{
+                    let out = Out::<$u> {
+                        sh: osh,
+                        ss: oss,
+                        sa: osa,
+                        base: out_base.cast(),
+                    };
+                    match &mask {
+                        Some(mask) => scheme!($t).calculate_masked_acc(mask, Some(&out), $store),
+                        None => scheme!($t).calculate_into_acc(*att_mask, *window, &out, $store),
+                    }
+                }};
+            }
+
+            match (dt, dt_out) {
+                (ty::F16, ty::F16) => calculate_acc!(f16 => f16, f16::from_f64),
+                (ty::F32, ty::F32) => calculate_acc!(f32 => f32, |x| x as f32),
+                (ty::F32, ty::F16) => calculate_acc!(f32 => f16, f16::from_f64),
+                (ty::F64, ty::F64) => calculate_acc!(f64 => f64, |x| x as f64),
+                _ => Err(type_not_support(format!(
+                    "cpu: softmax from {dt} to {dt_out}"
+                )))?,
+            }
+            return Ok(());
+        }
+
+        let Some(out_layout) = out_layout else {
+            macro_rules! calculate {
+                ($ty:ty, $store:expr) => {
+                    match &mask {
+                        Some(mask) => scheme!($ty).calculate_masked(mask, None, $store),
+                        None => scheme!($ty).calculate(*att_mask, *window),
+                    }
+                };
+            }
+
+            match dt {
+                ty::F16 => calculate!(f16, f16::from_f32),
+                ty::F32 => calculate!(f32, |x| x),
+                ty::F64 => calculate!(f64, |x| x),
+                _ => Err(type_not_support(format!("cpu: softmax of {dt}")))?,
+            }
+            return Ok(());
+        };
+
+        let &[osh, oss, osa] = out_layout.strides() else {
+            unreachable!()
+        };
+        get_static! {
+            osh oss osa
+        }
+
+        macro_rules! calculate_into {
+            ($t:ty => $u:ty, $store:expr) => {{
+                let out = Out::<$u> {
+                    sh: osh,
+                    ss: oss,
+                    sa: osa,
+                    base: out_base.cast(),
+                };
+                match &mask {
+                    Some(mask) => scheme!($t).calculate_masked(mask, Some(&out), $store),
+                    None => scheme!($t).calculate_into(*att_mask, *window, &out, $store),
+                }
+            }};
+        }
+
+        match (dt, dt_out) {
+            (ty::F16, ty::F16) => calculate_into!(f16 => f16, f16::from_f32),
+            (ty::F32, ty::F32) => calculate_into!(f32 => f32, |x| x),
+            (ty::F32, ty::F16) => calculate_into!(f32 => f16, f16::from_f32),
+            (ty::F64, ty::F64) => calculate_into!(f64 => f64, |x| x),
+            _ => Err(type_not_support(format!(
+                "cpu: softmax from {dt} to {dt_out}"
+            )))?,
         }
         Ok(())
     }
 }
 
+impl Operator {
+    /// 设置是否使用 rayon 在行间并行计算，默认开启。
+    ///
+    /// 各行独立计算，串行与并行的结果逐位一致。
+    pub fn set_parallel(&mut self, enable: bool) {
+        self.parallel = enable
+    }
+
+    /// 设置累加精度，只支持 f32 和 f64。
+    ///
+    /// 默认 (`None`) f16 和 f32 以 f32 累加，f64 以 f64 累加。
+    /// 作为参考实现时可以指定 f64 累加，使参考自身的误差远小于被测的实现。
+    pub fn set_accumulation(&mut self, acc: Option<DigitLayout>) -> Result<(), SchemeError> {
+        use digit_layout::types as ty;
+        match acc {
+            None | Some(ty::F32 | ty::F64) => {
+                self.acc = acc;
+                Ok(())
+            }
+            Some(dt) => Err(type_not_support(format!(
+                "softmax: accumulation in {dt} is not supported"
+            ))),
+        }
+    }
+
+    /// 设置是否缓存因果掩码，默认关闭。
+    ///
+    /// 开启后因果掩码预先计算为加性掩码，按 `(seq_len, offset, window)` 缓存并在发射间复用，
+    /// 其中 `offset = att_len - seq_len`。缓存只保存最近一次的掩码，长度变化时重新计算。
+    pub fn set_mask_cache(&mut self, enable: bool) {
+        self.cache_mask = enable;
+        if !enable {
+            *self.mask.get_mut().unwrap() = None
+        }
+    }
+
+    /// 取出与参数匹配的缓存掩码，不匹配时重新计算。
+    fn mask(&self, seq_len: usize, offset: usize, window: Option<usize>) -> Arc<[f32]> {
+        let mut cache = self.mask.lock().unwrap();
+        match &*cache {
+            Some(mask)
+                if mask.seq_len == seq_len && mask.offset == offset && mask.window == window =>
+            {
+                mask.buf.clone()
+            }
+            _ => cache
+                .insert(CausalMask::new(seq_len, offset, window))
+                .buf
+                .clone(),
+        }
+    }
+}
+
 struct Scheme<T> {
     nh: usize,
     seq_len: usize,
@@ -90,18 +289,34 @@ struct Scheme<T> {
     ss: isize,
     sa: isize,
     att_base: *mut T,
+    parallel: bool,
 }
 
 unsafe impl<T> Send for Scheme<T> {}
 unsafe impl<T> Sync for Scheme<T> {}
 
+/// 独立的输出张量。
+struct Out<T> {
+    sh: isize,
+    ss: isize,
+    sa: isize,
+    base: *mut T,
+}
+
+unsafe impl<T> Sync for Out<T> {}
+
 impl<T> Scheme<T> {
-    fn loop_(&self, mask: AttnMask, f: impl Sync + Fn(isize, *mut T)) {
+    fn loop_(
+        &self,
+        mask: AttnMask,
+        window: Option<usize>,
+        f: impl Sync + Fn(isize, isize, *mut T, [isize; 2]),
+    ) {
         let nh = self.nh as isize;
         let seq_len = self.seq_len as isize;
         let att_len = self.att_len as isize;
 
-        (0..nh * seq_len).into_par_iter().for_each(|i| {
+        let row = |i: isize| {
             let j = i / seq_len;
             let k = i % seq_len;
             let att = unsafe { self.att_base.byte_offset(j * self.sh + k * self.ss) };
@@ -109,24 +324,34 @@ impl<T> Scheme<T> {
                 AttnMask::None => att_len,
                 AttnMask::Causal => att_len - seq_len + k + 1,
             };
-            f(causal, att)
-        });
+            // 窗口之前的键被屏蔽，前几行的窗口起点截断到 0
+            let start = match window {
+                Some(w) => (causal - w as isize - 1).max(0),
+                None => 0,
+            };
+            f(start, causal, att, [j, k])
+        };
+        if self.parallel {
+            (0..nh * seq_len).into_par_iter().for_each(row)
+        } else {
+            (0..nh * seq_len).for_each(row)
+        }
     }
 }
 
 impl Scheme<f16> {
-    fn calculate(&self, mask: AttnMask) {
+    fn calculate(&self, mask: AttnMask, window: Option<usize>) {
         let att_len = self.att_len as isize;
-        self.loop_(mask, |causal, att| {
+        self.loop_(mask, window, |start, causal, att, _| {
             let att = |k| unsafe { &mut *att.byte_offset(k * self.sa) };
 
-            let max = (0..causal)
+            let max = (start..causal)
                 .map(att)
                 .max_by(|a, b| a.total_cmp(b))
                 .unwrap()
                 .to_f32();
 
-            let div = (0..causal)
+            let div = (start..causal)
                 .map(att)
                 .map(|x| {
                     let exp = (x.to_f32() - max).exp();
@@ -136,23 +361,29 @@ impl Scheme<f16> {
                 .sum::<f32>()
                 .recip();
 
-            (0..causal)
+            (start..causal)
                 .map(att)
                 .for_each(|x| *x = f16::from_f32(x.to_f32() * div));
-            (causal..att_len).map(att).for_each(|x| *x = f16::ZERO);
+            (0..start)
+                .chain(causal..att_len)
+                .map(att)
+                .for_each(|x| *x = f16::ZERO);
         });
     }
 }
 
 impl Scheme<f32> {
-    fn calculate(&self, mask: AttnMask) {
+    fn calculate(&self, mask: AttnMask, window: Option<usize>) {
         let att_len = self.att_len as isize;
-        self.loop_(mask, |causal, att| {
+        self.loop_(mask, window, |start, causal, att, _| {
             let att = |k| unsafe { &mut *att.byte_offset(k * self.sa) };
 
-            let max = *(0..causal).map(att).max_by(|a, b| a.total_cmp(b)).unwrap();
+            let max = *(start..causal)
+                .map(att)
+                .max_by(|a, b| a.total_cmp(b))
+                .unwrap();
 
-            let div = (0..causal)
+            let div = (start..causal)
                 .map(att)
                 .map(|x| {
                     let exp = (*x - max).exp();
@@ -162,21 +393,27 @@ impl Scheme<f32> {
                 .sum::<f32>()
                 .recip();
 
-            (0..causal).map(att).for_each(|x| *x *= div);
-            (causal..att_len).map(att).for_each(|x| *x = 0.);
+            (start..causal).map(att).for_each(|x| *x *= div);
+            (0..start)
+                .chain(causal..att_len)
+                .map(att)
+                .for_each(|x| *x = 0.);
         });
     }
 }
 
 impl Scheme<f64> {
-    fn calculate(&self, mask: AttnMask) {
+    fn calculate(&self, mask: AttnMask, window: Option<usize>) {
         let att_len = self.att_len as isize;
-        self.loop_(mask, |causal, att| {
+        self.loop_(mask, window, |start, causal, att, _| {
             let att = |k| unsafe { &mut *att.byte_offset(k * self.sa) };
 
-            let max = *(0..causal).map(att).max_by(|a, b| a.total_cmp(b)).unwrap();
+            let max = *(start..causal)
+                .map(att)
+                .max_by(|a, b| a.total_cmp(b))
+                .unwrap();
 
-            let div = (0..causal)
+            let div = (start..causal)
                 .map(att)
                 .map(|x| {
                     let exp = (*x - max).exp();
@@ -186,8 +423,444 @@ impl Scheme<f64> {
                 .sum::<f64>()
                 .recip();
 
-            (0..causal).map(att).for_each(|x| *x *= div);
-            (causal..att_len).map(att).for_each(|x| *x = 0.);
+            (start..causal).map(att).for_each(|x| *x *= div);
+            (0..start)
+                .chain(causal..att_len)
+                .map(att)
+                .for_each(|x| *x = 0.);
         });
     }
 }
+
+/// 结果写入独立的输出张量，不修改输入。`$acc` 为计算精度。
+///
+/// 输出与输入的布局相同时可以原地计算，每个元素先读后写。
+macro_rules! impl_calculate_into {
+    ($t:ty: $acc:ty, $load:expr) => {
+        impl_calculate_into!(calculate_into, $t: $acc, $load);
+    };
+    ($name:ident, $t:ty: $acc:ty, $load:expr) => {
+        impl Scheme<$t> {
+            fn $name<U>(
+                &self,
+                mask: AttnMask,
+                window: Option<usize>,
+                out: &Out<U>,
+                store: impl Sync + Fn($acc) -> U,
+            ) {
+                let att_len = self.att_len as isize;
+                self.loop_(mask, window, |start, causal, att, [j, k]| {
+                    let att = |i| $load(unsafe { *att.byte_offset(i * self.sa) });
+                    let row = unsafe { out.base.byte_offset(j * out.sh + k * out.ss) };
+                    let y = |i| unsafe { &mut *row.byte_offset(i * out.sa) };
+
+                    let max = (start..causal)
+                        .map(att)
+                        .fold(<$acc>::NEG_INFINITY, <$acc>::max);
+                    let div = (start..causal)
+                        .map(|i| (att(i) - max).exp())
+                        .sum::<$acc>()
+                        .recip();
+
+                    (start..causal).for_each(|i| *y(i) = store((att(i) - max).exp() * div));
+                    (0..start)
+                        .chain(causal..att_len)
+                        .for_each(|i| *y(i) = store(0.));
+                });
+            }
+        }
+    };
+}
+
+impl_calculate_into!(f16: f32, f16::to_f32);
+impl_calculate_into!(f32: f32, |x| x);
+impl_calculate_into!(f64: f64, |x| x);
+impl_calculate_into!(calculate_into_acc, f16: f64, f16::to_f64);
+impl_calculate_into!(calculate_into_acc, f32: f64, |x| x as f64);
+impl_calculate_into!(calculate_into_acc, f64: f32, |x| x as f32);
+
+/// 使用预先计算的加性掩码，`out` 为 `None` 时结果原地写回。`$acc` 为计算精度。
+macro_rules! impl_calculate_masked {
+    ($t:ty: $acc:ty, $load:expr) => {
+        impl_calculate_masked!(calculate_masked, $t: $acc, $load);
+    };
+    ($name:ident, $t:ty: $acc:ty, $load:expr) => {
+        impl Scheme<$t> {
+            fn $name<U>(
+                &self,
+                mask: &[f32],
+                out: Option<&Out<U>>,
+                store: impl Sync + Fn($acc) -> U,
+            ) {
+                let att_len = self.att_len;
+                self.loop_(AttnMask::None, None, |_, _, att, [j, k]| {
+                    let mask = &mask[k as usize * att_len..][..att_len];
+                    let x = |i: usize| {
+                        $load(unsafe { *att.byte_offset(i as isize * self.sa) })
+                            + <$acc>::from(mask[i])
+                    };
+                    let y = |i: usize| unsafe {
+                        let i = i as isize;
+                        match out {
+                            Some(out) => {
+                                &mut *out.base.byte_offset(j * out.sh + k * out.ss + i * out.sa)
+                            }
+                            None => &mut *att.byte_offset(i * self.sa).cast::<U>(),
+                        }
+                    };
+
+                    let max = (0..att_len).map(x).fold(<$acc>::NEG_INFINITY, <$acc>::max);
+                    let div = (0..att_len)
+                        .map(|i| (x(i) - max).exp())
+                        .sum::<$acc>()
+                        .recip();
+                    (0..att_len).for_each(|i| *y(i) = store((x(i) - max).exp() * div));
+                });
+            }
+        }
+    };
+}
+
+impl_calculate_masked!(f16: f32, f16::to_f32);
+impl_calculate_masked!(f32: f32, |x| x);
+impl_calculate_masked!(f64: f64, |x| x);
+impl_calculate_masked!(calculate_masked_acc, f16: f64, f16::to_f64);
+impl_calculate_masked!(calculate_masked_acc, f32: f64, |x| x as f64);
+impl_calculate_masked!(calculate_masked_acc, f64: f32, |x| x as f32);
+
+#[cfg(test)]
+mod test {
+    use super::{Args, AttnMask, Operator};
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        Operator as _, TensorLayout,
+    };
+    use digit_layout::types as ty;
+    use std::ptr::null_mut;
+
+    #[test]
+    fn test_window() {
+        const NH: usize = 2;
+        const SEQ: usize = 6;
+        const ATT: usize = 9;
+        const W: usize = 4;
+
+        let mut att = vec![0.0f64; NH * SEQ * ATT];
+        let mut op = Operator::new(&Cpu);
+        let mut args = Args::<Cpu> {
+            att_mask: AttnMask::Causal,
+            window: Some(W),
+            att_layout: TensorLayout::new_contiguous(ty::F64, &[NH, SEQ, ATT]),
+            att_base: att.as_mut_ptr().cast(),
+            out_layout: None,
+            out_base: null_mut(),
+        };
+        op.scheme(&args, 0).unwrap();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        for (i, row) in att.chunks(ATT).enumerate() {
+            // 第 k 行的因果终点为 ATT - SEQ + k，窗口包含其前 W 个键
+            let end = ATT - SEQ + i % SEQ;
+            let start = end.saturating_sub(W);
+            let len = (end - start + 1) as f64;
+            for (j, &x) in row.iter().enumerate() {
+                let expected = if (start..=end).contains(&j) {
+                    len.recip()
+                } else {
+                    0.
+                };
+                assert!((x - expected).abs() < 1e-12, "row {i} key {j}: {x}");
+            }
+        }
+
+        args.att_mask = AttnMask::None;
+        assert!(op.scheme(&args, 0).is_err());
+    }
+
+    #[test]
+    fn test_strided() {
+        const NH: usize = 2;
+        const SEQ: usize = 5;
+        const ATT: usize = 7;
+
+        // 按 [nh, att, seq] 存储，转置为 [nh, seq, att] 的视图
+        let att_t = (0..NH * ATT * SEQ)
+            .map(|i| (i as f64 * 0.37).sin())
+            .collect::<Vec<_>>();
+        let mut att = vec![0.0f64; NH * SEQ * ATT];
+        for h in 0..NH {
+            for s in 0..SEQ {
+                for a in 0..ATT {
+                    att[(h * SEQ + s) * ATT + a] = att_t[(h * ATT + a) * SEQ + s];
+                }
+            }
+        }
+
+        let unit = size_of::<f64>() as isize;
+        let mut op = Operator::new(&Cpu);
+        let mut att_t = att_t;
+        let args_t = Args::<Cpu> {
+            att_mask: AttnMask::Causal,
+            window: None,
+            att_layout: TensorLayout::new(
+                ty::F64,
+                &[NH, SEQ, ATT],
+                &[(ATT * SEQ) as isize * unit, unit, SEQ as isize * unit],
+            ),
+            att_base: att_t.as_mut_ptr().cast(),
+            out_layout: None,
+            out_base: null_mut(),
+        };
+        op.scheme(&args_t, 0).unwrap();
+        op.launch(&args_t, &mut [], &ThisThread).unwrap();
+
+        let args = Args::<Cpu> {
+            att_mask: AttnMask::Causal,
+            window: None,
+            att_layout: TensorLayout::new_contiguous(ty::F64, &[NH, SEQ, ATT]),
+            att_base: att.as_mut_ptr().cast(),
+            out_layout: None,
+            out_base: null_mut(),
+        };
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        for h in 0..NH {
+            for s in 0..SEQ {
+                for a in 0..ATT {
+                    let x = att_t[(h * ATT + a) * SEQ + s];
+                    let y = att[(h * SEQ + s) * ATT + a];
+                    assert!((x - y).abs() < 1e-12, "head {h} row {s} key {a}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_f16_out() {
+        use half::f16;
+
+        const NH: usize = 3;
+        const SEQ: usize = 4;
+        const ATT: usize = 11;
+
+        let att = (0..NH * SEQ * ATT)
+            .map(|i| (i as f32 * 0.61).sin() * 4.)
+            .collect::<Vec<_>>();
+        let att_in = att.clone();
+        let mut out_f32 = vec![0.0f32; att.len()];
+        let mut out_f16 = vec![f16::ZERO; att.len()];
+
+        let mut op = Operator::new(&Cpu);
+        let args = |out_dt, out_base| Args::<Cpu> {
+            att_mask: AttnMask::Causal,
+            window: None,
+            att_layout: TensorLayout::new_contiguous(ty::F32, &[NH, SEQ, ATT]),
+            att_base: att.as_ptr().cast_mut().cast(),
+            out_layout: Some(TensorLayout::new_contiguous(out_dt, &[NH, SEQ, ATT])),
+            out_base,
+        };
+        let args_f32 = args(ty::F32, out_f32.as_mut_ptr().cast());
+        let args_f16 = args(ty::F16, out_f16.as_mut_ptr().cast());
+        op.scheme(&args_f16, 0).unwrap();
+        op.launch(&args_f32, &mut [], &ThisThread).unwrap();
+        op.launch(&args_f16, &mut [], &ThisThread).unwrap();
+
+        // 输入不被修改
+        assert_eq!(att, att_in);
+        for (a, b) in out_f32.iter().zip(&out_f16) {
+            assert_eq!(f16::from_f32(*a), *b);
+        }
+
+        // f64 输入不能输出 f16
+        let mut bad = args(ty::F16, out_f16.as_mut_ptr().cast());
+        bad.att_layout = TensorLayout::new_contiguous(ty::F64, &[NH, SEQ, ATT]);
+        assert!(op.scheme(&bad, 0).is_err());
+        // 形状必须一致
+        let mut bad = args(ty::F16, out_f16.as_mut_ptr().cast());
+        bad.out_layout = Some(TensorLayout::new_contiguous(ty::F16, &[NH, SEQ, ATT - 1]));
+        assert!(op.scheme(&bad, 0).is_err());
+        // bf16 输入不支持，无论原地还是写入独立输出
+        let mut bf16 = vec![0u16; att.len()];
+        for out_dt in [None, Some(ty::BF16), Some(ty::F16)] {
+            let bad = Args::<Cpu> {
+                att_mask: AttnMask::Causal,
+                window: None,
+                att_layout: TensorLayout::new_contiguous(ty::BF16, &[NH, SEQ, ATT]),
+                att_base: bf16.as_mut_ptr().cast(),
+                out_layout: out_dt.map(|dt| TensorLayout::new_contiguous(dt, &[NH, SEQ, ATT])),
+                out_base: out_f16.as_mut_ptr().cast(),
+            };
+            assert!(op.scheme(&bad, 0).is_err());
+            assert!(op.launch(&bad, &mut [], &ThisThread).is_err());
+        }
+    }
+
+    #[test]
+    fn test_parallel() {
+        const NH: usize = 8;
+        const SEQ: usize = 64;
+        const ATT: usize = 1024;
+
+        let att = (0..NH * SEQ * ATT)
+            .map(|i| (i as f32 * 0.013).sin() * 8.)
+            .collect::<Vec<_>>();
+        let mut serial = att.clone();
+        let mut parallel = att;
+
+        let mut op = Operator::new(&Cpu);
+        let args = |base: &mut [f32]| Args::<Cpu> {
+            att_mask: AttnMask::Causal,
+            window: None,
+            att_layout: TensorLayout::new_contiguous(ty::F32, &[NH, SEQ, ATT]),
+            att_base: base.as_mut_ptr().cast(),
+            out_layout: None,
+            out_base: null_mut(),
+        };
+        op.scheme(&args(&mut parallel), 0).unwrap();
+        op.launch(&args(&mut parallel), &mut [], &ThisThread)
+            .unwrap();
+        op.set_parallel(false);
+        op.launch(&args(&mut serial), &mut [], &ThisThread).unwrap();
+
+        // 行内归约顺序不变，结果逐位一致
+        assert!(serial
+            .iter()
+            .zip(&parallel)
+            .all(|(a, b)| a.to_bits() == b.to_bits()));
+    }
+
+    #[test]
+    fn test_mask_cache() {
+        use std::sync::Arc;
+
+        const NH: usize = 2;
+        const W: usize = 3;
+
+        let input = |seq: usize, att: usize| {
+            (0..NH * seq * att)
+                .map(|i| (i as f64 * 0.29).sin() * 3.)
+                .collect::<Vec<_>>()
+        };
+        let args = |seq: usize, att: usize, window, base: &mut [f64]| Args::<Cpu> {
+            att_mask: AttnMask::Causal,
+            window,
+            att_layout: TensorLayout::new_contiguous(ty::F64, &[NH, seq, att]),
+            att_base: base.as_mut_ptr().cast(),
+            out_layout: None,
+            out_base: null_mut(),
+        };
+
+        let mut reference = Operator::new(&Cpu);
+        let mut op = Operator::new(&Cpu);
+        op.set_mask_cache(true);
+        let mut check = |seq, att, window| {
+            let mut expected = input(seq, att);
+            let mut ans = expected.clone();
+            reference
+                .scheme(&args(seq, att, window, &mut expected), 0)
+                .unwrap();
+            reference
+                .launch(&args(seq, att, window, &mut expected), &mut [], &ThisThread)
+                .unwrap();
+            op.scheme(&args(seq, att, window, &mut ans), 0).unwrap();
+            op.launch(&args(seq, att, window, &mut ans), &mut [], &ThisThread)
+                .unwrap();
+            for (a, b) in ans.iter().zip(&expected) {
+                assert!((a - b).abs() < 1e-12);
+            }
+            op.mask.lock().unwrap().as_ref().unwrap().buf.clone()
+        };
+
+        // 相同长度的两次发射复用同一个掩码
+        let first = check(4, 7, None);
+        let second = check(4, 7, None);
+        assert!(Arc::ptr_eq(&first, &second));
+        // 长度、偏移或窗口变化时重新计算
+        let longer = check(5, 7, None);
+        assert!(!Arc::ptr_eq(&first, &longer));
+        assert_eq!(longer.len(), 5 * 7);
+        let shifted = check(5, 9, None);
+        assert!(!Arc::ptr_eq(&longer, &shifted));
+        let windowed = check(5, 9, Some(W));
+        assert!(!Arc::ptr_eq(&shifted, &windowed));
+        assert!(Arc::ptr_eq(&windowed, &check(5, 9, Some(W))));
+
+        // 关闭时清空缓存
+        op.set_mask_cache(false);
+        assert!(op.mask.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_accumulation() {
+        const NH: usize = 2;
+        const SEQ: usize = 3;
+        const ATT: usize = 4096;
+
+        // 输入可以精确表示为 f32，误差只来自计算本身
+        let att = (0..NH * SEQ * ATT)
+            .map(|i| ((i as f32 * 0.017).sin() * 6.) as f64)
+            .collect::<Vec<_>>();
+        let args = |dt, att_base| Args::<Cpu> {
+            att_mask: AttnMask::Causal,
+            window: None,
+            att_layout: TensorLayout::new_contiguous(dt, &[NH, SEQ, ATT]),
+            att_base,
+            out_layout: None,
+            out_base: null_mut(),
+        };
+
+        // f64 输入、f64 累加的结果作为精确值
+        let mut exact = att.clone();
+        let mut op = Operator::new(&Cpu);
+        op.launch(
+            &args(ty::F64, exact.as_mut_ptr().cast()),
+            &mut [],
+            &ThisThread,
+        )
+        .unwrap();
+
+        let f32_err = |op: &Operator| {
+            let mut x = att.iter().map(|&x| x as f32).collect::<Vec<_>>();
+            op.launch(&args(ty::F32, x.as_mut_ptr().cast()), &mut [], &ThisThread)
+                .unwrap();
+            x.iter()
+                .zip(&exact)
+                .map(|(&a, &b)| (a as f64 - b).abs() / b.max(f64::MIN_POSITIVE))
+                .fold(0., f64::max)
+        };
+        let native = f32_err(&op);
+        op.set_accumulation(Some(ty::F64)).unwrap();
+        let wide = f32_err(&op);
+        println!(
+            "f32 input, relative error: f32 accumulation {native:e}, f64 accumulation {wide:e}"
+        );
+        // f64 累加只剩输出的舍入误差，f32 累加还有指数和求和的误差
+        assert!(wide <= f32::EPSILON as f64);
+        assert!(wide <= native);
+        assert!(native < 1e-4);
+
+        // f64 输入也可以降为 f32 累加，以估计 f32 参考自身的误差
+        let mut narrow = att.clone();
+        op.set_accumulation(Some(ty::F32)).unwrap();
+        op.launch(
+            &args(ty::F64, narrow.as_mut_ptr().cast()),
+            &mut [],
+            &ThisThread,
+        )
+        .unwrap();
+        assert!(narrow.iter().zip(&exact).any(|(a, b)| a != b));
+        for (a, b) in narrow.iter().zip(&exact) {
+            assert!((a - b).abs() <= b * 1e-4);
+        }
+
+        // 不支持的输入类型报错而不是崩溃
+        for dt in [ty::BF16, ty::U32] {
+            let mut x = vec![0u32; NH * SEQ * ATT];
+            assert!(op
+                .launch(&args(dt, x.as_mut_ptr().cast()), &mut [], &ThisThread)
+                .is_err());
+        }
+
+        assert!(op.set_accumulation(Some(ty::F16)).is_err());
+    }
+}
@@ -26,11 +26,12 @@ static __device__ void block_padding(
     Tdata *__restrict__ att,
     Tmask mask,
     unsigned int const tok_id,
-    unsigned int const seq_len) {
+    unsigned int const seq_len,
+    int const stride_a) {
 
     auto att_idx = threadIdx.x, att_len = blockDim.x;
     auto thread_data = mask(tok_id, seq_len, att_idx, att_len)
-                           ? float(att[att_idx])
+                           ? float(att[att_idx * stride_a])
                            : -__FLT_MAX__;
 
     using BlockOp = cub::BlockReduce<float, BLOCK_SIZE>;
@@ -51,7 +52,7 @@ static __device__ void block_padding(
     }
     __syncthreads();
 
-    att[att_idx] = Tdata(thread_data * mean);
+    att[att_idx * stride_a] = Tdata(thread_data * mean);
 }
 
 template<unsigned int BLOCK_SIZE, class Tdata, class Tmask>
@@ -60,7 +61,8 @@ static __device__ void block_folding(
     Tmask mask,
     unsigned int const tok_id,
     unsigned int const seq_len,
-    unsigned int const att_len) {
+    unsigned int const att_len,
+    int const stride_a) {
     // num items per thread
     auto local = (att_len + blockDim.x - 1) / blockDim.x;
     // shared memory for thread data
@@ -72,13 +74,13 @@ static __device__ void block_folding(
 
     auto thread_data = data_ + threadIdx.x;
     auto thread_offset = threadIdx.x * local;
-    att += thread_offset;
+    att += thread_offset * stride_a;
 
     float thread_max = -__FLT_MAX__;
     for (unsigned int i = 0; i < local; ++i) {
         auto att_idx = thread_offset + i;
         auto val = att_idx < att_len && mask(tok_id, seq_len, att_idx, att_len)
-                       ? float(att[i])
+                       ? float(att[i * stride_a])
                        : -__FLT_MAX__;
         thread_data[i * blockDim.x] = val;
         thread_max = cub::Max()(thread_max, val);
@@ -109,7 +111,7 @@ static __device__ void block_folding(
 
     for (unsigned int i = 0; i < local; ++i) {
         if (auto att_idx = thread_offset + i; att_idx < att_len) {
-            att[i] = Tdata(thread_data[i * blockDim.x] * mean);
+            att[i * stride_a] = Tdata(thread_data[i * blockDim.x] * mean);
         }
     }
 }
@@ -121,11 +123,12 @@ static __forceinline__ __device__ void padding(
     Tmask mask,
     int const stride_z,
     int const stride_y,
-    int const stride_x) {
+    int const stride_x,
+    int const stride_a) {
     auto offset = blockIdx.x * stride_x + blockIdx.y * stride_y + blockIdx.z * stride_z,
          tok_id = blockIdx.x,
          seq_len = gridDim.x;
-    block_padding<BLOCK_SIZE>(att + offset, mask, tok_id, seq_len);
+    block_padding<BLOCK_SIZE>(att + offset, mask, tok_id, seq_len, stride_a);
 }
 
 template<unsigned int BLOCK_SIZE, class Tdata, class Tmask>
@@ -135,9 +138,10 @@ static __forceinline__ __device__ void folding(
     unsigned int const att_len,
     int const stride_z,
     int const stride_y,
-    int const stride_x) {
+    int const stride_x,
+    int const stride_a) {
     auto offset = blockIdx.x * stride_x + blockIdx.y * stride_y + blockIdx.z * stride_z,
          tok_id = blockIdx.x,
          seq_len = gridDim.x;
-    block_folding<BLOCK_SIZE>(att + offset, mask, tok_id, seq_len, att_len);
+    block_folding<BLOCK_SIZE>(att + offset, mask, tok_id, seq_len, att_len, stride_a);
 }
@@ -3,6 +3,7 @@
     Args, FusedSoftmax,
 };
 use crate::{
+    args_not_support,
     cuda::{Gpu, Handle, ModuleBox},
     get_static, strides_not_support, type_not_support, ByteOf, LaunchError, QueueAlloc,
     SchemeError,
@@ -42,7 +43,7 @@ impl crate::Operator for Operator {
         args: &Self::Args,
         _max_workspace_size: usize,
     ) -> Result<usize, SchemeError> {
-        let Meta { dt } = args.meta()?;
+        let Meta { dt, .. } = args.meta()?;
         if dt == F16 {
             Ok(0)
         } else {
@@ -59,12 +60,21 @@ impl crate::Operator for Operator {
     where
         T: QueueAlloc<Hardware = Self::Hardware>,
     {
-        let Meta { dt } = args.meta()?;
+        let Meta { dt, .. } = args.meta()?;
         let Args {
             att_mask,
+            window,
             att_layout,
             att_base,
+            out_layout,
+            ..
         } = args;
+        if out_layout.is_some() {
+            Err(args_not_support("cuda: separate output"))?;
+        }
+        if window.is_some() {
+            Err(args_not_support("cuda: sliding window"))?;
+        }
         let &[nh, seq_len, att_len] = att_layout.shape() else {
             unreachable!()
         };
@@ -81,8 +91,9 @@ impl crate::Operator for Operator {
             sh ss      sa
         }
 
+        // 最后一维可以是任意步长，例如转置的注意力分数矩阵
         let unit = dt.nbytes() as isize;
-        if sa != unit {
+        if sa % unit != 0 {
             return Err(strides_not_support("").into());
         };
 
@@ -91,8 +102,9 @@ impl crate::Operator for Operator {
         let block_size = scheme.max_threads_block as u32;
         let sh = (sh / unit) as i32;
         let ss = (ss / unit) as i32;
+        let sa = (sa / unit) as i32;
         let att_len = att_len as u32;
-        let params = cuda::params![att_base, 0i32, sh, ss, att_len];
+        let params = cuda::params![att_base, 0i32, sh, ss, sa, att_len];
 
         if att_len <= block_size {
             scheme.module.launch(
@@ -150,10 +162,11 @@ extern "C" __global__ void {padding}(
     half *__restrict__ att,
     int const stride_z,
     int const stride_y,
-    int const stride_x
+    int const stride_x,
+    int const stride_a
 ){{
     padding<{max_threads_block}>
-    (att, {mask}(), stride_z, stride_y, stride_x);
+    (att, {mask}(), stride_z, stride_y, stride_x, stride_a);
 }}
 
 extern "C" __global__ void {folding}(
@@ -161,11 +174,12 @@ extern "C" __global__ void {folding}(
     int const stride_z,
     int const stride_y,
     int const stride_x,
+    int const stride_a,
 
     unsigned int const att_len
 ){{
     folding<{max_threads_block}>
-    (att, {mask}(), att_len, stride_z, stride_y, stride_x);
+    (att, {mask}(), att_len, stride_z, stride_y, stride_x, stride_a);
 }}
 "#
             )
@@ -190,8 +204,11 @@ mod test {
         use std::ptr::null_mut;
         Args {
             att_mask: AttnMask::Causal,
+            window: None,
             att_layout: TensorLayout::new_dyn(dt, &[dyn_(); 3], &[dyn_(); 3]),
             att_base: null_mut(),
+            out_layout: None,
+            out_base: null_mut(),
         }
     }
 
@@ -202,10 +219,14 @@ mod test {
         att_len: usize,
         att_base: *mut H::Byte,
     ) -> Args<H> {
+        use std::ptr::null_mut;
         Args {
             att_mask: AttnMask::Causal,
+            window: None,
             att_layout: TensorLayout::new_contiguous(dt, &[nh, seq_len, att_len]),
             att_base,
+            out_layout: None,
+            out_base: null_mut(),
         }
     }
 
@@ -294,4 +315,89 @@ mod test {
             assert!(out * 1000 <= count);
         }
     }
+
+    #[test]
+    fn test_compute_strided() {
+        use super::super::common_cpu::Operator as RefOp;
+        use crate::{
+            common_cpu::{Cpu, ThisThread},
+            cuda::cast_load,
+            test_utils::{Diff, ErrorCollector},
+        };
+        use cuda::memcpy_d2h;
+        use half::f16;
+        use rand::Rng;
+        use std::ptr::null_mut;
+
+        let Some(gpu) = Gpu::init() else {
+            return;
+        };
+
+        let mut cpu_op = RefOp::new(&Cpu);
+        let mut gpu_op = Operator::new(&gpu);
+        cpu_op.scheme(&dyn_args(ty::F64), 0).unwrap();
+        gpu_op.scheme(&dyn_args(ty::F16), 0).unwrap();
+
+        let nh = 4;
+        for (seq_len, att_len) in [(7, 511), (7, 2048)] {
+            // 按 [nh, att_len, seq_len] 存储，在 [nh, seq_len, att_len] 的转置视图上计算
+            let mut att_t = vec![0.0f64; nh * att_len * seq_len];
+            rand::rng().fill(&mut att_t[..]);
+            let unit = size_of::<f16>() as isize;
+            let strides = [
+                (att_len * seq_len) as isize * unit,
+                unit,
+                seq_len as isize * unit,
+            ];
+
+            let att_ans = gpu.apply(|ctx| {
+                let stream = ctx.stream();
+                let mut att = cast_load(&att_t, f16::from_f64, &stream);
+                let args = Args {
+                    att_mask: AttnMask::Causal,
+                    window: None,
+                    att_layout: TensorLayout::new(ty::F16, &[nh, seq_len, att_len], &strides),
+                    att_base: att.as_mut_ptr().cast(),
+                    out_layout: None,
+                    out_base: null_mut(),
+                };
+                gpu_op.launch(&args, &mut [], &stream).unwrap();
+                let mut host = vec![f16::ZERO; nh * att_len * seq_len];
+                memcpy_d2h(&mut host, &att);
+                host
+            });
+
+            let mut att_ref = vec![0.0f64; nh * seq_len * att_len];
+            for h in 0..nh {
+                for s in 0..seq_len {
+                    for a in 0..att_len {
+                        att_ref[(h * seq_len + s) * att_len + a] =
+                            att_t[(h * att_len + a) * seq_len + s];
+                    }
+                }
+            }
+            cpu_op
+                .launch(
+                    &args(ty::F64, nh, seq_len, att_len, att_ref.as_mut_ptr().cast()),
+                    &mut [],
+                    &ThisThread,
+                )
+                .unwrap();
+
+            let mut ec = ErrorCollector::new(f16::EPSILON.to_f64(), 0.);
+            for h in 0..nh {
+                for s in 0..seq_len {
+                    for a in 0..att_len {
+                        let x = att_ref[(h * seq_len + s) * att_len + a];
+                        let y = att_ans[(h * att_len + a) * seq_len + s];
+                        ec.push(Diff::new(x, y.to_f64()))
+                    }
+                }
+            }
+            println!("{ec}");
+
+            let (out, count) = ec.summary();
+            assert!(out * 1000 <= count);
+        }
+    }
 }
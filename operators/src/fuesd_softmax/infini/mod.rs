@@ -2,8 +2,8 @@
 
 use super::{args::Meta, Args, FusedSoftmax};
 use crate::{
-    fuesd_softmax::args::AttnMask, get_static, infini::Device, ByteOf, LaunchError, QueueAlloc,
-    SchemeError, Workspace,
+    args_not_support, fuesd_softmax::args::AttnMask, get_static, infini::Device, ByteOf,
+    LaunchError, QueueAlloc, SchemeError, Workspace,
 };
 
 pub struct Operator(Device);
@@ -38,12 +38,21 @@ impl crate::Operator for Operator {
     where
         QA: QueueAlloc<Hardware = Self::Hardware>,
     {
-        let Meta { dt } = args.meta()?;
+        let Meta { dt, .. } = args.meta()?;
         let Args {
             att_mask,
+            window,
             att_layout,
             att_base,
+            out_layout,
+            ..
         } = args;
+        if out_layout.is_some() {
+            Err(args_not_support("infini: separate output"))?;
+        }
+        if window.is_some() {
+            Err(args_not_support("infini: sliding window"))?;
+        }
         if !matches!(att_mask, AttnMask::Causal) {
             todo!()
         }
@@ -98,8 +107,11 @@ mod test {
         use std::ptr::null_mut;
         Args {
             att_mask: AttnMask::Causal,
+            window: None,
             att_layout: TensorLayout::new_dyn(dt, &[dyn_(); 3], &[dyn_(); 3]),
             att_base: null_mut(),
+            out_layout: None,
+            out_base: null_mut(),
         }
     }
 
@@ -110,10 +122,14 @@ mod test {
         att_len: usize,
         att_base: *mut H::Byte,
     ) -> Args<H> {
+        use std::ptr::null_mut;
         Args {
             att_mask: AttnMask::Causal,
+            window: None,
             att_layout: TensorLayout::new_contiguous(dt, &[nh, seq_len, att_len]),
             att_base,
+            out_layout: None,
+            out_base: null_mut(),
         }
     }
 
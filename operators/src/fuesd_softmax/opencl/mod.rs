@@ -1,5 +1,6 @@
 use super::{args::Meta, Args, FusedSoftmax};
 use crate::{
+    args_not_support,
     fuesd_softmax::args::AttnMask,
     get_static,
     opencl::{ClDevice, CodeGen, KernelCache, CL2_0},
@@ -52,7 +53,7 @@ impl crate::Operator for Operator {
         args: &Self::Args,
         _max_workspace_size: usize,
     ) -> Result<usize, SchemeError> {
-        let Meta { dt } = args.meta()?;
+        let Meta { dt, .. } = args.meta()?;
         self.cache_kernel(dt);
         Ok(0)
     }
@@ -66,14 +67,23 @@ impl crate::Operator for Operator {
     where
         QA: QueueAlloc<Hardware = Self::Hardware>,
     {
-        let Meta { dt } = args.meta()?;
+        let Meta { dt, .. } = args.meta()?;
         self.cache_kernel(args.att_layout.dt());
 
         let Args {
             att_mask,
+            window,
             att_layout,
             att_base,
+            out_layout,
+            ..
         } = args;
+        if out_layout.is_some() {
+            Err(args_not_support("opencl: separate output"))?;
+        }
+        if window.is_some() {
+            Err(args_not_support("opencl: sliding window"))?;
+        }
         if !matches!(*att_mask, AttnMask::Causal) {
             todo!()
         }
@@ -109,7 +119,7 @@ impl crate::Operator for Operator {
             .unwrap()
             .get(&dt)
             .unwrap()
-            .take(name)
+            .take_guard(name)
             .unwrap();
 
         softmax
@@ -126,10 +136,6 @@ impl crate::Operator for Operator {
                 None,
             );
 
-        let mut cache = self.schemes.lock().unwrap();
-        let program = cache.get(&dt).unwrap();
-        program.put(name, softmax);
-
         Ok(())
     }
 }
@@ -168,8 +174,11 @@ mod test {
         use std::ptr::null_mut;
         Args {
             att_mask: AttnMask::Causal,
+            window: None,
             att_layout: TensorLayout::new_dyn(dt, &[dyn_(); 3], &[dyn_(); 3]),
             att_base: null_mut(),
+            out_layout: None,
+            out_base: null_mut(),
         }
     }
 
@@ -180,10 +189,14 @@ mod test {
         att_len: usize,
         att_base: *mut H::Byte,
     ) -> Args<H> {
+        use std::ptr::null_mut;
         Args {
             att_mask: AttnMask::Causal,
+            window: None,
             att_layout: TensorLayout::new_contiguous(dt, &[nh, seq_len, att_len]),
             att_base,
+            out_layout: None,
+            out_base: null_mut(),
         }
     }
 
@@ -0,0 +1,50 @@
+use half::{bf16, f16};
+
+/// 经由 f64 计算的浮点类型。
+///
+/// f64 能精确表示其他类型的所有值，因此先扩展到 f64 计算再窄化，与直接在原类型上舍入到最近偶数一致。
+pub(crate) trait Float: Copy + Send + Sync {
+    fn to_f64(self) -> f64;
+    fn from_f64(val: f64) -> Self;
+}
+
+macro_rules! impl_float {
+    ($($ty:ty)+) => {
+        $(
+            impl Float for $ty {
+                #[inline]
+                fn to_f64(self) -> f64 {
+                    self.to_f64()
+                }
+                #[inline]
+                fn from_f64(val: f64) -> Self {
+                    <$ty>::from_f64(val)
+                }
+            }
+        )+
+    };
+}
+
+impl_float!(f16 bf16);
+
+impl Float for f32 {
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self as _
+    }
+    #[inline]
+    fn from_f64(val: f64) -> Self {
+        val as _
+    }
+}
+
+impl Float for f64 {
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self
+    }
+    #[inline]
+    fn from_f64(val: f64) -> Self {
+        val
+    }
+}
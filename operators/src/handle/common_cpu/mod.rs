@@ -1,8 +1,18 @@
+mod float;
 mod inproc_node;
+#[cfg(feature = "ndarray")]
+mod ndarray_interop;
+#[cfg(all(feature = "numa", target_os = "linux"))]
+mod numa;
 
 use crate::{Alloc, Blob, Hardware, QueueAlloc, QueueOf};
 
+pub(crate) use float::Float;
 pub use inproc_node::InprocNode;
+#[cfg(feature = "ndarray")]
+pub use ndarray_interop::{from_ndarray, from_ndarray_mut, NdElement};
+#[cfg(all(feature = "numa", target_os = "linux"))]
+pub use numa::{nodes as numa_nodes, NumaBlob, NumaNode};
 
 #[derive(Clone, Copy, Debug)]
 pub struct Cpu;
@@ -13,6 +23,11 @@ pub struct ThisThread;
 impl Hardware for Cpu {
     type Byte = u8;
     type Queue<'ctx> = ThisThread;
+    /// 与 [`Blob`] 的分配布局一致。
+    #[inline]
+    fn min_alignment() -> usize {
+        align_of::<usize>()
+    }
 }
 
 impl<T> Alloc<Blob> for T {
@@ -32,4 +47,57 @@ impl QueueAlloc for ThisThread {
     fn queue(&self) -> &QueueOf<Self::Hardware> {
         self
     }
+    #[inline]
+    fn synchronize(&self) {}
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn test_min_alignment() {
+        use super::{Cpu, ThisThread};
+        use crate::{Alloc, Hardware};
+
+        let align = Cpu::min_alignment();
+        assert!(align.is_power_of_two());
+        for size in [1, 3, 8, 17, 4096, 1 << 20] {
+            let mem = ThisThread.alloc(size);
+            assert_eq!(mem.as_ptr() as usize % align, 0);
+            ThisThread.free(mem)
+        }
+    }
+
+    #[test]
+    fn test_synchronize() {
+        use super::{Cpu, ThisThread};
+        use crate::{
+            rearrange::{common_cpu::Operator as Rearrange, Args},
+            Operator, QueueAlloc, TensorLayout,
+        };
+        use digit_layout::types::U32;
+
+        /// 与后端无关的验证流程：发射后同步，再读取结果。
+        fn run<Op, QA>(op: &Op, args: &Op::Args, queue_alloc: &QA)
+        where
+            Op: Operator,
+            QA: QueueAlloc<Hardware = Op::Hardware>,
+        {
+            op.launch(args, &mut [], queue_alloc).unwrap();
+            queue_alloc.synchronize()
+        }
+
+        let src = (0..12u32).collect::<Vec<_>>();
+        let mut dst = vec![0u32; 12];
+        let layout = TensorLayout::new_contiguous(U32, &[3, 4]);
+        let args = Args::<Cpu> {
+            dst_layout: layout.clone(),
+            dst_base: dst.as_mut_ptr().cast(),
+            src_layout: layout,
+            src_base: src.as_ptr().cast(),
+            scale: 1.,
+            bias: 0.,
+        };
+        run(&Rearrange::new(&Cpu), &args, &ThisThread);
+        assert_eq!(dst, src);
+    }
 }
@@ -0,0 +1,115 @@
+//! 与 [`ndarray`] 的互操作，将数组视图转换为算子参数需要的布局和基址。
+
+use crate::TensorLayout;
+use digit_layout::{types as ty, DigitLayout};
+use half::{bf16, f16};
+use ndarray::{ArrayView, ArrayViewMut, Dimension};
+
+/// 可以直接作为张量元素的 Rust 类型。
+pub trait NdElement {
+    /// 元素对应的数据类型。
+    const DT: DigitLayout;
+}
+
+macro_rules! nd_element {
+    ($($t:ty => $dt:expr;)+) => {
+        $(
+            impl NdElement for $t {
+                const DT: DigitLayout = $dt;
+            }
+        )+
+    };
+}
+
+nd_element! {
+    i8   => ty::I8  ;
+    i16  => ty::I16 ;
+    i32  => ty::I32 ;
+    i64  => ty::I64 ;
+    u8   => ty::U8  ;
+    u16  => ty::U16 ;
+    u32  => ty::U32 ;
+    u64  => ty::U64 ;
+    f16  => ty::F16 ;
+    bf16 => ty::BF16;
+    f32  => ty::F32 ;
+    f64  => ty::F64 ;
+}
+
+/// 由只读视图构造布局和基址。
+///
+/// ndarray 以元素为单位的步长被换算为字节步长，负步长保持不变，基址指向逻辑上的首个元素。
+/// 返回的基址只在 `view` 借用的存储有效期内有效。
+pub fn from_ndarray<T, D>(view: &ArrayView<T, D>) -> (TensorLayout, *const u8)
+where
+    T: NdElement,
+    D: Dimension,
+{
+    (
+        layout(view.shape(), view.strides(), T::DT),
+        view.as_ptr().cast(),
+    )
+}
+
+/// 由可写视图构造布局和基址。
+///
+/// 参见 [`from_ndarray`]。
+pub fn from_ndarray_mut<T, D>(view: &mut ArrayViewMut<T, D>) -> (TensorLayout, *mut u8)
+where
+    T: NdElement,
+    D: Dimension,
+{
+    (
+        layout(view.shape(), view.strides(), T::DT),
+        view.as_mut_ptr().cast(),
+    )
+}
+
+fn layout(shape: &[usize], strides: &[isize], dt: DigitLayout) -> TensorLayout {
+    let unit = dt.nbytes() as isize;
+    let strides = strides.iter().map(|&s| s * unit).collect::<Vec<_>>();
+    TensorLayout::new(dt, shape, &strides)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{from_ndarray, from_ndarray_mut};
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        rearrange::{common_cpu::Operator as Rearrange, Args},
+        MaybeDyn, Operator as _,
+    };
+    use ndarray::{Array2, ShapeBuilder};
+
+    #[test]
+    fn test_transposed_view() {
+        let src = Array2::from_shape_fn((3, 5), |(i, j)| (i * 5 + j) as f32);
+        let view = src.t();
+
+        let (src_layout, src_base) = from_ndarray(&view);
+        assert_eq!(MaybeDyn::get_all(src_layout.shape()), Some(&[5, 3][..]));
+        assert_eq!(MaybeDyn::get_all(src_layout.strides()), Some(&[4, 20][..]));
+
+        let mut dst = Array2::<f32>::zeros((5, 3));
+        let mut dst_view = dst.view_mut();
+        let (dst_layout, dst_base) = from_ndarray_mut(&mut dst_view);
+
+        let mut op = Rearrange::new(&Cpu);
+        let args = Args::<Cpu> {
+            dst_layout,
+            dst_base,
+            src_layout,
+            src_base,
+            scale: 1.,
+            bias: 0.,
+        };
+        op.scheme(&args, 0).unwrap();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+        assert_eq!(dst, view);
+
+        // 列主序数组的步长同样被正确换算
+        let f = Array2::<f64>::zeros((4, 6).f());
+        let (layout, _) = from_ndarray(&f.view());
+        assert_eq!(MaybeDyn::get_all(layout.strides()), Some(&[8, 32][..]));
+    }
+}
@@ -0,0 +1,205 @@
+use super::{Cpu, ThisThread};
+use crate::{Alloc, QueueAlloc, QueueOf};
+use std::{
+    ops::{Deref, DerefMut},
+    ptr::{null_mut, NonNull},
+    slice::{from_raw_parts, from_raw_parts_mut},
+};
+
+/// 将存储绑定到指定 NUMA 节点的分配器。
+///
+/// 绑定只是提示，内核拒绝时仍会得到可用的存储，只是不保证位于指定节点。
+#[derive(Clone, Copy, Debug)]
+pub struct NumaNode(u32);
+
+impl NumaNode {
+    /// 编号为 `node` 的节点不在线时返回 `None`。节点编号可能不连续。
+    pub fn new(node: u32) -> Option<Self> {
+        if nodes().contains(&node) {
+            Some(Self(node))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub const fn id(&self) -> u32 {
+        self.0
+    }
+}
+
+/// 系统中在线的 NUMA 节点编号，升序排列，不支持 NUMA 的系统视作只有节点 0。
+pub fn nodes() -> Vec<u32> {
+    let ans = std::fs::read_to_string("/sys/devices/system/node/online")
+        .ok()
+        .and_then(|list| parse_list(list.trim()))
+        .unwrap_or_default();
+    if ans.is_empty() {
+        vec![0]
+    } else {
+        ans
+    }
+}
+
+/// 解析形如 `0-1,4,6-7` 的节点列表。
+fn parse_list(list: &str) -> Option<Vec<u32>> {
+    let mut ans = Vec::new();
+    for range in list.split(',').filter(|s| !s.is_empty()) {
+        match range.split_once('-') {
+            Some((start, end)) => ans.extend(start.parse::<u32>().ok()?..=end.parse().ok()?),
+            None => ans.push(range.parse().ok()?),
+        }
+    }
+    ans.sort_unstable();
+    ans.dedup();
+    Some(ans)
+}
+
+/// 由 [`NumaNode`] 分配的存储，按页对齐。
+pub struct NumaBlob {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+unsafe impl Send for NumaBlob {}
+unsafe impl Sync for NumaBlob {}
+
+impl NumaNode {
+    /// [`Alloc::alloc`] 的可失败版本，映射失败时返回系统错误。
+    pub fn try_alloc(&self, size: usize) -> std::io::Result<NumaBlob> {
+        if size == 0 {
+            return Ok(NumaBlob {
+                ptr: NonNull::dangling(),
+                len: 0,
+            });
+        }
+
+        let ptr = unsafe {
+            libc::mmap(
+                null_mut(),
+                size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        // 页面在首次访问时才分配，因此在写入之前设置策略即可生效
+        const MPOL_BIND: libc::c_int = 2;
+        const BITS: usize = u64::BITS as usize;
+        let node = self.0 as usize;
+        let mut mask = vec![0u64; node / BITS + 1];
+        mask[node / BITS] |= 1 << (node % BITS);
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_mbind,
+                ptr,
+                size,
+                MPOL_BIND,
+                mask.as_ptr(),
+                mask.len() * BITS + 1,
+                0,
+            )
+        };
+        if ret != 0 {
+            log::warn!(
+                "failed to bind {size} bytes to numa node {node}: {}",
+                std::io::Error::last_os_error()
+            )
+        }
+
+        Ok(NumaBlob {
+            ptr: NonNull::new(ptr.cast()).unwrap(),
+            len: size,
+        })
+    }
+}
+
+impl Alloc<NumaBlob> for NumaNode {
+    fn alloc(&self, size: usize) -> NumaBlob {
+        self.try_alloc(size)
+            .unwrap_or_else(|e| panic!("mmap {size} bytes on numa node {} failed: {e}", self.0))
+    }
+
+    #[inline]
+    fn free(&self, _mem: NumaBlob) {}
+}
+
+impl QueueAlloc for NumaNode {
+    type Hardware = Cpu;
+    type DevMem = NumaBlob;
+    #[inline]
+    fn queue(&self) -> &QueueOf<Self::Hardware> {
+        &ThisThread
+    }
+    #[inline]
+    fn synchronize(&self) {}
+}
+
+impl Drop for NumaBlob {
+    fn drop(&mut self) {
+        let &mut NumaBlob { ptr, len } = self;
+        if len > 0 {
+            unsafe { libc::munmap(ptr.as_ptr().cast(), len) };
+        }
+    }
+}
+
+impl Deref for NumaBlob {
+    type Target = [u8];
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        unsafe { from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for NumaBlob {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{nodes, parse_list, NumaNode};
+    use crate::{Alloc, QueueAlloc};
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(parse_list("0"), Some(vec![0]));
+        assert_eq!(parse_list("0-1,4,6-7"), Some(vec![0, 1, 4, 6, 7]));
+        assert_eq!(parse_list(""), Some(vec![]));
+        assert_eq!(parse_list("0-x"), None);
+    }
+
+    #[test]
+    fn test_alloc() {
+        let ids = nodes();
+        assert!(!ids.is_empty());
+        // 编号可能不连续，不在线的编号被拒绝
+        let absent = (0..).find(|id| !ids.contains(id)).unwrap();
+        assert!(NumaNode::new(absent).is_none());
+
+        for id in ids {
+            let node = NumaNode::new(id).unwrap();
+            let _ = node.queue();
+
+            let mut mem = node.alloc(3 << 20);
+            assert_eq!(mem.len(), 3 << 20);
+            assert_eq!(mem.as_ptr() as usize % 4096, 0);
+            for (i, b) in mem.iter_mut().enumerate() {
+                *b = i as u8
+            }
+            assert!(mem.iter().enumerate().all(|(i, &b)| b == i as u8));
+            node.free(mem);
+
+            assert!(node.alloc(0).is_empty());
+            assert!(node.try_alloc(usize::MAX).is_err())
+        }
+    }
+}
@@ -87,6 +87,10 @@ impl<'ctx> QueueAlloc for StreamMemPool<'ctx> {
     fn queue(&self) -> &QueueOf<Self::Hardware> {
         &self.stream
     }
+    #[inline]
+    fn synchronize(&self) {
+        self.stream.synchronize()
+    }
 }
 
 impl<'ctx> Alloc<DevMem<'ctx>> for &'ctx CurrentCtx {
@@ -120,4 +124,8 @@ impl<'ctx> QueueAlloc for Stream<'ctx> {
     fn queue(&self) -> &QueueOf<Self::Hardware> {
         self
     }
+    #[inline]
+    fn synchronize(&self) {
+        Stream::synchronize(self)
+    }
 }
@@ -35,6 +35,11 @@ pub struct Gpu(pub(crate) Arc<Handle>);
 impl Hardware for Gpu {
     type Byte = cuda::DevByte;
     type Queue<'ctx> = cuda::Stream<'ctx>;
+    /// CUDA 的显存分配至少按 256 字节对齐。
+    #[inline]
+    fn min_alignment() -> usize {
+        256
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -71,6 +76,13 @@ impl Gpu {
         self.0.context.apply(f)
     }
 
+    /// 探测是否存在可用的 CUDA 设备。
+    ///
+    /// 没有驱动或探测过程出错时返回 `false` 而不是崩溃，用于在构造算子前选择后端。
+    pub fn is_available() -> bool {
+        std::panic::catch_unwind(|| cuda::init().is_ok()).unwrap_or(false)
+    }
+
     #[cfg(test)]
     pub(crate) fn init() -> Option<Self> {
         if let Err(cuda::NoDevice) = cuda::init() {
@@ -121,6 +133,10 @@ impl Handle {
         );
     }
 
+    /// 编译并加载模块。
+    ///
+    /// 已加载的模块按 (名字, 计算能力) 缓存在句柄上，PTX 在进程内缓存，
+    /// 与 OpenCL 的 `KernelCache` 类似，相同模块只编译加载一次，由各算子实例共享。
     pub fn compile_kernel(
         self: &Arc<Self>,
         name: impl AsRef<str>,
@@ -227,3 +243,9 @@ where
     let mem = stream.ctx().from_host(host);
     mem
 }
+
+#[test]
+fn test_is_available() {
+    // 无论是否有设备都不应崩溃
+    println!("cuda available: {}", Gpu::is_available());
+}
@@ -71,6 +71,11 @@ impl Deref for Device {
 impl Hardware for Device {
     type Byte = DevByte;
     type Queue<'ctx> = Stream;
+    /// 不同设备的分配器对齐各异，只保证与 `usize` 对齐。
+    #[inline]
+    fn min_alignment() -> usize {
+        align_of::<usize>()
+    }
 }
 
 impl Alloc<DevBlob> for Device {
@@ -102,6 +107,10 @@ impl QueueAlloc for Stream {
     fn queue(&self) -> &QueueOf<Self::Hardware> {
         self
     }
+    #[inline]
+    fn synchronize(&self) {
+        Stream::synchronize(self)
+    }
 }
 
 /// 并行转换类型并异步拷贝到显存。
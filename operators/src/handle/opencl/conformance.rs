@@ -0,0 +1,290 @@
+//! 一致性检查：在 OpenCL 设备上运行 rope、swiglu、rms_norm 和 cast，与 CPU 实现的结果对比。
+//!
+//! 用于在 CI 中对多种设备逐一验证，每个算子的检查结果汇总为一份 [`Report`]。
+//! 其他算子的 OpenCL 实现不在检查范围内，由各自的单元测试覆盖。
+
+use super::{read_to_vec, write_from_slice, ClDevice};
+use crate::{
+    cast,
+    common_cpu::{Cpu, ThisThread},
+    rms_norm, rope, swiglu, Operator, TensorLayout,
+};
+use clrt::{CommandQueue, Context, SvmBlob};
+use digit_layout::types as ty;
+use half::f16;
+use std::{cmp::Ordering, fmt, iter::zip};
+
+/// 单个算子的检查结果，失败时携带原因。
+#[derive(Clone, Debug)]
+pub struct Check {
+    pub op: &'static str,
+    pub result: Result<(), String>,
+}
+
+/// 一个设备上 rope、swiglu、rms_norm 和 cast 的检查结果。
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub device: String,
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    /// 所有检查是否都通过。
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.result.is_ok())
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "device: {}", self.device)?;
+        for Check { op, result } in &self.checks {
+            match result {
+                Ok(()) => writeln!(f, "  {op}: pass")?,
+                Err(reason) => writeln!(f, "  {op}: FAIL ({reason})")?,
+            }
+        }
+        let passed = self.checks.iter().filter(|c| c.result.is_ok()).count();
+        write!(f, "{passed}/{} passed", self.checks.len())
+    }
+}
+
+/// 在 `device` 上运行 rope、swiglu、rms_norm 和 cast，并与 CPU 实现对比。
+pub fn run(device: &ClDevice) -> Report {
+    let ctx = device.context();
+    let queue = ctx.queue();
+    let device_name = ctx
+        .devices()
+        .iter()
+        .map(|d| d.name())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let checks: [(
+        &'static str,
+        fn(&ClDevice, &CommandQueue) -> Result<(), String>,
+    ); 4] = [
+        ("rope", check_rope),
+        ("swiglu", check_swiglu),
+        ("rms_norm", check_rms_norm),
+        ("cast", check_cast),
+    ];
+    Report {
+        device: device_name,
+        checks: checks
+            .into_iter()
+            .map(|(op, check)| Check {
+                op,
+                result: check(device, &queue),
+            })
+            .collect(),
+    }
+}
+
+const RTOL: f32 = 1e-3;
+const ATOL: f32 = 1e-4;
+
+/// 确定性的测试数据，避免检查结果随运行变化。
+fn data(n: usize, seed: f32) -> Vec<f32> {
+    (0..n).map(|i| (i as f32 * 0.37 + seed).sin()).collect()
+}
+
+fn upload<T: Copy>(ctx: &Context, queue: &CommandQueue, data: &[T]) -> SvmBlob {
+    let mut svm = ctx.malloc::<T>(data.len());
+    write_from_slice(&mut svm, data, queue);
+    svm
+}
+
+/// 对比设备和 CPU 的结果，报告第一个超出容差的元素。
+fn compare(ans: &[f32], expected: &[f32]) -> Result<(), String> {
+    if ans.len() != expected.len() {
+        return Err(format!(
+            "{} elements, expected {}",
+            ans.len(),
+            expected.len()
+        ));
+    }
+    let close = |a: f32, b: f32| {
+        (a - b)
+            .abs()
+            .partial_cmp(&(ATOL + RTOL * b.abs()))
+            .is_some_and(Ordering::is_le)
+    };
+    match zip(ans, expected).position(|(&a, &b)| !close(a, b)) {
+        Some(i) => Err(format!("element {i}: {} vs {}", ans[i], expected[i])),
+        None => Ok(()),
+    }
+}
+
+fn check_rope(device: &ClDevice, queue: &CommandQueue) -> Result<(), String> {
+    const NT: usize = 5;
+    let (nh, dh) = (4, 64);
+    let t = data(NT * nh * dh, 0.);
+    let p = [0u32, 3, 1, 7, 2];
+    let t_layout = TensorLayout::new_contiguous(ty::F32, &[NT, nh, dh]);
+    let p_layout = TensorLayout::new_contiguous(ty::U32, &[NT]);
+
+    let ctx = device.context();
+    let mut t_svm = upload(ctx, queue, &t);
+    let p_svm = upload(ctx, queue, &p);
+    let args = rope::Args::<ClDevice>::builder(
+        t_layout.clone(),
+        t_svm.as_mut_ptr(),
+        p_layout.clone(),
+        p_svm.as_ptr(),
+        1e4,
+    )
+    .build();
+    rope::opencl::Operator::new(device)
+        .launch(&args, &mut [], queue)
+        .map_err(|e| format!("{e:?}"))?;
+
+    let mut t_ref = t;
+    let args = rope::Args::<Cpu>::builder(
+        t_layout,
+        t_ref.as_mut_ptr().cast(),
+        p_layout,
+        p.as_ptr().cast(),
+        1e4,
+    )
+    .build();
+    rope::common_cpu::Operator::new(&Cpu)
+        .launch(&args, &mut [], &ThisThread)
+        .map_err(|e| format!("cpu: {e:?}"))?;
+
+    compare(&read_to_vec(&mut t_svm, queue), &t_ref)
+}
+
+fn check_swiglu(device: &ClDevice, queue: &CommandQueue) -> Result<(), String> {
+    let (n, d) = (5, 256);
+    let gate = data(n * d, 0.);
+    let up = data(n * d, 1.);
+    let layout = TensorLayout::new_contiguous(ty::F32, &[n, d]);
+
+    let ctx = device.context();
+    let mut gate_svm = upload(ctx, queue, &gate);
+    let up_svm = upload(ctx, queue, &up);
+    let args = swiglu::Args {
+        gate_layout: layout.clone(),
+        gate_base: gate_svm.as_mut_ptr(),
+        up_layout: layout.clone(),
+        up_base: up_svm.as_ptr(),
+    };
+    swiglu::opencl::Operator::new(device)
+        .launch(&args, &mut [], queue)
+        .map_err(|e| format!("{e:?}"))?;
+
+    let mut gate_ref = gate;
+    let args = swiglu::Args {
+        gate_layout: layout.clone(),
+        gate_base: gate_ref.as_mut_ptr().cast(),
+        up_layout: layout,
+        up_base: up.as_ptr().cast(),
+    };
+    swiglu::common_cpu::Operator::new(&Cpu)
+        .launch(&args, &mut [], &ThisThread)
+        .map_err(|e| format!("cpu: {e:?}"))?;
+
+    compare(&read_to_vec(&mut gate_svm, queue), &gate_ref)
+}
+
+fn check_rms_norm(device: &ClDevice, queue: &CommandQueue) -> Result<(), String> {
+    let (n, d) = (5, 512);
+    let x = data(n * d, 0.);
+    let w = data(d, 2.);
+    let layout = TensorLayout::new_contiguous(ty::F32, &[n, d]);
+    let w_layout = TensorLayout::new_contiguous(ty::F32, &[d]);
+
+    let ctx = device.context();
+    let x_svm = upload(ctx, queue, &x);
+    let w_svm = upload(ctx, queue, &w);
+    let mut y_svm = ctx.malloc::<f32>(n * d);
+    let args = rms_norm::Args {
+        y_layout: layout.clone(),
+        y_base: y_svm.as_mut_ptr(),
+        x_layout: layout.clone(),
+        x_base: x_svm.as_ptr(),
+        w_layout: w_layout.clone(),
+        w_base: w_svm.as_ptr(),
+        epsilon: 1e-5,
+    };
+    rms_norm::opencl::Operator::new(device)
+        .launch(&args, &mut [], queue)
+        .map_err(|e| format!("{e:?}"))?;
+
+    let mut y_ref = vec![0.0f32; n * d];
+    let args = rms_norm::Args {
+        y_layout: layout.clone(),
+        y_base: y_ref.as_mut_ptr().cast(),
+        x_layout: layout,
+        x_base: x.as_ptr().cast(),
+        w_layout,
+        w_base: w.as_ptr().cast(),
+        epsilon: 1e-5,
+    };
+    rms_norm::common_cpu::Operator::new(&Cpu)
+        .launch(&args, &mut [], &ThisThread)
+        .map_err(|e| format!("cpu: {e:?}"))?;
+
+    compare(&read_to_vec(&mut y_svm, queue), &y_ref)
+}
+
+fn check_cast(device: &ClDevice, queue: &CommandQueue) -> Result<(), String> {
+    let (n, d) = (3, 100);
+    let x = data(n * d, 0.)
+        .into_iter()
+        .map(|x| x * 1e3)
+        .collect::<Vec<_>>();
+    let y_layout = TensorLayout::new_contiguous(ty::F16, &[n, d]);
+    let x_layout = TensorLayout::new_contiguous(ty::F32, &[n, d]);
+
+    let ctx = device.context();
+    let x_svm = upload(ctx, queue, &x);
+    let mut y_svm = ctx.malloc::<f16>(n * d);
+    let args = cast::Args {
+        y_layout: y_layout.clone(),
+        y_base: y_svm.as_mut_ptr(),
+        x_layout: x_layout.clone(),
+        x_base: x_svm.as_ptr(),
+        rounding: cast::Rounding::NearestEven,
+    };
+    cast::opencl::Operator::new(device)
+        .launch(&args, &mut [], queue)
+        .map_err(|e| format!("{e:?}"))?;
+
+    let mut y_ref = vec![f16::ZERO; n * d];
+    let args = cast::Args {
+        y_layout,
+        y_base: y_ref.as_mut_ptr().cast(),
+        x_layout,
+        x_base: x.as_ptr().cast(),
+        rounding: cast::Rounding::NearestEven,
+    };
+    cast::common_cpu::Operator::new(&Cpu)
+        .launch(&args, &mut [], &ThisThread)
+        .map_err(|e| format!("cpu: {e:?}"))?;
+
+    let ans = read_to_vec::<f16>(&mut y_svm, queue);
+    compare(
+        &ans.into_iter().map(f16::to_f32).collect::<Vec<_>>(),
+        &y_ref.into_iter().map(f16::to_f32).collect::<Vec<_>>(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{run, ClDevice};
+    use clrt::Platform;
+
+    #[test]
+    fn test_run() {
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                let device = ClDevice::new(device.context(), Default::default());
+                let report = run(&device);
+                println!("{report}");
+                assert!(report.passed(), "{report}");
+            }
+        }
+    }
+}
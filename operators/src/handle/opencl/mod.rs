@@ -1,12 +1,33 @@
-use crate::{Alloc, Hardware, Pool, QueueAlloc, QueueOf, SchemeCacheSize, SchemeDiversity};
-use clrt::{BuildError, CommandQueue, Context, Kernel, Program, SvmBlob, SvmByte};
+#[cfg(any(use_cpu, test))]
+pub mod conformance;
+
+use crate::{
+    type_not_support, Alloc, Hardware, Pool, QueueAlloc, QueueOf, SchemeCacheSize, SchemeDiversity,
+    SchemeError,
+};
+use clrt::{
+    bindings::{
+        clGetDeviceInfo, clGetEventProfilingInfo, clGetKernelWorkGroupInfo, clReleaseEvent,
+        clSetKernelArg, clWaitForEvents, cl_device_fp_config, cl_device_info,
+        cl_device_svm_capabilities, cl_event, cl_int, cl_uint, cl_ulong,
+        CL_DEVICE_DOUBLE_FP_CONFIG, CL_DEVICE_SVM_CAPABILITIES, CL_DEVICE_SVM_COARSE_GRAIN_BUFFER,
+        CL_INVALID_ARG_SIZE, CL_KERNEL_WORK_GROUP_SIZE, CL_PROFILING_COMMAND_END,
+        CL_PROFILING_COMMAND_START, CL_SUCCESS,
+    },
+    AsRaw, BuildError, CommandQueue, Context, Device, Kernel, Program, SvmBlob, SvmByte,
+};
+use digit_layout::{types as ty, DigitLayout};
 use lru::LruCache;
 use std::{
     collections::HashMap,
     ffi::{CStr, CString},
     fmt,
     hash::Hash,
-    sync::Mutex,
+    mem::size_of_val,
+    ops::{Deref, DerefMut},
+    ptr::{null, null_mut},
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
 pub struct ClDevice {
@@ -17,6 +38,11 @@ pub struct ClDevice {
 impl Hardware for ClDevice {
     type Byte = SvmByte;
     type Queue<'ctx> = CommandQueue;
+    /// 不指定对齐的 SVM 分配按设备支持的最大数据类型（16 个 64 位元素）对齐。
+    #[inline]
+    fn min_alignment() -> usize {
+        128
+    }
 }
 
 impl ClDevice {
@@ -28,6 +54,37 @@ impl ClDevice {
         }
     }
 
+    /// 检查上下文中的所有设备都支持 SVM 后再创建 [`ClDevice`]。
+    ///
+    /// 算子库使用 SVM 分配和映射设备存储，不支持 SVM 的设备（如多数 OpenCL 1.2 设备）无法使用。
+    pub fn try_new(context: Context, cache_size: SchemeCacheSize) -> Result<Self, SvmNotSupport> {
+        if let Some(device) = context.devices().iter().find(|d| !support_svm(d)) {
+            return Err(SvmNotSupport(device.name()));
+        }
+        Ok(Self::new(context, cache_size))
+    }
+
+    /// 探测是否存在算子库可用（支持 SVM）的 OpenCL 设备。
+    ///
+    /// 没有安装 OpenCL 运行时或探测过程出错时返回 `false` 而不是崩溃，
+    /// 用于在构造 [`ClDevice`] 前选择后端。
+    pub fn is_available() -> bool {
+        use clrt::Platform;
+        use std::panic::catch_unwind;
+
+        catch_unwind(|| {
+            Platform::all()
+                .into_iter()
+                .any(|platform| platform.devices().into_iter().any(|d| support_svm(&d)))
+        })
+        .unwrap_or(false)
+    }
+
+    /// 上下文中的所有设备是否都支持双精度浮点。
+    pub(crate) fn support_fp64(&self) -> bool {
+        self.ctx.devices().iter().all(support_fp64)
+    }
+
     #[inline]
     pub(crate) fn context(&self) -> &Context {
         &self.ctx
@@ -39,6 +96,43 @@ impl ClDevice {
     }
 }
 
+/// 设备不支持算子库所需的粗粒度 SVM 缓冲区，携带设备名。
+#[derive(Clone, Debug)]
+pub struct SvmNotSupport(pub String);
+
+impl fmt::Display for SvmNotSupport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "OpenCL device \"{}\" does not support SVM", self.0)
+    }
+}
+
+impl std::error::Error for SvmNotSupport {}
+
+fn device_info<T: Default>(device: &Device, param: cl_device_info) -> Option<T> {
+    let mut val = T::default();
+    let ret = unsafe {
+        clGetDeviceInfo(
+            device.as_raw(),
+            param,
+            size_of_val(&val),
+            (&mut val as *mut T).cast(),
+            null_mut(),
+        )
+    };
+    (ret == CL_SUCCESS as _).then_some(val)
+}
+
+fn support_svm(device: &Device) -> bool {
+    device_info::<cl_device_svm_capabilities>(device, CL_DEVICE_SVM_CAPABILITIES).is_some_and(
+        |caps| caps & CL_DEVICE_SVM_COARSE_GRAIN_BUFFER as cl_device_svm_capabilities != 0,
+    )
+}
+
+fn support_fp64(device: &Device) -> bool {
+    device_info::<cl_device_fp_config>(device, CL_DEVICE_DOUBLE_FP_CONFIG)
+        .is_some_and(|config| config != 0)
+}
+
 impl Alloc<SvmBlob> for Context {
     #[inline]
     fn alloc(&self, size: usize) -> SvmBlob {
@@ -68,18 +162,171 @@ impl QueueAlloc for CommandQueue {
     fn queue(&self) -> &QueueOf<Self::Hardware> {
         self
     }
+    #[inline]
+    fn synchronize(&self) {
+        self.finish()
+    }
+}
+
+/// 为核函数的 `__local` 指针参数分配局部存储。
+pub(crate) trait LocalArg {
+    /// 为第 `index` 个参数分配 `size` 字节的局部存储。
+    ///
+    /// 局部存储的大小通常由发射时选定的工作组大小决定，需要在每次发射前设置。
+    /// 设置失败（例如超出设备的局部存储容量）时返回 OpenCL 错误码，`size` 为 0 视为 `CL_INVALID_ARG_SIZE`。
+    fn set_local_arg(&mut self, index: cl_uint, size: usize) -> Result<&mut Self, cl_int>;
 }
 
-pub(crate) struct KernelCache {
+impl LocalArg for Kernel {
+    fn set_local_arg(&mut self, index: cl_uint, size: usize) -> Result<&mut Self, cl_int> {
+        if size == 0 {
+            return Err(CL_INVALID_ARG_SIZE);
+        }
+        match unsafe { clSetKernelArg(self.as_raw(), index, size, null()) } {
+            ret if ret == CL_SUCCESS as _ => Ok(self),
+            ret => Err(ret),
+        }
+    }
+}
+
+/// 查询核函数编译后的工作组上限。
+pub(crate) trait KernelWorkGroup {
+    /// 上下文中所有设备上这个核函数允许的最大工作组大小（`CL_KERNEL_WORK_GROUP_SIZE`）。
+    ///
+    /// 受寄存器和局部存储用量影响，可能小于设备的工作组上限。查询失败时返回 `None`。
+    fn work_group_size(&self, ctx: &Context) -> Option<usize>;
+}
+
+impl KernelWorkGroup for Kernel {
+    fn work_group_size(&self, ctx: &Context) -> Option<usize> {
+        ctx.devices()
+            .iter()
+            .map(|device| {
+                let mut val = 0usize;
+                let ret = unsafe {
+                    clGetKernelWorkGroupInfo(
+                        self.as_raw(),
+                        device.as_raw(),
+                        CL_KERNEL_WORK_GROUP_SIZE,
+                        size_of_val(&val),
+                        (&mut val as *mut usize).cast(),
+                        null_mut(),
+                    )
+                };
+                (ret == CL_SUCCESS as _).then_some(val)
+            })
+            .try_fold(usize::MAX, |min, val| val.map(|val| min.min(val)))
+    }
+}
+
+/// 等待事件完成并读取其在设备上的执行时间，然后释放事件。
+///
+/// 队列需要以 `CL_QUEUE_PROFILING_ENABLE` 创建，否则无法获取计时信息，返回 `None`。
+pub(crate) fn event_duration(event: cl_event) -> Option<Duration> {
+    let profile = |param| {
+        let mut val: cl_ulong = 0;
+        let ret = unsafe {
+            clGetEventProfilingInfo(
+                event,
+                param,
+                size_of_val(&val),
+                (&mut val as *mut cl_ulong).cast(),
+                null_mut(),
+            )
+        };
+        (ret == CL_SUCCESS as _).then_some(val)
+    };
+    let ans = if unsafe { clWaitForEvents(1, &event) } == CL_SUCCESS as _ {
+        profile(CL_PROFILING_COMMAND_START)
+            .zip(profile(CL_PROFILING_COMMAND_END))
+            .map(|(start, end)| Duration::from_nanos(end.saturating_sub(start)))
+    } else {
+        None
+    };
+    unsafe { clReleaseEvent(event) };
+    ans
+}
+
+/// 将设备存储映射到主机，按 `T` 类型复制出来。
+///
+/// 映射会等待队列中之前的任务完成。存储长度或对齐不满足 `T` 的要求时 panic。
+pub fn read_to_vec<T: Copy>(mem: &mut [SvmByte], queue: &CommandQueue) -> Vec<T> {
+    let map = queue.map(mem);
+    let ([], data, []) = (unsafe { map.align_to::<T>() }) else {
+        panic!("memory is not aligned to {}", std::any::type_name::<T>())
+    };
+    let ans = data.to_vec();
+    queue.unmap(map);
+    ans
+}
+
+/// 将 `data` 复制到设备存储，与 [`read_to_vec`] 相对。
+///
+/// 映射会等待队列中之前的任务完成。存储长度与 `data` 不同或对齐不满足 `T` 的要求时 panic。
+pub fn write_from_slice<T: Copy>(mem: &mut [SvmByte], data: &[T], queue: &CommandQueue) {
+    let mut map = queue.map_mut(mem, false);
+    let ([], dst, []) = (unsafe { map.align_to_mut::<T>() }) else {
+        panic!("memory is not aligned to {}", std::any::type_name::<T>())
+    };
+    dst.copy_from_slice(data);
+    queue.unmap(map);
+}
+
+pub struct KernelCache {
     program: Program,
-    kernels: HashMap<String, Pool<Kernel>>,
+    kernels: HashMap<String, Arc<Pool<Kernel>>>,
+}
+
+/// 从 [`KernelCache`] 中取出的核函数，释放时自动归还到缓存池，
+/// 即使发射过程中提前返回错误也不会使核函数从池中流失。
+pub struct KernelGuard {
+    kernel: Option<Kernel>,
+    pool: Arc<Pool<Kernel>>,
+}
+
+impl Deref for KernelGuard {
+    type Target = Kernel;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.kernel.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for KernelGuard {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.kernel.as_mut().unwrap()
+    }
+}
+
+impl Drop for KernelGuard {
+    fn drop(&mut self) {
+        if let Some(kernel) = self.kernel.take() {
+            self.pool.push(kernel)
+        }
+    }
 }
 
 pub(crate) const CL2_0: &CStr = c"-cl-std=CL2.0";
 
+/// 按数据类型生成核函数名，形如 `{prefix}_f16`。
+///
+/// 所有按数据类型区分核函数的算子都应通过此函数命名，`.cl` 中的核函数名以宏的形式传入。
+pub(crate) fn kernel_name(prefix: &str, dt: DigitLayout) -> Result<String, SchemeError> {
+    let suffix = match dt {
+        ty::F16 => "f16",
+        ty::BF16 => "bf16",
+        ty::F32 => "f32",
+        ty::F64 => "f64",
+        _ => return Err(type_not_support(format!("opencl: no kernel for {dt}"))),
+    };
+    Ok(format!("{prefix}_{suffix}"))
+}
+
 pub struct CodeGen {
     code: &'static str,
     defines: Vec<(&'static str, String)>,
+    snippets: Vec<&'static str>,
 }
 
 impl CodeGen {
@@ -87,6 +334,7 @@ impl CodeGen {
         Self {
             code,
             defines: Default::default(),
+            snippets: Default::default(),
         }
     }
 
@@ -94,13 +342,28 @@ impl CodeGen {
         self.defines.push((name, value.to_string()));
         self
     }
+
+    /// 引入共享的工作组归约，核函数源码中用 `DEFINE_GROUP_REDUCE` 定义所需的归约函数。
+    ///
+    /// 归约支持任意工作组大小，运算可以是 `REDUCE_SUM`、`REDUCE_MAX`、`REDUCE_MIN` 或自定义的宏。
+    pub fn reduce(&mut self) -> &mut Self {
+        if !self.snippets.contains(&REDUCE) {
+            self.snippets.push(REDUCE)
+        }
+        self
+    }
 }
 
+const REDUCE: &str = include_str!("reduce.cl");
+
 impl fmt::Display for CodeGen {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for (name, value) in &self.defines {
             writeln!(f, "#define {} {}", name, value)?
         }
+        for snippet in &self.snippets {
+            writeln!(f, "{snippet}")?
+        }
         write!(f, "{}", self.code)
     }
 }
@@ -117,27 +380,199 @@ impl KernelCache {
                 panic!("Failed to build cl kernels with error {err}")
             }
         };
-        let kernels = program
-            .kernels()
-            .into_iter()
-            .map(|k| {
-                let name = k.name();
-                let pool = Pool::new();
-                pool.push(k);
-                (name, pool)
-            })
-            .collect();
+        let kernels = pools(&program);
         Self { program, kernels }
     }
 
-    pub fn take(&self, name: &str) -> Option<Kernel> {
-        self.kernels
-            .get(name)?
+    /// 替换为重新编译的程序，同时清空所有缓存的核函数。
+    ///
+    /// 重建前取出的核函数仍可使用，但释放时归还到旧的缓存池，随旧池一起释放而不会进入新的缓存池。
+    pub fn rebuild(&mut self, program: Program) {
+        self.kernels = pools(&program);
+        self.program = program;
+    }
+
+    /// 取出核函数，并在返回的守卫释放时自动归还到取出时的缓存池。
+    pub fn take_guard(&self, name: &str) -> Option<KernelGuard> {
+        let pool = self.kernels.get(name)?;
+        let kernel = pool
             .pop()
-            .or_else(|| self.program.get_kernel(CString::new(name).unwrap()))
+            .or_else(|| self.program.get_kernel(CString::new(name).unwrap()))?;
+        Some(KernelGuard {
+            kernel: Some(kernel),
+            pool: pool.clone(),
+        })
+    }
+}
+
+/// 为程序中的每个核函数创建一个缓存池。
+fn pools(program: &Program) -> HashMap<String, Arc<Pool<Kernel>>> {
+    program
+        .kernels()
+        .into_iter()
+        .map(|k| {
+            let name = k.name();
+            let pool = Pool::new();
+            pool.push(k);
+            (name, Arc::new(pool))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn test_kernel_name() {
+        use super::kernel_name;
+        use digit_layout::types as ty;
+
+        assert_eq!(kernel_name("rope", ty::F16).unwrap(), "rope_f16");
+        assert_eq!(kernel_name("rope", ty::BF16).unwrap(), "rope_bf16");
+        assert_eq!(kernel_name("rope", ty::F32).unwrap(), "rope_f32");
+        assert_eq!(kernel_name("rope", ty::F64).unwrap(), "rope_f64");
+        assert!(kernel_name("rope", ty::U32).is_err());
+    }
+
+    #[test]
+    fn test_min_alignment() {
+        use super::ClDevice;
+        use crate::{Alloc, Hardware};
+        use clrt::{Platform, SvmBlob};
+
+        let align = ClDevice::min_alignment();
+        assert!(align.is_power_of_two());
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                let context = device.context();
+                let queue = context.queue();
+                for size in [1, 3, 8, 17, 4096, 1 << 20] {
+                    let mem: SvmBlob = Alloc::alloc(&queue, size);
+                    assert_eq!(mem.as_ptr() as usize % align, 0);
+                    Alloc::free(&queue, mem)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_to_vec() {
+        use super::{read_to_vec, write_from_slice};
+        use clrt::Platform;
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                let context = device.context();
+                let queue = context.queue();
+
+                let data = (0..100u32).map(|i| i * 3 + 1).collect::<Vec<_>>();
+                let mut svm = context.malloc::<u32>(data.len());
+                write_from_slice(&mut svm, &data, &queue);
+
+                assert_eq!(read_to_vec::<u32>(&mut svm, &queue), data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_kernel_guard() {
+        use super::{KernelCache, CL2_0};
+        use crate::{shape_not_support, SchemeError};
+        use clrt::{AsRaw, Platform};
+
+        const CODE: &str = "__kernel void noop(__global int *x) { x[0] = 0; }";
+
+        // 取出核函数后中途出错
+        fn fail(cache: &KernelCache) -> Result<(), SchemeError> {
+            let _kernel = cache.take_guard("noop").unwrap();
+            Err(shape_not_support(""))?;
+            unreachable!()
+        }
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                let cache = KernelCache::new(&device.context(), CODE, CL2_0);
+                let raw = {
+                    let kernel = cache.take_guard("noop").unwrap();
+                    unsafe { kernel.as_raw() }
+                };
+                assert!(fail(&cache).is_err());
+                // 出错路径上也已归还，再次取出的是池中同一个核函数
+                let kernel = cache.take_guard("noop").unwrap();
+                assert_eq!(unsafe { kernel.as_raw() }, raw);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rebuild() {
+        use super::{KernelCache, CL2_0};
+        use clrt::{
+            bindings::{clGetKernelInfo, cl_program, CL_KERNEL_PROGRAM, CL_SUCCESS},
+            AsRaw, Kernel, Platform,
+        };
+        use std::ptr::null_mut;
+
+        // 查询核函数所属的程序
+        fn program_of(kernel: &Kernel) -> Option<cl_program> {
+            let mut val: cl_program = null_mut();
+            let ret = unsafe {
+                clGetKernelInfo(
+                    kernel.as_raw(),
+                    CL_KERNEL_PROGRAM,
+                    size_of::<cl_program>(),
+                    (&mut val as *mut cl_program).cast(),
+                    null_mut(),
+                )
+            };
+            (ret == CL_SUCCESS as _).then_some(val)
+        }
+
+        const OLD: &str = "__kernel void noop(__global int *x) { x[0] = 0; }";
+        const NEW: &str = "__kernel void noop(__global int *x) { x[0] = 1; }";
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                let context = device.context();
+                let mut cache = KernelCache::new(&context, OLD, CL2_0);
+                let stale = cache.take_guard("noop").unwrap();
+
+                let Ok(program) = context.build_from_source(NEW, CL2_0) else {
+                    panic!("Failed to build cl kernels")
+                };
+                let raw = unsafe { program.as_raw() };
+                cache.rebuild(program);
+
+                // 重建前取出的核函数归还后不会再被取出
+                drop(stale);
+                let kernel = cache.take_guard("noop").unwrap();
+                assert_eq!(program_of(&kernel), Some(raw));
+                drop(kernel);
+                let kernel = cache.take_guard("noop").unwrap();
+                assert_eq!(program_of(&kernel), Some(raw));
+            }
+        }
+    }
+
+    #[test]
+    fn test_svm_check() {
+        use super::{support_svm, ClDevice};
+        use clrt::Platform;
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                let svm = support_svm(&device);
+                println!("device: {} svm: {svm}", device.name());
+                if svm {
+                    assert!(ClDevice::try_new(device.context(), Default::default()).is_ok())
+                }
+            }
+        }
     }
 
-    pub fn put(&self, name: &str, kernel: Kernel) {
-        self.kernels.get(name).unwrap().push(kernel)
+    #[test]
+    fn test_is_available() {
+        use super::ClDevice;
+        // 无论是否有设备都不应崩溃
+        println!("opencl available: {}", ClDevice::is_available());
     }
 }
@@ -0,0 +1,29 @@
+// 工作组内的树形归约，由 CodeGen::reduce 引入，在算子的核函数源码之前展开。
+//
+// DEFINE_GROUP_REDUCE(NAME, T, OP) 定义函数 `T NAME(local T *scratch, T val)`：
+// 归约工作组内所有工作项的 val，每个工作项都返回相同的结果。
+// - scratch 的长度不小于工作组大小，函数返回后可以复用；
+// - 工作组大小不要求是 2 的幂，超出工作组的位置不参与归约；
+// - 工作组内的所有工作项都必须调用，不能放在分歧的分支中。
+
+#define REDUCE_SUM(a, b) ((a) + (b))
+#define REDUCE_MAX(a, b) ((a) > (b) ? (a) : (b))
+#define REDUCE_MIN(a, b) ((a) < (b) ? (a) : (b))
+
+#define DEFINE_GROUP_REDUCE(NAME, T, OP)                                            \
+    T NAME(local T *scratch, T val) {                                               \
+        uint const l_idx = get_local_id(0), l_len = get_local_size(0);              \
+        uint span = 1;                                                              \
+        while (span < l_len) span <<= 1;                                            \
+                                                                                    \
+        scratch[l_idx] = val;                                                       \
+        barrier(CLK_LOCAL_MEM_FENCE);                                               \
+        for (uint stride = span >> 1; stride > 0; stride >>= 1) {                   \
+            if (l_idx < stride && l_idx + stride < l_len)                           \
+                scratch[l_idx] = OP(scratch[l_idx], scratch[l_idx + stride]);       \
+            barrier(CLK_LOCAL_MEM_FENCE);                                           \
+        }                                                                           \
+        T const ans = scratch[0];                                                   \
+        barrier(CLK_LOCAL_MEM_FENCE);                                               \
+        return ans;                                                                 \
+    }
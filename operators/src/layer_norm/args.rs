@@ -1,5 +1,5 @@
 ﻿use crate::{
-    utils::{dim_distinct, rank_error, type_distinct},
+    utils::{dim_distinct, rank_error, row_vector, type_distinct},
     ConstPtr, Hardware, MaybeDyn, MutPtr, SchemeError, TensorLayout,
 };
 use digit_layout::DigitLayout;
@@ -39,17 +39,15 @@ impl<H: Hardware> Args<H> {
         let &[nx, dx] = x.shape() else {
             return Err(rank_error("x", 2, x.ndim()));
         };
-        let &[ds] = scale.shape() else {
-            return Err(rank_error("scale", 1, scale.ndim()));
-        };
-        let &[db] = bias.shape() else {
-            return Err(rank_error("bias", 1, bias.ndim()));
-        };
+        let n = dim_distinct(&[ny, nx])?;
+        // 仿射参数可以是 [d]，也可以是以步长 0 广播到每一行的 [n, d]
+        let ds = row_vector("scale", scale, n)?;
+        let db = row_vector("bias", bias, n)?;
 
         Ok(Meta {
             dt_a: type_distinct(&[y.dt(), x.dt()])?,
             dt_w: type_distinct(&[scale.dt(), bias.dt()])?,
-            n: dim_distinct(&[ny, nx])?,
+            n,
             d: dim_distinct(&[dy, dx, ds, db])?,
         })
     }
@@ -53,10 +53,10 @@ impl crate::Operator for Operator {
         let &[nsx, dsx] = x_layout.strides() else {
             unreachable!()
         };
-        let &[dss] = scale_layout.strides() else {
+        let &[.., dss] = scale_layout.strides() else {
             unreachable!()
         };
-        let &[dsb] = bias_layout.strides() else {
+        let &[.., dsb] = bias_layout.strides() else {
             unreachable!()
         };
 
@@ -155,3 +155,50 @@ where
 fn get<X: NumCast, T: ToPrimitive>(ptr: *const T, offset: isize) -> X {
     X::from(unsafe { ptr.byte_offset(offset).read() }).unwrap()
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Args, Operator};
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        Operator as _, TensorLayout,
+    };
+    use digit_layout::types as ty;
+
+    #[test]
+    fn test_broadcast_affine() {
+        let (n, d) = (3, 16);
+        let x = (0..n * d)
+            .map(|i| ((i % d) as f64 * 0.7).cos())
+            .collect::<Vec<_>>();
+        let scale = (0..d).map(|i| 1. - i as f64 * 0.02).collect::<Vec<_>>();
+        let bias = (0..d).map(|i| i as f64 * 0.1).collect::<Vec<_>>();
+        let layout = TensorLayout::new_contiguous(ty::F64, &[n, d]);
+        let op = Operator::new(&Cpu);
+
+        let launch = |sb_layout: TensorLayout| {
+            let mut y = vec![0f64; n * d];
+            let args = Args::<Cpu> {
+                y_layout: layout.clone(),
+                y_base: y.as_mut_ptr().cast(),
+                x_layout: layout.clone(),
+                x_base: x.as_ptr().cast(),
+                scale_layout: sb_layout.clone(),
+                scale_base: scale.as_ptr().cast(),
+                bias_layout: sb_layout,
+                bias_base: bias.as_ptr().cast(),
+                epsilon: 1e-5,
+            };
+            op.launch(&args, &mut [], &ThisThread).unwrap();
+            y
+        };
+
+        let y_ref = launch(TensorLayout::new_contiguous(ty::F64, &[d]));
+        let y = launch(TensorLayout::new(ty::F64, &[n, d], &[0, 8]));
+        assert_eq!(y, y_ref);
+        // 每一行的输入相同，广播的仿射参数应使每一行的输出也相同
+        for row in y.chunks_exact(d) {
+            assert_eq!(row, &y[..d]);
+        }
+    }
+}
@@ -75,10 +75,10 @@ impl crate::Operator for Operator {
         let &[nsx, dsx] = x_layout.strides() else {
             unreachable!()
         };
-        let &[dss] = scale_layout.strides() else {
+        let &[.., dss] = scale_layout.strides() else {
             unreachable!()
         };
-        let &[dsb] = bias_layout.strides() else {
+        let &[.., dsb] = bias_layout.strides() else {
             unreachable!()
         };
 
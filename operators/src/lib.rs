@@ -1,5 +1,7 @@
 // #![deny(warnings)]
 
+extern crate alloc;
+
 mod common;
 mod handle;
 #[cfg(test)]
@@ -7,19 +9,28 @@ mod test;
 
 pub mod add;
 pub mod add_rows;
+pub mod all_close;
 pub mod all_reduce;
 pub mod attention;
 pub mod attention_kv_cached;
 pub mod broadcast;
+pub mod cast;
+pub mod clamp;
 pub mod conv;
+pub mod dequant;
 pub mod fuesd_softmax;
 pub mod gelu;
 pub mod layer_norm;
 pub mod mat_mul;
+pub mod mat_mul_i8;
+pub mod prelude;
+pub mod qk_score;
 pub mod random_sample;
 pub mod rearrange;
+pub mod reduce;
 pub mod rms_norm;
 pub mod rope;
+pub mod scatter_add;
 pub mod swiglu;
 
 pub use common::*;
@@ -47,6 +58,7 @@ pub extern crate cublas;
 #[cfg(use_nccl)]
 pub extern crate nccl;
 
+use digit_layout::DigitLayout;
 use rearrange::Rearrange;
 use std::{marker::PhantomData, ops::DerefMut, ptr::addr_eq};
 
@@ -59,6 +71,10 @@ pub trait Hardware {
     type Byte;
     /// 硬件的任务队列类型。
     type Queue<'ctx>;
+    /// 硬件上分配的存储保证的最小对齐字节数，是 2 的幂。
+    ///
+    /// 通用的分配和切分代码应以此对齐，而不是假设某种硬件的对齐方式。
+    fn min_alignment() -> usize;
 }
 
 pub trait TopoNode<H> {
@@ -101,6 +117,8 @@ pub trait QueueAlloc: Alloc<Self::DevMem> {
     type DevMem: DerefMut<Target = [ByteOf<Self::Hardware>]>;
     /// 分配器对应的队列。
     fn queue(&self) -> &QueueOf<Self::Hardware>;
+    /// 阻塞直到队列中已提交的任务全部完成。
+    fn synchronize(&self);
 }
 
 /// 算子。
@@ -115,6 +133,14 @@ pub trait Operator {
     /// 在指定拓扑节点上创建算子实例。
     fn new(node: &Self::TopoNode) -> Self;
 
+    /// 算子在此硬件上支持的主要数据类型，供调度器在构造参数之前按类型选择后端。
+    ///
+    /// 返回空切片表示算子未声明，需要通过 [`Operator::scheme`] 确认。
+    #[inline]
+    fn supported_dtypes() -> &'static [DigitLayout] {
+        &[]
+    }
+
     /// 规划执行方案。
     ///
     /// 通过向算子实例提供尽可能详细的参数来尽量确定算子执行方案。
@@ -132,6 +158,20 @@ pub trait Operator {
         max_workspace_size: usize,
     ) -> Result<usize, SchemeError>;
 
+    /// 规划执行方案，并返回方案的结构化描述。
+    ///
+    /// 除工作空间需求外，还给出工作空间的对齐要求和后端选用的算法标签。
+    /// 未覆盖此方法的算子以 [`Operator::scheme`] 的结果构造方案，对齐为 1，算法标签为 `"default"`。
+    #[inline]
+    fn plan(
+        &mut self,
+        args: &Self::Args,
+        max_workspace_size: usize,
+    ) -> Result<SchemePlan, SchemeError> {
+        self.scheme(args, max_workspace_size)
+            .map(SchemePlan::from_size)
+    }
+
     /// 发射算子到任务队列。
     ///
     /// 如果算子实际需要的工作空间大于通过参数提供的工作空间，将通过流分配器分配和释放工作空间。
@@ -219,7 +259,8 @@ where
         QA: QueueAlloc<Hardware = Self::Hardware>,
     {
         let args = args.as_ref();
-        if !addr_eq(args.dst_base, args.src_base) {
+        // 原地的纯复制无需执行，带仿射变换时仍要逐元素计算
+        if !addr_eq(args.dst_base, args.src_base) || !args.is_plain_copy() {
             self.0.launch(args, workspace, queue_alloc)?
         }
         Ok(())
@@ -20,7 +20,7 @@ pub struct Args<H: Hardware> {
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
-pub(super) struct SchemeLayout {
+pub(crate) struct SchemeLayout {
     pub dt: DigitLayout,
     pub ab_swap: bool,
     pub a_trans: bool,
@@ -69,10 +69,25 @@ impl<H: Hardware> Args<H> {
             ..
         } = self;
 
+        let mut layout = SchemeLayout::new(c_layout, a_layout, b_layout)?;
+        layout.dt = type_distinct(&[c_layout.dt(), a_layout.dt(), b_layout.dt()])?;
+        Ok(layout)
+    }
+}
+
+impl SchemeLayout {
+    /// 将 c = a · b 变换为列优先的形式，步长以各自的元素为单位。
+    ///
+    /// 不检查数据类型，`dt` 取 `a` 的类型，由调用者检查或替换。
+    pub(crate) fn new(
+        c_layout: &TensorLayout,
+        a_layout: &TensorLayout,
+        b_layout: &TensorLayout,
+    ) -> Result<Self, SchemeError> {
         // 确认矩阵结构匹配
-        let mut c = Matrix::try_from(&self.c_layout)?;
-        let mut a = Matrix::try_from(&self.a_layout)?;
-        let mut b = Matrix::try_from(&self.b_layout)?;
+        let mut c = Matrix::try_from(c_layout)?;
+        let mut a = Matrix::try_from(a_layout)?;
+        let mut b = Matrix::try_from(b_layout)?;
         if c.r != a.r || c.c != b.c || a.c != b.r {
             return Err(shape_mismatch("Inconsistent matrix shapes"));
         }
@@ -98,8 +113,8 @@ impl<H: Hardware> Args<H> {
 
         let (a_ld, a_trans) = a.ld_trans()?;
         let (b_ld, b_trans) = b.ld_trans()?;
-        Ok(SchemeLayout {
-            dt: type_distinct(&[c_layout.dt(), a_layout.dt(), b_layout.dt()])?,
+        Ok(Self {
+            dt: a_layout.dt(),
             ab_swap,
             a_trans,
             b_trans,
@@ -9,5 +9,6 @@ pub mod opencl;
 
 mod args;
 pub use args::Args;
+pub(crate) use args::SchemeLayout;
 
 crate::op_trait!(MatMul);
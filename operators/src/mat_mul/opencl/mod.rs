@@ -99,7 +99,7 @@ impl crate::Operator for Operator {
             .unwrap()
             .get(&key)
             .unwrap()
-            .take("general_gemm")
+            .take_guard("general_gemm")
             .unwrap();
 
         let queue = _queue_alloc.queue();
@@ -123,9 +123,6 @@ impl crate::Operator for Operator {
             .set_arg(16, alpha)
             .set_arg(17, beta)
             .launch(&[0, 0], &[batch, mn], &[1, groupsize], queue, None);
-        let mut cache = self.schemes.lock().unwrap();
-        let program = cache.get(&key).unwrap();
-        program.put("general_gemm", matmul);
         Ok(())
     }
 }
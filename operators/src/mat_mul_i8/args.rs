@@ -0,0 +1,151 @@
+use crate::{
+    mat_mul::SchemeLayout, shape_mismatch, shape_not_support, static_from, type_not_support,
+    utils::rank_error, ConstPtr, Hardware, MutPtr, SchemeError, TensorLayout,
+};
+use digit_layout::{types as ty, DigitLayout};
+use std::ptr::{null, null_mut};
+
+/// int32 累加不会溢出的最大 `k`：每项乘积的绝对值不超过 128 × 128。
+pub const MAX_K: usize = i32::MAX as usize / (128 * 128);
+
+pub struct Args<H: Hardware> {
+    /// 输出 [(batch,) m, n]，不缩放时为 `i32`，缩放时为 `f32`。
+    pub c_layout: TensorLayout,
+    pub c_base: MutPtr<H>,
+    /// [(batch,) m, k]，`i8` 类型。
+    pub a_layout: TensorLayout,
+    pub a_base: ConstPtr<H>,
+    /// [(batch,) k, n]，`i8` 类型。
+    pub b_layout: TensorLayout,
+    pub b_base: ConstPtr<H>,
+    /// a 的逐行缩放 [m] 和 b 的逐列缩放 [n]，`f32` 类型，所有批次共用。
+    pub scales: Option<[(TensorLayout, ConstPtr<H>); 2]>,
+}
+
+pub(super) struct Meta {
+    pub layout: SchemeLayout,
+    /// 缩放的步长，以元素为单位。
+    pub scale_strides: Option<[isize; 2]>,
+}
+
+impl<H: Hardware> Args<H> {
+    pub fn new_null(
+        c_layout: TensorLayout,
+        a_layout: TensorLayout,
+        b_layout: TensorLayout,
+        scale_layouts: Option<[TensorLayout; 2]>,
+    ) -> Self {
+        Self {
+            c_layout,
+            c_base: null_mut(),
+            a_layout,
+            a_base: null(),
+            b_layout,
+            b_base: null(),
+            scales: scale_layouts.map(|layouts| layouts.map(|layout| (layout, null()))),
+        }
+    }
+
+    pub(super) fn meta(&self) -> Result<Meta, SchemeError> {
+        let Self {
+            c_layout,
+            a_layout,
+            b_layout,
+            scales,
+            ..
+        } = self;
+
+        let dt_c = match scales {
+            Some(_) => ty::F32,
+            None => ty::I32,
+        };
+        check_dt("c", c_layout.dt(), dt_c)?;
+        check_dt("a", a_layout.dt(), ty::I8)?;
+        check_dt("b", b_layout.dt(), ty::I8)?;
+
+        let layout = SchemeLayout::new(c_layout, a_layout, b_layout)?;
+        if layout.k > MAX_K {
+            return Err(shape_not_support(format!(
+                "k = {} may overflow int32 accumulation (max {MAX_K})",
+                layout.k
+            )));
+        }
+
+        let scale_strides = match scales {
+            Some([(sa, _), (sb, _)]) => {
+                // 交换 a、b 时缩放也随之交换，与列优先的 m、n 对应
+                let (m, n) = if layout.ab_swap {
+                    (layout.n, layout.m)
+                } else {
+                    (layout.m, layout.n)
+                };
+                Some([scale("a scale", sa, m)?, scale("b scale", sb, n)?])
+            }
+            None => None,
+        };
+        Ok(Meta {
+            layout,
+            scale_strides,
+        })
+    }
+}
+
+fn check_dt(name: &str, dt: DigitLayout, expected: DigitLayout) -> Result<(), SchemeError> {
+    if dt == expected {
+        Ok(())
+    } else {
+        Err(type_not_support(format!(
+            "{name}: {dt}, expected {expected}"
+        )))
+    }
+}
+
+/// 检查缩放向量的形状和类型，返回以元素为单位的步长。
+fn scale(name: &str, layout: &TensorLayout, len: usize) -> Result<isize, SchemeError> {
+    check_dt(name, layout.dt(), ty::F32)?;
+    let &[n] = layout.shape() else {
+        return Err(rank_error(name, 1, layout.ndim()));
+    };
+    let &[s] = layout.strides() else {
+        unreachable!()
+    };
+    if *static_from(&n)? != len {
+        return Err(shape_mismatch(format!("{name}: {n} != {len}")));
+    }
+    Ok(*static_from(&s)? / size_of::<f32>() as isize)
+}
+
+#[test]
+fn test_meta() {
+    use crate::common_cpu::Cpu;
+
+    let layout = |dt, shape: &[usize]| TensorLayout::new_contiguous(dt, shape);
+    let args = |k, scales| {
+        Args::<Cpu>::new_null(
+            layout(if scales { ty::F32 } else { ty::I32 }, &[3, 5]),
+            layout(ty::I8, &[3, k]),
+            layout(ty::I8, &[k, 5]),
+            scales.then(|| [layout(ty::F32, &[3]), layout(ty::F32, &[5])]),
+        )
+    };
+
+    let Meta {
+        layout: scheme,
+        scale_strides,
+    } = args(7, true).meta().unwrap();
+    // c 行优先，转为列优先时交换 a 和 b
+    assert_eq!((scheme.m, scheme.n, scheme.k), (5, 3, 7));
+    assert!(scheme.ab_swap);
+    assert_eq!(scale_strides, Some([1, 1]));
+
+    assert!(args(MAX_K, false).meta().is_ok());
+    assert!(args(MAX_K + 1, false).meta().is_err());
+
+    // 不缩放时输出必须是 i32，缩放时必须是 f32
+    let mut wrong = args(7, false);
+    wrong.c_layout = layout(ty::F32, &[3, 5]);
+    assert!(wrong.meta().is_err());
+    let mut wrong = args(7, true);
+    wrong.scales.as_mut().unwrap()[1].0 = layout(ty::F32, &[4]);
+    assert!(wrong.meta().is_err());
+}
@@ -0,0 +1,228 @@
+use super::{args::Meta, Args, MatMulI8};
+use crate::{common_cpu::Cpu, mat_mul::SchemeLayout, ByteOf, LaunchError, QueueAlloc, SchemeError};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+pub struct Operator;
+
+impl MatMulI8<Cpu> for Operator {}
+
+impl crate::Operator for Operator {
+    type Hardware = Cpu;
+    type TopoNode = Cpu;
+    type Args = Args<Cpu>;
+
+    #[inline]
+    fn new(_node: &Self::TopoNode) -> Self {
+        Self
+    }
+
+    fn scheme(
+        &mut self,
+        args: &Self::Args,
+        _max_workspace_size: usize,
+    ) -> Result<usize, SchemeError> {
+        args.meta().map(|_| 0)
+    }
+
+    fn launch<QA>(
+        &self,
+        args: &Self::Args,
+        _workspace: &mut [ByteOf<Self::Hardware>],
+        _queue_alloc: &QA,
+    ) -> Result<(), LaunchError>
+    where
+        QA: QueueAlloc<Hardware = Self::Hardware>,
+    {
+        let Meta {
+            layout:
+                SchemeLayout {
+                    ab_swap,
+                    a_trans,
+                    b_trans,
+                    batch,
+                    m,
+                    n,
+                    k,
+                    c_stride,
+                    c_ld,
+                    a_stride,
+                    a_ld,
+                    b_stride,
+                    b_ld,
+                    ..
+                },
+            scale_strides,
+        } = args.meta()?;
+        let &Args {
+            c_base,
+            a_base,
+            b_base,
+            ref scales,
+            ..
+        } = args;
+
+        // 以下按列优先的 c' = a' · b' 计算，交换时 a'、b' 分别为 b、a 的转置
+        let c = c_base as usize;
+        let [a, b] = if ab_swap {
+            [b_base, a_base]
+        } else {
+            [a_base, b_base]
+        }
+        .map(|ptr| ptr as usize);
+        let (a_cs, a_rs) = if a_trans { (1, a_ld) } else { (a_ld, 1) };
+        let (b_cs, b_rs) = if b_trans { (1, b_ld) } else { (b_ld, 1) };
+        // c' 的行对应 a' 的行，列对应 b' 的列
+        let scales = scales.as_ref().zip(scale_strides).map(|(scales, strides)| {
+            let [(_, sa), (_, sb)] = scales;
+            let [ssa, ssb] = strides;
+            let (row, col) = if ab_swap {
+                ((*sb as usize, ssb), (*sa as usize, ssa))
+            } else {
+                ((*sa as usize, ssa), (*sb as usize, ssb))
+            };
+            [row, col]
+        });
+
+        (0..batch * n).into_par_iter().for_each(|i| {
+            let (i, j) = ((i / n) as isize, (i % n) as isize);
+            let a = (a as *const i8).wrapping_offset(i * a_stride);
+            let b = (b as *const i8).wrapping_offset(i * b_stride + j * b_cs);
+            for r in 0..m as isize {
+                let a = a.wrapping_offset(r * a_rs);
+                let acc = (0..k as isize)
+                    .map(|l| unsafe { *a.offset(l * a_cs) as i32 * *b.offset(l * b_rs) as i32 })
+                    .sum::<i32>();
+                let idx = i * c_stride + r + j * c_ld;
+                match scales {
+                    Some([(row, srow), (col, scol)]) => unsafe {
+                        let s = *(row as *const f32).offset(r * srow)
+                            * *(col as *const f32).offset(j * scol);
+                        *(c as *mut f32).offset(idx) = acc as f32 * s
+                    },
+                    None => unsafe { *(c as *mut i32).offset(idx) = acc },
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{super::MAX_K, Args, Operator};
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        Operator as _, TensorLayout,
+    };
+    use digit_layout::types as ty;
+    use rand::Rng;
+
+    /// 以 i64 累加的参考实现，`a` 为 [m, k]，`b` 为 [k, n]。
+    fn reference(a: &[i8], b: &[i8], m: usize, n: usize, k: usize) -> Vec<i64> {
+        let mut c = vec![0i64; m * n];
+        for i in 0..m {
+            for j in 0..n {
+                c[i * n + j] = (0..k)
+                    .map(|l| a[i * k + l] as i64 * b[l * n + j] as i64)
+                    .sum();
+            }
+        }
+        c
+    }
+
+    fn args(m: usize, n: usize, k: usize, c: &mut [i32], a: &[i8], b: &[i8]) -> Args<Cpu> {
+        Args {
+            c_layout: TensorLayout::new_contiguous(ty::I32, &[m, n]),
+            c_base: c.as_mut_ptr().cast(),
+            a_layout: TensorLayout::new_contiguous(ty::I8, &[m, k]),
+            a_base: a.as_ptr().cast(),
+            b_layout: TensorLayout::new_contiguous(ty::I8, &[k, n]),
+            b_base: b.as_ptr().cast(),
+            scales: None,
+        }
+    }
+
+    #[test]
+    fn test_compute() {
+        let mut op = Operator::new(&Cpu);
+        for (m, n, k) in [(1, 1, 1), (7, 13, 64), (33, 5, 1000)] {
+            let mut a = vec![0i8; m * k];
+            let mut b = vec![0i8; k * n];
+            rand::rng().fill(&mut a[..]);
+            rand::rng().fill(&mut b[..]);
+            let mut c = vec![0i32; m * n];
+
+            let args = args(m, n, k, &mut c, &a, &b);
+            op.scheme(&args, 0).unwrap();
+            op.launch(&args, &mut [], &ThisThread).unwrap();
+            let c_ref = reference(&a, &b, m, n, k);
+            assert!(c.iter().zip(&c_ref).all(|(&c, &r)| c as i64 == r));
+        }
+    }
+
+    #[test]
+    fn test_large_k() {
+        // 最坏情况的累加恰好不溢出 i32
+        let (m, n, k) = (2, 3, MAX_K);
+        let a = vec![i8::MIN; m * k];
+        let b = vec![i8::MIN; k * n];
+        let mut c = vec![0i32; m * n];
+
+        let op = Operator::new(&Cpu);
+        op.launch(&args(m, n, k, &mut c, &a, &b), &mut [], &ThisThread)
+            .unwrap();
+        let c_ref = reference(&a, &b, m, n, k);
+        assert!(c_ref.iter().all(|&r| r <= i32::MAX as i64));
+        assert!(c.iter().zip(&c_ref).all(|(&c, &r)| c as i64 == r));
+
+        // 再多一项就可能溢出，拒绝执行
+        let k = MAX_K + 1;
+        let a = vec![i8::MIN; m * k];
+        let b = vec![i8::MIN; k * n];
+        let mut op = Operator::new(&Cpu);
+        assert!(op.scheme(&args(m, n, k, &mut c, &a, &b), 0).is_err());
+        assert!(op
+            .launch(&args(m, n, k, &mut c, &a, &b), &mut [], &ThisThread)
+            .is_err());
+    }
+
+    #[test]
+    fn test_scales() {
+        let (m, n, k) = (5, 9, 100);
+        let mut a = vec![0i8; m * k];
+        let mut b = vec![0i8; k * n];
+        rand::rng().fill(&mut a[..]);
+        rand::rng().fill(&mut b[..]);
+        let sa = (0..m).map(|i| 0.5 + i as f32).collect::<Vec<_>>();
+        let sb = (0..n).map(|j| 0.25 * (j + 1) as f32).collect::<Vec<_>>();
+        let mut c = vec![0f32; m * n];
+
+        let op = Operator::new(&Cpu);
+        let args = Args::<Cpu> {
+            c_layout: TensorLayout::new_contiguous(ty::F32, &[m, n]),
+            c_base: c.as_mut_ptr().cast(),
+            a_layout: TensorLayout::new_contiguous(ty::I8, &[m, k]),
+            a_base: a.as_ptr().cast(),
+            b_layout: TensorLayout::new_contiguous(ty::I8, &[k, n]),
+            b_base: b.as_ptr().cast(),
+            scales: Some([
+                (
+                    TensorLayout::new_contiguous(ty::F32, &[m]),
+                    sa.as_ptr().cast(),
+                ),
+                (
+                    TensorLayout::new_contiguous(ty::F32, &[n]),
+                    sb.as_ptr().cast(),
+                ),
+            ]),
+        };
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        let c_ref = reference(&a, &b, m, n, k);
+        for i in 0..m {
+            for j in 0..n {
+                assert_eq!(c[i * n + j], c_ref[i * n + j] as f32 * (sa[i] * sb[j]));
+            }
+        }
+    }
+}
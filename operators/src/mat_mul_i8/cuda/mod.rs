@@ -0,0 +1,334 @@
+use super::{args::Meta, Args, MatMulI8};
+use crate::{
+    cuda::{Gpu, Handle, ModuleBox},
+    mat_mul::SchemeLayout,
+    strides_not_support, ByteOf, LaunchError, QueueAlloc, SchemeError,
+};
+use cublas::cublas;
+use cuda::AsRaw;
+use std::{
+    ffi::{c_void, CString},
+    sync::Arc,
+};
+
+pub struct Operator {
+    handle: Arc<Handle>,
+    max_threads_block: usize,
+    module: Arc<ModuleBox>,
+}
+
+const NAME: &str = "mat_mul_i8_scale";
+
+impl MatMulI8<Gpu> for Operator {}
+
+impl crate::Operator for Operator {
+    type Hardware = Gpu;
+    type TopoNode = Gpu;
+    type Args = Args<Gpu>;
+
+    fn new(processor: &Self::TopoNode) -> Self {
+        // 保证 cublas Handle 池非空
+        processor.0.cublas_init();
+        let device = processor.0.device();
+        Self {
+            handle: processor.0.clone(),
+            max_threads_block: device.block_limit().max_threads,
+            module: processor
+                .0
+                .compile_kernel(NAME, device.compute_capability(), format_code),
+        }
+    }
+
+    fn scheme(
+        &mut self,
+        args: &Self::Args,
+        _max_workspace_size: usize,
+    ) -> Result<usize, SchemeError> {
+        args.meta().map(|_| 0)
+    }
+
+    fn launch<QA>(
+        &self,
+        args: &Self::Args,
+        _workspace: &mut [ByteOf<Self::Hardware>],
+        queue_alloc: &QA,
+    ) -> Result<(), LaunchError>
+    where
+        QA: QueueAlloc<Hardware = Self::Hardware>,
+    {
+        let Meta {
+            layout,
+            scale_strides,
+        } = args.meta()?;
+        let SchemeLayout {
+            ab_swap,
+            a_trans,
+            b_trans,
+            batch,
+            m,
+            n,
+            k,
+            c_stride,
+            c_ld,
+            a_stride,
+            a_ld,
+            b_stride,
+            b_ld,
+            ..
+        } = layout;
+        let &Args {
+            c_base,
+            a_base,
+            b_base,
+            ref scales,
+            ..
+        } = args;
+
+        if batch * m * n == 0 {
+            return Ok(());
+        }
+        // IMMA 要求 int8 矩阵的主维是 4 的倍数
+        if a_ld % 4 != 0 || b_ld % 4 != 0 {
+            Err(strides_not_support(format!(
+                "cuda: int8 matmul leading dimensions {a_ld} and {b_ld} must be multiples of 4"
+            )))?
+        }
+
+        let (a, b) = if ab_swap {
+            (b_base, a_base)
+        } else {
+            (a_base, b_base)
+        };
+
+        let c = c_base.cast::<c_void>();
+        let a = a.cast::<c_void>();
+        let b = b.cast::<c_void>();
+        let alpha = 1i32;
+        let beta = 0i32;
+
+        self.handle.cublas(queue_alloc.queue(), |handle| {
+            cublas!(cublasGemmStridedBatchedEx(
+                handle.as_raw(),
+                if a_trans {
+                    cublasOperation_t::CUBLAS_OP_T
+                } else {
+                    cublasOperation_t::CUBLAS_OP_N
+                },
+                if b_trans {
+                    cublasOperation_t::CUBLAS_OP_T
+                } else {
+                    cublasOperation_t::CUBLAS_OP_N
+                },
+                m as _,
+                n as _,
+                k as _,
+                ((&alpha) as *const i32).cast(),
+                a,
+                cudaDataType_t::CUDA_R_8I,
+                a_ld as _,
+                a_stride as _,
+                b,
+                cudaDataType_t::CUDA_R_8I,
+                b_ld as _,
+                b_stride as _,
+                ((&beta) as *const i32).cast(),
+                c,
+                cudaDataType_t::CUDA_R_32I,
+                c_ld as _,
+                c_stride as _,
+                batch as _,
+                cublas::bindings::cublasComputeType_t::CUBLAS_COMPUTE_32I,
+                cublasGemmAlgo_t::CUBLAS_GEMM_DFALT,
+            ));
+        });
+
+        // 缩放时先把 i32 结果写入 c，再原地转为 f32 并乘以缩放，两者大小相同
+        if let Some(([(_, sa), (_, sb)], [ssa, ssb])) = scales.as_ref().zip(scale_strides) {
+            // c' 的行对应 a' 的行，列对应 b' 的列
+            let (row, srow, col, scol) = if ab_swap {
+                (*sb, ssb as i32, *sa, ssa as i32)
+            } else {
+                (*sa, ssa as i32, *sb, ssb as i32)
+            };
+            let sc = c_stride as i32;
+            let ld = c_ld as i32;
+            let m = m as i32;
+            let params = cuda::params![c_base, sc, ld, row, srow, col, scol, m];
+            let block = self.max_threads_block.min(m as usize);
+
+            self.module.launch(
+                CString::new(NAME).unwrap(),
+                (n as u32, batch as u32),
+                block as u32,
+                params.as_ptr(),
+                0,
+                queue_alloc.queue(),
+            );
+        }
+        Ok(())
+    }
+}
+
+fn format_code() -> String {
+    format!(
+        r#"extern "C" __global__ void {NAME}(
+    int *__restrict__ c,
+    int const stride_batch,
+    int const ld,
+    float const *__restrict__ row_scale,
+    int const stride_row,
+    float const *__restrict__ col_scale,
+    int const stride_col,
+    int const m
+){{
+    c += blockIdx.y * stride_batch + blockIdx.x * ld;
+    float const col = col_scale[blockIdx.x * stride_col];
+    for (int r = threadIdx.x; r < m; r += blockDim.x) {{
+        float const val = (float) c[r] * (row_scale[r * stride_row] * col);
+        reinterpret_cast<float *>(c)[r] = val;
+    }}
+}}"#
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Args, Gpu, Operator};
+    use crate::{Operator as _, TensorLayout};
+    use digit_layout::types as ty;
+
+    #[test]
+    fn test_compute() {
+        use cuda::memcpy_d2h;
+        use rand::Rng;
+
+        let Some(gpu) = Gpu::init() else {
+            return;
+        };
+        let op = Operator::new(&gpu);
+
+        let (batch, m, n, k) = (3, 64, 96, 4096);
+        let mut a = vec![0i8; batch * m * k];
+        let mut b = vec![0i8; batch * k * n];
+        rand::rng().fill(&mut a[..]);
+        rand::rng().fill(&mut b[..]);
+
+        let c_ans = gpu.apply(|ctx| {
+            let stream = ctx.stream();
+            let a_dev = stream.from_host(&a);
+            let b_dev = stream.from_host(&b);
+            let mut c_dev = stream.malloc::<i32>(batch * m * n);
+            op.launch(
+                &Args {
+                    c_layout: TensorLayout::new_contiguous(ty::I32, &[batch, m, n]),
+                    c_base: c_dev.as_mut_ptr().cast(),
+                    a_layout: TensorLayout::new_contiguous(ty::I8, &[batch, m, k]),
+                    a_base: a_dev.as_ptr().cast(),
+                    b_layout: TensorLayout::new_contiguous(ty::I8, &[batch, k, n]),
+                    b_base: b_dev.as_ptr().cast(),
+                    scales: None,
+                },
+                &mut [],
+                &stream,
+            )
+            .unwrap();
+
+            let mut ans = vec![0i32; batch * m * n];
+            memcpy_d2h(&mut ans, &c_dev);
+            ans
+        });
+
+        // 以 i64 累加作为参考
+        for i in 0..batch {
+            for r in 0..m {
+                for j in 0..n {
+                    let expected = (0..k)
+                        .map(|l| a[(i * m + r) * k + l] as i64 * b[(i * k + l) * n + j] as i64)
+                        .sum::<i64>();
+                    assert_eq!(c_ans[(i * m + r) * n + j] as i64, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_scales() {
+        use super::super::common_cpu::Operator as RefOp;
+        use crate::common_cpu::{Cpu, ThisThread};
+        use cuda::memcpy_d2h;
+        use rand::Rng;
+
+        let Some(gpu) = Gpu::init() else {
+            return;
+        };
+        let op = Operator::new(&gpu);
+
+        let (m, n, k) = (40, 72, 256);
+        let mut a = vec![0i8; m * k];
+        let mut b = vec![0i8; k * n];
+        rand::rng().fill(&mut a[..]);
+        rand::rng().fill(&mut b[..]);
+        let sa = (0..m).map(|i| 0.5 + i as f32).collect::<Vec<_>>();
+        let sb = (0..n).map(|j| 0.25 * (j + 1) as f32).collect::<Vec<_>>();
+        let scales = || {
+            [
+                TensorLayout::new_contiguous(ty::F32, &[m]),
+                TensorLayout::new_contiguous(ty::F32, &[n]),
+            ]
+        };
+
+        let c_ans = gpu.apply(|ctx| {
+            let stream = ctx.stream();
+            let a_dev = stream.from_host(&a);
+            let b_dev = stream.from_host(&b);
+            let sa_dev = stream.from_host(&sa);
+            let sb_dev = stream.from_host(&sb);
+            let mut c_dev = stream.malloc::<f32>(m * n);
+            let [sa_layout, sb_layout] = scales();
+            op.launch(
+                &Args {
+                    c_layout: TensorLayout::new_contiguous(ty::F32, &[m, n]),
+                    c_base: c_dev.as_mut_ptr().cast(),
+                    a_layout: TensorLayout::new_contiguous(ty::I8, &[m, k]),
+                    a_base: a_dev.as_ptr().cast(),
+                    b_layout: TensorLayout::new_contiguous(ty::I8, &[k, n]),
+                    b_base: b_dev.as_ptr().cast(),
+                    scales: Some([
+                        (sa_layout, sa_dev.as_ptr().cast()),
+                        (sb_layout, sb_dev.as_ptr().cast()),
+                    ]),
+                },
+                &mut [],
+                &stream,
+            )
+            .unwrap();
+
+            let mut ans = vec![0f32; m * n];
+            memcpy_d2h(&mut ans, &c_dev);
+            ans
+        });
+
+        let mut c_ref = vec![0f32; m * n];
+        let [sa_layout, sb_layout] = scales();
+        RefOp::new(&Cpu)
+            .launch(
+                &Args::<Cpu> {
+                    c_layout: TensorLayout::new_contiguous(ty::F32, &[m, n]),
+                    c_base: c_ref.as_mut_ptr().cast(),
+                    a_layout: TensorLayout::new_contiguous(ty::I8, &[m, k]),
+                    a_base: a.as_ptr().cast(),
+                    b_layout: TensorLayout::new_contiguous(ty::I8, &[k, n]),
+                    b_base: b.as_ptr().cast(),
+                    scales: Some([
+                        (sa_layout, sa.as_ptr().cast()),
+                        (sb_layout, sb.as_ptr().cast()),
+                    ]),
+                },
+                &mut [],
+                &ThisThread,
+            )
+            .unwrap();
+        // 两侧都是精确的 i32 累加后按相同顺序做 f32 乘法
+        assert_eq!(c_ans, c_ref);
+    }
+}
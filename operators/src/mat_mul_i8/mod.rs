@@ -0,0 +1,14 @@
+//! c = a · b 或 c = (sa ⊗ sb) ⊙ (a · b)
+//!
+//! int8 矩阵乘，以 int32 精确累加。不缩放时输出 int32；
+//! 提供 a 的逐行缩放和 b 的逐列缩放时，累加结果乘以对应的缩放后输出 f32。
+
+#[cfg(any(use_cpu, test))]
+pub mod common_cpu;
+#[cfg(use_nvidia)]
+pub mod cuda;
+
+mod args;
+pub use args::{Args, MAX_K};
+
+crate::op_trait!(MatMulI8);
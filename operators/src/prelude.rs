@@ -0,0 +1,52 @@
+//! 常用类型和特质的集中导出。
+//!
+//! `use operators::prelude::*` 即可获得构造和发射算子所需的大部分名字。
+//! 各算子的 `Args` 和后端 `Operator` 同名，仍需通过算子模块路径访问。
+
+pub use crate::{
+    dyn_, empty_like, zeros_like, Alloc, ArgsOf, Blob, ByteOf, Hardware, LaunchError, MaybeDyn,
+    Operator, QueueAlloc, QueueOf, SchemeError, TensorLayout, TopoNode, Workspace,
+};
+pub use digit_layout::{types as ty, DigitLayout};
+
+#[cfg(any(use_cpu, test))]
+pub use crate::common_cpu::{Cpu, ThisThread};
+
+#[cfg(use_cl)]
+pub use crate::opencl::ClDevice;
+
+#[cfg(use_cuda)]
+pub use crate::cuda::Gpu;
+
+#[cfg(use_infini)]
+pub use crate::infini::Device as InfiniDevice;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rope_args() {
+        use std::ptr::{null, null_mut};
+
+        let args = crate::rope::Args::<Cpu> {
+            t_layout: TensorLayout::new_dyn(ty::F32, &[dyn_(); 3], &[dyn_(); 3]),
+            t_base: null_mut(),
+            p_layout: TensorLayout::new_contiguous(ty::U32, &[7]),
+            p_base: null(),
+            sin_layout: TensorLayout::new_contiguous(ty::F32, &[0, 64]),
+            sin_base: null(),
+            cos_layout: TensorLayout::new_contiguous(ty::F32, &[0, 64]),
+            cos_base: null(),
+            theta: 1e4,
+            theta_groups: Vec::new(),
+            scaling: crate::rope::RopeScaling::None,
+            table_step: 1,
+            sink: Default::default(),
+            inverse: false,
+            mask: None,
+        };
+        let mut op = crate::rope::common_cpu::Operator::new(&Cpu);
+        assert_eq!(op.scheme(&args, 0).unwrap(), 0);
+    }
+}
@@ -0,0 +1,67 @@
+﻿use crate::{
+    shape_not_support,
+    utils::{dim_distinct, rank_error, type_distinct},
+    ConstPtr, Hardware, MaybeDyn, MutPtr, SchemeError, TensorLayout,
+};
+use digit_layout::DigitLayout;
+
+pub struct Args<H: Hardware> {
+    /// 查询张量，形状为 `[nh, seq, dh]`。
+    pub q_layout: TensorLayout,
+    pub q_base: ConstPtr<H>,
+    /// 键张量，形状为 `[nkvh, att, dh]`，`nh` 须为 `nkvh` 的整数倍。
+    pub k_layout: TensorLayout,
+    pub k_base: ConstPtr<H>,
+    /// 分数张量，形状为 `[nh, seq, att]`。
+    pub s_layout: TensorLayout,
+    pub s_base: MutPtr<H>,
+    /// 分数的缩放系数，通常为 `1 / sqrt(dh)`。
+    pub scale: f32,
+}
+
+pub(super) struct Meta {
+    pub dt: DigitLayout,
+    pub nh: MaybeDyn<usize>,
+    pub nkvh: MaybeDyn<usize>,
+    pub seq: MaybeDyn<usize>,
+    pub att: MaybeDyn<usize>,
+    pub dh: MaybeDyn<usize>,
+}
+
+impl<H: Hardware> Args<H> {
+    pub(super) fn meta(&self) -> Result<Meta, SchemeError> {
+        let Self {
+            q_layout,
+            k_layout,
+            s_layout,
+            ..
+        } = self;
+
+        let &[nh_q, seq_q, dh_q] = q_layout.shape() else {
+            return Err(rank_error("q", 3, q_layout.ndim()));
+        };
+        let &[nkvh, att_k, dh_k] = k_layout.shape() else {
+            return Err(rank_error("k", 3, k_layout.ndim()));
+        };
+        let &[nh_s, seq_s, att_s] = s_layout.shape() else {
+            return Err(rank_error("s", 3, s_layout.ndim()));
+        };
+
+        let nh = dim_distinct(&[nh_q, nh_s])?;
+        if let (Some(&nh), Some(&nkvh)) = (nh.get_static(), nkvh.get_static()) {
+            if nkvh == 0 || nh % nkvh != 0 {
+                return Err(shape_not_support(format!(
+                    "nh ({nh}) is not a multiple of nkvh ({nkvh})"
+                )));
+            }
+        }
+        Ok(Meta {
+            dt: type_distinct(&[q_layout.dt(), k_layout.dt(), s_layout.dt()])?,
+            nh,
+            nkvh,
+            seq: dim_distinct(&[seq_q, seq_s])?,
+            att: dim_distinct(&[att_k, att_s])?,
+            dh: dim_distinct(&[dh_q, dh_k])?,
+        })
+    }
+}
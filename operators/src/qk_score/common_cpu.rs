@@ -0,0 +1,72 @@
+﻿impl_op!(common_cpu, Cpu);
+
+#[cfg(test)]
+mod test {
+    use super::{super::Args, Operator};
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        Operator as _, TensorLayout,
+    };
+    use digit_layout::types as ty;
+    use std::ptr::{null, null_mut};
+
+    #[test]
+    fn test_compute() {
+        let (seq, att, dh) = (3, 5, 8);
+        let scale = (dh as f32).sqrt().recip();
+        let mut op = Operator::new(&Cpu);
+        // 不分组和分组查询两种情况
+        for (nh, nkvh) in [(2, 2), (4, 2)] {
+            let q = (0..nh * seq * dh)
+                .map(|i| (i as f64 * 0.7).sin())
+                .collect::<Vec<_>>();
+            let k = (0..nkvh * att * dh)
+                .map(|i| (i as f64 * 0.3).cos())
+                .collect::<Vec<_>>();
+            let mut s = vec![f64::NAN; nh * seq * att];
+            let args = Args::<Cpu> {
+                q_layout: TensorLayout::new_contiguous(ty::F64, &[nh, seq, dh]),
+                q_base: q.as_ptr().cast(),
+                k_layout: TensorLayout::new_contiguous(ty::F64, &[nkvh, att, dh]),
+                k_base: k.as_ptr().cast(),
+                s_layout: TensorLayout::new_contiguous(ty::F64, &[nh, seq, att]),
+                s_base: s.as_mut_ptr().cast(),
+                scale,
+            };
+            op.scheme(&args, 0).unwrap();
+            op.launch(&args, &mut [], &ThisThread).unwrap();
+
+            let group = nh / nkvh;
+            for h in 0..nh {
+                for i in 0..seq {
+                    for j in 0..att {
+                        let q = &q[(h * seq + i) * dh..][..dh];
+                        let k = &k[(h / group * att + j) * dh..][..dh];
+                        let expected =
+                            q.iter().zip(k).map(|(q, k)| q * k).sum::<f64>() * scale as f64;
+                        let ans = s[(h * seq + i) * att + j];
+                        assert!((ans - expected).abs() < 1e-12, "{ans} vs {expected}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_meta() {
+        let args = |nh: usize, nkvh: usize, att_s: usize| Args::<Cpu> {
+            q_layout: TensorLayout::new_contiguous(ty::F32, &[nh, 3, 8]),
+            q_base: null(),
+            k_layout: TensorLayout::new_contiguous(ty::F32, &[nkvh, 5, 8]),
+            k_base: null(),
+            s_layout: TensorLayout::new_contiguous(ty::F32, &[nh, 3, att_s]),
+            s_base: null_mut(),
+            scale: 1.,
+        };
+        assert!(args(4, 2, 5).meta().is_ok());
+        // 查询头数不是键头数的整数倍
+        assert!(args(3, 2, 5).meta().is_err());
+        // 分数与键的长度不一致
+        assert!(args(4, 2, 6).meta().is_err());
+    }
+}
@@ -0,0 +1,127 @@
+﻿impl_op!(cuda, Gpu);
+
+#[cfg(test)]
+mod test {
+    use super::{super::Args, Operator};
+    use crate::{cuda::Gpu, ByteOf, Hardware, Operator as _, TensorLayout};
+    use digit_layout::{types as ty, DigitLayout};
+
+    fn args<H: Hardware>(
+        dt: DigitLayout,
+        nh: usize,
+        nkvh: usize,
+        seq: usize,
+        att: usize,
+        dh: usize,
+        q_base: *const ByteOf<H>,
+        k_base: *const ByteOf<H>,
+        s_base: *mut ByteOf<H>,
+    ) -> Args<H> {
+        Args {
+            q_layout: TensorLayout::new_contiguous(dt, &[nh, seq, dh]),
+            q_base,
+            k_layout: TensorLayout::new_contiguous(dt, &[nkvh, att, dh]),
+            k_base,
+            s_layout: TensorLayout::new_contiguous(dt, &[nh, seq, att]),
+            s_base,
+            scale: (dh as f32).sqrt().recip(),
+        }
+    }
+
+    #[test]
+    fn test_compute() {
+        use super::super::common_cpu::Operator as RefOp;
+        use crate::{
+            common_cpu::{Cpu, ThisThread},
+            cuda::cast_load,
+            test_utils::{Diff, ErrorCollector},
+        };
+        use cuda::memcpy_d2h;
+        use half::f16;
+        use rand::Rng;
+
+        let Some(gpu) = Gpu::init() else {
+            return;
+        };
+
+        let nh = 32;
+        let nkvh = 4;
+        let seq = 7;
+        let att = 127;
+        let dh = 64;
+
+        let cpu_op = RefOp::new(&Cpu);
+        let gpu_op = Operator::new(&gpu);
+
+        let mut q = vec![0.0f64; nh * seq * dh];
+        let mut k = vec![0.0f64; nkvh * att * dh];
+        rand::rng().fill(&mut q[..]);
+        rand::rng().fill(&mut k[..]);
+        let q = q;
+        let k = k;
+
+        let s_ans = gpu.apply(|ctx| {
+            let stream = ctx.stream();
+            #[cfg(use_nvidia)]
+            let rt = &stream;
+            #[cfg(use_iluvatar)]
+            let rt = ctx;
+            let q = cast_load(&q, f16::from_f64, &stream);
+            let k = cast_load(&k, f16::from_f64, &stream);
+            let mut s = rt.malloc::<f16>(nh * seq * att);
+            gpu_op
+                .launch(
+                    &args(
+                        ty::F16,
+                        nh,
+                        nkvh,
+                        seq,
+                        att,
+                        dh,
+                        q.as_ptr().cast(),
+                        k.as_ptr().cast(),
+                        s.as_mut_ptr().cast(),
+                    ),
+                    &mut [],
+                    &stream,
+                )
+                .unwrap();
+
+            let mut host = vec![f16::ZERO; nh * seq * att];
+            memcpy_d2h(&mut host, &s);
+            host
+        });
+
+        let mut s_ref = vec![0.0f64; nh * seq * att];
+        cpu_op
+            .launch(
+                &args(
+                    ty::F64,
+                    nh,
+                    nkvh,
+                    seq,
+                    att,
+                    dh,
+                    q.as_ptr().cast(),
+                    k.as_ptr().cast(),
+                    s_ref.as_mut_ptr().cast(),
+                ),
+                &mut [],
+                &ThisThread,
+            )
+            .unwrap();
+
+        let diff = s_ref
+            .into_iter()
+            .zip(s_ans)
+            .map(|(a, b)| Diff::new(a, b.to_f64()))
+            .collect::<Vec<_>>();
+
+        let mut ec = ErrorCollector::new(f16::EPSILON.to_f64(), 1e-3);
+        diff.into_iter().for_each(|diff| ec.push(diff));
+        println!("{ec}");
+
+        let (out, count) = ec.summary();
+        assert!(out * 1000 <= count);
+    }
+}
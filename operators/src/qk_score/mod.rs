@@ -0,0 +1,20 @@
+﻿//! 注意力分数 `s = scale · q · kᵀ`。
+
+mod args;
+mod operator;
+
+pub use args::Args;
+
+crate::op_trait!(QkScore);
+
+macro_rules! impl_op {
+    ($dev:ident, $proc:ident) => {
+        pub type Operator =
+            super::operator::Operator<crate::$dev::$proc, crate::mat_mul::$dev::Operator>;
+    };
+}
+
+#[cfg(any(use_cpu, test))]
+pub mod common_cpu;
+#[cfg(use_cuda)]
+pub mod cuda;
@@ -0,0 +1,123 @@
+use super::{args::Meta, Args, QkScore};
+use crate::{
+    dyn_, get_static, mat_mul, ByteOf, Hardware, LaunchError, QueueAlloc, SchemeError, TensorLayout,
+};
+use std::marker::PhantomData;
+
+pub struct Operator<Hardware, MatMul> {
+    mat_mul: MatMul,
+    _phantom: PhantomData<Hardware>,
+}
+
+impl<H, M> QkScore<H> for Operator<H, M>
+where
+    H: Hardware,
+    M: mat_mul::MatMul<H>,
+{
+}
+
+impl<H, M> crate::Operator for Operator<H, M>
+where
+    H: Hardware,
+    M: mat_mul::MatMul<H>,
+{
+    type Hardware = H;
+    type TopoNode = H;
+    type Args = Args<H>;
+
+    fn new(node: &Self::TopoNode) -> Self {
+        Self {
+            mat_mul: M::new(node),
+            _phantom: PhantomData,
+        }
+    }
+
+    fn scheme(
+        &mut self,
+        args: &Self::Args,
+        max_workspace_size: usize,
+    ) -> Result<usize, SchemeError> {
+        let Meta { dt, .. } = args.meta()?;
+        // 分组查询时按键头拆分矩阵乘，形状在发射时才能确定
+        let layout = TensorLayout::new_dyn(dt, &[dyn_(); 3], &[dyn_(); 3]);
+        self.mat_mul.scheme(
+            &mat_mul::Args::new_null(layout.clone(), 0., layout.clone(), layout, args.scale),
+            max_workspace_size,
+        )
+    }
+
+    fn launch<QA>(
+        &self,
+        args: &Self::Args,
+        workspace: &mut [ByteOf<Self::Hardware>],
+        queue_alloc: &QA,
+    ) -> Result<(), LaunchError>
+    where
+        QA: QueueAlloc<Hardware = Self::Hardware>,
+    {
+        let Meta {
+            dt,
+            nh,
+            nkvh,
+            seq,
+            att,
+            dh,
+        } = args.meta()?;
+        let Args {
+            q_layout,
+            q_base,
+            k_layout,
+            k_base,
+            s_layout,
+            s_base,
+            scale,
+        } = args;
+
+        let &[nh_sq, seq_sq, dh_sq] = q_layout.strides() else {
+            unreachable!()
+        };
+        let &[nkvh_sk, att_sk, dh_sk] = k_layout.strides() else {
+            unreachable!()
+        };
+        let &[nh_ss, seq_ss, att_ss] = s_layout.strides() else {
+            unreachable!()
+        };
+
+        get_static! {
+            nh      seq    att    dh     nkvh
+            nh_sq   seq_sq dh_sq
+            nkvh_sk att_sk dh_sk
+            nh_ss   seq_ss att_ss
+        };
+
+        // 每个键头对应 head_group 个查询头，不分组时一次计算所有头，否则逐个键头广播
+        let head_group = nh / nkvh;
+        let (n, batch, k_batch) = if head_group == 1 {
+            (1, nh, nkvh)
+        } else {
+            (nkvh, head_group, 1)
+        };
+
+        let q_layout = TensorLayout::new(dt, &[batch, seq, dh], &[nh_sq, seq_sq, dh_sq]);
+        let k_layout = TensorLayout::new(dt, &[k_batch, dh, att], &[nkvh_sk, dh_sk, att_sk]);
+        let s_layout = TensorLayout::new(dt, &[batch, seq, att], &[nh_ss, seq_ss, att_ss]);
+        for i in 0..n as isize {
+            // s = scale · q . k^T
+            self.mat_mul.launch(
+                &mat_mul::Args {
+                    c_layout: s_layout.clone(),
+                    c_base: unsafe { s_base.byte_offset(i * batch as isize * nh_ss) },
+                    beta: 0.,
+                    a_layout: q_layout.clone(),
+                    a_base: unsafe { q_base.byte_offset(i * batch as isize * nh_sq) },
+                    b_layout: k_layout.clone(),
+                    b_base: unsafe { k_base.byte_offset(i * nkvh_sk) },
+                    alpha: *scale,
+                },
+                workspace,
+                queue_alloc,
+            )?
+        }
+        Ok(())
+    }
+}
@@ -109,8 +109,8 @@ impl crate::Operator for Operator {
         let (mut build_pairs, mut reduce) = {
             let mut cache = self.schemes.lock().unwrap();
             let program = cache.get(&key).unwrap();
-            let build_pairs = program.take("argmax_build_pairs").unwrap();
-            let reduce = program.take("argmax_reduce").unwrap();
+            let build_pairs = program.take_guard("argmax_build_pairs").unwrap();
+            let reduce = program.take_guard("argmax_reduce").unwrap();
             (build_pairs, reduce)
         };
 
@@ -159,11 +159,6 @@ impl crate::Operator for Operator {
                 );
         }
 
-        let mut cache = self.schemes.lock().unwrap();
-        let program = cache.get(&key).unwrap();
-        program.put("argmax_build_pairs", build_pairs);
-        program.put("argmax_reduce", reduce);
-
         Ok(())
     }
 }
@@ -1,9 +1,10 @@
-﻿use crate::{
-    rank_mismatch, shape_mismatch, shape_not_support, static_from, utils::type_distinct, ConstPtr,
-    Hardware, MutPtr, SchemeError, TensorLayout,
+﻿use super::compact::{compact, Dim};
+use crate::{
+    rank_mismatch, rank_not_support, shape_mismatch, shape_not_support, static_from,
+    utils::type_distinct, ConstPtr, Hardware, MutPtr, SchemeError, TensorLayout,
 };
+use digit_layout::DigitLayout;
 use std::{
-    cmp::Ordering,
     iter::zip,
     ptr::{null, null_mut},
 };
@@ -14,6 +15,11 @@ pub struct Args<H: Hardware> {
     pub dst_base: MutPtr<H>,
     pub src_layout: TensorLayout,
     pub src_base: ConstPtr<H>,
+    /// 复制时对每个元素计算 `scale * x + bias`，只用于浮点类型。
+    ///
+    /// `scale` 为 1 且 `bias` 为 0 时是纯复制，不依赖数据类型。
+    pub scale: f32,
+    pub bias: f32,
 }
 
 impl<H: Hardware> Args<H> {
@@ -23,16 +29,132 @@ impl<H: Hardware> Args<H> {
             dst_base: null_mut(),
             src_layout,
             src_base: null(),
+            scale: 1.,
+            bias: 0.,
         }
     }
+
+    /// 是否为纯复制，即不需要逐元素计算仿射变换。
+    #[inline]
+    pub fn is_plain_copy(&self) -> bool {
+        self.scale == 1. && self.bias == 0.
+    }
+
+    /// 构造交换 `src` 的 `axes` 两个维度的重排参数。
+    ///
+    /// `dst` 是交换后形状的连续张量，以 `src` 的维度顺序描述。
+    pub fn transpose(
+        src_layout: &TensorLayout,
+        src_base: ConstPtr<H>,
+        axes: [usize; 2],
+        dst_base: MutPtr<H>,
+    ) -> Result<Self, SchemeError> {
+        let dt = src_layout.dt();
+        let ndim = src_layout.ndim();
+        let [a, b] = axes;
+        if a >= ndim || b >= ndim {
+            return Err(rank_not_support(format!(
+                "transpose axes {axes:?} out of rank {ndim}"
+            )));
+        }
+        let shape = src_layout
+            .shape()
+            .iter()
+            .map(|d| static_from(d).copied())
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut transposed = shape.clone();
+        transposed.swap(a, b);
+        let mut strides = TensorLayout::new_contiguous(dt, &transposed)
+            .strides()
+            .iter()
+            .map(|s| s.0)
+            .collect::<Vec<_>>();
+        strides.swap(a, b);
+        Ok(Self {
+            dst_layout: TensorLayout::new(dt, &shape, &strides),
+            dst_base,
+            src_layout: src_layout.clone(),
+            src_base,
+            scale: 1.,
+            bias: 0.,
+        })
+    }
+
+    /// 构造从交错的 `[h, w, c]` 到平面的 `[c, h, w]` 的重排参数，两者都连续存储。
+    pub fn interleaved_to_planar(
+        dt: DigitLayout,
+        [h, w, c]: [usize; 3],
+        src_base: ConstPtr<H>,
+        dst_base: MutPtr<H>,
+    ) -> Self {
+        let [planar, interleaved] = channel_layouts(dt, [h, w, c]);
+        Self {
+            dst_layout: planar,
+            dst_base,
+            src_layout: interleaved,
+            src_base,
+            scale: 1.,
+            bias: 0.,
+        }
+    }
+
+    /// 构造从平面的 `[c, h, w]` 到交错的 `[h, w, c]` 的重排参数，两者都连续存储。
+    pub fn planar_to_interleaved(
+        dt: DigitLayout,
+        [h, w, c]: [usize; 3],
+        src_base: ConstPtr<H>,
+        dst_base: MutPtr<H>,
+    ) -> Self {
+        let [planar, interleaved] = channel_layouts(dt, [h, w, c]);
+        Self {
+            dst_layout: interleaved,
+            dst_base,
+            src_layout: planar,
+            src_base,
+            scale: 1.,
+            bias: 0.,
+        }
+    }
+}
+
+/// 以 [c, h, w] 的维度顺序描述连续的平面和交错布局。
+fn channel_layouts(dt: DigitLayout, [h, w, c]: [usize; 3]) -> [TensorLayout; 2] {
+    let unit = dt.nbytes() as isize;
+    let [h_, w_, c_] = [h, w, c].map(|n| n as isize);
+    let shape = [c, h, w];
+    [
+        TensorLayout::new(dt, &shape, &[h_ * w_ * unit, w_ * unit, unit]),
+        TensorLayout::new(dt, &shape, &[unit, w_ * c_ * unit, c_ * unit]),
+    ]
 }
 
+/// 压缩后的重排方案，可对相同布局的多次重排复用。
 #[derive(Clone, Debug)]
 #[repr(transparent)]
-pub(super) struct Scheme(Vec<isize>);
+pub struct Scheme(Vec<isize>);
+
+impl Dim {
+    /// 剔除 1 长维度，并拒绝归约。
+    ///
+    /// src 步长为 0 的维度是广播，将同一份数据复制到 dst 的各个位置。
+    fn push_to(self, dims: &mut Vec<Dim>) -> Result<(), SchemeError> {
+        if self.len != 1 {
+            if self.dst == 0 {
+                return Err(shape_not_support(
+                    "Reducing is not allowed for rearrangement.",
+                ));
+            }
+            dims.push(self);
+        }
+        Ok(())
+    }
+}
 
 impl Scheme {
-    pub fn new<H: Hardware>(args: &Args<H>) -> Result<Self, SchemeError> {
+    /// 由参数构造重排方案。
+    ///
+    /// `max_ndim` 是后端核函数支持的最大维数，合并后维数仍超出时返回错误。
+    pub fn new<H: Hardware>(args: &Args<H>, max_ndim: Option<usize>) -> Result<Self, SchemeError> {
         let Args {
             dst_layout: dst_,
             src_layout: src_,
@@ -48,13 +170,20 @@ impl Scheme {
                 src_.ndim()
             )));
         }
-        // # 输入形状
-        #[derive(Clone, PartialEq, Eq, Debug)]
-        struct Dim {
-            len: usize,
-            dst: isize,
-            src: isize,
+        // # 元素总数，先于逐维比较报告，便于定位形状或步长的笔误
+        let count = |layout: &TensorLayout| -> Result<usize, SchemeError> {
+            layout
+                .shape()
+                .iter()
+                .try_fold(1, |acc, d| Ok(acc * *static_from(d)?))
+        };
+        let (count_dst, count_src) = (count(dst_)?, count(src_)?);
+        if count_dst != count_src {
+            return Err(shape_mismatch(format!(
+                "dst has {count_dst} elements, src has {count_src}"
+            )));
         }
+        // # 输入形状
         let mut dims = Vec::with_capacity(ndim);
         {
             let dd = dst_.shape();
@@ -68,88 +197,46 @@ impl Scheme {
                     Err(shape_mismatch(format!("dst[{i}] = {dd}, src[{i}] = {ds}")))?;
                 }
                 // 静态化
-                let dim = Dim {
+                Dim {
                     len: dd,
                     dst: *static_from(&sd[i])?,
                     src: *static_from(&ss[i])?,
-                };
-                // 剔除初始的 1 长维度
-                if dim.len != 1 {
-                    if dim.dst == 0 {
-                        return Err(shape_not_support(
-                            "Reducing is not allowed for rearrangement.",
-                        ));
-                    }
-                    dims.push(dim);
                 }
+                .push_to(&mut dims)?;
             }
         }
-        // # 排序
-        impl PartialOrd for Dim {
-            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-                Some(self.cmp(other))
-            }
+        let scheme = Self(compact(dst_.dt().nbytes(), dims));
+        match max_ndim {
+            Some(max) if scheme.ndim() > max => Err(rank_not_support(format!(
+                "Rearrange rank {} exceeds backend limit {max}",
+                scheme.ndim()
+            ))),
+            _ => Ok(scheme),
         }
-        impl Ord for Dim {
-            /// dst 绝对值降序 -> src 绝对值降序 -> len 升序
-            fn cmp(&self, other: &Self) -> Ordering {
-                use Ordering::Equal as Eq;
-                match self.dst.abs().cmp(&other.dst.abs()) {
-                    Eq => match self.src.abs().cmp(&other.src.abs()) {
-                        Eq => self.len.cmp(&other.len),
-                        neq => neq.reverse(),
-                    },
-                    neq => neq.reverse(),
-                }
-            }
-        }
-        dims.sort_unstable();
-        // # 合并连续维度
-        let mut unit = dst_.dt().nbytes() as isize;
-        let mut ndim = dims.len();
-        // ## 合并末尾连续维度到 unit
-        for dim in dims.iter_mut().rev() {
-            if dim.dst == unit && dim.src == unit {
-                unit *= dim.len as isize;
-                ndim -= 1;
-            } else {
-                break;
-            }
-        }
-        dims.truncate(ndim);
-        // ## 合并任意连续维度
-        for i in (1..dims.len()).rev() {
-            let (head, tail) = dims.split_at_mut(i);
-            let f = &mut head[i - 1]; // f for front
-            let b = &mut tail[0]; // b for back
-            let len = b.len as isize;
-            if b.dst * len == f.dst && b.src * len == f.src {
-                *f = Dim {
-                    len: b.len * f.len,
-                    dst: b.dst,
-                    src: b.src,
-                };
-                *b = Dim {
-                    len: 1,
-                    dst: 0,
-                    src: 0,
-                };
-                ndim -= 1;
-            }
-        }
-        // # 合并空间
-        let mut layout = vec![0isize; 2 + ndim * 3];
-        layout[0] = unit as _;
-        layout[ndim + 1] = 1;
-        for (i, Dim { len, dst, src }) in dims.into_iter().filter(|d| d.len != 1).enumerate() {
-            layout[1 + i] = len as _;
-            layout[2 + ndim + i] = dst;
-            layout[2 + ndim * 2 + i] = src;
+    }
+
+    /// 以字节为单位构造重排方案，不依赖数据类型。
+    ///
+    /// `unit` 是每个元素的字节数，`shape` 以元素计数，两组步长以字节计数。
+    pub fn new_bytes(
+        unit: usize,
+        shape: &[usize],
+        dst_strides: &[isize],
+        src_strides: &[isize],
+    ) -> Result<Self, SchemeError> {
+        let ndim = shape.len();
+        if dst_strides.len() != ndim || src_strides.len() != ndim {
+            return Err(rank_mismatch(format!(
+                "shape.ndim = {ndim}, dst.ndim = {}, src.ndim = {}",
+                dst_strides.len(),
+                src_strides.len()
+            )));
         }
-        for i in (1..=ndim).rev() {
-            layout[i] *= layout[i + 1];
+        let mut dims = Vec::with_capacity(ndim);
+        for ((&len, &dst), &src) in zip(zip(shape, dst_strides), src_strides) {
+            Dim { len, dst, src }.push_to(&mut dims)?;
         }
-        Ok(Self(layout))
+        Ok(Self(compact(unit, dims)))
     }
 
     /// 拆分 unit 到更小的规模以利于并行
@@ -237,6 +324,123 @@ impl Scheme {
             .windows(2)
             .map(|pair| (pair[0] / pair[1]) as usize)
     }
+
+    /// 沿最外维将方案切分为多个分块，每块读写的数据量不超过 `tile` 字节。
+    ///
+    /// 返回各分块的方案及其 dst、src 基址的字节偏移。
+    /// 最外维的单个切片已超过 `tile` 时，每块仍包含一个切片；没有可切分的维度时只有一块。
+    /// 张量为空时没有分块。
+    pub fn split_outer(&self, tile: usize) -> Vec<(Self, isize, isize)> {
+        if self.count() == 0 {
+            return Vec::new();
+        }
+        let ndim = self.ndim();
+        if ndim == 0 {
+            return vec![(self.clone(), 0, 0)];
+        }
+        // 总数非零，内层维度都不为空
+        let len = self.count() / self.idx_strides()[0] as usize;
+        let slice = self.idx_strides()[0] as usize * self.unit();
+        let step = (tile / slice).clamp(1, len);
+        let dst = self.dst_strides()[0];
+        let src = self.src_strides()[0];
+        (0..len)
+            .step_by(step)
+            .map(|start| {
+                let mut chunk = self.clone();
+                chunk.0[1] = ((len - start).min(step) * self.idx_strides()[0] as usize) as _;
+                let start = start as isize;
+                (chunk, start * dst, start * src)
+            })
+            .collect()
+    }
+
+    /// 在主机上按方案执行重排。
+    ///
+    /// # Safety
+    ///
+    /// `dst` 和 `src` 必须是主机可访问的地址，且覆盖方案描述的全部存储区域。
+    #[allow(dead_code)]
+    pub unsafe fn launch_host(&self, dst: *mut u8, src: *const u8) {
+        self.launch_batch_host(&[dst], &[src])
+    }
+
+    /// 在主机上对一批同形状的张量执行同一方案，所有张量的所有行一起并行复制。
+    ///
+    /// # Safety
+    ///
+    /// 每对 `dst` 和 `src` 都必须满足 [`Scheme::launch_host`] 的要求，且两者长度相同。
+    #[allow(dead_code)]
+    pub unsafe fn launch_batch_host(&self, dst: &[*mut u8], src: &[*const u8]) {
+        /// 常见的单元长度按整数类型非对齐读写，不要求地址对齐到单元长度。
+        #[inline(always)]
+        unsafe fn copy<T: Copy>(dst: isize, src: isize) {
+            (dst as *mut T).write_unaligned((src as *const T).read_unaligned())
+        }
+
+        debug_assert_eq!(dst.len(), src.len());
+        let unit = self.unit();
+        match unit {
+            1 => self.for_each_unit(dst, src, |d, s| copy::<u8>(d, s)),
+            2 => self.for_each_unit(dst, src, |d, s| copy::<u16>(d, s)),
+            4 => self.for_each_unit(dst, src, |d, s| copy::<u32>(d, s)),
+            8 => self.for_each_unit(dst, src, |d, s| copy::<u64>(d, s)),
+            16 => self.for_each_unit(dst, src, |d, s| copy::<u128>(d, s)),
+            _ => self.for_each_unit(dst, src, |d, s| {
+                std::ptr::copy_nonoverlapping::<u8>(s as _, d as _, unit)
+            }),
+        }
+    }
+
+    /// 对每个单元的 dst、src 地址调用 `f`。
+    ///
+    /// 按最内维划分为行，每行只分解一次下标，行内按步长递增，避免逐单元做除法。
+    unsafe fn for_each_unit(
+        &self,
+        dst: &[*mut u8],
+        src: &[*const u8],
+        f: impl Fn(isize, isize) + Sync,
+    ) {
+        use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+        let count = self.count() as isize;
+        // 裸指针不能跨线程共享，转为地址
+        let dst = dst.iter().map(|&p| p as isize).collect::<Vec<_>>();
+        let src = src.iter().map(|&p| p as isize).collect::<Vec<_>>();
+        let ndim = self.ndim();
+        if count == 0 {
+            return;
+        }
+        if ndim == 0 {
+            return zip(dst, src).for_each(|(d, s)| f(d, s));
+        }
+
+        let idx_strides = &self.idx_strides()[..ndim - 1];
+        let dst_strides = self.dst_strides();
+        let src_strides = self.src_strides();
+        // 最内维的长度和步长
+        let len = idx_strides.last().copied().unwrap_or(count);
+        let dst_step = dst_strides[ndim - 1];
+        let src_step = src_strides[ndim - 1];
+        let rows = count / len;
+        (0..dst.len() as isize * rows)
+            .into_par_iter()
+            .for_each(|i| {
+                let b = (i / rows) as usize;
+                let mut rem = i % rows * len;
+                let mut dst = dst[b];
+                let mut src = src[b];
+                for (i, &s) in idx_strides.iter().enumerate() {
+                    let k = rem / s;
+                    dst += k * dst_strides[i];
+                    src += k * src_strides[i];
+                    rem %= s;
+                }
+                for j in 0..len {
+                    f(dst + j * dst_step, src + j * src_step)
+                }
+            });
+    }
 }
 
 #[test]
@@ -252,8 +456,10 @@ fn test_scheme() {
             dst_base: null_mut(),
             src_layout: TensorLayout::new(F16, &shape, &[576, 192, 96, 48, 8, 16, 2]),
             src_base: null(),
+            scale: 1.,
+            bias: 0.,
         };
-        let scheme = Scheme::new(&args).unwrap();
+        let scheme = Scheme::new(&args, None).unwrap();
         assert_eq!(scheme.ndim(), 3);
         assert_eq!(scheme.unit(), 8);
         assert_eq!(scheme.count(), 24 * 2 * 3);
@@ -277,8 +483,10 @@ fn test_scheme() {
                 &[33554432 * 2, 16777216 * 2, 524288 * 2, 128 * 2, 1 * 2],
             ),
             src_base: null(),
+            scale: 1.,
+            bias: 0.,
         };
-        let scheme = Scheme::new(&args).unwrap();
+        let scheme = Scheme::new(&args, None).unwrap();
         #[rustfmt::skip]
         assert_eq!(
             scheme.0,
@@ -302,3 +510,375 @@ fn test_scheme() {
         );
     }
 }
+
+#[test]
+fn test_scheme_bytes() {
+    const UNIT: usize = 3;
+    let (r, c) = (4, 5);
+    let src = (0..r * c * UNIT).map(|i| i as u8).collect::<Vec<_>>();
+    let mut dst = vec![0u8; src.len()];
+
+    // src 按 [r, c] 存储，dst 按 [c, r] 存储
+    let unit = UNIT as isize;
+    let scheme = Scheme::new_bytes(
+        UNIT,
+        &[r, c],
+        &[unit, r as isize * unit],
+        &[c as isize * unit, unit],
+    )
+    .unwrap();
+    assert_eq!(scheme.unit(), UNIT);
+    unsafe { scheme.launch_host(dst.as_mut_ptr(), src.as_ptr()) };
+
+    for i in 0..r {
+        for j in 0..c {
+            let d = (j * r + i) * UNIT;
+            let s = (i * c + j) * UNIT;
+            assert_eq!(dst[d..][..UNIT], src[s..][..UNIT]);
+        }
+    }
+    assert!(Scheme::new_bytes(UNIT, &[r, c], &[unit], &[unit, unit]).is_err());
+}
+
+#[test]
+fn test_scheme_max_ndim() {
+    use crate::common_cpu::Cpu;
+    use digit_layout::types::F16;
+    use std::ptr::{null, null_mut};
+
+    // 三个维度两两不连续，无法合并
+    let shape = [2, 3, 4];
+    let args = Args::<Cpu> {
+        dst_layout: TensorLayout::new(F16, &shape, &[48, 16, 4]),
+        dst_base: null_mut(),
+        src_layout: TensorLayout::new(F16, &shape, &[2, 8, 32]),
+        src_base: null(),
+        scale: 1.,
+        bias: 0.,
+    };
+    assert_eq!(Scheme::new(&args, Some(3)).unwrap().ndim(), 3);
+    let err = Scheme::new(&args, Some(2)).unwrap_err();
+    assert_eq!(err.kind, crate::SchemeErrorKind::RankNotSupport);
+    assert_eq!(err.info, "Rearrange rank 3 exceeds backend limit 2");
+}
+
+#[test]
+fn test_scheme_broadcast() {
+    use crate::common_cpu::Cpu;
+    use digit_layout::types::F32;
+    use std::ptr::{null, null_mut};
+
+    const N: usize = 5;
+    const D: usize = 7;
+    let unit = size_of::<f32>() as isize;
+    let src = (0..D).map(|i| i as f32 * 1.5).collect::<Vec<_>>();
+    let mut dst = vec![0.0f32; N * D];
+
+    // [1, d] 扩展为 [n, d]
+    let shape = [N, D];
+    let args = Args::<Cpu> {
+        dst_layout: TensorLayout::new(F32, &shape, &[D as isize * unit, unit]),
+        dst_base: null_mut(),
+        src_layout: TensorLayout::new(F32, &shape, &[0, unit]),
+        src_base: null(),
+        scale: 1.,
+        bias: 0.,
+    };
+    let scheme = Scheme::new(&args, None).unwrap();
+    assert_eq!(scheme.ndim(), 1);
+    assert_eq!(scheme.unit(), D * size_of::<f32>());
+    assert_eq!(scheme.src_strides(), [0]);
+    unsafe { scheme.launch_host(dst.as_mut_ptr().cast(), src.as_ptr().cast()) };
+    for row in dst.chunks(D) {
+        assert_eq!(row, src);
+    }
+}
+
+#[test]
+fn test_scheme_permuted() {
+    use crate::common_cpu::Cpu;
+    use digit_layout::types::F32;
+    use std::ptr::{null, null_mut};
+
+    // 逻辑形状 [2, 5, 3]，两侧都只有中间维度连续
+    let (a, b, c) = (2, 5, 3);
+    let unit = size_of::<f32>() as isize;
+    let [a_, b_] = [a, b].map(|n| n as isize);
+    let dst_strides = [c as isize * b_ * unit, unit, b_ * unit];
+    let src_strides = [b_ * unit, unit, a_ * b_ * unit];
+    let args = Args::<Cpu> {
+        dst_layout: TensorLayout::new(F32, &[a, b, c], &dst_strides),
+        dst_base: null_mut(),
+        src_layout: TensorLayout::new(F32, &[a, b, c], &src_strides),
+        src_base: null(),
+        scale: 1.,
+        bias: 0.,
+    };
+    let scheme = Scheme::new(&args, None).unwrap();
+    // 只看最后一维时无法合并，unit 为单个元素
+    assert_eq!(scheme.unit(), b * size_of::<f32>());
+    assert_eq!(scheme.ndim(), 2);
+
+    let src = (0..a * b * c).map(|i| i as f32).collect::<Vec<_>>();
+    let mut dst = vec![0.0f32; src.len()];
+    unsafe { scheme.launch_host(dst.as_mut_ptr().cast(), src.as_ptr().cast()) };
+    for i in 0..a {
+        for j in 0..b {
+            for k in 0..c {
+                let d = (i as isize * dst_strides[0]
+                    + j as isize * dst_strides[1]
+                    + k as isize * dst_strides[2])
+                    / unit;
+                let s = (i as isize * src_strides[0]
+                    + j as isize * src_strides[1]
+                    + k as isize * src_strides[2])
+                    / unit;
+                assert_eq!(dst[d as usize], src[s as usize]);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_transpose() {
+    use super::{common_cpu::Operator as Rearrange, Rearrange as _};
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        Operator as _,
+    };
+    use digit_layout::types::F32;
+
+    let (a, b, c) = (3, 4, 5);
+    let unit = size_of::<f32>() as isize;
+    let src = (0..a * b * c).map(|i| i as f32).collect::<Vec<_>>();
+    let src_layout = TensorLayout::new_contiguous(F32, &[a, b, c]);
+    let op = Rearrange::new(&Cpu);
+
+    let mut ans = vec![0.0f32; src.len()];
+    op.transpose(
+        &src_layout,
+        src.as_ptr().cast(),
+        [0, 1],
+        ans.as_mut_ptr().cast(),
+        &ThisThread,
+    )
+    .unwrap();
+
+    // 手动构造 [b, a, c] 连续的 dst
+    let mut expected = vec![0.0f32; src.len()];
+    let [a_, c_] = [a, c].map(|n| n as isize);
+    op.launch(
+        &Args::<Cpu> {
+            dst_layout: TensorLayout::new(F32, &[a, b, c], &[c_ * unit, a_ * c_ * unit, unit]),
+            dst_base: expected.as_mut_ptr().cast(),
+            src_layout: src_layout.clone(),
+            src_base: src.as_ptr().cast(),
+            scale: 1.,
+            bias: 0.,
+        },
+        &mut [],
+        &ThisThread,
+    )
+    .unwrap();
+    assert_eq!(ans, expected);
+    assert_eq!(ans[c..][..c], src[b * c..][..c]);
+
+    assert!(Args::<Cpu>::transpose(&src_layout, null(), [0, 3], null_mut()).is_err());
+}
+
+#[test]
+fn test_channel_format() {
+    use super::common_cpu::Operator as Rearrange;
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        Operator as _,
+    };
+    use digit_layout::types::U16;
+
+    let (h, w, c) = (3, 5, 4);
+    let interleaved = (0..h * w * c).map(|i| i as u16).collect::<Vec<_>>();
+    let op = Rearrange::new(&Cpu);
+
+    let mut planar = vec![0u16; interleaved.len()];
+    op.launch(
+        &Args::<Cpu>::interleaved_to_planar(
+            U16,
+            [h, w, c],
+            interleaved.as_ptr().cast(),
+            planar.as_mut_ptr().cast(),
+        ),
+        &mut [],
+        &ThisThread,
+    )
+    .unwrap();
+    for y in 0..h {
+        for x in 0..w {
+            for ch in 0..c {
+                assert_eq!(
+                    planar[(ch * h + y) * w + x],
+                    interleaved[(y * w + x) * c + ch]
+                );
+            }
+        }
+    }
+
+    let mut back = vec![0u16; interleaved.len()];
+    op.launch(
+        &Args::<Cpu>::planar_to_interleaved(
+            U16,
+            [h, w, c],
+            planar.as_ptr().cast(),
+            back.as_mut_ptr().cast(),
+        ),
+        &mut [],
+        &ThisThread,
+    )
+    .unwrap();
+    assert_eq!(back, interleaved);
+}
+
+#[test]
+fn test_split_outer() {
+    use crate::common_cpu::Cpu;
+    use digit_layout::types::F32;
+
+    // [10, 6] 转置为 [6, 10]，每个最外维切片 10 * 4 字节
+    let args = Args::<Cpu>::new_null(
+        TensorLayout::new_contiguous(F32, &[6, 10]),
+        TensorLayout::new(F32, &[6, 10], &[4, 24]),
+    );
+    let scheme = Scheme::new(&args, None).unwrap();
+    let chunks = scheme.split_outer(100);
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(
+        chunks.iter().map(|(s, ..)| s.count()).collect::<Vec<_>>(),
+        [20, 20, 20]
+    );
+    assert_eq!(
+        chunks.iter().map(|&(_, d, s)| [d, s]).collect::<Vec<_>>(),
+        [[0, 0], [80, 8], [160, 16]]
+    );
+
+    // 不能整除时最后一块较小，单个切片超过分块大小时每块一个切片
+    let counts = |tile| {
+        scheme
+            .split_outer(tile)
+            .iter()
+            .map(|(s, ..)| s.count())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(counts(160), [40, 20]);
+    assert_eq!(counts(1), [10; 6]);
+
+    assert_eq!(counts(usize::MAX), [60]);
+    // 存在空维度时没有分块
+    let args = Args::<Cpu>::new_null(
+        TensorLayout::new(F32, &[6, 0], &[40, 4]),
+        TensorLayout::new(F32, &[6, 0], &[4, 24]),
+    );
+    let scheme = Scheme::new(&args, None).unwrap();
+    assert!(scheme.split_outer(100).is_empty());
+}
+
+#[test]
+fn test_element_count() {
+    use crate::{common_cpu::Cpu, SchemeErrorKind};
+    use digit_layout::types::F32;
+
+    // 两侧都可以合并为一维，但元素总数不同
+    let args = Args::<Cpu>::new_null(
+        TensorLayout::new_contiguous(F32, &[2, 3]),
+        TensorLayout::new_contiguous(F32, &[2, 4]),
+    );
+    let err = Scheme::new(&args, None).unwrap_err();
+    assert_eq!(err.kind, SchemeErrorKind::ShapeMismatch);
+    assert_eq!(err.info, "dst has 6 elements, src has 8");
+
+    // 总数相同但逐维不同时仍然报告维度
+    let args = Args::<Cpu>::new_null(
+        TensorLayout::new_contiguous(F32, &[2, 6]),
+        TensorLayout::new_contiguous(F32, &[3, 4]),
+    );
+    let err = Scheme::new(&args, None).unwrap_err();
+    assert_eq!(err.info, "dst[0] = 2, src[0] = 3");
+}
+
+#[test]
+fn test_to_contiguous() {
+    use super::{common_cpu::Operator as Rearrange, Rearrange as _};
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        Operator as _,
+    };
+    use digit_layout::types::F32;
+
+    let (m, n) = (6, 7);
+    let unit = size_of::<f32>() as isize;
+    let src = (0..m * n).map(|i| i as f32).collect::<Vec<_>>();
+    // [m, n] 连续存储的转置视图 [n, m]
+    let view = TensorLayout::new(F32, &[n, m], &[unit, n as isize * unit]);
+    let op = Rearrange::new(&Cpu);
+
+    let (mem, layout) = op
+        .to_contiguous(&view, src.as_ptr().cast(), &ThisThread)
+        .unwrap();
+    assert_eq!(
+        layout.shape(),
+        TensorLayout::new_contiguous(F32, &[n, m]).shape()
+    );
+    assert_eq!(
+        layout.strides(),
+        TensorLayout::new_contiguous(F32, &[n, m]).strides()
+    );
+    assert_eq!(mem.len(), m * n * size_of::<f32>());
+
+    let ([], ans, []) = (unsafe { mem.align_to::<f32>() }) else {
+        panic!()
+    };
+    for i in 0..m {
+        for j in 0..n {
+            assert_eq!(ans[j * m + i], src[i * n + j]);
+        }
+    }
+}
+
+#[test]
+fn test_to_contiguous_as() {
+    use super::{common_cpu::Operator as Rearrange, Rearrange as _};
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        Operator as _,
+    };
+    use digit_layout::types::{F16, F32, U32};
+
+    let (m, n) = (3, 4);
+    let unit = size_of::<f32>() as isize;
+    let src = (0..m * n).map(|i| i as f32).collect::<Vec<_>>();
+    let view = TensorLayout::new(F32, &[n, m], &[unit, n as isize * unit]);
+    let op = Rearrange::new(&Cpu);
+
+    // 默认保持源数据类型
+    let (_, layout) = op
+        .to_contiguous(&view, src.as_ptr().cast(), &ThisThread)
+        .unwrap();
+    assert_eq!(layout.dt(), F32);
+
+    // 字节数相同时按指定类型标记，内容逐字节复制
+    let (mem, layout) = op
+        .to_contiguous_as(&view, src.as_ptr().cast(), U32, &ThisThread)
+        .unwrap();
+    assert_eq!(layout.dt(), U32);
+    let ([], ans, []) = (unsafe { mem.align_to::<u32>() }) else {
+        panic!()
+    };
+    for i in 0..m {
+        for j in 0..n {
+            assert_eq!(ans[j * m + i], src[i * n + j].to_bits());
+        }
+    }
+
+    // 字节数不同不能标记
+    assert!(op
+        .to_contiguous_as(&view, src.as_ptr().cast(), F16, &ThisThread)
+        .is_err());
+}
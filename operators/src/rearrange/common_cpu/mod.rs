@@ -1,8 +1,14 @@
 ﻿use super::{args::Scheme, Args, Rearrange};
-use crate::{common_cpu::Cpu, ByteOf, LaunchError, QueueAlloc, SchemeError};
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use crate::{
+    common_cpu::Cpu, shape_mismatch, type_not_support, ByteOf, ConstPtr, LaunchError, MutPtr,
+    QueueAlloc, QueueOf, SchemeError,
+};
+use digit_layout::types as ty;
+use half::{bf16, f16};
 
-pub struct Operator;
+pub struct Operator {
+    tile: Option<usize>,
+}
 
 impl Rearrange<Cpu> for Operator {}
 
@@ -12,7 +18,7 @@ impl crate::Operator for Operator {
     type Args = Args<Cpu>;
 
     fn new(_node: &Self::TopoNode) -> Self {
-        Self
+        Self { tile: None }
     }
 
     fn scheme(
@@ -27,35 +33,330 @@ impl crate::Operator for Operator {
         &self,
         args: &Self::Args,
         _workspace: &mut [ByteOf<Self::Hardware>],
-        _queue_alloc: &QA,
+        queue_alloc: &QA,
     ) -> Result<(), LaunchError>
     where
         QA: QueueAlloc<Hardware = Self::Hardware>,
     {
-        let scheme = Scheme::new(args)?;
-        let unit = scheme.unit();
-        if scheme.count() == 1 {
-            unsafe { std::ptr::copy_nonoverlapping(args.src_base, args.dst_base, unit) };
-        } else {
-            let dst = args.dst_base as isize;
-            let src = args.src_base as isize;
-            let idx_strides = scheme.idx_strides();
-            let dst_strides = scheme.dst_strides();
-            let src_strides = scheme.src_strides();
-            (0..scheme.count() as isize)
-                .into_par_iter()
-                .for_each(|mut rem| {
-                    let mut dst = dst;
-                    let mut src = src;
-                    for (i, &s) in idx_strides.iter().enumerate() {
-                        let k = rem / s;
-                        dst += k * dst_strides[i];
-                        src += k * src_strides[i];
-                        rem %= s;
-                    }
-                    unsafe { std::ptr::copy_nonoverlapping::<u8>(src as _, dst as _, unit) };
-                });
+        let scheme = Scheme::new(args, None)?;
+        if !args.is_plain_copy() {
+            return launch_affine(&scheme, args);
+        }
+        let Some(tile) = self.tile else {
+            return self.launch_with_scheme(
+                &scheme,
+                args.dst_base,
+                args.src_base,
+                queue_alloc.queue(),
+            );
+        };
+        for (chunk, dst, src) in scheme.split_outer(tile) {
+            self.launch_with_scheme(
+                &chunk,
+                args.dst_base.wrapping_byte_offset(dst),
+                args.src_base.wrapping_byte_offset(src),
+                queue_alloc.queue(),
+            )?
+        }
+        Ok(())
+    }
+}
+
+impl Operator {
+    /// 设置分块大小，以字节为单位。
+    ///
+    /// 设置后沿最外维将重排切分为多次执行，每次读写的数据量不超过分块大小。
+    /// 各分块仍直接读写原存储，不会换入换出。`None` 表示不分块。
+    pub fn set_tile_size(&mut self, tile: Option<usize>) {
+        self.tile = tile
+    }
+
+    /// 使用预先构造的方案执行重排，跳过布局的排序和合并。
+    pub fn launch_with_scheme(
+        &self,
+        scheme: &Scheme,
+        dst_base: MutPtr<Cpu>,
+        src_base: ConstPtr<Cpu>,
+        _queue: &QueueOf<Cpu>,
+    ) -> Result<(), LaunchError> {
+        unsafe { scheme.launch_host(dst_base, src_base) };
+        Ok(())
+    }
+
+    /// 使用同一方案重排一批同形状的张量，例如所有层的 kv cache。
+    ///
+    /// `dst_bases` 和 `src_bases` 逐个配对，数量必须相同。
+    pub fn launch_batch_with_scheme(
+        &self,
+        scheme: &Scheme,
+        dst_bases: &[MutPtr<Cpu>],
+        src_bases: &[ConstPtr<Cpu>],
+        _queue: &QueueOf<Cpu>,
+    ) -> Result<(), LaunchError> {
+        if dst_bases.len() != src_bases.len() {
+            Err(shape_mismatch(format!(
+                "batched rearrange with {} dst and {} src tensors",
+                dst_bases.len(),
+                src_bases.len(),
+            )))?
         }
+        unsafe { scheme.launch_batch_host(dst_bases, src_bases) };
         Ok(())
     }
 }
+
+/// 复制的同时对每个元素计算 `scale * x + bias`。
+fn launch_affine(scheme: &Scheme, args: &Args<Cpu>) -> Result<(), LaunchError> {
+    let &Args {
+        dst_base,
+        src_base,
+        scale: a,
+        bias: b,
+        ..
+    } = args;
+    let dt = args.dst_layout.dt();
+    unsafe {
+        match dt {
+            ty::F16 => affine(scheme, dst_base, src_base, |x: f16| {
+                f16::from_f32(a * x.to_f32() + b)
+            }),
+            ty::BF16 => affine(scheme, dst_base, src_base, |x: bf16| {
+                bf16::from_f32(a * x.to_f32() + b)
+            }),
+            ty::F32 => affine(scheme, dst_base, src_base, |x: f32| a * x + b),
+            ty::F64 => {
+                let (a, b) = (a as f64, b as f64);
+                affine(scheme, dst_base, src_base, |x: f64| a * x + b)
+            }
+            _ => Err(type_not_support(format!(
+                "rearrange with scale/bias on {dt}"
+            )))?,
+        }
+    }
+    Ok(())
+}
+
+unsafe fn affine<T: Copy>(
+    scheme: &Scheme,
+    dst: *mut u8,
+    src: *const u8,
+    f: impl Fn(T) -> T + Sync,
+) {
+    use rayon::iter::{IntoParallelIterator, ParallelIterator};
+    use std::mem::size_of;
+
+    let n = scheme.unit() / size_of::<T>();
+    let dst = dst as isize;
+    let src = src as isize;
+    let idx_strides = scheme.idx_strides();
+    let dst_strides = scheme.dst_strides();
+    let src_strides = scheme.src_strides();
+    (0..scheme.count() as isize)
+        .into_par_iter()
+        .for_each(|mut rem| {
+            let mut dst = dst;
+            let mut src = src;
+            for (i, &s) in idx_strides.iter().enumerate() {
+                let k = rem / s;
+                dst += k * dst_strides[i];
+                src += k * src_strides[i];
+                rem %= s;
+            }
+            let dst = dst as *mut T;
+            let src = src as *const T;
+            for j in 0..n {
+                dst.add(j).write_unaligned(f(src.add(j).read_unaligned()))
+            }
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Args, Operator};
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        Operator as _, TensorLayout,
+    };
+    use digit_layout::types as ty;
+
+    #[test]
+    fn test_tiled() {
+        const M: usize = 37;
+        const N: usize = 129;
+
+        let src = (0..M * N).map(|i| i as u32).collect::<Vec<_>>();
+        let mut dst = vec![0u32; M * N];
+
+        let unit = size_of::<u32>() as isize;
+        let mut op = Operator::new(&Cpu);
+        // 每块不超过 3 个最外维切片，整个张量需要分多块完成
+        op.set_tile_size(Some(3 * M * size_of::<u32>()));
+        let args = Args::<Cpu> {
+            dst_layout: TensorLayout::new_contiguous(ty::U32, &[N, M]),
+            dst_base: dst.as_mut_ptr().cast(),
+            src_layout: TensorLayout::new(ty::U32, &[N, M], &[unit, N as isize * unit]),
+            src_base: src.as_ptr().cast(),
+            scale: 1.,
+            bias: 0.,
+        };
+        op.scheme(&args, 0).unwrap();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        for i in 0..M {
+            for j in 0..N {
+                assert_eq!(dst[j * M + i], src[i * N + j]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_affine() {
+        const M: usize = 7;
+        const N: usize = 13;
+
+        let src = (0..M * N).map(|i| i as f32).collect::<Vec<_>>();
+        let mut dst = vec![0f32; M * N];
+
+        let unit = size_of::<f32>() as isize;
+        let op = Operator::new(&Cpu);
+        let args = Args::<Cpu> {
+            dst_layout: TensorLayout::new_contiguous(ty::F32, &[N, M]),
+            dst_base: dst.as_mut_ptr().cast(),
+            src_layout: TensorLayout::new(ty::F32, &[N, M], &[unit, N as isize * unit]),
+            src_base: src.as_ptr().cast(),
+            scale: 2.,
+            bias: 1.,
+        };
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        for i in 0..M {
+            for j in 0..N {
+                assert_eq!(dst[j * M + i], src[i * N + j] * 2. + 1.);
+            }
+        }
+
+        // 整数类型不支持仿射变换
+        let x = vec![0u32; M * N];
+        let mut y = vec![0u32; M * N];
+        let args = Args::<Cpu> {
+            dst_layout: TensorLayout::new_contiguous(ty::U32, &[N, M]),
+            dst_base: y.as_mut_ptr().cast(),
+            src_layout: TensorLayout::new_contiguous(ty::U32, &[N, M]),
+            src_base: x.as_ptr().cast(),
+            scale: 2.,
+            bias: 1.,
+        };
+        assert!(op.launch(&args, &mut [], &ThisThread).is_err());
+    }
+
+    #[test]
+    fn test_batch() {
+        use super::Scheme;
+        use crate::{ConstPtr, MutPtr};
+
+        const B: usize = 4;
+        const M: usize = 5;
+        const N: usize = 11;
+
+        let unit = size_of::<u32>() as isize;
+        let layouts = || {
+            (
+                TensorLayout::new_contiguous(ty::U32, &[N, M]),
+                TensorLayout::new(ty::U32, &[N, M], &[unit, N as isize * unit]),
+            )
+        };
+        let src = (0..B)
+            .map(|b| {
+                (0..M * N)
+                    .map(|i| (b * M * N + i) as u32)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let mut dst = vec![vec![0u32; M * N]; B];
+
+        let op = Operator::new(&Cpu);
+        let (dst_layout, src_layout) = layouts();
+        let scheme = Scheme::new(&Args::<Cpu>::new_null(dst_layout, src_layout), None).unwrap();
+        let mut dst_bases = dst
+            .iter_mut()
+            .map(|d| d.as_mut_ptr().cast())
+            .collect::<Vec<MutPtr<Cpu>>>();
+        let src_bases = src
+            .iter()
+            .map(|s| s.as_ptr().cast())
+            .collect::<Vec<ConstPtr<Cpu>>>();
+        op.launch_batch_with_scheme(&scheme, &dst_bases, &src_bases, &ThisThread)
+            .unwrap();
+
+        // 与逐个重排的结果一致
+        for (d, s) in dst.iter().zip(&src) {
+            let mut single = vec![0u32; M * N];
+            let (dst_layout, src_layout) = layouts();
+            let args = Args::<Cpu> {
+                dst_base: single.as_mut_ptr().cast(),
+                src_base: s.as_ptr().cast(),
+                ..Args::new_null(dst_layout, src_layout)
+            };
+            op.launch(&args, &mut [], &ThisThread).unwrap();
+            assert_eq!(d, &single);
+        }
+
+        // 数量不一致的批次
+        dst_bases.pop();
+        assert!(op
+            .launch_batch_with_scheme(&scheme, &dst_bases, &src_bases, &ThisThread)
+            .is_err());
+    }
+
+    #[test]
+    fn test_half() {
+        const M: usize = 9;
+        const N: usize = 13;
+
+        let op = Operator::new(&Cpu);
+        let unit = size_of::<u16>() as isize;
+        for dt in [ty::F16, ty::BF16] {
+            // 按位模式比较，结果必须逐字节一致
+            let src = (0..M * N)
+                .map(|i| (i as u16).wrapping_mul(0x9e37))
+                .collect::<Vec<_>>();
+
+            // 2 字节单元的转置
+            let mut dst = vec![0u16; M * N];
+            let args = Args::<Cpu> {
+                dst_base: dst.as_mut_ptr().cast(),
+                src_base: src.as_ptr().cast(),
+                ..Args::new_null(
+                    TensorLayout::new_contiguous(dt, &[N, M]),
+                    TensorLayout::new(dt, &[N, M], &[unit, N as isize * unit]),
+                )
+            };
+            op.launch(&args, &mut [], &ThisThread).unwrap();
+            for i in 0..M {
+                for j in 0..N {
+                    assert_eq!(dst[j * M + i], src[i * N + j]);
+                }
+            }
+
+            // 相邻两个元素合并为 4 字节单元，基址只对齐到 2 字节
+            let (m, n) = (M, (N - 1) / 2);
+            let mut dst = vec![0u16; M * N];
+            let args = Args::<Cpu> {
+                dst_base: dst[1..].as_mut_ptr().cast(),
+                src_base: src[1..].as_ptr().cast(),
+                ..Args::new_null(
+                    TensorLayout::new_contiguous(dt, &[n, m, 2]),
+                    TensorLayout::new(dt, &[n, m, 2], &[2 * unit, 2 * n as isize * unit, unit]),
+                )
+            };
+            op.launch(&args, &mut [], &ThisThread).unwrap();
+            for i in 0..m {
+                for j in 0..n {
+                    for k in 0..2 {
+                        assert_eq!(dst[1 + (j * m + i) * 2 + k], src[1 + (i * n + j) * 2 + k]);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! 重排方案的布局压缩。
+//!
+//! 只依赖 `core` 和 `alloc`，不涉及任何硬件，可在 `no_std` 环境中复用，
+//! `tests/no_std_compact.rs` 在 `no_std` 下编译并测试本模块。
+
+use alloc::{vec, vec::Vec};
+use core::cmp::Ordering;
+
+/// 重排的一个维度，步长以字节为单位。
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Dim {
+    pub len: usize,
+    pub dst: isize,
+    pub src: isize,
+}
+
+impl PartialOrd for Dim {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Dim {
+    /// dst 绝对值降序 -> src 绝对值降序 -> len 升序
+    fn cmp(&self, other: &Self) -> Ordering {
+        use Ordering::Equal as Eq;
+        match self.dst.abs().cmp(&other.dst.abs()) {
+            Eq => match self.src.abs().cmp(&other.src.abs()) {
+                Eq => self.len.cmp(&other.len),
+                neq => neq.reverse(),
+            },
+            neq => neq.reverse(),
+        }
+    }
+}
+
+/// 排序并合并维度，生成 [Scheme](super::Scheme) 的布局。
+///
+/// 布局依次为 unit、ndim + 1 个索引步长、ndim 个 dst 步长和 ndim 个 src 步长。
+///
+/// 排序按 dst 步长降序，dst 和 src 都连续的维度无论在原布局中的位置都会排到末尾，
+/// 因此置换过的布局也能将连续部分并入 unit。
+///
+/// src 步长为 0 的维度表示广播，只会与同样是广播的相邻维度合并，不会并入 unit。
+pub fn compact(unit: usize, mut dims: Vec<Dim>) -> Vec<isize> {
+    // # 排序
+    dims.sort_unstable();
+    // # 合并连续维度
+    let mut unit = unit as isize;
+    let mut ndim = dims.len();
+    // ## 合并末尾连续维度到 unit，排序后连续维度必然在末尾
+    for dim in dims.iter_mut().rev() {
+        if dim.dst == unit && dim.src == unit {
+            unit *= dim.len as isize;
+            ndim -= 1;
+        } else {
+            break;
+        }
+    }
+    dims.truncate(ndim);
+    // ## 合并任意连续维度
+    for i in (1..dims.len()).rev() {
+        let (head, tail) = dims.split_at_mut(i);
+        let f = &mut head[i - 1]; // f for front
+        let b = &mut tail[0]; // b for back
+        let len = b.len as isize;
+        if b.dst * len == f.dst && b.src * len == f.src {
+            *f = Dim {
+                len: b.len * f.len,
+                dst: b.dst,
+                src: b.src,
+            };
+            *b = Dim {
+                len: 1,
+                dst: 0,
+                src: 0,
+            };
+            ndim -= 1;
+        }
+    }
+    // # 合并空间
+    let mut layout = vec![0isize; 2 + ndim * 3];
+    layout[0] = unit as _;
+    layout[ndim + 1] = 1;
+    for (i, Dim { len, dst, src }) in dims.into_iter().filter(|d| d.len != 1).enumerate() {
+        layout[1 + i] = len as _;
+        layout[2 + ndim + i] = dst;
+        layout[2 + ndim * 2 + i] = src;
+    }
+    for i in (1..=ndim).rev() {
+        layout[i] *= layout[i + 1];
+    }
+    layout
+}
+
+#[test]
+fn test_compact() {
+    // 与 `test_scheme` 相同的布局，已剔除 1 长维度
+    let dims = [
+        (4, 288, 576),
+        (3, 96, 192),
+        (2, 48, 96),
+        (2, 24, 8),
+        (3, 8, 16),
+        (4, 2, 2),
+    ]
+    .into_iter()
+    .map(|(len, dst, src)| Dim { len, dst, src })
+    .collect();
+    #[rustfmt::skip]
+    assert_eq!(
+        compact(2, dims),
+        [
+            8,
+            144, 6, 3, 1,
+            48, 24, 8,
+            96, 8, 16,
+        ]
+    );
+}
+
+#[test]
+fn test_compact_broadcast() {
+    // [1, 8] 扩展为 [3, 4, 8]，两个广播维度合并为一个
+    let dims = [(3, 64, 0), (4, 16, 0), (8, 2, 2)]
+        .into_iter()
+        .map(|(len, dst, src)| Dim { len, dst, src })
+        .collect();
+    #[rustfmt::skip]
+    assert_eq!(
+        compact(2, dims),
+        [
+            16,
+            12, 1,
+            16,
+            0,
+        ]
+    );
+    // 广播维度的 dst 连续时也不能并入 unit
+    let dims = [(5, 8, 2), (4, 2, 0)]
+        .into_iter()
+        .map(|(len, dst, src)| Dim { len, dst, src })
+        .collect();
+    #[rustfmt::skip]
+    assert_eq!(
+        compact(2, dims),
+        [
+            2,
+            20, 4, 1,
+            8, 2,
+            2, 0,
+        ]
+    );
+}
@@ -1,10 +1,13 @@
 use super::{args::Scheme, Args, Rearrange};
 use crate::{
+    args_not_support,
     cuda::{Gpu, Handle, ModuleBox},
-    rank_not_support, shape_not_support, ByteOf, LaunchError, QueueAlloc, SchemeError,
+    rank_not_support, shape_mismatch, shape_not_support, ByteOf, ConstPtr, LaunchError, MutPtr,
+    QueueAlloc, QueueOf, SchemeError,
 };
 use std::{
     ffi::CString,
+    iter::zip,
     slice::{from_raw_parts, from_raw_parts_mut},
     sync::Arc,
 };
@@ -60,12 +63,28 @@ impl crate::Operator for Operator {
     where
         QA: QueueAlloc<Hardware = Self::Hardware>,
     {
-        let scheme = Scheme::new(args)?;
+        if !args.is_plain_copy() {
+            Err(args_not_support("cuda: rearrange with scale/bias"))?;
+        }
+        let scheme = Scheme::new(args, Some(2))?;
+        self.launch_with_scheme(&scheme, args.dst_base, args.src_base, queue_alloc.queue())
+    }
+}
+
+impl Operator {
+    /// 使用预先构造的方案执行重排，跳过布局的排序和合并。
+    pub fn launch_with_scheme(
+        &self,
+        scheme: &Scheme,
+        dst_base: MutPtr<Gpu>,
+        src_base: ConstPtr<Gpu>,
+        queue: &QueueOf<Gpu>,
+    ) -> Result<(), LaunchError> {
         if scheme.ndim() == 0 {
             let unit = scheme.unit();
-            let dst = unsafe { from_raw_parts_mut(args.dst_base, unit) };
-            let src = unsafe { from_raw_parts(args.src_base, unit) };
-            queue_alloc.queue().memcpy_d2d(dst, src);
+            let dst = unsafe { from_raw_parts_mut(dst_base, unit) };
+            let src = unsafe { from_raw_parts(src_base, unit) };
+            queue.memcpy_d2d(dst, src);
             return Ok(());
         }
 
@@ -152,17 +171,40 @@ impl crate::Operator for Operator {
         let src_cs = src_cs / unit;
 
         let params = cuda::params![
-            args.dst_base,
+            dst_base,
             dst_rs,
             dst_cs,
-            args.src_base,
+            src_base,
             src_rs,
             src_cs,
             c,
             bytes_thread
         ];
         self.module
-            .launch(&name, grid, block, params.as_ptr(), 0, queue_alloc.queue());
+            .launch(&name, grid, block, params.as_ptr(), 0, queue);
+        Ok(())
+    }
+
+    /// 使用同一方案重排一批同形状的张量，依次提交到同一流上，方案只构造一次。
+    ///
+    /// `dst_bases` 和 `src_bases` 逐个配对，数量必须相同。
+    pub fn launch_batch_with_scheme(
+        &self,
+        scheme: &Scheme,
+        dst_bases: &[MutPtr<Gpu>],
+        src_bases: &[ConstPtr<Gpu>],
+        queue: &QueueOf<Gpu>,
+    ) -> Result<(), LaunchError> {
+        if dst_bases.len() != src_bases.len() {
+            Err(shape_mismatch(format!(
+                "batched rearrange with {} dst and {} src tensors",
+                dst_bases.len(),
+                src_bases.len(),
+            )))?
+        }
+        for (&dst, &src) in zip(dst_bases, src_bases) {
+            self.launch_with_scheme(scheme, dst, src, queue)?
+        }
         Ok(())
     }
 }
@@ -208,6 +250,8 @@ mod test {
             dst_base: null_mut(),
             src_layout: TensorLayout::new_dyn(dt, &[dyn_(); 2], &[dyn_(); 2]),
             src_base: null(),
+            scale: 1.,
+            bias: 0.,
         }
     }
 
@@ -224,6 +268,8 @@ mod test {
             dst_base,
             src_layout: TensorLayout::new(dt, shape, s_src),
             src_base,
+            scale: 1.,
+            bias: 0.,
         }
     }
 
@@ -1,5 +1,5 @@
 use super::{args::Scheme, Args, Rearrange};
-use crate::{infini::Device, ByteOf, LaunchError, QueueAlloc, SchemeError};
+use crate::{args_not_support, infini::Device, ByteOf, LaunchError, QueueAlloc, SchemeError};
 use digit_layout::types;
 use infini_op::{infiniop, AsRaw, Descriptor, Handle};
 use std::{
@@ -41,7 +41,10 @@ impl crate::Operator for Operator {
     {
         use std::iter::once;
 
-        let scheme = Scheme::new(args)?;
+        if !args.is_plain_copy() {
+            Err(args_not_support("infini: rearrange with scale/bias"))?;
+        }
+        let scheme = Scheme::new(args, None)?;
         if scheme.ndim() == 0 {
             let unit = scheme.unit();
             let dst = unsafe { from_raw_parts_mut(args.dst_base, unit) };
@@ -99,6 +102,8 @@ mod test {
             dst_base: null_mut(),
             src_layout: TensorLayout::new_dyn(dt, &[dyn_(); 2], &[dyn_(); 2]),
             src_base: null(),
+            scale: 1.,
+            bias: 0.,
         }
     }
 
@@ -115,6 +120,8 @@ mod test {
             dst_base,
             src_layout: TensorLayout::new(dt, shape, s_src),
             src_base,
+            scale: 1.,
+            bias: 0.,
         }
     }
 
@@ -8,6 +8,80 @@ pub mod infini;
 pub mod opencl;
 
 mod args;
-pub use args::Args;
+pub mod compact;
+pub use args::{Args, Scheme};
 
-crate::op_trait!(Rearrange);
+crate::op_trait! { Rearrange
+    /// 交换 `src` 的 `axes` 两个维度，结果按交换后的形状连续存储到 `dst_base`。
+    fn transpose<QA>(
+        &self,
+        src_layout: &crate::TensorLayout,
+        src_base: crate::ConstPtr<H>,
+        axes: [usize; 2],
+        dst_base: crate::MutPtr<H>,
+        queue_alloc: &QA,
+    ) -> Result<(), crate::LaunchError>
+    where
+        QA: crate::QueueAlloc<Hardware = H>,
+    {
+        let args = Args::transpose(src_layout, src_base, axes, dst_base)?;
+        self.launch(&args, &mut [], queue_alloc)
+    }
+
+    /// 将 `src` 描述的视图物化为连续张量，返回新分配的存储和它的连续布局。
+    ///
+    /// `src` 的形状必须是静态的，返回的布局保持 `src` 的数据类型。
+    fn to_contiguous<QA>(
+        &self,
+        src_layout: &crate::TensorLayout,
+        src_base: crate::ConstPtr<H>,
+        queue_alloc: &QA,
+    ) -> Result<(QA::DevMem, crate::TensorLayout), crate::LaunchError>
+    where
+        QA: crate::QueueAlloc<Hardware = H>,
+    {
+        self.to_contiguous_as(src_layout, src_base, src_layout.dt(), queue_alloc)
+    }
+
+    /// 同 [`to_contiguous`](Self::to_contiguous)，但返回的布局标记为 `dt`。
+    ///
+    /// 重排只复制字节，`dt` 的字节数必须与 `src` 的数据类型相同。
+    fn to_contiguous_as<QA>(
+        &self,
+        src_layout: &crate::TensorLayout,
+        src_base: crate::ConstPtr<H>,
+        dt: digit_layout::DigitLayout,
+        queue_alloc: &QA,
+    ) -> Result<(QA::DevMem, crate::TensorLayout), crate::LaunchError>
+    where
+        QA: crate::QueueAlloc<Hardware = H>,
+    {
+        if dt.nbytes() != src_layout.dt().nbytes() {
+            Err(crate::type_mismatch(format!(
+                "cannot tag {} as {dt}: element sizes differ",
+                src_layout.dt()
+            )))?
+        }
+        let shape = src_layout
+            .shape()
+            .iter()
+            .map(|d| crate::static_from(d).copied())
+            .collect::<Result<Vec<_>, _>>()?;
+        let dst_layout = crate::TensorLayout::new_contiguous(src_layout.dt(), &shape);
+        let size = shape.iter().product::<usize>() * src_layout.dt().nbytes();
+        let mut dst = queue_alloc.alloc(size);
+        if size > 0 {
+            let args = Args {
+                dst_layout: dst_layout.clone(),
+                dst_base: dst.as_mut_ptr(),
+                src_layout: src_layout.clone(),
+                src_base,
+                scale: 1.,
+                bias: 0.,
+            };
+            self.launch(&args, &mut [], queue_alloc)?
+        }
+        let dst_layout = crate::TensorLayout::new_contiguous(dt, &shape);
+        Ok((dst, dst_layout))
+    }
+}
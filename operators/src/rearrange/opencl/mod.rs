@@ -1,18 +1,29 @@
 use super::{args::Scheme, Args, Rearrange};
 use crate::{
+    args_not_support, execution_failed,
     opencl::{ClDevice, CodeGen, KernelCache, CL2_0},
-    rank_not_support, ByteOf, LaunchError, QueueAlloc,
+    rank_not_support, shape_mismatch, ByteOf, ConstPtr, LaunchError, MutPtr, QueueAlloc, QueueOf,
     SchemeDiversity::Low as LowDiversity,
-    SchemeError,
+    SchemeError, TensorLayout,
+};
+use clrt::{
+    bindings::{
+        clEnqueueBarrierWithWaitList, clEnqueueMarkerWithWaitList, cl_event, cl_int, CL_SUCCESS,
+    },
+    AsRaw, CommandQueue, Context,
 };
-use clrt::{bindings::cl_int, Context};
 use lru::LruCache;
-use std::slice::{from_raw_parts, from_raw_parts_mut};
-use std::sync::Mutex;
+use std::{
+    iter::zip,
+    ptr::{null, null_mut},
+    slice::{from_raw_parts, from_raw_parts_mut},
+    sync::Mutex,
+};
 
 pub struct Operator {
     ctx: Context,
     max_group_size: usize,
+    tile: Option<usize>,
     schemes: Mutex<LruCache<SchemeKey, KernelCache>>,
 }
 
@@ -35,6 +46,7 @@ impl crate::Operator for Operator {
         Self {
             ctx,
             max_group_size,
+            tile: None,
             schemes: node.new_cache(LowDiversity),
         }
     }
@@ -56,12 +68,61 @@ impl crate::Operator for Operator {
     where
         QA: QueueAlloc<Hardware = Self::Hardware>,
     {
-        let scheme = Scheme::new(args)?;
+        if !args.is_plain_copy() {
+            Err(args_not_support("opencl: rearrange with scale/bias"))?;
+        }
+        let scheme = Scheme::new(args, Some(2))?;
+        self.launch_with_scheme(&scheme, args.dst_base, args.src_base, queue_alloc.queue())
+    }
+}
+
+impl Operator {
+    /// 设置 [`Operator::launch_streamed`] 的分块大小，以字节为单位。`None` 表示整个张量作为一块。
+    pub fn set_tile_size(&mut self, tile: Option<usize>) {
+        self.tile = tile
+    }
+
+    /// 等待 `wait` 中的事件全部完成后执行重排，返回重排完成的事件，用于在计算步骤之间流水地变换布局。
+    ///
+    /// `wait` 可以来自其他队列，例如前一步 rope 的完成事件。返回的事件由调用者释放。
+    pub fn launch_with_events(
+        &self,
+        args: &Args<ClDevice>,
+        queue: &CommandQueue,
+        wait: &[cl_event],
+    ) -> Result<cl_event, LaunchError> {
+        let queue_raw = unsafe { queue.as_raw() };
+        if !wait.is_empty() {
+            let ret = unsafe {
+                clEnqueueBarrierWithWaitList(queue_raw, wait.len() as _, wait.as_ptr(), null_mut())
+            };
+            if ret != CL_SUCCESS as cl_int {
+                Err(execution_failed(format!("enqueue barrier failed: {ret}")))?
+            }
+        }
+        crate::Operator::launch(self, args, &mut [], queue)?;
+        // 不带等待列表的标记在队列中之前的所有命令完成后触发
+        let mut event = null_mut();
+        let ret = unsafe { clEnqueueMarkerWithWaitList(queue_raw, 0, null(), &mut event) };
+        if ret != CL_SUCCESS as cl_int {
+            Err(execution_failed(format!("enqueue marker failed: {ret}")))?
+        }
+        Ok(event)
+    }
+
+    /// 使用预先构造的方案执行重排，跳过布局的排序和合并。
+    pub fn launch_with_scheme(
+        &self,
+        scheme: &Scheme,
+        dst_base: MutPtr<ClDevice>,
+        src_base: ConstPtr<ClDevice>,
+        queue: &QueueOf<ClDevice>,
+    ) -> Result<(), LaunchError> {
         let unit = scheme.unit();
         if scheme.count() == 1 {
-            let dst = unsafe { from_raw_parts_mut(args.dst_base, unit) };
-            let src = unsafe { from_raw_parts(args.src_base, unit) };
-            queue_alloc.queue().memcpy(dst, src, None);
+            let dst = unsafe { from_raw_parts_mut(dst_base, unit) };
+            let src = unsafe { from_raw_parts(src_base, unit) };
+            queue.memcpy(dst, src, None);
             return Ok(());
         }
 
@@ -122,16 +183,18 @@ impl crate::Operator for Operator {
                 "rearrange not support ndim > 2 on Mobile GPU",
             ))?,
         };
-        let unit_size = unit / 4;
-        let (key, group_size) = self.cache_kernel(unit_size);
+        // 按能整除单元的最宽字复制，f16、bf16 转置等 2 字节单元不能按 4 字节复制
+        let word = [4, 2, 1].into_iter().find(|w| unit % w == 0).unwrap();
+        let unit_size = unit / word;
+        let (key, group_size) = self.cache_kernel(word, unit_size);
         let mut rearrange = self
             .schemes
             .lock()
             .unwrap()
             .get(&key)
             .unwrap()
-            .take("rearrange")
-            .unwrap();
+            .take_guard("rearrange")
+            .ok_or_else(|| execution_failed("opencl: kernel rearrange not found"))?;
 
         let unit = unit as i32;
         let dst_rs = dst_rs / unit;
@@ -140,10 +203,10 @@ impl crate::Operator for Operator {
         let src_cs = src_cs / unit;
 
         rearrange
-            .set_arg(0, args.dst_base)
+            .set_arg(0, dst_base)
             .set_arg(1, dst_rs as cl_int)
             .set_arg(2, dst_cs as cl_int)
-            .set_arg(3, args.src_base)
+            .set_arg(3, src_base)
             .set_arg(4, src_rs as cl_int)
             .set_arg(5, src_cs as cl_int)
             .set_arg(6, c as cl_int)
@@ -152,35 +215,199 @@ impl crate::Operator for Operator {
                 &[0],
                 &[(r * c * (unit_size as u32)) as usize],
                 &[group_size],
-                queue_alloc.queue(),
+                queue,
                 None,
             );
 
-        let mut cache = self.schemes.lock().unwrap();
-        let program = cache.get(&key).unwrap();
-        program.put("rearrange", rearrange);
         Ok(())
     }
-}
 
-impl Operator {
-    fn cache_kernel(&self, unit_size: usize) -> (SchemeKey, usize) {
+    /// 使用同一方案重排一批同形状的张量，依次提交到同一队列上，方案只构造一次。
+    ///
+    /// `dst_bases` 和 `src_bases` 逐个配对，数量必须相同。
+    pub fn launch_batch_with_scheme(
+        &self,
+        scheme: &Scheme,
+        dst_bases: &[MutPtr<ClDevice>],
+        src_bases: &[ConstPtr<ClDevice>],
+        queue: &QueueOf<ClDevice>,
+    ) -> Result<(), LaunchError> {
+        if dst_bases.len() != src_bases.len() {
+            Err(shape_mismatch(format!(
+                "batched rearrange with {} dst and {} src tensors",
+                dst_bases.len(),
+                src_bases.len(),
+            )))?
+        }
+        for (&dst, &src) in zip(dst_bases, src_bases) {
+            self.launch_with_scheme(scheme, dst, src, queue)?
+        }
+        Ok(())
+    }
+
+    /// `word` 为每个工作项复制的字节数，`unit_size` 为每个单元的字数。
+    fn cache_kernel(&self, word: usize, unit_size: usize) -> (SchemeKey, usize) {
         let items_per_thread = unit_size.div_ceil(self.max_group_size);
         let group_size = match items_per_thread {
             1 => unit_size,
             _ => self.max_group_size,
         };
-        let key = SchemeKey { unit_size };
+        let key = SchemeKey { word, unit_size };
         self.schemes.lock().unwrap().get_or_insert(key, || {
-            let src = CodeGen::new(include_str!("rearrange.cl")).to_string();
+            let ty = match word {
+                4 => "uint",
+                2 => "ushort",
+                _ => "uchar",
+            };
+            let src = CodeGen::new(include_str!("rearrange.cl"))
+                .define("Tword", ty)
+                .to_string();
             KernelCache::new(&self.ctx, &src, CL2_0)
         });
         (key, group_size)
     }
 }
 
+impl Operator {
+    /// 将主机上的 `src` 重排到设备上的 `dst`，变换在映射期间完成。
+    ///
+    /// 两个布局的偏移都相对于各自存储区的起始位置。
+    pub fn launch_from_host(
+        &self,
+        dst_layout: &TensorLayout,
+        dst: &mut [ByteOf<ClDevice>],
+        src_layout: &TensorLayout,
+        src: &[u8],
+        queue: &CommandQueue,
+    ) -> Result<(), LaunchError> {
+        check_host_range("dst", dst_layout, dst.len())?;
+        check_host_range("src", src_layout, src.len())?;
+        let scheme = Scheme::new(
+            &Args::<ClDevice>::new_null(dst_layout.clone(), src_layout.clone()),
+            None,
+        )?;
+        let mut map = queue.map_mut(dst, false);
+        unsafe { scheme.launch_host(map.as_mut_ptr(), src.as_ptr()) };
+        queue.unmap(map);
+        Ok(())
+    }
+
+    /// 将设备上的 `src` 重排到主机上的 `dst`，变换在映射期间完成。
+    ///
+    /// 两个布局的偏移都相对于各自存储区的起始位置。
+    pub fn launch_to_host(
+        &self,
+        dst_layout: &TensorLayout,
+        dst: &mut [u8],
+        src_layout: &TensorLayout,
+        src: &mut [ByteOf<ClDevice>],
+        queue: &CommandQueue,
+    ) -> Result<(), LaunchError> {
+        check_host_range("dst", dst_layout, dst.len())?;
+        check_host_range("src", src_layout, src.len())?;
+        let scheme = Scheme::new(
+            &Args::<ClDevice>::new_null(dst_layout.clone(), src_layout.clone()),
+            None,
+        )?;
+        let map = queue.map(src);
+        unsafe { scheme.launch_host(dst.as_mut_ptr(), map.as_ptr()) };
+        queue.unmap(map);
+        Ok(())
+    }
+}
+
+impl Operator {
+    /// 在主机存储之间重排，每次只把最外维的一个分块换入设备，用于超出设备存储的张量。
+    ///
+    /// 每个分块的 src 按其存储顺序紧凑地写入设备上的暂存区，由核函数重排到另一块暂存区后写回主机的 dst。
+    /// 两块暂存区由 `queue_alloc` 分配并在所有分块间复用，各不超过分块大小；
+    /// 最外维的单个切片已超过分块大小时按一个切片分配。两个布局的偏移都相对于各自存储区的起始位置。
+    pub fn launch_streamed<QA>(
+        &self,
+        dst_layout: &TensorLayout,
+        dst: &mut [u8],
+        src_layout: &TensorLayout,
+        src: &[u8],
+        queue_alloc: &QA,
+    ) -> Result<(), LaunchError>
+    where
+        QA: QueueAlloc<Hardware = ClDevice>,
+    {
+        check_host_range("dst", dst_layout, dst.len())?;
+        check_host_range("src", src_layout, src.len())?;
+        let scheme = Scheme::new(
+            &Args::<ClDevice>::new_null(dst_layout.clone(), src_layout.clone()),
+            Some(2),
+        )?;
+        let chunks = scheme.split_outer(self.tile.unwrap_or(usize::MAX));
+        let Some(size) = chunks.iter().map(|(c, ..)| c.count() * c.unit()).max() else {
+            return Ok(());
+        };
+
+        let queue = queue_alloc.queue();
+        let mut src_tile = queue_alloc.alloc(size);
+        let mut dst_tile = queue_alloc.alloc(size);
+        let mut stream = || -> Result<(), LaunchError> {
+            for (chunk, dst_offset, src_offset) in &chunks {
+                let unit = chunk.unit();
+                let shape = chunk.shape().collect::<Vec<_>>();
+                let src_packed = packed_strides(&shape, chunk.src_strides(), unit);
+                let dst_packed = packed_strides(&shape, chunk.dst_strides(), unit);
+                // 主机 -> 设备，映射等待上一块的核函数读完暂存区
+                let gather = Scheme::new_bytes(unit, &shape, &src_packed, chunk.src_strides())?;
+                let mut map = queue.map_mut(&mut src_tile, false);
+                unsafe { gather.launch_host(map.as_mut_ptr(), src.as_ptr().offset(*src_offset)) };
+                queue.unmap(map);
+                // 设备上重排
+                let rearrange = Scheme::new_bytes(unit, &shape, &dst_packed, &src_packed)?;
+                self.launch_with_scheme(
+                    &rearrange,
+                    dst_tile.as_mut_ptr(),
+                    src_tile.as_ptr(),
+                    queue,
+                )?;
+                // 设备 -> 主机
+                let scatter = Scheme::new_bytes(unit, &shape, chunk.dst_strides(), &dst_packed)?;
+                let map = queue.map(&mut dst_tile);
+                unsafe { scatter.launch_host(dst.as_mut_ptr().offset(*dst_offset), map.as_ptr()) };
+                queue.unmap(map);
+            }
+            Ok(())
+        };
+        let ans = stream();
+        queue_alloc.free(src_tile);
+        queue_alloc.free(dst_tile);
+        ans
+    }
+}
+
+/// 按 `strides` 绝对值从大到小的顺序紧凑排列 `shape` 时各维的字节步长。
+fn packed_strides(shape: &[usize], strides: &[isize], unit: usize) -> Vec<isize> {
+    let mut order = (0..shape.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| std::cmp::Reverse(strides[i].unsigned_abs()));
+    let mut ans = vec![0; shape.len()];
+    let mut size = unit as isize;
+    for &i in order.iter().rev() {
+        ans[i] = size;
+        size *= shape[i] as isize;
+    }
+    ans
+}
+
+/// 检查布局访问的字节范围落在长度为 `len` 的存储区内。
+fn check_host_range(name: &str, layout: &TensorLayout, len: usize) -> Result<(), SchemeError> {
+    match layout.byte_range() {
+        Some(range) if range.start >= 0 && range.end as usize <= len => Ok(()),
+        Some(range) => Err(shape_mismatch(format!(
+            "{name}: byte range {range:?} exceeds buffer of {len} bytes"
+        ))),
+        None => Err(shape_mismatch(format!("{name}: layout must be static"))),
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 struct SchemeKey {
+    word: usize,
     unit_size: usize,
 }
 
@@ -198,6 +425,8 @@ mod test {
             dst_base: null_mut(),
             src_layout: TensorLayout::new_dyn(dt, &[dyn_(); 2], &[dyn_(); 2]),
             src_base: null(),
+            scale: 1.,
+            bias: 0.,
         }
     }
 
@@ -214,6 +443,8 @@ mod test {
             dst_base,
             src_layout: TensorLayout::new(dt, shape, s_src),
             src_base,
+            scale: 1.,
+            bias: 0.,
         }
     }
 
@@ -315,4 +546,398 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_host_transfer() {
+        use super::Operator;
+        use crate::{opencl::ClDevice, Operator as _, TensorLayout};
+        use clrt::Platform;
+        use digit_layout::types as ty;
+        use rand::Rng;
+
+        let dt = ty::U32;
+        let r = 37;
+        let c = 64;
+        let unit = dt.nbytes() as isize;
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+
+                // 主机上按 [c, r] 存储，以 [r, c] 的转置视图读出
+                let mut host = vec![0u32; r * c];
+                rand::rng().fill(&mut host[..]);
+                let transposed = TensorLayout::new(dt, &[r, c], &[unit, r as isize * unit]);
+                let contiguous = TensorLayout::new_contiguous(dt, &[r, c]);
+
+                let mut d_svm = context.malloc::<u32>(r * c);
+                cl_op
+                    .launch_from_host(
+                        &contiguous,
+                        &mut d_svm,
+                        &transposed,
+                        unsafe { host.align_to::<u8>().1 },
+                        &queue,
+                    )
+                    .unwrap();
+
+                let mut back = vec![0u32; r * c];
+                cl_op
+                    .launch_to_host(
+                        &contiguous,
+                        unsafe { back.align_to_mut::<u8>().1 },
+                        &contiguous,
+                        &mut d_svm,
+                        &queue,
+                    )
+                    .unwrap();
+                queue.finish();
+
+                for i in 0..r {
+                    for j in 0..c {
+                        assert_eq!(back[i * c + j], host[j * r + i]);
+                    }
+                }
+
+                // 布局越出主机存储区时拒绝执行
+                let short = &mut back[..r * c - 1];
+                assert!(cl_op
+                    .launch_to_host(
+                        &contiguous,
+                        unsafe { short.align_to_mut::<u8>().1 },
+                        &contiguous,
+                        &mut d_svm,
+                        &queue,
+                    )
+                    .is_err());
+                let reversed = TensorLayout::new(dt, &[r, c], &[-(c as isize) * unit, unit]);
+                assert!(cl_op
+                    .launch_from_host(
+                        &contiguous,
+                        &mut d_svm,
+                        &reversed,
+                        unsafe { host.align_to::<u8>().1 },
+                        &queue,
+                    )
+                    .is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_launch_with_scheme() {
+        use super::{super::Scheme, Operator};
+        use crate::{opencl::ClDevice, Operator as _};
+        use clrt::Platform;
+        use digit_layout::types as ty;
+        use ndarray_layout::{ArrayLayout, Endian::BigEndian};
+        use rand::Rng;
+        use std::iter::zip;
+
+        let dt = ty::U32;
+        let nh = 5;
+        let seq = 32;
+        let dh = 64;
+        let s_src = ArrayLayout::<3>::new_contiguous(&[nh, seq, dh], BigEndian, dt.nbytes());
+        let s_dst = ArrayLayout::<3>::new_contiguous(&[seq, nh, dh], BigEndian, dt.nbytes())
+            .transpose(&[1, 0]);
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let mut cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+                cl_op.scheme(&dyn_args(dt), 0).unwrap();
+
+                let mut s_svm = context.malloc::<u32>(nh * seq * dh);
+                let mut d_args = context.malloc::<u32>(nh * seq * dh);
+                let mut d_scheme = context.malloc::<u32>(nh * seq * dh);
+
+                let mut map = queue.map_mut(&mut s_svm, false);
+                let ([], mem, []) = (unsafe { map.align_to_mut::<u32>() }) else {
+                    panic!()
+                };
+                rand::rng().fill(mem);
+                queue.unmap(map);
+
+                let args = args(
+                    dt,
+                    &[nh, seq, dh],
+                    s_src.strides(),
+                    s_dst.strides(),
+                    s_svm.as_ptr().cast(),
+                    d_args.as_mut_ptr().cast(),
+                );
+                cl_op.launch(&args, &mut [], &queue).unwrap();
+
+                let scheme = Scheme::new(&args, None).unwrap();
+                for _ in 0..3 {
+                    cl_op
+                        .launch_with_scheme(
+                            &scheme,
+                            d_scheme.as_mut_ptr().cast(),
+                            s_svm.as_ptr().cast(),
+                            &queue,
+                        )
+                        .unwrap();
+                }
+                queue.finish();
+
+                let map_args = queue.map(&mut d_args);
+                let map_scheme = queue.map(&mut d_scheme);
+                for (a, b) in zip(&*map_args, &*map_scheme) {
+                    assert_eq!(a, b);
+                }
+                queue.unmap(map_scheme);
+                queue.unmap(map_args);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tiled() {
+        use super::Operator;
+        use crate::{opencl::ClDevice, Alloc, Operator as _, QueueAlloc, QueueOf, TensorLayout};
+        use clrt::{CommandQueue, Platform, SvmBlob};
+        use digit_layout::types as ty;
+        use rand::Rng;
+        use std::cell::RefCell;
+
+        /// 记录每次分配的大小。
+        struct Recorder<'a> {
+            queue: &'a CommandQueue,
+            sizes: RefCell<Vec<usize>>,
+        }
+
+        impl Alloc<SvmBlob> for Recorder<'_> {
+            fn alloc(&self, size: usize) -> SvmBlob {
+                self.sizes.borrow_mut().push(size);
+                self.queue.ctx().malloc::<u8>(size)
+            }
+
+            fn free(&self, mem: SvmBlob) {
+                self.queue.free(mem, None)
+            }
+        }
+
+        impl QueueAlloc for Recorder<'_> {
+            type Hardware = ClDevice;
+            type DevMem = SvmBlob;
+            fn queue(&self) -> &QueueOf<Self::Hardware> {
+                self.queue
+            }
+            fn synchronize(&self) {
+                self.queue.finish()
+            }
+        }
+
+        const TILE: usize = 4096;
+        let dt = ty::U32;
+        let unit = dt.nbytes() as isize;
+        let nh = 5;
+        let seq = 32;
+        let dh = 64;
+        // 主机上 [nh, seq, dh] 连续的 src 重排为 [seq, nh, dh] 连续的 dst
+        let src_layout = TensorLayout::new_contiguous(dt, &[nh, seq, dh]);
+        let dst_layout = TensorLayout::new(
+            dt,
+            &[nh, seq, dh],
+            &[dh as isize * unit, (nh * dh) as isize * unit, unit],
+        );
+        let mut src = vec![0u32; nh * seq * dh];
+        rand::rng().fill(&mut src[..]);
+        assert!(size_of_val(&src[..]) > TILE);
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let mut cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+                cl_op.scheme(&dyn_args(dt), 0).unwrap();
+                cl_op.set_tile_size(Some(TILE));
+
+                let recorder = Recorder {
+                    queue: &queue,
+                    sizes: RefCell::new(Vec::new()),
+                };
+                let mut dst = vec![0u32; nh * seq * dh];
+                cl_op
+                    .launch_streamed(
+                        &dst_layout,
+                        unsafe { dst.align_to_mut::<u8>().1 },
+                        &src_layout,
+                        unsafe { src.align_to::<u8>().1 },
+                        &recorder,
+                    )
+                    .unwrap();
+
+                // 设备上只有两块不超过分块大小的暂存区
+                let sizes = recorder.sizes.into_inner();
+                assert_eq!(sizes.len(), 2);
+                assert!(sizes.iter().all(|&size| size <= TILE));
+                for i in 0..nh {
+                    for j in 0..seq {
+                        let src = &src[(i * seq + j) * dh..][..dh];
+                        let dst = &dst[(j * nh + i) * dh..][..dh];
+                        assert_eq!(src, dst);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_launch_with_events() {
+        use super::Operator;
+        use crate::opencl::{read_to_vec, ClDevice};
+        use clrt::{
+            bindings::{clReleaseEvent, clWaitForEvents},
+            Platform,
+        };
+        use digit_layout::types as ty;
+
+        let dt = ty::U32;
+        let r = 37;
+        let c = 129;
+        let unit = dt.nbytes() as isize;
+        let data = (0..r * c).map(|i| i as u32).collect::<Vec<_>>();
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                // 两次重排在不同的队列上，只靠事件保证先后
+                let first = context.queue();
+                let second = context.queue();
+                let cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+
+                let mut a_svm = context.malloc::<u32>(r * c);
+                let mut b_svm = context.malloc::<u32>(r * c);
+                let mut c_svm = context.malloc::<u32>(r * c);
+                let mut map = first.map_mut(&mut a_svm, false);
+                let ([], mem, []) = (unsafe { map.align_to_mut::<u32>() }) else {
+                    panic!()
+                };
+                mem.copy_from_slice(&data);
+                first.unmap(map);
+
+                // a [r, c] 转置为 b [c, r]，再转置回 c [r, c]
+                let transpose = args(
+                    dt,
+                    &[c, r],
+                    &[unit, c as isize * unit],
+                    &[r as isize * unit, unit],
+                    a_svm.as_ptr().cast(),
+                    b_svm.as_mut_ptr().cast(),
+                );
+                let back = args(
+                    dt,
+                    &[r, c],
+                    &[unit, r as isize * unit],
+                    &[c as isize * unit, unit],
+                    b_svm.as_ptr().cast(),
+                    c_svm.as_mut_ptr().cast(),
+                );
+                let e1 = cl_op.launch_with_events(&transpose, &first, &[]).unwrap();
+                let e2 = cl_op.launch_with_events(&back, &second, &[e1]).unwrap();
+                unsafe {
+                    clWaitForEvents(1, &e2);
+                    clReleaseEvent(e1);
+                    clReleaseEvent(e2);
+                }
+
+                assert_eq!(read_to_vec::<u32>(&mut c_svm, &second), data);
+                let b = read_to_vec::<u32>(&mut b_svm, &first);
+                for i in 0..r {
+                    for j in 0..c {
+                        assert_eq!(b[j * r + i], data[i * c + j]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_half() {
+        use super::{super::common_cpu::Operator as RefOp, Operator};
+        use crate::{
+            common_cpu::{Cpu, ThisThread},
+            opencl::{read_to_vec, ClDevice},
+            Operator as _,
+        };
+        use clrt::Platform;
+        use digit_layout::types as ty;
+
+        const M: usize = 33;
+        const N: usize = 17;
+        let unit = size_of::<u16>() as isize;
+        let src = (0..M * N)
+            .map(|i| (i as u16).wrapping_mul(0x9e37))
+            .collect::<Vec<_>>();
+        let s_src = [unit, N as isize * unit];
+        let s_dst = [M as isize * unit, unit];
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+
+                let mut s_svm = context.malloc::<u16>(M * N);
+                let mut d_svm = context.malloc::<u16>(M * N);
+                let mut map = queue.map_mut(&mut s_svm, false);
+                let ([], mem, []) = (unsafe { map.align_to_mut::<u16>() }) else {
+                    panic!()
+                };
+                mem.copy_from_slice(&src);
+                queue.unmap(map);
+
+                for dt in [ty::F16, ty::BF16] {
+                    cl_op
+                        .launch(
+                            &args(
+                                dt,
+                                &[N, M],
+                                &s_src,
+                                &s_dst,
+                                s_svm.as_ptr().cast(),
+                                d_svm.as_mut_ptr().cast(),
+                            ),
+                            &mut [],
+                            &queue,
+                        )
+                        .unwrap();
+                    let ans = read_to_vec::<u16>(&mut d_svm, &queue);
+
+                    let mut dst_ref = vec![0u16; M * N];
+                    RefOp::new(&Cpu)
+                        .launch(
+                            &args(
+                                dt,
+                                &[N, M],
+                                &s_src,
+                                &s_dst,
+                                src.as_ptr().cast(),
+                                dst_ref.as_mut_ptr().cast(),
+                            ),
+                            &mut [],
+                            &ThisThread,
+                        )
+                        .unwrap();
+                    assert_eq!(ans, dst_ref);
+                }
+            }
+        }
+    }
 }
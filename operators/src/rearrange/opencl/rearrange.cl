@@ -1,11 +1,16 @@
 #define CL_TARGET_OPENCL_VERSION 200
 #pragma OPENCL EXTENSION cl_khr_fp16 : enable
 
+// 每个工作项复制的字，unit 以字计
+#ifndef Tword
+#define Tword uint
+#endif
+
 __kernel void rearrange(
-    __global unsigned int *dst,
+    __global Tword *dst,
     unsigned int rsa,
     unsigned int csa,
-    __global unsigned int *src,
+    __global Tword *src,
     unsigned int rsb,
     unsigned int csb,
     unsigned int ncols,
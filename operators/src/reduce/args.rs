@@ -0,0 +1,131 @@
+use crate::{
+    shape_not_support, static_from, strides_not_support, type_not_support,
+    utils::{dim_distinct, rank_error, type_distinct},
+    ConstPtr, Hardware, MaybeDyn, MutPtr, SchemeError, TensorLayout,
+};
+use digit_layout::DigitLayout;
+use std::iter::zip;
+
+pub struct Args<H: Hardware> {
+    pub y_layout: TensorLayout,
+    pub y_base: MutPtr<H>,
+    pub x_layout: TensorLayout,
+    pub x_base: ConstPtr<H>,
+    /// 被规约的维度，输出张量没有这个维度。
+    pub axis: usize,
+    pub op: ReduceOp,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[repr(u8)]
+pub enum ReduceOp {
+    Sum,
+    Mean,
+    Max,
+}
+
+pub(super) struct Meta {
+    pub dt: DigitLayout,
+    #[allow(dead_code)]
+    pub n: MaybeDyn<usize>,
+}
+
+/// 将输入视作 [outer, n, inner]，输出视作 [outer, inner]。
+pub(super) struct View {
+    pub outer: usize,
+    pub n: usize,
+    pub inner: usize,
+    pub x_strides: [isize; 3],
+    pub y_strides: [isize; 2],
+}
+
+impl<H: Hardware> Args<H> {
+    pub(super) fn meta(&self) -> Result<Meta, SchemeError> {
+        let Self {
+            y_layout,
+            x_layout,
+            axis,
+            ..
+        } = self;
+
+        let ndim = x_layout.ndim();
+        if *axis >= ndim {
+            return Err(shape_not_support(format!("axis = {axis}, x.ndim = {ndim}")));
+        }
+        if y_layout.ndim() + 1 != ndim {
+            return Err(rank_error("y", ndim - 1, y_layout.ndim()));
+        }
+
+        let dt = type_distinct(&[y_layout.dt(), x_layout.dt()])?;
+        use digit_layout::LayoutContent::Real;
+        if !matches!(dt.decode(), Real { exponent: 1.., .. }) {
+            return Err(type_not_support(format!(
+                "data type {dt} is not supported, must be floating-point numbers",
+            )));
+        }
+
+        let x_shape = x_layout.shape();
+        let (head, tail) = x_shape.split_at(*axis);
+        let (n, tail) = tail.split_first().unwrap();
+        for (x, y) in zip(head.iter().chain(tail), y_layout.shape()) {
+            dim_distinct(&[*x, *y])?;
+        }
+
+        Ok(Meta { dt, n: *n })
+    }
+
+    pub(super) fn view(&self) -> Result<View, SchemeError> {
+        let Self {
+            y_layout,
+            x_layout,
+            axis,
+            ..
+        } = self;
+
+        let mut dims = Vec::with_capacity(x_layout.ndim());
+        for i in 0..x_layout.ndim() {
+            let len = *static_from(&x_layout.shape()[i])?;
+            let sx = *static_from(&x_layout.strides()[i])?;
+            let sy = match i.cmp(axis) {
+                std::cmp::Ordering::Less => *static_from(&y_layout.strides()[i])?,
+                std::cmp::Ordering::Equal => 0,
+                std::cmp::Ordering::Greater => *static_from(&y_layout.strides()[i - 1])?,
+            };
+            dims.push((len, sx, sy));
+        }
+
+        let (head, tail) = dims.split_at(*axis);
+        let (&(n, sxn, _), tail) = tail.split_first().unwrap();
+        let Some((outer, sxa, sya)) = merge(head) else {
+            return Err(strides_not_support("dims before axis cannot be merged"));
+        };
+        let Some((inner, sxb, syb)) = merge(tail) else {
+            return Err(strides_not_support("dims after axis cannot be merged"));
+        };
+        Ok(View {
+            outer,
+            n,
+            inner,
+            x_strides: [sxa, sxn, sxb],
+            y_strides: [sya, syb],
+        })
+    }
+}
+
+/// 将一组维度合并为一个维度，无法合并时返回 `None`。
+fn merge(dims: &[(usize, isize, isize)]) -> Option<(usize, isize, isize)> {
+    let mut ans = (1, 0, 0);
+    for &(len, sx, sy) in dims {
+        if len == 1 {
+            continue;
+        }
+        if ans.0 == 1 {
+            ans = (len, sx, sy)
+        } else if ans.1 == sx * len as isize && ans.2 == sy * len as isize {
+            ans = (ans.0 * len, sx, sy)
+        } else {
+            return None;
+        }
+    }
+    Some(ans)
+}
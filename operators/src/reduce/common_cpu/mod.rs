@@ -0,0 +1,156 @@
+use super::{
+    args::{Meta, View},
+    Args, Reduce, ReduceOp,
+};
+use crate::{common_cpu::Cpu, type_not_support, ByteOf, LaunchError, QueueAlloc, SchemeError};
+use half::f16;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+pub struct Operator;
+
+impl Reduce<Cpu> for Operator {}
+
+impl crate::Operator for Operator {
+    type Hardware = Cpu;
+    type TopoNode = Cpu;
+    type Args = Args<Cpu>;
+
+    #[inline]
+    fn new(_node: &Self::TopoNode) -> Self {
+        Self
+    }
+
+    fn scheme(
+        &mut self,
+        args: &Self::Args,
+        _max_workspace_size: usize,
+    ) -> Result<usize, SchemeError> {
+        let _meta = args.meta()?;
+        Ok(0)
+    }
+
+    fn launch<QA>(
+        &self,
+        args: &Self::Args,
+        _workspace: &mut [ByteOf<Self::Hardware>],
+        _queue_alloc: &QA,
+    ) -> Result<(), LaunchError>
+    where
+        QA: QueueAlloc<Hardware = Self::Hardware>,
+    {
+        let Meta { dt, .. } = args.meta()?;
+        let View {
+            outer,
+            n,
+            inner,
+            x_strides: [sxa, sxn, sxb],
+            y_strides: [sya, syb],
+        } = args.view()?;
+
+        macro_rules! calculate {
+            ($ty:ty) => {
+                Scheme::<$ty> {
+                    outer,
+                    n,
+                    inner,
+                    sxa,
+                    sxn,
+                    sxb,
+                    sya,
+                    syb,
+                    op: args.op,
+                    y_base: args.y_base.cast(),
+                    x_base: args.x_base.cast(),
+                }
+                .calculate()
+            };
+        }
+
+        use digit_layout::types as ty;
+        match dt {
+            ty::F16 => calculate!(f16),
+            ty::F32 => calculate!(f32),
+            ty::F64 => calculate!(f64),
+            _ => Err(type_not_support(format!("reduce does not support {dt}")))?,
+        }
+        Ok(())
+    }
+}
+
+struct Scheme<T> {
+    outer: usize,
+    n: usize,
+    inner: usize,
+    sxa: isize,
+    sxn: isize,
+    sxb: isize,
+    sya: isize,
+    syb: isize,
+    op: ReduceOp,
+    y_base: *mut T,
+    x_base: *const T,
+}
+
+unsafe impl<T> Send for Scheme<T> {}
+unsafe impl<T> Sync for Scheme<T> {}
+
+/// 规约的数据类型，统一以 f64 累加。
+trait Data: Copy {
+    fn to_f64(self) -> f64;
+    fn from_f64(val: f64) -> Self;
+}
+
+impl Data for f16 {
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self.to_f64()
+    }
+    #[inline]
+    fn from_f64(val: f64) -> Self {
+        f16::from_f64(val)
+    }
+}
+
+impl Data for f32 {
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self as _
+    }
+    #[inline]
+    fn from_f64(val: f64) -> Self {
+        val as _
+    }
+}
+
+impl Data for f64 {
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self
+    }
+    #[inline]
+    fn from_f64(val: f64) -> Self {
+        val
+    }
+}
+
+impl<T: Data> Scheme<T> {
+    fn calculate(&self) {
+        let n = self.n as isize;
+        let inner = self.inner as isize;
+        (0..(self.outer * self.inner) as isize)
+            .into_par_iter()
+            .for_each(|i| {
+                let a = i / inner;
+                let b = i % inner;
+                let x = unsafe { self.x_base.byte_offset(a * self.sxa + b * self.sxb) };
+                let vals = (0..n).map(|k| unsafe { *x.byte_offset(k * self.sxn) }.to_f64());
+                let ans = match self.op {
+                    ReduceOp::Sum => vals.sum(),
+                    ReduceOp::Mean => vals.sum::<f64>() / n as f64,
+                    ReduceOp::Max => vals.fold(f64::NEG_INFINITY, f64::max),
+                };
+                let y = unsafe { &mut *self.y_base.byte_offset(a * self.sya + b * self.syb) };
+                *y = T::from_f64(ans)
+            })
+    }
+}
@@ -0,0 +1,9 @@
+#[cfg(any(use_cpu, test))]
+pub mod common_cpu;
+#[cfg(use_cl)]
+pub mod opencl;
+
+mod args;
+pub use args::{Args, ReduceOp};
+
+crate::op_trait!(Reduce);
@@ -0,0 +1,301 @@
+use super::{
+    args::{Meta, View},
+    Args, Reduce, ReduceOp,
+};
+use crate::{
+    opencl::{ClDevice, CodeGen, KernelCache, CL2_0},
+    type_not_support, ByteOf, LaunchError, QueueAlloc,
+    SchemeDiversity::Low as LowDiversity,
+    SchemeError,
+};
+use clrt::{
+    bindings::{cl_int, cl_uint},
+    Context,
+};
+use digit_layout::{types as Ty, DigitLayout};
+use lru::LruCache;
+use std::sync::Mutex;
+
+pub struct Operator {
+    ctx: Context,
+    max_group_size: usize,
+    schemes: Mutex<LruCache<SchemeKey, KernelCache>>,
+}
+
+impl Reduce<ClDevice> for Operator {}
+
+impl crate::Operator for Operator {
+    type Hardware = ClDevice;
+    type TopoNode = ClDevice;
+    type Args = Args<ClDevice>;
+
+    fn new(node: &Self::TopoNode) -> Self {
+        let ctx = node.context().clone();
+        let max_group_size = ctx
+            .devices()
+            .iter()
+            .map(|d| d.max_group_size())
+            .min()
+            .unwrap()
+            / 2; // 直接用最大 group 可能导致资源不足
+        Self {
+            ctx,
+            max_group_size,
+            schemes: node.new_cache(LowDiversity),
+        }
+    }
+
+    fn scheme(
+        &mut self,
+        args: &Self::Args,
+        _max_workspace_size: usize,
+    ) -> Result<usize, SchemeError> {
+        let Meta { dt, .. } = args.meta()?;
+        self.cache_kernel(dt, args.op)?;
+        Ok(0)
+    }
+
+    fn launch<QA>(
+        &self,
+        args: &Self::Args,
+        _workspace: &mut [ByteOf<Self::Hardware>],
+        queue_alloc: &QA,
+    ) -> Result<(), LaunchError>
+    where
+        QA: QueueAlloc<Hardware = Self::Hardware>,
+    {
+        let Meta { dt, .. } = args.meta()?;
+        let View {
+            outer,
+            n,
+            inner,
+            x_strides: [sxa, sxn, sxb],
+            y_strides: [sya, syb],
+        } = args.view()?;
+        // 输出为空时无需发射
+        if outer * inner == 0 {
+            return Ok(());
+        }
+
+        let unit = dt.nbytes() as isize;
+        let group_size = last_power_of_two(n.clamp(1, self.max_group_size));
+
+        let key = self.cache_kernel(dt, args.op)?;
+        let mut reduce = self
+            .schemes
+            .lock()
+            .unwrap()
+            .get(&key)
+            .unwrap()
+            .take_guard("reduce")
+            .unwrap();
+
+        reduce
+            .set_arg(0, &args.y_base)
+            .set_arg(1, (sya / unit) as cl_int)
+            .set_arg(2, (syb / unit) as cl_int)
+            .set_arg(3, &args.x_base)
+            .set_arg(4, (sxa / unit) as cl_int)
+            .set_arg(5, (sxn / unit) as cl_int)
+            .set_arg(6, (sxb / unit) as cl_int)
+            .set_arg(7, n as cl_uint)
+            .set_arg(8, inner as cl_uint)
+            .launch(
+                &[0],
+                &[group_size * outer * inner],
+                &[group_size],
+                queue_alloc.queue(),
+                None,
+            );
+        Ok(())
+    }
+}
+
+impl Operator {
+    fn cache_kernel(&self, dt: DigitLayout, op: ReduceOp) -> Result<SchemeKey, SchemeError> {
+        let ty = match dt {
+            Ty::F32 => "float",
+            Ty::F16 => "half",
+            _ => {
+                return Err(type_not_support(format!(
+                    "opencl: reduce does not support {dt}"
+                )))
+            }
+        };
+        let key = SchemeKey { dt, op };
+        self.schemes.lock().unwrap().get_or_insert(key, || {
+            let src = CodeGen::new(include_str!("reduce.cl"))
+                .define("Tval", ty)
+                .define("OP", op as u8)
+                .to_string();
+            KernelCache::new(&self.ctx, &src, CL2_0)
+        });
+        Ok(key)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+struct SchemeKey {
+    dt: DigitLayout,
+    op: ReduceOp,
+}
+
+#[inline(always)]
+const fn last_power_of_two(n: usize) -> usize {
+    1 << (usize::BITS - n.leading_zeros() - 1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Args, ReduceOp};
+    use crate::{Hardware, TensorLayout};
+    use digit_layout::DigitLayout;
+
+    fn dyn_args<H: Hardware>(dt: DigitLayout, op: ReduceOp) -> Args<H> {
+        use crate::dyn_;
+        use std::ptr::{null, null_mut};
+        Args {
+            y_layout: TensorLayout::new_dyn(dt, &[dyn_(); 2], &[dyn_(); 2]),
+            y_base: null_mut(),
+            x_layout: TensorLayout::new_dyn(dt, &[dyn_(); 3], &[dyn_(); 3]),
+            x_base: null(),
+            axis: 2,
+            op,
+        }
+    }
+
+    fn args<H: Hardware>(
+        dt: DigitLayout,
+        shape: [usize; 3],
+        axis: usize,
+        op: ReduceOp,
+        y_base: *mut H::Byte,
+        x_base: *const H::Byte,
+    ) -> Args<H> {
+        let mut y_shape = shape.to_vec();
+        y_shape.remove(axis);
+        Args {
+            y_layout: TensorLayout::new_contiguous(dt, &y_shape),
+            y_base,
+            x_layout: TensorLayout::new_contiguous(dt, &shape),
+            x_base,
+            axis,
+            op,
+        }
+    }
+
+    #[test]
+    fn test_compute() {
+        use super::{super::common_cpu::Operator as RefOp, Operator};
+        use crate::{
+            common_cpu::{Cpu, ThisThread},
+            opencl::ClDevice,
+            test_utils::{Diff, ErrorCollector},
+            Operator as _,
+        };
+        use clrt::Platform;
+        use digit_layout::types as ty;
+        use rand::Rng;
+        use std::iter::zip;
+
+        let mut cpu_op = RefOp::new(&Cpu);
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let mut cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+
+                let shape = [3, 77, 300];
+                for op in [ReduceOp::Sum, ReduceOp::Max] {
+                    cpu_op.scheme(&dyn_args(ty::F64, op), 0).unwrap();
+                    cl_op.scheme(&dyn_args(ty::F32, op), 0).unwrap();
+                    // 最后一维和中间一维
+                    for axis in [2, 1] {
+                        let len = shape.iter().product::<usize>();
+                        let y_len = len / shape[axis];
+
+                        let mut x = vec![0.0f64; len];
+                        rand::rng().fill(&mut x[..]);
+                        let mut x_svm = context.malloc::<f32>(len);
+                        let mut y_svm = context.malloc::<f32>(y_len);
+
+                        let mut map = queue.map_mut(&mut x_svm, false);
+                        let ([], mem, []) = (unsafe { map.align_to_mut::<f32>() }) else {
+                            panic!()
+                        };
+                        for (dst, src) in zip(mem, &x) {
+                            *dst = *src as _;
+                        }
+                        queue.unmap(map);
+
+                        cl_op
+                            .launch(
+                                &args(
+                                    ty::F32,
+                                    shape,
+                                    axis,
+                                    op,
+                                    y_svm.as_mut_ptr().cast(),
+                                    x_svm.as_ptr().cast(),
+                                ),
+                                &mut [],
+                                &queue,
+                            )
+                            .unwrap();
+                        queue.finish();
+
+                        let mut y_ref = vec![0.0f64; y_len];
+                        cpu_op
+                            .launch(
+                                &args(
+                                    ty::F64,
+                                    shape,
+                                    axis,
+                                    op,
+                                    y_ref.as_mut_ptr().cast(),
+                                    x.as_ptr().cast(),
+                                ),
+                                &mut [],
+                                &ThisThread,
+                            )
+                            .unwrap();
+
+                        let map = queue.map(&mut y_svm);
+                        let ([], y_ans, []) = (unsafe { map.align_to::<f32>() }) else {
+                            panic!()
+                        };
+                        let mut ec = ErrorCollector::new(f32::EPSILON as f64, 1e-5);
+                        zip(&y_ref, y_ans).for_each(|(a, b)| ec.push(Diff::new(*a, *b as _)));
+                        queue.unmap(map);
+                        println!("{op:?} axis {axis}: {ec}");
+
+                        let (out, _) = ec.summary();
+                        assert_eq!(out, 0);
+                    }
+                }
+
+                // 后端未实现的类型在规划时报错
+                assert!(cl_op.scheme(&dyn_args(ty::F64, ReduceOp::Sum), 0).is_err());
+                // 输出为空时不发射
+                let x_svm = context.malloc::<f32>(1);
+                let mut y_svm = context.malloc::<f32>(1);
+                cl_op
+                    .launch(
+                        &args(
+                            ty::F32,
+                            [0, 5, 3],
+                            2,
+                            ReduceOp::Sum,
+                            y_svm.as_mut_ptr().cast(),
+                            x_svm.as_ptr().cast(),
+                        ),
+                        &mut [],
+                        &queue,
+                    )
+                    .unwrap();
+            }
+        }
+    }
+}
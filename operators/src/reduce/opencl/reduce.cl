@@ -0,0 +1,58 @@
+#define CL_TARGET_OPENCL_VERSION 200
+#pragma OPENCL EXTENSION cl_khr_fp16 : enable
+
+#ifndef Tval
+#define Tval float
+#endif
+
+// 0: sum, 1: mean, 2: max
+#ifndef OP
+#define OP 0
+#endif
+
+#if OP == 2
+#define INIT -FLT_MAX
+#define COMBINE(a, b) fmax(a, b)
+#define GROUP_REDUCE(x) work_group_reduce_max(x)
+#else
+#define INIT 0
+#define COMBINE(a, b) ((a) + (b))
+#define GROUP_REDUCE(x) work_group_reduce_add(x)
+#endif
+
+typedef unsigned int Tidx;
+
+kernel void reduce(
+    global Tval *y,
+    int const y_stride_outer,
+    int const y_stride_inner,
+    global Tval const *x,
+    int const x_stride_outer,
+    int const x_stride_n,
+    int const x_stride_inner,
+    Tidx const n,
+    Tidx const inner) {
+
+    Tidx const
+        g_idx = get_group_id(0),
+        l_idx = get_local_id(0),
+        l_len = get_local_size(0);
+
+    int const
+        outer_id = g_idx / inner,
+        inner_id = g_idx % inner;
+
+    global Tval const *x_ = x + outer_id * x_stride_outer + inner_id * x_stride_inner;
+
+    float acc = INIT;
+    for (Tidx i = l_idx; i < n; i += l_len)
+        acc = COMBINE(acc, (float) x_[(int) i * x_stride_n]);
+    acc = GROUP_REDUCE(acc);
+
+    if (l_idx == 0) {
+#if OP == 1
+        acc /= (float) n;
+#endif
+        y[outer_id * y_stride_outer + inner_id * y_stride_inner] = (Tval) acc;
+    }
+}
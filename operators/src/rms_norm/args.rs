@@ -1,5 +1,5 @@
 ﻿use crate::{
-    utils::{dim_distinct, rank_error, type_distinct},
+    utils::{dim_distinct, rank_error, row_vector, type_distinct},
     ConstPtr, Hardware, MaybeDyn, MutPtr, SchemeError, TensorLayout,
 };
 use digit_layout::DigitLayout;
@@ -36,14 +36,14 @@ impl<H: Hardware> Args<H> {
         let &[nx, dx] = x_layout.shape() else {
             return Err(rank_error("x", 2, x_layout.ndim()));
         };
-        let &[dw] = w_layout.shape() else {
-            return Err(rank_error("w", 1, w_layout.ndim()));
-        };
+        let n = dim_distinct(&[ny, nx])?;
+        // 权重可以是 [d]，也可以是以步长 0 广播到每一行的 [n, d]
+        let dw = row_vector("w", w_layout, n)?;
 
         Ok(Meta {
             dt_a: type_distinct(&[y_layout.dt(), x_layout.dt()])?,
             dt_w: w_layout.dt(),
-            n: dim_distinct(&[ny, nx])?,
+            n,
             d: dim_distinct(&[dy, dx, dw])?,
         })
     }
@@ -50,7 +50,7 @@ impl crate::Operator for Operator {
         let &[nsx, dsx] = x_layout.strides() else {
             unreachable!()
         };
-        let &[dsw] = w_layout.strides() else {
+        let &[.., dsw] = w_layout.strides() else {
             unreachable!()
         };
 
@@ -137,6 +137,7 @@ macro_rules! impl_k {
 }
 
 impl<W> Scheme<W, f16> {
+    // 平方和与 epsilon 都在 f32 中计算，f16 的平方会下溢
     impl_k!(f32);
 
     #[inline]
@@ -211,3 +212,83 @@ impl_scheme!(f16, f16);
 impl_scheme!(f32, f16);
 impl_scheme!(f32, f32);
 impl_scheme!(f64, f64);
+
+#[cfg(test)]
+mod test {
+    use super::{Args, Operator};
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        Operator as _, TensorLayout,
+    };
+    use digit_layout::types as ty;
+
+    #[test]
+    fn test_broadcast_weight() {
+        let (n, d) = (4, 32);
+        // 每一行的输入相同，广播的权重应使每一行的输出也相同
+        let x = (0..n * d)
+            .map(|i| ((i % d) as f32 * 0.3).sin())
+            .collect::<Vec<_>>();
+        let w = (0..d).map(|i| 1. + i as f32 * 0.01).collect::<Vec<_>>();
+        let layout = TensorLayout::new_contiguous(ty::F32, &[n, d]);
+        let op = Operator::new(&Cpu);
+
+        let launch = |w_layout: TensorLayout| {
+            let mut y = vec![0f32; n * d];
+            let args = Args::<Cpu> {
+                y_layout: layout.clone(),
+                y_base: y.as_mut_ptr().cast(),
+                x_layout: layout.clone(),
+                x_base: x.as_ptr().cast(),
+                w_layout,
+                w_base: w.as_ptr().cast(),
+                epsilon: 1e-5,
+            };
+            op.launch(&args, &mut [], &ThisThread).map(|()| y)
+        };
+
+        let y_ref = launch(TensorLayout::new_contiguous(ty::F32, &[d])).unwrap();
+        for w_layout in [
+            TensorLayout::new(ty::F32, &[n, d], &[0, 4]),
+            TensorLayout::new_contiguous(ty::F32, &[1, d]),
+        ] {
+            let y = launch(w_layout).unwrap();
+            assert_eq!(y, y_ref);
+            for row in y.chunks_exact(d) {
+                assert_eq!(row, &y[..d]);
+            }
+        }
+
+        // 权重逐行存储（首维步长非 0）或行数不匹配时报错
+        assert!(launch(TensorLayout::new(ty::F32, &[n, d], &[4, 4])).is_err());
+        assert!(launch(TensorLayout::new(ty::F32, &[n + 1, d], &[0, 4])).is_err());
+    }
+
+    #[test]
+    fn test_f16_small() {
+        use half::f16;
+
+        let d = 64;
+        // 2e-4 的平方低于 f16 的最小次正规数，在 f16 中累加平方和为 0
+        let x = vec![f16::from_f32(2e-4); d];
+        assert_eq!(x[0] * x[0], f16::ZERO);
+        let w = vec![f16::ONE; d];
+        let mut y = vec![f16::ZERO; d];
+        let layout = TensorLayout::new_contiguous(ty::F16, &[1, d]);
+        let args = Args::<Cpu> {
+            y_layout: layout.clone(),
+            y_base: y.as_mut_ptr().cast(),
+            x_layout: layout,
+            x_base: x.as_ptr().cast(),
+            w_layout: TensorLayout::new_contiguous(ty::F16, &[d]),
+            w_base: w.as_ptr().cast(),
+            epsilon: 1e-12,
+        };
+        Operator::new(&Cpu)
+            .launch(&args, &mut [], &ThisThread)
+            .unwrap();
+        for y in y {
+            assert!((y.to_f32() - 1.).abs() < 1e-2, "{y}");
+        }
+    }
+}
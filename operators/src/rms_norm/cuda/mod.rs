@@ -71,7 +71,7 @@ impl crate::Operator for Operator {
         let &[xns, xds] = x_layout.strides() else {
             unreachable!()
         };
-        let &[wds] = w_layout.strides() else {
+        let &[.., wds] = w_layout.strides() else {
             unreachable!()
         };
 
@@ -1,7 +1,7 @@
 use super::{args::Meta, Args, RmsNorm};
 use crate::{
-    get_static,
-    opencl::{ClDevice, CodeGen, KernelCache, CL2_0},
+    execution_failed, get_static,
+    opencl::{ClDevice, CodeGen, KernelCache, LocalArg, CL2_0},
     ByteOf, LaunchError, QueueAlloc,
     SchemeDiversity::Low as LowDiversity,
     SchemeError,
@@ -94,10 +94,11 @@ impl crate::Operator for Operator {
             .unwrap()
             .get(&key)
             .unwrap()
-            .take("rms_norm")
+            .take_guard("rms_norm")
             .unwrap();
 
         let unit = dt_a.nbytes() as isize;
+        let scratch = scratch_size(group_size);
         rms_norm
             .set_arg(0, y_base)
             .set_arg(1, (nsy / unit) as cl_int)
@@ -106,6 +107,12 @@ impl crate::Operator for Operator {
             .set_arg(4, w_base)
             .set_arg(5, epsilon)
             .set_arg(6, d as cl_uint)
+            .set_local_arg(7, scratch)
+            .map_err(|e| {
+                execution_failed(format!(
+                    "opencl: rms_norm failed to allocate {scratch} bytes of local memory ({e})"
+                ))
+            })?
             .launch(
                 &[0],
                 &[n * group_size],
@@ -114,18 +121,14 @@ impl crate::Operator for Operator {
                 None,
             );
 
-        let mut cache = self.schemes.lock().unwrap();
-        let program = cache.get(&key).unwrap();
-        program.put("rms_norm", rms_norm);
-
         Ok(())
     }
 }
 
 impl Operator {
     fn cache_kernel(&self, dt_a: DigitLayout, dt_w: DigitLayout, d: usize) -> (SchemeKey, usize) {
-        // group_size 是不大于 d 且不大于 max_group_size 的 2 的幂；
-        let group_size = last_power_of_two(d.min(self.max_group_size));
+        // 共享的归约支持任意工作组大小，不必取 2 的幂
+        let group_size = d.min(self.max_group_size);
         // 每线程可能处理多个数据
         let items_thread = d.div_ceil(group_size);
         let key = SchemeKey { dt_a, dt_w, d };
@@ -144,6 +147,7 @@ impl Operator {
                 .define("Ta", dt_a)
                 .define("Tw", dt_w)
                 .define("ITEMS_THREAD", items_thread)
+                .reduce()
                 .to_string();
             KernelCache::new(&self.ctx, &src, CL2_0)
         });
@@ -151,9 +155,10 @@ impl Operator {
     }
 }
 
-#[inline(always)]
-const fn last_power_of_two(n: usize) -> usize {
-    1 << (usize::BITS - n.leading_zeros() - 1)
+/// 归约所需的局部存储字节数，每个工作项一个 f32 部分和。
+#[inline]
+const fn scratch_size(group_size: usize) -> usize {
+    group_size * size_of::<f32>()
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
@@ -228,9 +233,9 @@ mod test {
                 let queue = context.queue();
                 let mut cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
 
-                for k in 2..=12 {
+                // 包含不是 2 的幂的 d，工作组大小随之不是 2 的幂
+                for d in (2..=12).map(|k| 1 << k).chain([3, 100, 1000, 3000]) {
                     let n = 5;
-                    let d = 1 << k;
 
                     cpu_op.scheme(&dyn_args(ty::F64, ty::F64, d), 0).unwrap();
                     cl_op.scheme(&dyn_args(ty::F32, ty::F32, d), 0).unwrap();
@@ -323,4 +328,81 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_scratch_size() {
+        use super::{scratch_size, Operator};
+        use crate::{opencl::ClDevice, Operator as _};
+        use clrt::Platform;
+        use digit_layout::types as ty;
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                let context = device.context();
+                let op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+                for d in [1, 3, 64, 1000, 4096] {
+                    let (_, group_size) = op.cache_kernel(ty::F32, ty::F32, d);
+                    // 每个工作项一个部分和
+                    assert!(group_size <= d);
+                    assert_eq!(scratch_size(group_size), group_size * size_of::<f32>());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_f16_small() {
+        use super::Operator;
+        use crate::{
+            opencl::{read_to_vec, ClDevice},
+            Operator as _,
+        };
+        use clrt::Platform;
+        use digit_layout::types as ty;
+        use half::f16;
+
+        let (n, d) = (3, 1000);
+        // 2e-4 的平方低于 f16 的最小次正规数，只有在 f32 中累加才不会下溢
+        let x = vec![f16::from_f32(2e-4); n * d];
+        let w = vec![f16::ONE; d];
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let mut cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+
+                let mut x_svm = context.malloc::<f16>(n * d);
+                let mut w_svm = context.malloc::<f16>(d);
+                let mut y_svm = context.malloc::<f16>(n * d);
+                for (svm, data) in [(&mut x_svm, &x), (&mut w_svm, &w)] {
+                    let mut map = queue.map_mut(svm, false);
+                    let ([], mem, []) = (unsafe { map.align_to_mut::<f16>() }) else {
+                        panic!()
+                    };
+                    mem.copy_from_slice(data);
+                    queue.unmap(map);
+                }
+
+                let mut args = args(
+                    ty::F16,
+                    ty::F16,
+                    n,
+                    d,
+                    y_svm.as_mut_ptr().cast(),
+                    x_svm.as_ptr().cast(),
+                    w_svm.as_ptr().cast(),
+                );
+                args.epsilon = 1e-12;
+                cl_op.scheme(&args, 0).unwrap();
+                cl_op.launch(&args, &mut [], &queue).unwrap();
+
+                for y in read_to_vec::<f16>(&mut y_svm, &queue) {
+                    assert!((y.to_f32() - 1.).abs() < 1e-2, "{y}");
+                }
+            }
+        }
+    }
 }
@@ -15,6 +15,8 @@
 
 typedef unsigned int Tidx;
 
+DEFINE_GROUP_REDUCE(group_sum, float, REDUCE_SUM)
+
 kernel void rms_norm(
     global Ta *y_,
     int const y_stride,
@@ -22,7 +24,8 @@ kernel void rms_norm(
     int const x_stride,
     global Tw const *w,
     float const epsilon,
-    Tidx const d) {
+    Tidx const d,
+    local float *scratch) {
 
     Tidx g_idx = get_group_id(0),
          l_idx = get_local_id(0),
@@ -42,7 +45,8 @@ kernel void rms_norm(
         squared += val_x[i] * val_x[i];
     }
 
-    float rms = native_rsqrt(work_group_reduce_add(squared) / d + epsilon);
+    // scratch 的长度为工作组大小
+    float rms = native_rsqrt(group_sum(scratch, squared) / d + epsilon);
 
     for (Tidx i = 0, idx = l_idx; idx < d; ++i, idx += l_len)
         y[idx] = rms * val_x[i] * val_w[i];
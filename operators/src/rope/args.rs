@@ -1,11 +1,18 @@
-﻿use crate::{
-    type_not_support,
+﻿use super::SinCosTable;
+use crate::{
+    args_not_support, rank_not_support, shape_mismatch, shape_not_support, static_from,
+    strides_not_support, type_mismatch, type_not_support,
     utils::{dim_distinct, rank_error},
-    ConstPtr, Hardware, MaybeDyn, MutPtr, SchemeError, TensorLayout,
+    ByteOf, ConstPtr, Hardware, MaybeDyn, MutPtr, SchemeError, TensorLayout,
 };
 use digit_layout::DigitLayout;
+use std::{
+    ops::{Deref, Range},
+    ptr::null,
+};
 
 pub struct Args<H: Hardware> {
+    /// 最后一维中相邻的两个元素为一个旋转对，复数交错存储的张量见 [`ArgsBuilder::complex_interleaved`]。
     pub t_layout: TensorLayout,
     pub t_base: MutPtr<H>,
     pub p_layout: TensorLayout,
@@ -15,17 +22,411 @@ pub struct Args<H: Hardware> {
     pub cos_layout: TensorLayout,
     pub cos_base: ConstPtr<H>,
     pub theta: f32,
+    /// 按头分组的 `theta`，为空时所有头使用 `theta`。
+    pub theta_groups: Vec<ThetaGroup>,
+    /// 按频率缩放旋转角，不缩放时为 [`RopeScaling::None`]。
+    pub scaling: RopeScaling,
+    /// sin/cos 表相邻两行间隔的位置数，为 1 时逐位置存储。
+    ///
+    /// 大于 1 时表只在 `step` 的整数倍位置采样，其余位置在相邻两行之间线性插值，
+    /// 用于超长上下文下缩小表的尺寸。插值误差随 `step` 与频率之积的平方增长。
+    pub table_step: usize,
+    /// 注意力汇聚的词元，默认没有，见 [`Sink`]。
+    pub sink: Sink,
+    /// 反向旋转，撤销同一组参数的正向旋转。
+    ///
+    /// 用于 KV cache 压缩：缓存中已旋转的 key 按原位置反旋转，恢复为旋转前的值。
+    pub inverse: bool,
+    /// 按词元的掩码，形状与 `p` 相同，类型为 u8 或 bool。
+    ///
+    /// 掩码为 0 的词元（例如批次中的填充）保持不变，`None` 时旋转所有词元。
+    pub mask: Option<(TensorLayout, ConstPtr<H>)>,
+}
+
+/// StreamingLLM 的注意力汇聚：每个批次开头的 `tokens` 个词元固定按位置 `pos` 旋转，
+/// 不读取位置向量中它们的位置。
+///
+/// `pos` 为 0 时这些词元不旋转。`tokens` 为 0 时不启用。
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Sink {
+    pub tokens: usize,
+    pub pos: usize,
+}
+
+/// 一组连续的头使用的 rope 底数，例如局部和全局注意力头使用不同的底数。
+#[derive(Clone, PartialEq, Debug)]
+pub struct ThetaGroup {
+    pub heads: Range<usize>,
+    pub theta: f32,
+}
+
+/// 按频率缩放旋转角，用于扩展模型的上下文长度。
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub enum RopeScaling {
+    #[default]
+    None,
+    /// Llama 3 的分段缩放。
+    ///
+    /// 波长短于 `original_ctx / high_freq_factor` 的高频不缩放，
+    /// 波长长于 `original_ctx / low_freq_factor` 的低频除以 `factor`，
+    /// 两者之间按 `original_ctx / 波长` 线性插值。
+    Llama3 {
+        factor: f32,
+        low_freq_factor: f32,
+        high_freq_factor: f32,
+        original_ctx: usize,
+    },
+}
+
+impl RopeScaling {
+    /// 缩放以弧度每位置为单位的频率。
+    pub fn scale(&self, freq: f64) -> f64 {
+        match *self {
+            Self::None => freq,
+            Self::Llama3 {
+                factor,
+                low_freq_factor,
+                high_freq_factor,
+                original_ctx,
+            } => {
+                let factor = factor as f64;
+                let low = low_freq_factor as f64;
+                let high = high_freq_factor as f64;
+                let ctx = original_ctx as f64;
+
+                let wavelen = std::f64::consts::TAU / freq;
+                if wavelen < ctx / high {
+                    freq
+                } else if wavelen > ctx / low {
+                    freq / factor
+                } else {
+                    let smooth = (ctx / wavelen - low) / (high - low);
+                    (1. - smooth) * freq / factor + smooth * freq
+                }
+            }
+        }
+    }
+
+    fn check(&self) -> Result<(), SchemeError> {
+        match *self {
+            Self::None => Ok(()),
+            Self::Llama3 {
+                factor,
+                low_freq_factor,
+                high_freq_factor,
+                original_ctx,
+            } => {
+                if factor > 0.
+                    && 0. < low_freq_factor
+                    && low_freq_factor < high_freq_factor
+                    && original_ctx > 0
+                {
+                    Ok(())
+                } else {
+                    Err(args_not_support(format!("invalid rope scaling {self:?}")))
+                }
+            }
+        }
+    }
+}
+
+/// [`Args`] 的构造器，只需提供 `t`、`p` 和 `theta`。
+///
+/// 未设置的 sin/cos 表为空表（[0, dh]，基址为空），此时由算子现场计算。
+pub struct ArgsBuilder<H: Hardware> {
+    t_layout: TensorLayout,
+    t_base: MutPtr<H>,
+    p_layout: TensorLayout,
+    p_base: ConstPtr<H>,
+    theta: f32,
+    sin_cos: Option<[(TensorLayout, ConstPtr<H>); 2]>,
+    table_step: usize,
+    sink: Sink,
+    head_major: bool,
+    inverse: bool,
+    mask: Option<(TensorLayout, ConstPtr<H>)>,
+}
+
+impl<H: Hardware> ArgsBuilder<H> {
+    /// 使用预先计算的 sin/cos 表。
+    pub fn sin_cos(
+        mut self,
+        sin_layout: TensorLayout,
+        sin_base: ConstPtr<H>,
+        cos_layout: TensorLayout,
+        cos_base: ConstPtr<H>,
+    ) -> Self {
+        self.sin_cos = Some([(sin_layout, sin_base), (cos_layout, cos_base)]);
+        self
+    }
+
+    /// 设置 sin/cos 表的采样间隔，见 [`Args::table_step`]。
+    pub fn table_step(mut self, step: usize) -> Self {
+        self.table_step = step;
+        self
+    }
+
+    /// `t` 按头优先的 [nh, nt, dh] 或 [nb, nh, nt, dh] 排列，例如注意力中转置后的张量。
+    ///
+    /// 构造时交换头和词元两个维度的形状与步长，得到 [nt, nh, dh] 的视图，不移动数据。
+    pub fn head_major(mut self) -> Self {
+        self.head_major = true;
+        self
+    }
+
+    /// 开头的 `tokens` 个词元固定按位置 `pos` 旋转，见 [`Sink`]。
+    pub fn sink(mut self, tokens: usize, pos: usize) -> Self {
+        self.sink = Sink { tokens, pos };
+        self
+    }
+
+    /// `t` 为复数交错存储的 [..., dh / 2, 2]，最后一维为 (实部, 虚部)。
+    ///
+    /// 每个复数乘以 `e^(i * pos * theta_k)`，等价于相邻实数对的旋转，因此构造时合并最后两维，
+    /// 由各后端原有的实现计算。`t` 须为 3 到 5 维；使用 [`ArgsBuilder::table`] 时须先调用本方法。
+    pub fn complex_interleaved(mut self) -> Result<Self, SchemeError> {
+        let ndim = self.t_layout.ndim();
+        if !(3..=5).contains(&ndim) {
+            return Err(rank_not_support(format!(
+                "t.ndim = {ndim}, 3 to 5 expected for complex interleaved [..., dh / 2, 2]"
+            )));
+        }
+        let shape = self.t_layout.shape();
+        let strides = self.t_layout.strides();
+        let &[.., half, two] = shape else {
+            unreachable!()
+        };
+        let &[.., s_half, s_two] = strides else {
+            unreachable!()
+        };
+        let two = *static_from(&two)?;
+        if two != 2 {
+            return Err(shape_not_support(format!(
+                "t: last dimension is {two}, 2 expected for (real, imag)"
+            )));
+        }
+        let half = *static_from(&half)?;
+        let s_half = *static_from(&s_half)?;
+        let s_two = *static_from(&s_two)?;
+        if s_half != s_two * 2 {
+            return Err(strides_not_support(format!(
+                "t: real and imag parts are not interleaved, strides {s_half} and {s_two}"
+            )));
+        }
+
+        let mut shape_ = shape[..ndim - 2].to_vec();
+        let mut strides_ = strides[..ndim - 2].to_vec();
+        shape_.push(MaybeDyn(half * 2));
+        strides_.push(MaybeDyn(s_two));
+        self.t_layout = TensorLayout::new_dyn(self.t_layout.dt(), &shape_, &strides_);
+        Ok(self)
+    }
+
+    /// 跳过掩码为 0 的词元，见 [`Args::mask`]。
+    pub fn mask(mut self, layout: TensorLayout, base: ConstPtr<H>) -> Self {
+        self.mask = Some((layout, base));
+        self
+    }
+
+    /// 反向旋转，见 [`Args::inverse`]。
+    pub fn inverse(mut self) -> Self {
+        self.inverse = true;
+        self
+    }
+
+    /// 使用 [`Rope::build_sincos`](super::Rope::build_sincos) 生成的表，`dt` 为生成表时的类型。
+    ///
+    /// 只记录表的地址，表须在 launch 期间保持有效，同一张表可以用于构造多组参数。空表等同于不设置。
+    ///
+    /// 表的 `theta` 必须与参数的 `theta` 相同，表的尺寸必须是 sin 和 cos 各 [nctx, dh]，
+    /// `dh` 取自 `t` 的最后一维。
+    pub fn table<M>(self, table: &SinCosTable<M>, dt: DigitLayout) -> Result<Self, SchemeError>
+    where
+        M: Deref<Target = [ByteOf<H>]>,
+    {
+        let &SinCosTable {
+            nctx,
+            theta,
+            ref mem,
+        } = table;
+        if nctx == 0 {
+            return Ok(self);
+        }
+        if theta != self.theta {
+            return Err(args_not_support(format!(
+                "sin/cos table built with theta {theta}, but rope uses theta {}",
+                self.theta
+            )));
+        }
+        let Some(dh) = self.t_layout.shape().last() else {
+            return Err(rank_error("t", 3, 0));
+        };
+        let dh = *static_from(dh)?;
+        let size = size_of_val(&**mem);
+        if size != 2 * nctx * dh * dt.nbytes() {
+            return Err(shape_mismatch(format!(
+                "sin/cos table of {size} bytes is not 2 x [{nctx}, {dh}] of {dt}"
+            )));
+        }
+        let layout = TensorLayout::new_contiguous(dt, &[nctx, dh]);
+        let base = mem.as_ptr();
+        let half = size / 2;
+        Ok(self.sin_cos(layout.clone(), base, layout, base.wrapping_byte_add(half)))
+    }
+
+    pub fn build(self) -> Args<H> {
+        let Self {
+            t_layout,
+            t_base,
+            p_layout,
+            p_base,
+            theta,
+            sin_cos,
+            table_step,
+            sink,
+            head_major,
+            inverse,
+            mask,
+        } = self;
+        let t_layout = if head_major {
+            swap_heads(&t_layout)
+        } else {
+            t_layout
+        };
+        let [(sin_layout, sin_base), (cos_layout, cos_base)] = sin_cos.unwrap_or_else(|| {
+            let dt = t_layout.dt();
+            let dh = *t_layout.shape().last().unwrap();
+            let empty = || TensorLayout::new_dyn(dt, &[MaybeDyn(0), dh], &[MaybeDyn(0); 2]);
+            [(empty(), null()), (empty(), null())]
+        });
+        Args {
+            t_layout,
+            t_base,
+            p_layout,
+            p_base,
+            sin_layout,
+            sin_base,
+            cos_layout,
+            cos_base,
+            theta,
+            theta_groups: Vec::new(),
+            scaling: RopeScaling::None,
+            table_step,
+            sink,
+            inverse,
+            mask,
+        }
+    }
+}
+
+/// 交换 `t` 的头和词元两个维度，少于 3 维时只有 1 个头，不需要交换。
+fn swap_heads(t_layout: &TensorLayout) -> TensorLayout {
+    let mut shape = t_layout.shape().to_vec();
+    let mut strides = t_layout.strides().to_vec();
+    let n = shape.len();
+    if n >= 3 {
+        shape.swap(n - 3, n - 2);
+        strides.swap(n - 3, n - 2);
+    }
+    TensorLayout::new_dyn(t_layout.dt(), &shape, &strides)
 }
 
 pub(super) struct Meta {
     pub dt_t: DigitLayout,
     pub dt_p: DigitLayout,
+    /// sin/cos 表的类型，可以与 `t` 不同（例如以 f16 存储表以节省空间）。
+    #[allow(dead_code)]
+    pub dt_sc: DigitLayout,
+    /// 批次数，3 维的 `t` 视作只有 1 个批次。
+    #[allow(dead_code)]
+    pub nb: MaybeDyn<usize>,
     pub nt: MaybeDyn<usize>,
     #[allow(dead_code)]
     pub dh: MaybeDyn<usize>,
 }
 
+/// 将 `t` 视作 [nb, nt, nh, dh]，`p` 视作 [nb, nt] 时的头数和步长。
+///
+/// 3 维的 `t` 和 1 维的 `p` 的批次步长为 0。
+/// 2 维的 `t` 视作时间序列 [nt, dh]，只有 1 个头。
+#[allow(dead_code)]
+pub(super) struct Strides {
+    pub nh: MaybeDyn<usize>,
+    pub t: [MaybeDyn<isize>; 4],
+    pub p: [MaybeDyn<isize>; 2],
+}
+
 impl<H: Hardware> Args<H> {
+    /// 创建 [`ArgsBuilder`]，sin/cos 表默认为空。
+    pub fn builder(
+        t_layout: TensorLayout,
+        t_base: MutPtr<H>,
+        p_layout: TensorLayout,
+        p_base: ConstPtr<H>,
+        theta: f32,
+    ) -> ArgsBuilder<H> {
+        ArgsBuilder {
+            t_layout,
+            t_base,
+            p_layout,
+            p_base,
+            theta,
+            sin_cos: None,
+            table_step: 1,
+            sink: Sink::default(),
+            head_major: false,
+            inverse: false,
+            mask: None,
+        }
+    }
+
+    /// 只旋转 KV cache 中从 `offset` 开始新追加的 token，而不是整个 cache。
+    ///
+    /// `cache_layout` 为 [nctx, nh, dh]，新 token 数由 `p_layout` 决定，增量解码时为 1。
+    pub fn kv_append(
+        cache_layout: &TensorLayout,
+        cache_base: MutPtr<H>,
+        offset: usize,
+        p_layout: TensorLayout,
+        p_base: ConstPtr<H>,
+        theta: f32,
+    ) -> Result<Self, SchemeError> {
+        let &[nctx, nh, dh] = cache_layout.shape() else {
+            return Err(rank_error("cache", 3, cache_layout.ndim()));
+        };
+        let &[np] = p_layout.shape() else {
+            return Err(rank_error("p", 1, p_layout.ndim()));
+        };
+        let &[st, sh, sd] = cache_layout.strides() else {
+            unreachable!()
+        };
+        let nctx = *static_from(&nctx)?;
+        let np = *static_from(&np)?;
+        let st = *static_from(&st)?;
+        if offset + np > nctx {
+            return Err(shape_not_support(format!(
+                "appending {np} tokens at {offset} overflows cache of {nctx}"
+            )));
+        }
+        let t_layout = TensorLayout::new_dyn(
+            cache_layout.dt(),
+            &[MaybeDyn(np), nh, dh],
+            &[MaybeDyn(st), sh, sd],
+        );
+        let t_base = cache_base.wrapping_byte_offset(offset as isize * st);
+        Ok(Self::builder(t_layout, t_base, p_layout, p_base, theta).build())
+    }
+
+    /// 旋转角的符号，反向旋转时为 -1。
+    #[allow(dead_code)]
+    pub(super) fn sign(&self) -> f32 {
+        if self.inverse {
+            -1.
+        } else {
+            1.
+        }
+    }
+
     pub(super) fn meta(&self) -> Result<Meta, SchemeError> {
         let Self {
             t_layout,
@@ -35,13 +436,14 @@ impl<H: Hardware> Args<H> {
             ..
         } = self;
 
-        let &[nt, _, dh] = t_layout.shape() else {
-            return Err(rank_error("t", 3, t_layout.ndim()));
+        let (nb, nt, dh, nbp, np) = match (t_layout.shape(), p_layout.shape()) {
+            (&[nt, dh], &[np]) | (&[nt, _, dh], &[np]) => (MaybeDyn(1), nt, dh, MaybeDyn(1), np),
+            (&[nb, nt, _, dh], &[nbp, np]) => (nb, nt, dh, nbp, np),
+            (&[_, _], _) | (&[_, _, _], _) => return Err(rank_error("p", 1, p_layout.ndim())),
+            (&[_, _, _, _], _) => return Err(rank_error("p", 2, p_layout.ndim())),
+            _ => return Err(rank_error("t", 3, t_layout.ndim())),
         };
-        let &[np] = p_layout.shape() else {
-            return Err(rank_error("p", 1, p_layout.ndim()));
-        };
-        let &[_, dh_sin] = sin_layout.shape() else {
+        let &[nctx, dh_sin] = sin_layout.shape() else {
             return Err(rank_error("sin", 2, sin_layout.ndim()));
         };
         let &[_, dh_cos] = cos_layout.shape() else {
@@ -50,24 +452,249 @@ impl<H: Hardware> Args<H> {
 
         let dt_t = t_layout.dt();
         let dt_p = p_layout.dt();
-        use digit_layout::LayoutContent::{Real, Unsigned};
+        use digit_layout::{types as ty, LayoutContent::Real};
         // tokens must be floating-point numbers
-        if !matches!(dt_t.decode(), Real { exponent: 1.., .. },) {
+        if !matches!(dt_t, ty::F16 | ty::F32 | ty::F64) {
             return Err(type_not_support(format!(
-                "data type {dt_t} is not supported, must be floating-point numbers",
+                "data type {dt_t} is not supported, must be f16, f32 or f64",
             )));
         }
-        // positions must be unsigned integers
-        if !matches!(dt_p.decode(), Unsigned { .. }) {
+        // positions must be 32/64-bit integers, negative positions are left unrotated
+        if !matches!(dt_p, ty::U32 | ty::U64 | ty::I32 | ty::I64) {
             return Err(type_not_support(format!(
-                "data type {dt_p} is not supported, must be unsigned integers"
+                "data type {dt_p} is not supported, must be u32, u64, i32 or i64"
             )));
         }
+        self.scaling.check()?;
+        // every token of every head is written, so neither dimension may be broadcast
+        let Strides {
+            nh,
+            t: [_, st, sh, _],
+            ..
+        } = self.strides();
+        for (name, len, stride) in [("token", nt, st), ("head", nh, sh)] {
+            if let (Some(&len), Some(&0)) = (len.get_static(), stride.get_static()) {
+                if len > 1 {
+                    return Err(strides_not_support(format!(
+                        "t: {len} {name}s overlap with stride 0"
+                    )));
+                }
+            }
+        }
+        // sin and cos tables must share a floating-point type, which may differ from tokens
+        let dt_sc = sin_layout.dt();
+        if cos_layout.dt() != dt_sc {
+            return Err(type_mismatch(format!(
+                "sin: {dt_sc}, cos: {}",
+                cos_layout.dt()
+            )));
+        }
+        if !matches!(dt_sc.decode(), Real { exponent: 1.., .. }) {
+            return Err(type_not_support(format!(
+                "data type {dt_sc} is not supported for sin/cos table, must be floating-point numbers"
+            )));
+        }
+        // a precomputed table already fixes the frequencies of every head
+        if nctx.get_static().is_some_and(|&n| n > 0)
+            && (!self.theta_groups.is_empty() || self.scaling != RopeScaling::None)
+        {
+            return Err(args_not_support(
+                "sin/cos table can not be combined with theta groups or frequency scaling",
+            ));
+        }
+        // interpolation needs at least one sampled interval
+        match self.table_step {
+            0 => return Err(args_not_support("sin/cos table step must be positive")),
+            1 => {}
+            step if nctx.get_static().is_some_and(|&n| n < 2) => {
+                return Err(args_not_support(format!(
+                    "sin/cos table step {step} requires a table of at least 2 rows"
+                )))
+            }
+            _ => {}
+        }
+        // the mask has one byte per token and the same shape as positions
+        let (mut nbs, mut nts) = (vec![nb, nbp], vec![nt, np]);
+        if let Some((mask_layout, _)) = &self.mask {
+            let dt_m = mask_layout.dt();
+            if !matches!(dt_m, ty::U8 | ty::Bool) {
+                return Err(type_not_support(format!(
+                    "data type {dt_m} is not supported for mask, must be u8 or bool"
+                )));
+            }
+            match (mask_layout.shape(), p_layout.shape()) {
+                (&[nm], &[_]) => nts.push(nm),
+                (&[nbm, nm], &[_, _]) => {
+                    nbs.push(nbm);
+                    nts.push(nm)
+                }
+                _ => return Err(rank_error("mask", p_layout.ndim(), mask_layout.ndim())),
+            }
+        }
+        // a rotation needs at least one head and one pair of dimensions
+        let dh = dim_distinct(&[dh, dh_sin, dh_cos])?;
+        for (name, len) in [("dh", dh), ("nh", nh)] {
+            if len.get_static() == Some(&0) {
+                return Err(shape_not_support(format!("t: {name} must be positive")));
+            }
+        }
         Ok(Meta {
             dt_t,
             dt_p,
-            nt: dim_distinct(&[nt, np])?,
-            dh: dim_distinct(&[dh, dh_sin, dh_cos])?,
+            dt_sc,
+            nb: dim_distinct(&nbs)?,
+            nt: dim_distinct(&nts)?,
+            dh,
         })
     }
+
+    #[allow(dead_code)]
+    pub(super) fn strides(&self) -> Strides {
+        let zero = MaybeDyn(0);
+        match (self.t_layout.strides(), self.p_layout.strides()) {
+            (&[st, sd], &[sp]) => Strides {
+                nh: MaybeDyn(1),
+                t: [zero, st, zero, sd],
+                p: [zero, sp],
+            },
+            (&[st, sh, sd], &[sp]) => Strides {
+                nh: self.t_layout.shape()[1],
+                t: [zero, st, sh, sd],
+                p: [zero, sp],
+            },
+            (&[sb, st, sh, sd], &[spb, sp]) => Strides {
+                nh: self.t_layout.shape()[2],
+                t: [sb, st, sh, sd],
+                p: [spb, sp],
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// 掩码的批次和词元步长，没有掩码时返回 `None`。
+    #[allow(dead_code)]
+    pub(super) fn mask_strides(&self) -> Option<[MaybeDyn<isize>; 2]> {
+        let (layout, _) = self.mask.as_ref()?;
+        Some(match layout.strides() {
+            &[sm] => [MaybeDyn(0), sm],
+            &[smb, sm] => [smb, sm],
+            _ => unreachable!(),
+        })
+    }
+
+    /// 各组头的范围和 `theta`，按头的顺序排列。
+    ///
+    /// 分组必须恰好划分 `nh` 个头；没有分组时返回覆盖全部头的一组。
+    #[allow(dead_code)]
+    pub(super) fn theta_groups(&self, nh: usize) -> Result<Vec<(Range<usize>, f32)>, SchemeError> {
+        if self.theta_groups.is_empty() {
+            return Ok(vec![(0..nh, self.theta)]);
+        }
+        let mut groups = self
+            .theta_groups
+            .iter()
+            .map(|g| (g.heads.clone(), g.theta))
+            .collect::<Vec<_>>();
+        groups.sort_unstable_by_key(|(heads, _)| heads.start);
+        let mut end = 0;
+        for (heads, _) in &groups {
+            if heads.start != end || heads.is_empty() {
+                return Err(args_not_support(format!(
+                    "theta groups must partition {nh} heads, found {heads:?} after {end}"
+                )));
+            }
+            end = heads.end
+        }
+        if end != nh {
+            return Err(args_not_support(format!(
+                "theta groups must partition {nh} heads, covered {end}"
+            )));
+        }
+        Ok(groups)
+    }
+
+    /// 检查输出张量与 `t` 逻辑形状相同，返回按 [`Strides`] 中 `t` 的约定展开的输出步长。
+    #[allow(dead_code)]
+    pub(super) fn dst_strides(
+        &self,
+        dst: &TensorLayout,
+    ) -> Result<[MaybeDyn<isize>; 4], SchemeError> {
+        let t = &self.t_layout;
+        if dst.dt() != t.dt() {
+            return Err(type_mismatch(format!("t: {}, dst: {}", t.dt(), dst.dt())));
+        }
+        if dst.ndim() != t.ndim() {
+            return Err(rank_error("dst", t.ndim(), dst.ndim()));
+        }
+        for (&a, &b) in t.shape().iter().zip(dst.shape()) {
+            dim_distinct(&[a, b])?;
+        }
+        let zero = MaybeDyn(0);
+        Ok(match dst.strides() {
+            &[st, sd] => [zero, st, zero, sd],
+            &[st, sh, sd] => [zero, st, sh, sd],
+            &[sb, st, sh, sd] => [sb, st, sh, sd],
+            _ => unreachable!(),
+        })
+    }
+
+    /// 将 2 维时间序列 `t` 的通道分为 `groups` 组，每组独立旋转。
+    ///
+    /// `t` 由 [nt, c] 变为 [nt, groups, c / groups]，等价于 `groups` 个头。
+    pub fn group_channels(&mut self, groups: usize) -> Result<(), SchemeError> {
+        let &[nt, c] = self.t_layout.shape() else {
+            return Err(rank_error("t", 2, self.t_layout.ndim()));
+        };
+        let &[st, sd] = self.t_layout.strides() else {
+            unreachable!()
+        };
+        let c = *static_from(&c)?;
+        let nt = *static_from(&nt)?;
+        let st = *static_from(&st)?;
+        let sd = *static_from(&sd)?;
+        if groups == 0 || c % groups != 0 || (c / groups) % 2 != 0 {
+            return Err(shape_not_support(format!(
+                "{c} channels can not be split into {groups} groups of rotary pairs"
+            )));
+        }
+        let dh = c / groups;
+        self.t_layout = TensorLayout::new(
+            self.t_layout.dt(),
+            &[nt, groups, dh],
+            &[st, sd * dh as isize, sd],
+        );
+        Ok(())
+    }
+}
+
+#[test]
+fn test_llama3_scaling() {
+    // Llama 3.1 的配置，参考值由原始实现在 f64 下计算
+    let scaling = RopeScaling::Llama3 {
+        factor: 8.,
+        low_freq_factor: 1.,
+        high_freq_factor: 4.,
+        original_ctx: 8192,
+    };
+    let freq = |k: i32| 5e5f64.powf(-2. * k as f64 / 128.);
+    let close = |a: f64, b: f64| (a - b).abs() <= b * 1e-12;
+
+    // 最高频不缩放
+    assert_eq!(scaling.scale(freq(0)), 1.);
+    assert!(close(scaling.scale(freq(28)), 0.003211445994752591));
+    // 过渡区间
+    assert!(close(scaling.scale(freq(31)), 0.0008567514129196321));
+    // 最低频除以 factor
+    assert!(close(scaling.scale(freq(63)), 3.068925988914511e-7));
+
+    assert_eq!(RopeScaling::None.scale(0.5), 0.5);
+    assert!(scaling.check().is_ok());
+    assert!(RopeScaling::Llama3 {
+        factor: 8.,
+        low_freq_factor: 4.,
+        high_freq_factor: 1.,
+        original_ctx: 8192,
+    }
+    .check()
+    .is_err());
 }
@@ -1,27 +1,29 @@
-use super::{args::Meta, fill_pos, Args, Rope, Seq, SinCosTable};
+﻿use super::{
+    args::{Meta, Strides},
+    fill_pos, pos_size, Args, PosTy, Rope, RopeScaling, Seq, SinCosTable, Sink,
+};
 use crate::{
-    common_cpu::Cpu, get_static, strides_not_support, ByteOf, LaunchError, QueueAlloc, SchemeError,
-    Unsigned,
+    args_not_support, common_cpu::Cpu, get_static, shape_not_support, strides_not_support,
+    type_not_support, utils::debug_check_tensor, ByteOf, LaunchError, MutPtr, QueueAlloc,
+    SchemeError, SchemePlan, TensorLayout, Unsigned,
 };
 use digit_layout::{types as ty, DigitLayout};
 use half::f16;
+use std::ops::{Neg, Range};
 
 pub struct Operator;
 
 impl Rope<Cpu> for Operator {
     fn build_sincos<QA>(
-        _dt: DigitLayout,
-        _nctx: usize,
-        _dh: usize,
+        dt: DigitLayout,
+        nctx: usize,
+        dh: usize,
         queue_alloc: &QA,
     ) -> SinCosTable<QA::DevMem>
     where
         QA: QueueAlloc<Hardware = Self::Hardware>,
     {
-        SinCosTable {
-            nctx: 0,
-            mem: queue_alloc.alloc(0),
-        }
+        Self::try_build_sincos(dt, nctx, dh, 1e4, queue_alloc).unwrap()
     }
 
     fn build_pos<I, QA>(
@@ -34,13 +36,7 @@ impl Rope<Cpu> for Operator {
         I: IntoIterator<Item = Seq>,
         QA: QueueAlloc<Hardware = Self::Hardware>,
     {
-        let mut blob = queue_alloc.alloc(dt.nbytes() * nt);
-        match dt {
-            ty::U32 => fill_pos(blob.as_mut_ptr().cast::<u32>(), nt, iter),
-            ty::U64 => fill_pos(blob.as_mut_ptr().cast::<u64>(), nt, iter),
-            _ => todo!(),
-        }
-        blob
+        Self::try_build_pos(dt, nt, iter, queue_alloc).unwrap()
     }
 }
 
@@ -53,13 +49,30 @@ impl crate::Operator for Operator {
         Self
     }
 
+    #[inline]
+    fn supported_dtypes() -> &'static [DigitLayout] {
+        &[ty::F16, ty::F32, ty::F64]
+    }
+
     fn scheme(
         &mut self,
         args: &Self::Args,
-        _max_workspace_size: usize,
+        max_workspace_size: usize,
     ) -> Result<usize, SchemeError> {
+        crate::Operator::plan(self, args, max_workspace_size).map(|plan| plan.workspace_size)
+    }
+
+    fn plan(
+        &mut self,
+        args: &Self::Args,
+        _max_workspace_size: usize,
+    ) -> Result<SchemePlan, SchemeError> {
         let _meta = args.meta()?;
-        Ok(0)
+        Ok(SchemePlan {
+            workspace_size: 0,
+            align: 1,
+            algorithm: "cpu",
+        })
     }
 
     fn launch<QA>(
@@ -71,60 +84,326 @@ impl crate::Operator for Operator {
     where
         QA: QueueAlloc<Hardware = Self::Hardware>,
     {
-        let Meta { dt_t, dt_p, nt, .. } = args.meta()?;
-        let Args {
-            t_layout,
-            t_base,
-            p_layout,
-            p_base,
-            theta,
-            ..
-        } = args;
-        let &[_, nh, dh] = t_layout.shape() else {
-            unreachable!()
-        };
-        let &[st, sh, sd] = t_layout.strides() else {
-            unreachable!()
+        self.launch_impl(args, None)
+    }
+}
+
+impl Operator {
+    /// [`Rope::build_sincos`] 的可失败版本，可指定 `theta`，类型不支持时返回错误。
+    pub fn try_build_sincos<QA>(
+        dt: DigitLayout,
+        nctx: usize,
+        dh: usize,
+        theta: f32,
+        queue_alloc: &QA,
+    ) -> Result<SinCosTable<QA::DevMem>, SchemeError>
+    where
+        QA: QueueAlloc<Hardware = Cpu>,
+    {
+        fn build<T, QA>(
+            nctx: usize,
+            dh: usize,
+            theta: f64,
+            queue_alloc: &QA,
+            from_f64: fn(f64) -> T,
+        ) -> Result<QA::DevMem, SchemeError>
+        where
+            QA: QueueAlloc<Hardware = Cpu>,
+        {
+            let len = nctx
+                .checked_mul(dh)
+                .filter(|len| len.checked_mul(2 * size_of::<T>()).is_some())
+                .ok_or_else(|| {
+                    shape_not_support(format!("sin/cos table [{nctx}, {dh}] overflow"))
+                })?;
+            let mut blob = queue_alloc.alloc(2 * len * size_of::<T>());
+            let ptr = blob.as_mut_ptr().cast::<T>();
+            let (sin, cos) =
+                unsafe { std::slice::from_raw_parts_mut(ptr, 2 * len) }.split_at_mut(len);
+            for (i, (sin, cos)) in sin
+                .chunks_exact_mut(2)
+                .zip(cos.chunks_exact_mut(2))
+                .enumerate()
+            {
+                let (p, k) = (i * 2 / dh, (i * 2 % dh / 2) as f64);
+                let (sin_, cos_) = (p as f64 / theta.powf(k / (dh / 2) as f64)).sin_cos();
+                sin.fill(from_f64(sin_));
+                cos.fill(from_f64(cos_));
+            }
+            Ok(blob)
+        }
+
+        if dh % 2 != 0 {
+            return Err(shape_not_support(format!("sin/cos table with odd dh {dh}")));
+        }
+        if !(theta.is_finite() && theta > 0.) {
+            return Err(args_not_support(format!(
+                "sin/cos table with theta {theta}"
+            )));
+        }
+        let theta_ = theta as f64;
+        let mem = match dt {
+            ty::F16 => build(nctx, dh, theta_, queue_alloc, f16::from_f64)?,
+            ty::F32 => build(nctx, dh, theta_, queue_alloc, |x| x as f32)?,
+            ty::F64 => build(nctx, dh, theta_, queue_alloc, |x| x)?,
+            _ => return Err(type_not_support(format!("sin/cos table of {dt}"))),
         };
-        let &[sp] = p_layout.strides() else {
-            unreachable!()
+        Ok(SinCosTable { nctx, theta, mem })
+    }
+
+    /// [`Rope::build_pos`] 的可失败版本，`nt` 过大或类型不支持时返回错误。
+    pub fn try_build_pos<I, QA>(
+        dt: DigitLayout,
+        nt: usize,
+        iter: I,
+        queue_alloc: &QA,
+    ) -> Result<QA::DevMem, SchemeError>
+    where
+        I: IntoIterator<Item = Seq>,
+        QA: QueueAlloc<Hardware = Cpu>,
+    {
+        fn build<T, I, QA>(nt: usize, iter: I, queue_alloc: &QA) -> Result<QA::DevMem, SchemeError>
+        where
+            T: PosTy,
+            I: IntoIterator<Item = Seq>,
+            QA: QueueAlloc<Hardware = Cpu>,
+        {
+            let mut blob = queue_alloc.alloc(pos_size::<T>(nt)?);
+            fill_pos(
+                unsafe { std::slice::from_raw_parts_mut(blob.as_mut_ptr().cast::<T>(), nt) },
+                iter,
+            );
+            Ok(blob)
+        }
+
+        match dt {
+            ty::U32 => build::<u32, _, _>(nt, iter, queue_alloc),
+            ty::U64 => build::<u64, _, _>(nt, iter, queue_alloc),
+            ty::I32 => build::<i32, _, _>(nt, iter, queue_alloc),
+            ty::I64 => build::<i64, _, _>(nt, iter, queue_alloc),
+            _ => Err(type_not_support(format!("position type {dt}"))),
+        }
+    }
+
+    /// 旋转 `t`，结果按 `dst_layout` 写入 `dst_base`，`t` 保持不变。
+    ///
+    /// `dst_layout` 必须与 `t` 的逻辑形状相同，步长任意（例如交换头和序列维度），
+    /// 从而将 rope 与随后的重排融合为一次计算。
+    pub fn launch_into(
+        &self,
+        args: &Args<Cpu>,
+        dst_layout: &TensorLayout,
+        dst_base: MutPtr<Cpu>,
+    ) -> Result<(), LaunchError> {
+        self.launch_impl(args, Some((dst_layout, dst_base)))
+    }
+
+    fn launch_impl(
+        &self,
+        args: &Args<Cpu>,
+        dst: Option<(&TensorLayout, MutPtr<Cpu>)>,
+    ) -> Result<(), LaunchError> {
+        let Meta {
+            dt_t,
+            dt_p,
+            dt_sc,
+            nb,
+            nt,
+            dh,
+        } = args.meta()?;
+        let Args { t_base, p_base, .. } = args;
+        let Strides {
+            nh,
+            t: [sb, st, sh, sd],
+            p: [spb, sp],
+        } = args.strides();
+        let ([db, dt, dh_, dd], d_base) = match dst {
+            Some((layout, base)) => (args.dst_strides(layout)?, base),
+            None => ([sb, st, sh, sd], *t_base),
         };
 
         get_static! {
-            nt nh dh
-            st sh sd
-            sp
+            nb nt nh dh
+            sb st sh sd
+            db dt dh_ dd
+            spb sp
+        }
+        // 没有词元时无事可做
+        if nb == 0 || nt == 0 {
+            return Ok(());
         }
-        if sd != dt_t.nbytes() as isize {
-            return Err(strides_not_support("").into());
+        debug_check_tensor("t", &args.t_layout, args.t_base);
+        debug_check_tensor("p", &args.p_layout, args.p_base);
+        if let Some((layout, base)) = dst {
+            debug_check_tensor("dst", layout, base);
         }
+        let unit = dt_t.nbytes() as isize;
+        if sd != unit || dd != unit {
+            Err(strides_not_support(""))?;
+        }
+        let table = Table::new(args, dt_sc)?;
+        let groups = args.theta_groups(nh)?;
+        let mask = match (&args.mask, args.mask_strides()) {
+            (Some((layout, base)), Some([smb, sm])) => {
+                get_static! {
+                    smb sm
+                }
+                debug_check_tensor("mask", layout, *base);
+                Some((base.cast::<u8>(), [smb, sm]))
+            }
+            _ => None,
+        };
 
         macro_rules! calculate {
-            ($t:ty, $p:ty) => {
-                Scheme::<$t, $p> {
+            ($t:ty, $p:ty) => {{
+                let scheme = Scheme::<$t, $p> {
+                    nb,
                     nt,
-                    nh,
                     dh,
+                    sb,
                     st,
                     sh,
+                    spb,
                     sp,
-                    theta: *theta,
+                    d: [db, dt, dh_],
+                    groups: groups.clone(),
+                    scaling: args.scaling,
+                    sink: args.sink,
+                    inverse: args.inverse,
+                    mask,
+                    table,
                     t_base: t_base.cast(),
+                    d_base: d_base.cast(),
                     p_base: p_base.cast(),
-                }
-                .calculate()
-            };
+                };
+                scheme.check_table()?;
+                scheme.calculate()
+            }};
         }
 
         use digit_layout::types as ty;
         match (dt_t, dt_p) {
             (ty::F16, ty::U32) => calculate!(f16, u32),
             (ty::F16, ty::U64) => calculate!(f16, u64),
+            (ty::F16, ty::I32) => calculate!(f16, i32),
+            (ty::F16, ty::I64) => calculate!(f16, i64),
             (ty::F32, ty::U32) => calculate!(f32, u32),
             (ty::F32, ty::U64) => calculate!(f32, u64),
+            (ty::F32, ty::I32) => calculate!(f32, i32),
+            (ty::F32, ty::I64) => calculate!(f32, i64),
             (ty::F64, ty::U32) => calculate!(f64, u32),
             (ty::F64, ty::U64) => calculate!(f64, u64),
-            _ => todo!(),
+            (ty::F64, ty::I32) => calculate!(f64, i32),
+            (ty::F64, ty::I64) => calculate!(f64, i64),
+            _ => Err(type_not_support(format!(
+                "cpu: rope does not support {dt_t} with pos type {dt_p}"
+            )))?,
+        }
+        Ok(())
+    }
+
+    /// 试运行：不旋转 `t`，而是把每个 (位置, 频率) 对应的旋转角写入 `angles`。
+    ///
+    /// `angles` 按 `[nb, nt, dh / 2]` 连续存储，用于区分频率计算和旋转计算中的错误。
+    /// 支持 `scaling`、`sink` 和 `inverse`；分组 `theta`、sin/cos 表和掩码不由旋转角描述，设置时返回错误。
+    pub fn dry_run(&self, args: &Args<Cpu>, angles: &mut [f64]) -> Result<(), LaunchError> {
+        let Meta {
+            dt_p, nb, nt, dh, ..
+        } = args.meta()?;
+        if !args.theta_groups.is_empty() {
+            Err(args_not_support("rope: dry run with theta groups"))?
+        }
+        if !args.sin_base.is_null() || !args.cos_base.is_null() {
+            Err(args_not_support("rope: dry run with sin/cos table"))?
+        }
+        if args.mask.is_some() {
+            Err(args_not_support("rope: dry run with mask"))?
+        }
+        let Strides { p: [spb, sp], .. } = args.strides();
+
+        get_static! {
+            nb nt dh
+            spb sp
+        }
+        let dh = dh / 2;
+        if angles.len() != nb * nt * dh {
+            Err(shape_not_support("rope: angles buffer mismatch"))?;
+        }
+
+        fn fill<P: Position<f64> + PosTy + Copy>(
+            p_base: *const P,
+            [nt, dh]: [usize; 2],
+            [spb, sp]: [isize; 2],
+            theta: f32,
+            scaling: RopeScaling,
+            sink: Sink,
+            angles: &mut [f64],
+        ) {
+            for (i, angles) in angles.chunks_exact_mut(dh).enumerate() {
+                let (b, i) = ((i / nt) as isize, (i % nt) as isize);
+                let p = if (i as usize) < sink.tokens {
+                    P::from_usize(sink.pos)
+                } else {
+                    unsafe { *p_base.byte_offset(b * spb + i * sp) }
+                };
+                for (k, angle) in angles.iter_mut().enumerate() {
+                    *angle = match scaling {
+                        RopeScaling::None => p.freq(k as _, dh as _, theta),
+                        _ => p.row().map_or(0., |row| {
+                            row as f64 * scaling.scale(inv_freq(k as _, dh as _, theta))
+                        }),
+                    }
+                }
+            }
+        }
+
+        let p_base = args.p_base;
+        let shape = [nt, dh];
+        let strides = [spb, sp];
+        let theta = args.theta;
+        let scaling = args.scaling;
+        let sink = args.sink;
+        match dt_p {
+            ty::U32 => fill(
+                p_base.cast::<u32>(),
+                shape,
+                strides,
+                theta,
+                scaling,
+                sink,
+                angles,
+            ),
+            ty::U64 => fill(
+                p_base.cast::<u64>(),
+                shape,
+                strides,
+                theta,
+                scaling,
+                sink,
+                angles,
+            ),
+            ty::I32 => fill(
+                p_base.cast::<i32>(),
+                shape,
+                strides,
+                theta,
+                scaling,
+                sink,
+                angles,
+            ),
+            ty::I64 => fill(
+                p_base.cast::<i64>(),
+                shape,
+                strides,
+                theta,
+                scaling,
+                sink,
+                angles,
+            ),
+            _ => Err(type_not_support(""))?,
+        }
+        if args.inverse {
+            angles.iter_mut().for_each(|angle| *angle = -*angle)
         }
         Ok(())
     }
@@ -133,24 +412,153 @@ impl crate::Operator for Operator {
 /// Calculate scheme.
 /// A for activation, P for position.
 struct Scheme<A, P> {
+    nb: usize,
     nt: usize,
-    nh: usize,
     dh: usize,
+    sb: isize,
     st: isize,
     sh: isize,
+    spb: isize,
     sp: isize,
-    theta: f32,
-    t_base: *mut A,
+    /// 输出的批次、序列和头步长，原地计算时与 `t` 相同。
+    d: [isize; 3],
+    /// 各组头的范围和 `theta`。
+    groups: Vec<(Range<usize>, f32)>,
+    scaling: RopeScaling,
+    sink: Sink,
+    /// 反向旋转，sin 取反。
+    inverse: bool,
+    /// 掩码的基址和批次、词元步长，掩码为 0 的词元不旋转。
+    mask: Option<(*const u8, [isize; 2])>,
+    t_base: *const A,
+    d_base: *mut A,
     p_base: *const P,
+    table: Option<Table>,
+}
+
+/// 预先计算的 sin/cos 表，按 [nctx, dh] 存储，每个旋转对的两项相同。
+///
+/// 表的类型可以与激活值不同，读取时转换为计算类型。
+/// 第 `i` 行对应位置 `i * step`，位于两行之间的位置线性插值。
+#[derive(Clone, Copy)]
+struct Table {
+    nctx: usize,
+    step: usize,
+    sin: *const u8,
+    cos: *const u8,
+    /// sin 和 cos 表的行、列步长。
+    strides: [[isize; 2]; 2],
+    load: unsafe fn(*const u8) -> f64,
+}
+
+impl Table {
+    /// 没有提供表或表为空时返回 `None`，此时现场计算 sin/cos。
+    fn new(args: &Args<Cpu>, dt: DigitLayout) -> Result<Option<Self>, LaunchError> {
+        let Args {
+            sin_layout,
+            sin_base,
+            cos_layout,
+            cos_base,
+            table_step: step,
+            ..
+        } = args;
+        if sin_base.is_null() || cos_base.is_null() {
+            return Ok(None);
+        }
+        let &[nctx, _] = sin_layout.shape() else {
+            unreachable!()
+        };
+        let &[nctx_, _] = cos_layout.shape() else {
+            unreachable!()
+        };
+        let &[ssn, ssd] = sin_layout.strides() else {
+            unreachable!()
+        };
+        let &[scn, scd] = cos_layout.strides() else {
+            unreachable!()
+        };
+
+        get_static! {
+            nctx nctx_
+            ssn ssd
+            scn scd
+        }
+        if nctx == 0 {
+            return Ok(None);
+        }
+        if nctx_ != nctx {
+            Err(shape_not_support(
+                "rope: sin and cos tables differ in length",
+            ))?;
+        }
+        debug_check_tensor("sin", sin_layout, *sin_base);
+        debug_check_tensor("cos", cos_layout, *cos_base);
+
+        unsafe fn load<T: Copy + Into<f64>>(ptr: *const u8) -> f64 {
+            ptr.cast::<T>().read_unaligned().into()
+        }
+        let load = match dt {
+            ty::F16 => load::<f16>,
+            ty::F32 => load::<f32>,
+            ty::F64 => load::<f64>,
+            _ => Err(type_not_support(format!("rope: sin/cos table of {dt}")))?,
+        };
+        Ok(Some(Self {
+            nctx,
+            step,
+            sin: sin_base.cast(),
+            cos: cos_base.cast(),
+            strides: [[ssn, ssd], [scn, scd]],
+            load,
+        }))
+    }
+
+    /// 表能覆盖的最大位置。
+    #[inline]
+    fn max_pos(&self) -> usize {
+        (self.nctx - 1) * self.step
+    }
+
+    /// 读取位置 `pos` 第 `k` 个旋转对的 sin 和 cos。
+    #[inline]
+    fn get(&self, pos: usize, k: isize) -> (f64, f64) {
+        if self.step == 1 {
+            return self.row(pos, k);
+        }
+        let (row, rem) = (pos / self.step, pos % self.step);
+        if rem == 0 {
+            return self.row(row, k);
+        }
+        let (sin0, cos0) = self.row(row, k);
+        let (sin1, cos1) = self.row(row + 1, k);
+        let w = rem as f64 / self.step as f64;
+        (sin0 + (sin1 - sin0) * w, cos0 + (cos1 - cos0) * w)
+    }
+
+    /// 读取表中第 `row` 行第 `k` 个旋转对的 sin 和 cos。
+    #[inline]
+    fn row(&self, row: usize, k: isize) -> (f64, f64) {
+        assert!(row < self.nctx, "row {row} out of sin/cos table");
+        let row = row as isize;
+        let [[ssn, ssd], [scn, scd]] = self.strides;
+        unsafe {
+            (
+                (self.load)(self.sin.byte_offset(row * ssn + 2 * k * ssd)),
+                (self.load)(self.cos.byte_offset(row * scn + 2 * k * scd)),
+            )
+        }
+    }
 }
 
 unsafe impl<A, P> Send for Scheme<A, P> {}
 unsafe impl<A, P> Sync for Scheme<A, P> {}
 
 /// 激活值。
-trait Activation: Sized {
+trait Activation: Sized + Copy {
     /// 激活值类型决定计算类型。
-    type Calculation;
+    type Calculation: Copy + Neg<Output = Self::Calculation>;
+    /// 将 sin/cos 表中读出的值转换为计算类型。
+    fn calculation(val: f64) -> Self::Calculation;
     /// 计算流程。
     fn calculate(pair: &mut [Self; 2], sin: Self::Calculation, cos: Self::Calculation);
 }
@@ -164,6 +572,10 @@ macro_rules! multilpy {
 impl Activation for f16 {
     type Calculation = f32;
     #[inline]
+    fn calculation(val: f64) -> f32 {
+        val as _
+    }
+    #[inline]
     fn calculate(pair: &mut [Self; 2], sin: Self::Calculation, cos: Self::Calculation) {
         let [a, b] = pair.map(f16::to_f32);
         *pair = multilpy!(a, b, sin, cos).map(f16::from_f32);
@@ -172,6 +584,10 @@ impl Activation for f16 {
 impl Activation for f32 {
     type Calculation = Self;
     #[inline]
+    fn calculation(val: f64) -> Self {
+        val as _
+    }
+    #[inline]
     fn calculate(pair: &mut [Self; 2], sin: Self::Calculation, cos: Self::Calculation) {
         let &mut [a, b] = pair;
         *pair = multilpy!(a, b, sin, cos)
@@ -180,22 +596,43 @@ impl Activation for f32 {
 impl Activation for f64 {
     type Calculation = Self;
     #[inline]
+    fn calculation(val: f64) -> Self {
+        val as _
+    }
+    #[inline]
     fn calculate(pair: &mut [Self; 2], sin: Self::Calculation, cos: Self::Calculation) {
         let &mut [a, b] = pair;
         *pair = multilpy!(a, b, sin, cos)
     }
 }
 
+/// 第 `k` 个旋转对的频率，`dh` 为旋转对的数量。
+#[inline]
+fn inv_freq(k: isize, dh: isize, theta: f32) -> f64 {
+    (theta as f64).powf(-k as f64 / dh as f64)
+}
+
 trait Position<Calculation> {
+    /// 在 sin/cos 表中的行号，`None` 表示不旋转。
+    fn row(self) -> Option<usize>;
+    fn freq(self, k: isize, dh: isize, theta: f32) -> Calculation;
     fn freq_sin_cos(self, k: isize, dh: isize, theta: f32) -> (Calculation, Calculation);
 }
 
 macro_rules! impl_position {
     ($a:ty) => {
         impl<T: Unsigned> Position<$a> for T {
+            #[inline]
+            fn row(self) -> Option<usize> {
+                Some(self.val())
+            }
+            #[inline]
+            fn freq(self, k: isize, dh: isize, theta: f32) -> $a {
+                self.val() as $a / (theta as $a).powf(k as $a / dh as $a)
+            }
             #[inline]
             fn freq_sin_cos(self, k: isize, dh: isize, theta: f32) -> ($a, $a) {
-                (self.val() as $a / (theta as $a).powf(k as $a / dh as $a)).sin_cos()
+                self.freq(k, dh, theta).sin_cos()
             }
         }
     };
@@ -204,38 +641,1789 @@ macro_rules! impl_position {
 impl_position!(f32);
 impl_position!(f64);
 
+macro_rules! impl_signed_position {
+    ($a:ty: $( $p:ty ),+) => {
+        $(
+            impl Position<$a> for $p {
+                #[inline]
+                fn row(self) -> Option<usize> {
+                    (self >= 0).then_some(self as _)
+                }
+                #[inline]
+                fn freq(self, k: isize, dh: isize, theta: f32) -> $a {
+                    // 负位置表示填充，不旋转
+                    if self < 0 {
+                        return 0.;
+                    }
+                    self as $a / (theta as $a).powf(k as $a / dh as $a)
+                }
+                #[inline]
+                fn freq_sin_cos(self, k: isize, dh: isize, theta: f32) -> ($a, $a) {
+                    self.freq(k, dh, theta).sin_cos()
+                }
+            }
+        )+
+    };
+}
+
+impl_signed_position!(f32: i32, i64);
+impl_signed_position!(f64: i32, i64);
+
 impl<A, P> Scheme<A, P>
 where
     A: Activation,
-    P: Position<A::Calculation> + Sync + Copy,
+    P: Position<A::Calculation> + PosTy + Sync + Copy,
 {
+    /// 第 `b` 个批次第 `i` 个词元的位置，注意力汇聚的词元使用固定位置。
+    #[inline]
+    fn pos(&self, b: isize, i: isize) -> P {
+        if (i as usize) < self.sink.tokens {
+            P::from_usize(self.sink.pos)
+        } else {
+            unsafe { *self.p_base.byte_offset(b * self.spb + i * self.sp) }
+        }
+    }
+
+    /// 头的总数，即最后一组头的结束位置。
+    #[inline]
+    fn nh(&self) -> usize {
+        self.groups.last().map_or(0, |(heads, _)| heads.end)
+    }
+
+    /// 第 `b` 个批次第 `i` 个词元是否被掩码跳过。
+    #[inline]
+    fn masked(&self, b: isize, i: isize) -> bool {
+        self.mask
+            .is_some_and(|(base, [smb, sm])| unsafe { *base.byte_offset(b * smb + i * sm) == 0 })
+    }
+
+    /// 跳过的词元原样复制到输出，原地计算时不需要复制。
+    #[inline]
+    fn skip(&self, t: *const [A; 2], d: *mut [A; 2]) {
+        if t != d.cast_const() {
+            unsafe { std::ptr::copy_nonoverlapping(t, d, self.dh / 2) }
+        }
+    }
+
+    /// 有表时检查所有位置都在表的范围内，避免越界读取。
+    fn check_table(&self) -> Result<(), SchemeError> {
+        let Some(table) = &self.table else {
+            return Ok(());
+        };
+        let max = table.max_pos();
+        for b in 0..self.nb as isize {
+            for i in 0..self.nt as isize {
+                // 跳过的词元不读表，位置可以是任意值
+                if self.masked(b, i) {
+                    continue;
+                }
+                let p = self.pos(b, i);
+                if let Some(row) = p.row().filter(|&row| row > max) {
+                    return Err(shape_not_support(format!(
+                        "rope: position {row} exceeds sin/cos table of {} rows (max position {max})",
+                        table.nctx,
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn calculate(&self) {
+        if self.nt == 1 {
+            return self.calculate_token();
+        }
+        // 常见的头维度使用编译期长度，便于展开和向量化
+        match self.dh {
+            64 => self.calculate_const::<32>(),
+            128 => self.calculate_const::<64>(),
+            _ => self.calculate_dyn(),
+        }
+    }
+
+    fn calculate_dyn(&self) {
+        let dh = self.dh as isize / 2;
+        let sd = size_of::<[A; 2]>() as isize;
+        self.for_each_head(|t, d, p, theta| {
+            for k in 0..dh {
+                let mut pair = unsafe { *t.byte_offset(k * sd) };
+                let (sin, cos) = self.sin_cos(p, k, dh, theta);
+                A::calculate(&mut pair, sin, cos);
+                unsafe { *d.byte_offset(k * sd) = pair }
+            }
+        })
+    }
+
+    /// `DH` 为每个头中旋转对的数量。
+    fn calculate_const<const DH: usize>(&self) {
+        debug_assert_eq!(self.dh, DH * 2);
+        self.for_each_head(|t, d, p, theta| {
+            let mut head = unsafe { *t.cast::<[[A; 2]; DH]>() };
+            for (k, pair) in head.iter_mut().enumerate() {
+                let (sin, cos) = self.sin_cos(p, k as _, DH as _, theta);
+                A::calculate(pair, sin, cos)
+            }
+            unsafe { *d.cast::<[[A; 2]; DH]>() = head }
+        })
+    }
+
+    /// 单个词元的快速路径：同一组头共用一组 sin/cos，每个批次每组只计算一次。
+    fn calculate_token(&self) {
+        let &Self {
+            nb,
+            sb,
+            sh,
+            d: [db, _, dsh],
+            t_base,
+            d_base,
+            ..
+        } = self;
+        let dh = self.dh as isize / 2;
+        let sd = size_of::<[A; 2]>() as isize;
+
+        let mut sin_cos = Vec::with_capacity(dh as _);
+        for b in 0..nb as isize {
+            let t = unsafe { t_base.byte_offset(b * sb).cast::<[A; 2]>() };
+            let d = unsafe { d_base.byte_offset(b * db).cast::<[A; 2]>() };
+            if self.masked(b, 0) {
+                for j in 0..self.nh() as isize {
+                    self.skip(unsafe { t.byte_offset(j * sh) }, unsafe {
+                        d.byte_offset(j * dsh)
+                    })
+                }
+                continue;
+            }
+            let p = self.pos(b, 0);
+            for (heads, theta) in &self.groups {
+                sin_cos.clear();
+                sin_cos.extend((0..dh).map(|k| self.sin_cos(p, k, dh, *theta)));
+                for j in heads.clone() {
+                    let j = j as isize;
+                    let t = unsafe { t.byte_offset(j * sh) };
+                    let d = unsafe { d.byte_offset(j * dsh) };
+                    for (k, &(sin, cos)) in sin_cos.iter().enumerate() {
+                        let k = k as isize;
+                        let mut pair = unsafe { *t.byte_offset(k * sd) };
+                        A::calculate(&mut pair, sin, cos);
+                        unsafe { *d.byte_offset(k * sd) = pair }
+                    }
+                }
+            }
+        }
+    }
+
+    /// 有表时查表，否则现场计算。反向旋转时 sin 取反。
+    ///
+    /// 需要缩放频率时以 f64 计算旋转角。
+    #[inline]
+    fn sin_cos(&self, p: P, k: isize, dh: isize, theta: f32) -> (A::Calculation, A::Calculation) {
+        let (sin, cos) = match &self.table {
+            Some(table) => p.row().map_or((0., 1.), |row| table.get(row, k)),
+            None if self.scaling != RopeScaling::None => {
+                let freq = self.scaling.scale(inv_freq(k, dh, theta));
+                p.row()
+                    .map_or((0., 1.), |row| (row as f64 * freq).sin_cos())
+            }
+            None => {
+                let (sin, cos) = p.freq_sin_cos(k, dh, theta);
+                return if self.inverse {
+                    (-sin, cos)
+                } else {
+                    (sin, cos)
+                };
+            }
+        };
+        let sin = if self.inverse { -sin } else { sin };
+        (A::calculation(sin), A::calculation(cos))
+    }
+
+    fn for_each_head(&self, f: impl Fn(*const [A; 2], *mut [A; 2], P, f32)) {
         let &Self {
+            nb,
             nt,
-            nh,
-            dh,
+            sb,
             st,
             sh,
-            sp,
-            theta,
+            d: [db, dt, dh],
             t_base,
-            p_base,
+            d_base,
+            ..
         } = self;
+        let nb = nb as isize;
         let nt = nt as isize;
-        let nh = nh as isize;
-        let dh = dh as isize / 2;
-        let sd = size_of::<[A; 2]>() as isize;
 
-        for i in 0..nt {
-            let t = unsafe { t_base.byte_offset(i * st).cast::<[A; 2]>() };
-            let p = unsafe { *p_base.byte_offset(i * sp) };
-            for j in 0..nh {
-                for k in 0..dh {
-                    let pair = unsafe { &mut *t.byte_offset(j * sh + k * sd) };
-                    let (sin, cos) = p.freq_sin_cos(k, dh, theta);
-                    A::calculate(pair, sin, cos)
+        for b in 0..nb {
+            for i in 0..nt {
+                let t = unsafe { t_base.byte_offset(b * sb + i * st).cast::<[A; 2]>() };
+                let d = unsafe { d_base.byte_offset(b * db + i * dt).cast::<[A; 2]>() };
+                if self.masked(b, i) {
+                    for j in 0..self.nh() as isize {
+                        self.skip(unsafe { t.byte_offset(j * sh) }, unsafe {
+                            d.byte_offset(j * dh)
+                        })
+                    }
+                    continue;
+                }
+                let p = self.pos(b, i);
+                for (heads, theta) in &self.groups {
+                    for j in heads.clone() {
+                        let j = j as isize;
+                        f(
+                            unsafe { t.byte_offset(j * sh) },
+                            unsafe { d.byte_offset(j * dh) },
+                            p,
+                            *theta,
+                        )
+                    }
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Args, Operator, RopeScaling, Seq, SinCosTable};
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        rope::Rope,
+        Operator as _, TensorLayout,
+    };
+    use digit_layout::types as ty;
+    use std::ptr::null;
+
+    #[test]
+    fn test_fill_pos() {
+        use crate::rope::fill_pos;
+
+        // 两个请求：一个从位置 3 开始的 2 个 token，一个新请求的 3 个 token
+        let seqs = [Seq { pos: 3, len: 2 }, Seq { pos: 0, len: 3 }];
+
+        let mut pos = vec![0u32; 7];
+        fill_pos(&mut pos, seqs);
+        assert_eq!(pos, [3, 4, 0, 1, 2, 0, 0]);
+
+        let mut pos = vec![0i64; 7];
+        fill_pos(&mut pos, seqs);
+        assert_eq!(pos, [3, 4, 0, 1, 2, -1, -1]);
+
+        // 输出不足时多出的位置被丢弃
+        let mut pos = vec![0u64; 4];
+        fill_pos(&mut pos, seqs);
+        assert_eq!(pos, [3, 4, 0, 1]);
+    }
+
+    #[test]
+    fn test_signed_pos() {
+        const NT: usize = 5;
+        let nh = 4;
+        let dh = 16;
+
+        let pos = Operator::build_pos(ty::I32, NT, [Seq { pos: 7, len: 3 }], &ThisThread);
+        let ([], pos, []) = (unsafe { pos.align_to::<i32>() }) else {
+            panic!()
+        };
+        assert_eq!(pos, [7, 8, 9, -1, -1]);
+
+        let t = (0..NT * nh * dh).map(|i| i as f64).collect::<Vec<_>>();
+        let mut t_ans = t.clone();
+        let mut op = Operator::new(&Cpu);
+        let args = Args::<Cpu> {
+            t_layout: TensorLayout::new_contiguous(ty::F64, &[NT, nh, dh]),
+            t_base: t_ans.as_mut_ptr().cast(),
+            p_layout: TensorLayout::new_contiguous(ty::I32, &[NT]),
+            p_base: pos.as_ptr().cast(),
+            sin_layout: TensorLayout::new_contiguous(ty::F64, &[0, dh]),
+            sin_base: null(),
+            cos_layout: TensorLayout::new_contiguous(ty::F64, &[0, dh]),
+            cos_base: null(),
+            theta: 1e4,
+            theta_groups: Vec::new(),
+            scaling: RopeScaling::None,
+            table_step: 1,
+            sink: Default::default(),
+            inverse: false,
+            mask: None,
+        };
+        op.scheme(&args, 0).unwrap();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        let len = nh * dh;
+        for (i, (t, t_ans)) in t.chunks(len).zip(t_ans.chunks(len)).enumerate() {
+            if pos[i] < 0 {
+                assert_eq!(t, t_ans);
+            } else {
+                assert_ne!(t, t_ans);
+            }
+        }
+    }
+    #[test]
+    fn test_time_series() {
+        const SEQ: usize = 6;
+        let dh = 16;
+        let groups = 3;
+
+        let pos = (0..SEQ as u32).map(|i| i * 2).collect::<Vec<_>>();
+        let t = (0..SEQ * groups * dh)
+            .map(|i| (i as f64).sin())
+            .collect::<Vec<_>>();
+        let mut op = Operator::new(&Cpu);
+
+        let args = |t_layout, t_base| Args::<Cpu> {
+            t_layout,
+            t_base,
+            p_layout: TensorLayout::new_contiguous(ty::U32, &[SEQ]),
+            p_base: pos.as_ptr().cast(),
+            sin_layout: TensorLayout::new_contiguous(ty::F64, &[0, dh]),
+            sin_base: null(),
+            cos_layout: TensorLayout::new_contiguous(ty::F64, &[0, dh]),
+            cos_base: null(),
+            theta: 1e4,
+            theta_groups: Vec::new(),
+            scaling: RopeScaling::None,
+            table_step: 1,
+            sink: Default::default(),
+            inverse: false,
+            mask: None,
+        };
+
+        // [seq, dh] 与 [seq, 1, dh] 等价
+        let mut t_2d = t[..SEQ * dh].to_vec();
+        let mut t_3d = t_2d.clone();
+        let args_2d = args(
+            TensorLayout::new_contiguous(ty::F64, &[SEQ, dh]),
+            t_2d.as_mut_ptr().cast(),
+        );
+        op.scheme(&args_2d, 0).unwrap();
+        op.launch(&args_2d, &mut [], &ThisThread).unwrap();
+        let args_3d = args(
+            TensorLayout::new_contiguous(ty::F64, &[SEQ, 1, dh]),
+            t_3d.as_mut_ptr().cast(),
+        );
+        op.launch(&args_3d, &mut [], &ThisThread).unwrap();
+        assert_eq!(t_2d, t_3d);
+
+        // 通道分组与多头等价
+        let mut t_grouped = t.clone();
+        let mut t_heads = t;
+        let mut args_grouped = args(
+            TensorLayout::new_contiguous(ty::F64, &[SEQ, groups * dh]),
+            t_grouped.as_mut_ptr().cast(),
+        );
+        args_grouped.group_channels(groups).unwrap();
+        op.launch(&args_grouped, &mut [], &ThisThread).unwrap();
+        let args_heads = args(
+            TensorLayout::new_contiguous(ty::F64, &[SEQ, groups, dh]),
+            t_heads.as_mut_ptr().cast(),
+        );
+        op.launch(&args_heads, &mut [], &ThisThread).unwrap();
+        assert_eq!(t_grouped, t_heads);
+
+        let mut bad = args(
+            TensorLayout::new_contiguous(ty::F64, &[SEQ, 10]),
+            t_2d.as_mut_ptr().cast(),
+        );
+        assert!(bad.group_channels(4).is_err());
+    }
+    #[test]
+    fn test_const_dh() {
+        use super::Scheme;
+
+        const NT: usize = 5;
+        let nh = 3;
+        let dh = 128;
+        let pos = [0u32, 1, 5, 9, 100];
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f64).cos())
+            .collect::<Vec<_>>();
+
+        let scheme = |t: &mut [f64]| Scheme::<f64, u32> {
+            nb: 1,
+            nt: NT,
+            dh,
+            sb: 0,
+            st: (nh * dh * size_of::<f64>()) as _,
+            sh: (dh * size_of::<f64>()) as _,
+            spb: 0,
+            sp: size_of::<u32>() as _,
+            d: [
+                0,
+                (nh * dh * size_of::<f64>()) as _,
+                (dh * size_of::<f64>()) as _,
+            ],
+            groups: vec![(0..nh, 1e4)],
+            scaling: RopeScaling::None,
+            sink: Default::default(),
+            inverse: false,
+            mask: None,
+            t_base: t.as_ptr(),
+            d_base: t.as_mut_ptr(),
+            p_base: pos.as_ptr(),
+            table: None,
+        };
+
+        let mut t_dyn = t.clone();
+        let mut t_const = t;
+        scheme(&mut t_dyn).calculate_dyn();
+        scheme(&mut t_const).calculate();
+        assert_eq!(t_dyn, t_const);
+    }
+    #[test]
+    fn test_single_token() {
+        use super::Scheme;
+
+        const NT: usize = 4;
+        let (nh, dh) = (3, 128);
+        let pos = [7u32, 0, 42, 1000];
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f32 * 0.01).cos())
+            .collect::<Vec<_>>();
+
+        let scheme = |t: &mut [f32], nt: usize, i: usize| Scheme::<f32, u32> {
+            nb: 1,
+            nt,
+            dh,
+            sb: 0,
+            st: (nh * dh * size_of::<f32>()) as _,
+            sh: (dh * size_of::<f32>()) as _,
+            spb: 0,
+            sp: size_of::<u32>() as _,
+            d: [
+                0,
+                (nh * dh * size_of::<f32>()) as _,
+                (dh * size_of::<f32>()) as _,
+            ],
+            groups: vec![(0..1, 1e4), (1..nh, 5e5)],
+            scaling: RopeScaling::None,
+            sink: Default::default(),
+            inverse: false,
+            mask: None,
+            t_base: t[i * nh * dh..].as_ptr(),
+            d_base: t[i * nh * dh..].as_mut_ptr(),
+            p_base: pos[i..].as_ptr(),
+            table: None,
+        };
+
+        // 批量路径一次旋转所有词元，单词元路径逐个旋转
+        let mut t_batched = t.clone();
+        scheme(&mut t_batched, NT, 0).calculate();
+        let mut t_single = t;
+        for i in 0..NT {
+            scheme(&mut t_single, 1, i).calculate();
+        }
+        assert_eq!(t_single, t_batched);
+    }
+
+    #[test]
+    fn test_dry_run() {
+        use super::super::ThetaGroup;
+        use crate::LaunchErrorCategory;
+        use std::iter::zip;
+
+        const NT: usize = 4;
+        let nh = 2;
+        let dh = 32;
+        let theta = 1e4f32;
+        let pos = [0i64, 3, 17, -1];
+
+        let mut t = vec![1.0f64; NT * nh * dh];
+        let t_base = t.as_mut_ptr().cast();
+        let builder = || {
+            Args::<Cpu>::builder(
+                TensorLayout::new_contiguous(ty::F64, &[NT, nh, dh]),
+                t_base,
+                TensorLayout::new_contiguous(ty::I64, &[NT]),
+                pos.as_ptr().cast(),
+                theta,
+            )
+        };
+        let args = builder().build();
+        let op = Operator::new(&Cpu);
+        let mut angles = vec![f64::NAN; NT * dh / 2];
+        op.dry_run(&args, &mut angles).unwrap();
+        // 试运行不修改张量
+        assert!(t.iter().all(|&x| x == 1.));
+
+        for (i, k) in [(1, 0), (1, 5), (2, 1), (2, 15)] {
+            let expected = pos[i] as f64 * (theta as f64).powf(-2. * k as f64 / dh as f64);
+            let angle = angles[i * dh / 2 + k];
+            assert!((angle - expected).abs() <= expected.abs() * 1e-12);
+        }
+        // 位置 0 与填充位置不旋转
+        assert!(angles[..dh / 2].iter().all(|&x| x == 0.));
+        assert!(angles[3 * dh / 2..].iter().all(|&x| x == 0.));
+
+        let mut short = vec![0.; 3];
+        assert!(op.dry_run(&args, &mut short).is_err());
+
+        // 反向旋转的旋转角取反
+        let mut inverse = vec![f64::NAN; NT * dh / 2];
+        op.dry_run(&builder().inverse().build(), &mut inverse)
+            .unwrap();
+        assert!(zip(&angles, &inverse).all(|(a, b)| *a == -*b));
+
+        // 旋转角无法描述的参数直接拒绝，而不是给出与 launch 不一致的结果
+        let table = Operator::build_sincos(ty::F64, 32, dh, &ThisThread);
+        let mask = [1u8; NT];
+        let mut grouped = builder().build();
+        grouped.theta_groups = vec![ThetaGroup {
+            heads: 1..2,
+            theta: 5e5,
+        }];
+        let rejected = [
+            grouped,
+            builder().table(&table, ty::F64).unwrap().build(),
+            builder()
+                .mask(TensorLayout::new_contiguous(ty::U8, &[NT]), mask.as_ptr())
+                .build(),
+        ];
+        for args in rejected {
+            let e = op.dry_run(&args, &mut angles).unwrap_err();
+            assert_eq!(e.category(), LaunchErrorCategory::Validation);
+        }
+    }
+
+    #[test]
+    fn test_complex_interleaved() {
+        use crate::SchemeErrorKind;
+
+        const NT: usize = 4;
+        let nh = 3;
+        let dh = 16;
+        let theta = 1e4f32;
+        let pos = [0u32, 2, 5, 11];
+
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f64 * 0.3).cos())
+            .collect::<Vec<_>>();
+        let builder = |t_layout, t_base| {
+            Args::<Cpu>::builder(
+                t_layout,
+                t_base,
+                TensorLayout::new_contiguous(ty::U32, &[NT]),
+                pos.as_ptr().cast(),
+                theta,
+            )
+        };
+        let op = Operator::new(&Cpu);
+
+        // 复数交错存储的 [nt, nh, dh / 2, 2]
+        let mut t_complex = t.clone();
+        let args = builder(
+            TensorLayout::new_contiguous(ty::F64, &[NT, nh, dh / 2, 2]),
+            t_complex.as_mut_ptr().cast(),
+        )
+        .complex_interleaved()
+        .unwrap()
+        .build();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        // 与实数对旋转一致
+        let mut t_real = t.clone();
+        let args = builder(
+            TensorLayout::new_contiguous(ty::F64, &[NT, nh, dh]),
+            t_real.as_mut_ptr().cast(),
+        )
+        .build();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+        assert_eq!(t_complex, t_real);
+
+        // 逐个复数乘以 e^(i * pos * theta_k)
+        for (i, (x, y)) in t.chunks(2).zip(t_complex.chunks(2)).enumerate() {
+            let k = i % (dh / 2);
+            let p = pos[i / (nh * dh / 2)] as f64;
+            let (sin, cos) = (p * (theta as f64).powf(-2. * k as f64 / dh as f64)).sin_cos();
+            let re = x[0] * cos - x[1] * sin;
+            let im = x[0] * sin + x[1] * cos;
+            assert!((re - y[0]).abs() < 1e-12 && (im - y[1]).abs() < 1e-12);
+        }
+
+        // 报告调用者传入的维数
+        let ptr = t_real.as_mut_ptr().cast();
+        let Err(e) =
+            builder(TensorLayout::new_contiguous(ty::F64, &[NT, dh]), ptr).complex_interleaved()
+        else {
+            panic!()
+        };
+        assert_eq!(e.kind, SchemeErrorKind::RankNotSupport);
+        assert!(e.info.starts_with("t.ndim = 2,"), "{}", e.info);
+        // 最后一维不是 (实部, 虚部) 或者两者不相邻
+        let bad = [
+            TensorLayout::new_contiguous(ty::F64, &[NT, nh, dh / 4, 4]),
+            TensorLayout::new(
+                ty::F64,
+                &[NT, nh, dh / 2, 2],
+                &[(nh * dh * 8) as _, (dh * 8) as _, 8, 64],
+            ),
+        ];
+        for layout in bad {
+            assert!(builder(layout, ptr).complex_interleaved().is_err());
+        }
+    }
+
+    #[cfg(feature = "debug-assertions")]
+    #[test]
+    #[should_panic(expected = "t: base pointer is null")]
+    fn test_null_base() {
+        use std::ptr::null_mut;
+
+        let pos = [0u32; 2];
+        let args = Args::<Cpu> {
+            t_layout: TensorLayout::new_contiguous(ty::F32, &[2, 4, 16]),
+            t_base: null_mut(),
+            p_layout: TensorLayout::new_contiguous(ty::U32, &[2]),
+            p_base: pos.as_ptr().cast(),
+            sin_layout: TensorLayout::new_contiguous(ty::F32, &[0, 16]),
+            sin_base: null(),
+            cos_layout: TensorLayout::new_contiguous(ty::F32, &[0, 16]),
+            cos_base: null(),
+            theta: 1e4,
+            theta_groups: Vec::new(),
+            scaling: RopeScaling::None,
+            table_step: 1,
+            sink: Default::default(),
+            inverse: false,
+            mask: None,
+        };
+        let _ = Operator::new(&Cpu).launch(&args, &mut [], &ThisThread);
+    }
+
+    #[test]
+    fn test_launch_into() {
+        use crate::rearrange::{common_cpu::Operator as Rearrange, Args as RearrangeArgs};
+
+        const NT: usize = 5;
+        let nh = 4;
+        let dh = 32;
+        let pos = [3u32, 0, 9, 1, 4];
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f64 * 0.7).sin())
+            .collect::<Vec<_>>();
+
+        let unit = size_of::<f64>() as isize;
+        let t_layout = TensorLayout::new_contiguous(ty::F64, &[NT, nh, dh]);
+        // 输出按 [nh, nt, dh] 存储
+        let dst_layout = TensorLayout::new(
+            ty::F64,
+            &[NT, nh, dh],
+            &[dh as isize * unit, (NT * dh) as isize * unit, unit],
+        );
+        let args = |t_base| Args::<Cpu> {
+            t_layout: t_layout.clone(),
+            t_base,
+            p_layout: TensorLayout::new_contiguous(ty::U32, &[NT]),
+            p_base: pos.as_ptr().cast(),
+            sin_layout: TensorLayout::new_contiguous(ty::F64, &[0, dh]),
+            sin_base: null(),
+            cos_layout: TensorLayout::new_contiguous(ty::F64, &[0, dh]),
+            cos_base: null(),
+            theta: 1e4,
+            theta_groups: Vec::new(),
+            scaling: RopeScaling::None,
+            table_step: 1,
+            sink: Default::default(),
+            inverse: false,
+            mask: None,
+        };
+        let op = Operator::new(&Cpu);
+
+        // 融合：一次计算写入转置的输出，输入不变
+        let mut t_in = t.clone();
+        let mut fused = vec![0.0f64; t.len()];
+        op.launch_into(
+            &args(t_in.as_mut_ptr().cast()),
+            &dst_layout,
+            fused.as_mut_ptr().cast(),
+        )
+        .unwrap();
+        assert_eq!(t_in, t);
+
+        // 先原地旋转再重排
+        let mut rotated = t;
+        op.launch(&args(rotated.as_mut_ptr().cast()), &mut [], &ThisThread)
+            .unwrap();
+        let mut expected = vec![0.0f64; rotated.len()];
+        Rearrange::new(&Cpu)
+            .launch(
+                &RearrangeArgs::<Cpu> {
+                    dst_layout: dst_layout.clone(),
+                    dst_base: expected.as_mut_ptr().cast(),
+                    src_layout: t_layout.clone(),
+                    src_base: rotated.as_ptr().cast(),
+                    scale: 1.,
+                    bias: 0.,
+                },
+                &mut [],
+                &ThisThread,
+            )
+            .unwrap();
+        assert_eq!(fused, expected);
+
+        let bad = TensorLayout::new_contiguous(ty::F64, &[NT, nh, dh / 2]);
+        assert!(op
+            .launch_into(
+                &args(t_in.as_mut_ptr().cast()),
+                &bad,
+                fused.as_mut_ptr().cast()
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_builder() {
+        const NT: usize = 4;
+        let nh = 2;
+        let dh = 8;
+        let pos = [0u32, 3, 5, 2];
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f64).cos())
+            .collect::<Vec<_>>();
+        let op = Operator::new(&Cpu);
+
+        let mut t_ans = t.clone();
+        let args = Args::<Cpu>::builder(
+            TensorLayout::new_contiguous(ty::F64, &[NT, nh, dh]),
+            t_ans.as_mut_ptr().cast(),
+            TensorLayout::new_contiguous(ty::U32, &[NT]),
+            pos.as_ptr().cast(),
+            1e4,
+        )
+        .build();
+        assert!(args.sin_base.is_null() && args.cos_base.is_null());
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        let mut t_ref = t;
+        let args = Args::<Cpu> {
+            t_layout: TensorLayout::new_contiguous(ty::F64, &[NT, nh, dh]),
+            t_base: t_ref.as_mut_ptr().cast(),
+            p_layout: TensorLayout::new_contiguous(ty::U32, &[NT]),
+            p_base: pos.as_ptr().cast(),
+            sin_layout: TensorLayout::new_contiguous(ty::F64, &[0, dh]),
+            sin_base: null(),
+            cos_layout: TensorLayout::new_contiguous(ty::F64, &[0, dh]),
+            cos_base: null(),
+            theta: 1e4,
+            theta_groups: Vec::new(),
+            scaling: RopeScaling::None,
+            table_step: 1,
+            sink: Default::default(),
+            inverse: false,
+            mask: None,
+        };
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+        assert_eq!(t_ans, t_ref);
+    }
+
+    #[test]
+    fn test_build_pos_overflow() {
+        let nt = usize::MAX - 3;
+        assert!(Operator::try_build_pos(ty::U32, nt, [], &ThisThread).is_err());
+        assert!(Operator::try_build_pos(ty::I64, nt / 8 + 1, [], &ThisThread).is_err());
+        assert!(Operator::try_build_pos(ty::F32, 4, [], &ThisThread).is_err());
+
+        let pos =
+            Operator::try_build_pos(ty::U64, 4, [Seq { pos: 2, len: 4 }], &ThisThread).unwrap();
+        let ([], pos, []) = (unsafe { pos.align_to::<u64>() }) else {
+            panic!()
+        };
+        assert_eq!(pos, [2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_sin_cos_table() {
+        use half::f16;
+
+        const NT: usize = 6;
+        let nh = 3;
+        let dh = 16;
+        let theta = 1e4f32;
+        let pos = [0u32, 5, 2, 9, 1, 7];
+        let nctx = 10;
+
+        // f16 的表，按 [nctx, dh] 存储，每个旋转对的两项相同
+        let (sin, cos): (Vec<_>, Vec<_>) = (0..nctx * dh)
+            .map(|i| {
+                let (p, k) = (i / dh, (i % dh / 2) as f64);
+                let freq = p as f64 / (theta as f64).powf(k / (dh / 2) as f64);
+                let (sin, cos) = freq.sin_cos();
+                (f16::from_f64(sin), f16::from_f64(cos))
+            })
+            .unzip();
+
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f64 * 0.3).sin())
+            .collect::<Vec<_>>();
+        let op = Operator::new(&Cpu);
+
+        let mut t_ans = t.iter().map(|&x| x as f32).collect::<Vec<_>>();
+        let table = TensorLayout::new_contiguous(ty::F16, &[nctx, dh]);
+        let args = Args::<Cpu>::builder(
+            TensorLayout::new_contiguous(ty::F32, &[NT, nh, dh]),
+            t_ans.as_mut_ptr().cast(),
+            TensorLayout::new_contiguous(ty::U32, &[NT]),
+            pos.as_ptr().cast(),
+            theta,
+        )
+        .sin_cos(
+            table.clone(),
+            sin.as_ptr().cast(),
+            table,
+            cos.as_ptr().cast(),
+        )
+        .build();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        let mut t_ref = t;
+        let args = Args::<Cpu>::builder(
+            TensorLayout::new_contiguous(ty::F64, &[NT, nh, dh]),
+            t_ref.as_mut_ptr().cast(),
+            TensorLayout::new_contiguous(ty::U32, &[NT]),
+            pos.as_ptr().cast(),
+            theta,
+        )
+        .build();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        for (a, b) in t_ans.iter().zip(&t_ref) {
+            assert!((*a as f64 - b).abs() < 2. * f16::EPSILON.to_f64());
+        }
+
+        // sin 与 cos 表类型不同
+        let args = Args::<Cpu>::builder(
+            TensorLayout::new_contiguous(ty::F32, &[NT, nh, dh]),
+            t_ans.as_mut_ptr().cast(),
+            TensorLayout::new_contiguous(ty::U32, &[NT]),
+            pos.as_ptr().cast(),
+            theta,
+        )
+        .sin_cos(
+            TensorLayout::new_contiguous(ty::F16, &[nctx, dh]),
+            sin.as_ptr().cast(),
+            TensorLayout::new_contiguous(ty::F32, &[nctx, dh]),
+            cos.as_ptr().cast(),
+        )
+        .build();
+        assert!(op.launch(&args, &mut [], &ThisThread).is_err());
+    }
+
+    #[test]
+    fn test_shared_table() {
+        const NT: usize = 5;
+        let dh = 16;
+        let pos = [3u32, 0, 8, 1, 6];
+        let table = Operator::build_sincos(ty::F32, 10, dh, &ThisThread);
+        let op = Operator::new(&Cpu);
+
+        // 同一张表依次用于 Q 和 K，两者头数不同
+        for nh in [4, 2] {
+            let t = (0..NT * nh * dh)
+                .map(|i| (i as f64 * 0.7).cos())
+                .collect::<Vec<_>>();
+
+            let mut t_ans = t.iter().map(|&x| x as f32).collect::<Vec<_>>();
+            let args = Args::<Cpu>::builder(
+                TensorLayout::new_contiguous(ty::F32, &[NT, nh, dh]),
+                t_ans.as_mut_ptr().cast(),
+                TensorLayout::new_contiguous(ty::U32, &[NT]),
+                pos.as_ptr().cast(),
+                1e4,
+            )
+            .table(&table, ty::F32)
+            .unwrap()
+            .build();
+            assert!(!args.sin_base.is_null());
+            op.launch(&args, &mut [], &ThisThread).unwrap();
+
+            let mut t_ref = t;
+            let args = Args::<Cpu>::builder(
+                TensorLayout::new_contiguous(ty::F64, &[NT, nh, dh]),
+                t_ref.as_mut_ptr().cast(),
+                TensorLayout::new_contiguous(ty::U32, &[NT]),
+                pos.as_ptr().cast(),
+                1e4,
+            )
+            .build();
+            op.launch(&args, &mut [], &ThisThread).unwrap();
+
+            for (a, b) in t_ans.iter().zip(&t_ref) {
+                assert!((*a as f64 - b).abs() < 1e-5);
+            }
+        }
+
+        // 空表等同于不设置
+        let empty = SinCosTable {
+            nctx: 0,
+            theta: 1e4,
+            mem: Vec::<u8>::new(),
+        };
+        let mut t = vec![0f32; dh];
+        let args = Args::<Cpu>::builder(
+            TensorLayout::new_contiguous(ty::F32, &[1, 1, dh]),
+            t.as_mut_ptr().cast(),
+            TensorLayout::new_contiguous(ty::U32, &[1]),
+            pos.as_ptr().cast(),
+            1e4,
+        )
+        .table(&empty, ty::F32)
+        .unwrap()
+        .build();
+        assert!(args.sin_base.is_null());
+    }
+
+    #[test]
+    fn test_table_theta() {
+        const NT: usize = 3;
+        let (nh, dh) = (2, 16);
+        let theta = 5e5f32;
+        let pos = [0u32, 9, 4];
+        let table = Operator::try_build_sincos(ty::F32, 16, dh, theta, &ThisThread).unwrap();
+        assert_eq!(table.theta, theta);
+        let op = Operator::new(&Cpu);
+
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f64 * 0.4).sin())
+            .collect::<Vec<_>>();
+        let mut t_ans = t.iter().map(|&x| x as f32).collect::<Vec<_>>();
+        let builder = |t: *mut f32, theta| {
+            Args::<Cpu>::builder(
+                TensorLayout::new_contiguous(ty::F32, &[NT, nh, dh]),
+                t.cast(),
+                TensorLayout::new_contiguous(ty::U32, &[NT]),
+                pos.as_ptr().cast(),
+                theta,
+            )
+        };
+
+        // 非默认底数的表与直接计算一致
+        let args = builder(t_ans.as_mut_ptr(), theta)
+            .table(&table, ty::F32)
+            .unwrap()
+            .build();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+        let mut t_ref = t;
+        let args = Args::<Cpu>::builder(
+            TensorLayout::new_contiguous(ty::F64, &[NT, nh, dh]),
+            t_ref.as_mut_ptr().cast(),
+            TensorLayout::new_contiguous(ty::U32, &[NT]),
+            pos.as_ptr().cast(),
+            theta,
+        )
+        .build();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+        for (a, b) in t_ans.iter().zip(&t_ref) {
+            assert!((*a as f64 - b).abs() < 1e-5);
+        }
+
+        // 底数不一致的表被拒绝
+        let err = builder(t_ans.as_mut_ptr(), 1e4)
+            .table(&table, ty::F32)
+            .unwrap_err();
+        assert!(err.info.contains("theta"), "{}", err.info);
+        // 尺寸与 [nctx, dh] 不符的表被拒绝
+        let short = SinCosTable {
+            nctx: 16,
+            theta,
+            mem: &table.mem[..table.mem.len() / 2],
+        };
+        assert!(builder(t_ans.as_mut_ptr(), theta)
+            .table(&short, ty::F32)
+            .is_err());
+        assert!(builder(t_ans.as_mut_ptr(), theta)
+            .table(&table, ty::F16)
+            .is_err());
+        // 表已固定频率，不能再分组或缩放
+        let mut args = builder(t_ans.as_mut_ptr(), theta)
+            .table(&table, ty::F32)
+            .unwrap()
+            .build();
+        args.scaling = RopeScaling::Llama3 {
+            factor: 8.,
+            low_freq_factor: 1.,
+            high_freq_factor: 4.,
+            original_ctx: 8192,
+        };
+        assert!(op.launch(&args, &mut [], &ThisThread).is_err());
+        // 底数必须为正
+        assert!(Operator::try_build_sincos(ty::F32, 16, dh, 0., &ThisThread).is_err());
+    }
+
+    #[test]
+    fn test_table_capacity() {
+        let dh = 16;
+        let table = Operator::build_sincos(ty::F32, 8, dh, &ThisThread);
+        assert_eq!(table.capacity(), 8);
+        let op = Operator::new(&Cpu);
+
+        let launch = |pos: &[u32], step: usize| {
+            let mut t = vec![1f32; pos.len() * dh];
+            let args = Args::<Cpu>::builder(
+                TensorLayout::new_contiguous(ty::F32, &[pos.len(), 1, dh]),
+                t.as_mut_ptr().cast(),
+                TensorLayout::new_contiguous(ty::U32, &[pos.len()]),
+                pos.as_ptr().cast(),
+                1e4,
+            )
+            .table(&table, ty::F32)
+            .unwrap()
+            .table_step(step)
+            .build();
+            op.launch(&args, &mut [], &ThisThread).map(|()| t)
+        };
+
+        assert!(launch(&[0, 7, 3], 1).is_ok());
+        // 越界的位置报错，而不是读取表外的数据
+        let err = launch(&[0, 8, 3], 1).unwrap_err();
+        assert!(err.info.contains("position 8"), "{}", err.info);
+        // 插值时每行覆盖 step 个位置，最后一行之后不能插值
+        assert!(launch(&[28], 4).is_ok());
+        assert!(launch(&[29], 4).is_err());
+    }
+
+    #[test]
+    fn test_table_step() {
+        const NT: usize = 6;
+        const STEP: usize = 4;
+        let nh = 2;
+        let dh = 16;
+        let theta = 1e4f32;
+        let pos = [0u32, 1, 6, 13, 24, 35];
+        let nctx = 10;
+
+        let freq = |k: usize| (theta as f64).powf(-(k as f64) / (dh / 2) as f64);
+        // 每 STEP 个位置采样一行
+        let (sin, cos): (Vec<_>, Vec<_>) = (0..nctx * dh)
+            .map(|i| ((i / dh * STEP) as f64 * freq(i % dh / 2)).sin_cos())
+            .unzip();
+
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f64 * 0.3).sin())
+            .collect::<Vec<_>>();
+        let op = Operator::new(&Cpu);
+
+        let mut t_ans = t.clone();
+        let table = TensorLayout::new_contiguous(ty::F64, &[nctx, dh]);
+        let args = Args::<Cpu>::builder(
+            TensorLayout::new_contiguous(ty::F64, &[NT, nh, dh]),
+            t_ans.as_mut_ptr().cast(),
+            TensorLayout::new_contiguous(ty::U32, &[NT]),
+            pos.as_ptr().cast(),
+            theta,
+        )
+        .sin_cos(
+            table.clone(),
+            sin.as_ptr().cast(),
+            table,
+            cos.as_ptr().cast(),
+        )
+        .table_step(STEP)
+        .build();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        let mut t_ref = t;
+        let args = Args::<Cpu>::builder(
+            TensorLayout::new_contiguous(ty::F64, &[NT, nh, dh]),
+            t_ref.as_mut_ptr().cast(),
+            TensorLayout::new_contiguous(ty::U32, &[NT]),
+            pos.as_ptr().cast(),
+            theta,
+        )
+        .build();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        // 线性插值 sin/cos 的误差不超过 (STEP * freq)^2 / 8，旋转对的两项各受两者影响
+        for (i, (a, b)) in t_ans.iter().zip(&t_ref).enumerate() {
+            let tol = if pos[i / (nh * dh)] as usize % STEP == 0 {
+                1e-12
+            } else {
+                (STEP as f64 * freq(i % dh / 2)).powi(2) / 4. + 1e-12
+            };
+            assert!((a - b).abs() <= tol, "{i}: {a} vs {b}");
+        }
+
+        // 采样间隔为 0 或没有表时不能插值
+        for (step, with_table) in [(0, true), (STEP, false)] {
+            let table = TensorLayout::new_contiguous(ty::F64, &[nctx, dh]);
+            let builder = Args::<Cpu>::builder(
+                TensorLayout::new_contiguous(ty::F64, &[NT, nh, dh]),
+                t_ans.as_mut_ptr().cast(),
+                TensorLayout::new_contiguous(ty::U32, &[NT]),
+                pos.as_ptr().cast(),
+                theta,
+            );
+            let builder = if with_table {
+                builder.sin_cos(
+                    table.clone(),
+                    sin.as_ptr().cast(),
+                    table,
+                    cos.as_ptr().cast(),
+                )
+            } else {
+                builder
+            };
+            let args = builder.table_step(step).build();
+            assert!(op.launch(&args, &mut [], &ThisThread).is_err());
+        }
+    }
+
+    #[test]
+    fn test_theta_groups() {
+        use crate::rope::ThetaGroup;
+
+        const NT: usize = 4;
+        let (nh, dh) = (4, 16);
+        let pos = [1u32, 6, 3, 8];
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f64 * 0.1).cos())
+            .collect::<Vec<_>>();
+        let op = Operator::new(&Cpu);
+        let layout = TensorLayout::new_contiguous(ty::F64, &[NT, nh, dh]);
+        let p_layout = TensorLayout::new_contiguous(ty::U32, &[NT]);
+        let rope = |t: &mut [f64], theta, theta_groups| {
+            let mut args = Args::<Cpu>::builder(
+                layout.clone(),
+                t.as_mut_ptr().cast(),
+                p_layout.clone(),
+                pos.as_ptr().cast(),
+                theta,
+            )
+            .build();
+            args.theta_groups = theta_groups;
+            op.launch(&args, &mut [], &ThisThread)
+        };
+
+        // 后两个头为全局头，使用更大的底数
+        let groups = vec![
+            ThetaGroup {
+                heads: 2..4,
+                theta: 1e6,
+            },
+            ThetaGroup {
+                heads: 0..2,
+                theta: 1e4,
+            },
+        ];
+        let mut t_ans = t.clone();
+        rope(&mut t_ans, 0., groups).unwrap();
+
+        let mut local = t.clone();
+        rope(&mut local, 1e4, vec![]).unwrap();
+        let mut global = t.clone();
+        rope(&mut global, 1e6, vec![]).unwrap();
+        for (i, head) in t_ans.chunks(dh).enumerate() {
+            let expected = if i % nh < 2 { &local } else { &global };
+            assert_eq!(head, &expected[i * dh..][..dh]);
+        }
+
+        // 分组必须恰好划分所有头
+        let mut t_err = t.clone();
+        for heads in [vec![0..2, 3..4], vec![0..3, 2..4], vec![0..2]] {
+            let groups = heads
+                .into_iter()
+                .map(|heads| ThetaGroup { heads, theta: 1e4 })
+                .collect();
+            assert!(rope(&mut t_err, 1e4, groups).is_err());
+        }
+        assert_eq!(t_err, t);
+    }
+
+    #[test]
+    fn test_kv_append() {
+        const NCTX: usize = 8;
+        let (nh, dh) = (2, 16);
+        let offset = 5;
+        let cache_layout = TensorLayout::new_contiguous(ty::F64, &[NCTX, nh, dh]);
+        let cache = (0..NCTX * nh * dh)
+            .map(|i| (i as f64 * 0.2).sin())
+            .collect::<Vec<_>>();
+        let pos = [offset as u32];
+        let p_layout = TensorLayout::new_contiguous(ty::U32, &[1]);
+        let op = Operator::new(&Cpu);
+
+        let mut cache_ans = cache.clone();
+        let args = Args::<Cpu>::kv_append(
+            &cache_layout,
+            cache_ans.as_mut_ptr().cast(),
+            offset,
+            p_layout.clone(),
+            pos.as_ptr().cast(),
+            1e4,
+        )
+        .unwrap();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        // 单独旋转新 token
+        let row = nh * dh;
+        let mut token = cache[offset * row..][..row].to_vec();
+        let args = Args::<Cpu>::builder(
+            TensorLayout::new_contiguous(ty::F64, &[1, nh, dh]),
+            token.as_mut_ptr().cast(),
+            p_layout.clone(),
+            pos.as_ptr().cast(),
+            1e4,
+        )
+        .build();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        assert_eq!(cache_ans[offset * row..][..row], token);
+        assert_eq!(cache_ans[..offset * row], cache[..offset * row]);
+        assert_eq!(cache_ans[(offset + 1) * row..], cache[(offset + 1) * row..]);
+
+        assert!(Args::<Cpu>::kv_append(
+            &cache_layout,
+            cache_ans.as_mut_ptr().cast(),
+            NCTX,
+            p_layout,
+            pos.as_ptr().cast(),
+            1e4,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_plan() {
+        use crate::SchemePlan;
+        use std::ptr::null_mut;
+
+        let mut op = Operator::new(&Cpu);
+        let args = Args::<Cpu>::builder(
+            TensorLayout::new_contiguous(ty::F32, &[7, 4, 16]),
+            null_mut(),
+            TensorLayout::new_contiguous(ty::U32, &[7]),
+            null(),
+            1e4,
+        )
+        .build();
+        let plan = op.plan(&args, usize::MAX).unwrap();
+        assert_eq!(
+            plan,
+            SchemePlan {
+                workspace_size: 0,
+                align: 1,
+                algorithm: "cpu",
+            }
+        );
+        assert_eq!(op.scheme(&args, usize::MAX).unwrap(), plan.workspace_size);
+    }
+
+    #[test]
+    fn test_llama3_scaling() {
+        const NT: usize = 3;
+        let nh = 2;
+        let dh = 128;
+        let theta = 5e5f32;
+        let pos = [1u32, 100, 5000];
+        let scaling = RopeScaling::Llama3 {
+            factor: 8.,
+            low_freq_factor: 1.,
+            high_freq_factor: 4.,
+            original_ctx: 8192,
+        };
+
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f64 * 0.29).cos())
+            .collect::<Vec<_>>();
+        let mut t_ans = t.clone();
+        let mut args = Args::<Cpu>::builder(
+            TensorLayout::new_contiguous(ty::F64, &[NT, nh, dh]),
+            t_ans.as_mut_ptr().cast(),
+            TensorLayout::new_contiguous(ty::U32, &[NT]),
+            pos.as_ptr().cast(),
+            theta,
+        )
+        .build();
+        args.scaling = scaling;
+
+        let mut op = Operator::new(&Cpu);
+        op.scheme(&args, 0).unwrap();
+        let mut angles = vec![0.; NT * dh / 2];
+        op.dry_run(&args, &mut angles).unwrap();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        let freq = |k: usize| (theta as f64).powf(-2. * k as f64 / dh as f64);
+        for (i, &p) in pos.iter().enumerate() {
+            for k in 0..dh / 2 {
+                let angle = p as f64 * scaling.scale(freq(k));
+                assert!((angles[i * dh / 2 + k] - angle).abs() <= angle * 1e-12);
+                // 逐头检查旋转结果
+                let (sin, cos) = angle.sin_cos();
+                for h in 0..nh {
+                    let j = (i * nh + h) * dh + 2 * k;
+                    let [a, b] = [t[j], t[j + 1]];
+                    assert!((t_ans[j] - (a * cos - b * sin)).abs() < 1e-12);
+                    assert!((t_ans[j + 1] - (a * sin + b * cos)).abs() < 1e-12);
+                }
+            }
+        }
+        // 最高频不缩放，最低频除以 factor
+        assert_eq!(angles[dh / 2], 100.);
+        let lowest = 100. * freq(dh / 2 - 1) / 8.;
+        assert!((angles[dh - 1] - lowest).abs() <= lowest * 1e-12);
+    }
+
+    #[test]
+    fn test_sink() {
+        const NT: usize = 6;
+        let nh = 2;
+        let dh = 64;
+        let theta = 1e4f32;
+        let pos = [100u32, 101, 102, 103, 104, 105];
+
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f64 * 0.13).sin())
+            .collect::<Vec<_>>();
+        let op = Operator::new(&Cpu);
+
+        for (sink, sink_pos) in [(2, 0), (3, 4), (1, 0), (NT + 2, 7)] {
+            let mut t_ans = t.clone();
+            let args = Args::<Cpu>::builder(
+                TensorLayout::new_contiguous(ty::F64, &[NT, nh, dh]),
+                t_ans.as_mut_ptr().cast(),
+                TensorLayout::new_contiguous(ty::U32, &[NT]),
+                pos.as_ptr().cast(),
+                theta,
+            )
+            .sink(sink, sink_pos)
+            .build();
+            op.launch(&args, &mut [], &ThisThread).unwrap();
+
+            // 汇聚词元按固定位置旋转，其余词元按实际位置旋转
+            for (i, &p) in pos.iter().enumerate() {
+                let p = if i < sink { sink_pos as f64 } else { p as f64 };
+                for k in 0..dh / 2 {
+                    let angle = p * (theta as f64).powf(-2. * k as f64 / dh as f64);
+                    let (sin, cos) = angle.sin_cos();
+                    for h in 0..nh {
+                        let j = (i * nh + h) * dh + 2 * k;
+                        let [a, b] = [t[j], t[j + 1]];
+                        assert!((t_ans[j] - (a * cos - b * sin)).abs() < 1e-12);
+                        assert!((t_ans[j + 1] - (a * sin + b * cos)).abs() < 1e-12);
+                    }
+                }
+            }
+            // 位置为 0 的汇聚词元保持不变
+            if sink_pos == 0 {
+                let n = sink.min(NT) * nh * dh;
+                assert_eq!(t_ans[..n], t[..n]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_contiguous_args() {
+        use crate::{contiguous, contiguous_mut, Alloc, Blob};
+
+        let (nt, nh, dh) = (5, 3, 16);
+        let theta = 1e4f32;
+        let t = (0..nt * nh * dh)
+            .map(|i| (i as f32 * 0.37).cos())
+            .collect::<Vec<_>>();
+        let pos = (0..nt as u32).map(|i| i * 3).collect::<Vec<_>>();
+        let op = Operator::new(&Cpu);
+
+        // 从新分配的连续存储构造参数
+        let mut t_mem: Blob = ThisThread.alloc(size_of_val(&t[..]));
+        t_mem.copy_from_slice(unsafe { t.align_to::<u8>().1 });
+        let mut p_mem: Blob = ThisThread.alloc(size_of_val(&pos[..]));
+        p_mem.copy_from_slice(unsafe { pos.align_to::<u8>().1 });
+        let (t_layout, t_base) = contiguous_mut::<Cpu>(ty::F32, &[nt, nh, dh], &mut t_mem).unwrap();
+        let (p_layout, p_base) = contiguous::<Cpu>(ty::U32, &[nt], &p_mem).unwrap();
+        let args = Args::<Cpu>::builder(t_layout, t_base, p_layout, p_base, theta).build();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        // 与手动构造的参数结果一致
+        let mut t_ans = t.clone();
+        let args = Args::<Cpu>::builder(
+            TensorLayout::new_contiguous(ty::F32, &[nt, nh, dh]),
+            t_ans.as_mut_ptr().cast(),
+            TensorLayout::new_contiguous(ty::U32, &[nt]),
+            pos.as_ptr().cast(),
+            theta,
+        )
+        .build();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        let ans = unsafe { t_mem.align_to::<f32>().1 };
+        assert_eq!(ans, t_ans);
+        assert_ne!(ans, t);
+
+        // 存储不足时报错
+        assert!(contiguous::<Cpu>(ty::U32, &[nt + 1], &p_mem).is_err());
+    }
+
+    #[test]
+    fn test_head_major() {
+        use crate::rearrange::{common_cpu::Operator as Rearrange, Args as RearrangeArgs};
+
+        let (nh, nt, dh) = (3, 5, 8);
+        let theta = 1e4f32;
+        let pos = [7u32, 1, 4, 0, 9];
+        let t = (0..nh * nt * dh)
+            .map(|i| (i as f64 * 0.21).sin())
+            .collect::<Vec<_>>();
+        let op = Operator::new(&Cpu);
+
+        // 直接旋转头优先的 [nh, nt, dh]
+        let mut head_major = t.clone();
+        let args = Args::<Cpu>::builder(
+            TensorLayout::new_contiguous(ty::F64, &[nh, nt, dh]),
+            head_major.as_mut_ptr().cast(),
+            TensorLayout::new_contiguous(ty::U32, &[nt]),
+            pos.as_ptr().cast(),
+            theta,
+        )
+        .head_major()
+        .build();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        // 先重排为 [nt, nh, dh] 再旋转
+        let unit = size_of::<f64>() as isize;
+        let mut token_major = vec![0.0f64; t.len()];
+        Rearrange::new(&Cpu)
+            .launch(
+                &RearrangeArgs::<Cpu> {
+                    dst_layout: TensorLayout::new_contiguous(ty::F64, &[nt, nh, dh]),
+                    dst_base: token_major.as_mut_ptr().cast(),
+                    src_layout: TensorLayout::new(
+                        ty::F64,
+                        &[nt, nh, dh],
+                        &[dh as isize * unit, (nt * dh) as isize * unit, unit],
+                    ),
+                    src_base: t.as_ptr().cast(),
+                    scale: 1.,
+                    bias: 0.,
+                },
+                &mut [],
+                &ThisThread,
+            )
+            .unwrap();
+        let args = Args::<Cpu>::builder(
+            TensorLayout::new_contiguous(ty::F64, &[nt, nh, dh]),
+            token_major.as_mut_ptr().cast(),
+            TensorLayout::new_contiguous(ty::U32, &[nt]),
+            pos.as_ptr().cast(),
+            theta,
+        )
+        .build();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        for h in 0..nh {
+            for i in 0..nt {
+                let a = &head_major[(h * nt + i) * dh..][..dh];
+                let b = &token_major[(i * nh + h) * dh..][..dh];
+                assert_eq!(a, b, "head {h} token {i}");
+            }
+        }
+
+        // 头或词元维度的步长为 0 时拒绝
+        let args = Args::<Cpu>::builder(
+            TensorLayout::new(ty::F64, &[nh, nt, dh], &[0, dh as isize * unit, unit]),
+            head_major.as_mut_ptr().cast(),
+            TensorLayout::new_contiguous(ty::U32, &[nt]),
+            pos.as_ptr().cast(),
+            theta,
+        )
+        .head_major()
+        .build();
+        assert!(op.launch(&args, &mut [], &ThisThread).is_err());
+    }
+
+    #[test]
+    fn test_packed() {
+        let cu_seqlens = [0, 3, 3, 7];
+        let nt = 7;
+        let (nh, dh) = (2, 8);
+        let theta = 1e4f32;
+
+        let seqs = Seq::packed(&cu_seqlens, nt).unwrap();
+        let pos = Operator::build_pos(ty::U32, nt, seqs, &ThisThread);
+        let ([], pos, []) = (unsafe { pos.align_to::<u32>() }) else {
+            panic!()
+        };
+        // 空序列不占据位置，每个序列从 0 开始
+        assert_eq!(pos, [0, 1, 2, 0, 1, 2, 3]);
+
+        // 不从 0 开始、不单调或与词元数不符的 cu_seqlens 被拒绝
+        for (cu_seqlens, nt) in [
+            (&[][..], 0),
+            (&[1, 3][..], 3),
+            (&[0, 3, 2, 7][..], 7),
+            (&[0, 3, 3, 7][..], 6),
+        ] {
+            assert!(Seq::packed(cu_seqlens, nt).is_err());
+        }
+
+        let t = (0..nt * nh * dh)
+            .map(|i| (i as f64 * 0.43).cos())
+            .collect::<Vec<_>>();
+        let mut t_ans = t.clone();
+        let args = Args::<Cpu>::builder(
+            TensorLayout::new_contiguous(ty::F64, &[nt, nh, dh]),
+            t_ans.as_mut_ptr().cast(),
+            TensorLayout::new_contiguous(ty::U32, &[nt]),
+            pos.as_ptr().cast(),
+            theta,
+        )
+        .build();
+        Operator::new(&Cpu)
+            .launch(&args, &mut [], &ThisThread)
+            .unwrap();
+
+        // 每个序列的首个词元位置为 0，保持不变
+        for i in [0, 3] {
+            let n = nh * dh;
+            assert_eq!(t_ans[i * n..][..n], t[i * n..][..n]);
+        }
+        for (i, &p) in pos.iter().enumerate() {
+            for k in 0..dh / 2 {
+                let angle = p as f64 * (theta as f64).powf(-2. * k as f64 / dh as f64);
+                let (sin, cos) = angle.sin_cos();
+                for h in 0..nh {
+                    let j = (i * nh + h) * dh + 2 * k;
+                    let [a, b] = [t[j], t[j + 1]];
+                    assert!((t_ans[j] - (a * cos - b * sin)).abs() < 1e-12);
+                    assert!((t_ans[j + 1] - (a * sin + b * cos)).abs() < 1e-12);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_inverse() {
+        let (nh, theta) = (3, 1e4f32);
+        let pos = [7u32, 1, 4000, 0, 9];
+        let op = Operator::new(&Cpu);
+        // 编译期头维度、任意头维度和单词元三条路径
+        for (nt, dh) in [(5, 64), (5, 12), (1, 64)] {
+            let t = (0..nt * nh * dh)
+                .map(|i| (i as f32 * 0.37).sin())
+                .collect::<Vec<_>>();
+            let mut t_ans = t.clone();
+            let builder = || {
+                Args::<Cpu>::builder(
+                    TensorLayout::new_contiguous(ty::F32, &[nt, nh, dh]),
+                    t_ans.as_mut_ptr().cast(),
+                    TensorLayout::new_contiguous(ty::U32, &[nt]),
+                    pos.as_ptr().cast(),
+                    theta,
+                )
+            };
+            let forward = builder().build();
+            let inverse = builder().inverse().build();
+            op.launch(&forward, &mut [], &ThisThread).unwrap();
+            assert!(t_ans.iter().zip(&t).any(|(a, b)| a != b));
+            op.launch(&inverse, &mut [], &ThisThread).unwrap();
+            for (a, b) in t_ans.iter().zip(&t) {
+                assert!((a - b).abs() < 1e-5, "{a} vs {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_mask() {
+        const NT: usize = 6;
+        let (nh, dh, theta) = (2, 16, 1e4f32);
+        let pos: [u32; NT] = [3, 8, 1, 5, 0, 9];
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f32 * 0.29).cos())
+            .collect::<Vec<_>>();
+        let op = Operator::new(&Cpu);
+        let launch = |mask: Option<&[u8]>, dst: Option<&mut [f32]>| {
+            let mut t = t.clone();
+            let mut builder = Args::<Cpu>::builder(
+                TensorLayout::new_contiguous(ty::F32, &[NT, nh, dh]),
+                t.as_mut_ptr().cast(),
+                TensorLayout::new_contiguous(ty::U32, &[NT]),
+                pos.as_ptr().cast(),
+                theta,
+            );
+            if let Some(mask) = mask {
+                builder = builder.mask(
+                    TensorLayout::new_contiguous(ty::U8, &[NT]),
+                    mask.as_ptr().cast(),
+                )
+            }
+            let args = builder.build();
+            match dst {
+                Some(dst) => op
+                    .launch_into(
+                        &args,
+                        &TensorLayout::new_contiguous(ty::F32, &[NT, nh, dh]),
+                        dst.as_mut_ptr().cast(),
+                    )
+                    .unwrap(),
+                None => op.launch(&args, &mut [], &ThisThread).unwrap(),
+            }
+            t
+        };
+
+        let rotated = launch(None, None);
+        // 掩码交替跳过词元，原地和写入新张量两种方式
+        let mask = [1u8, 0, 1, 0, 1, 0];
+        let mut into = vec![0f32; t.len()];
+        let in_place = launch(Some(&mask[..]), None);
+        launch(Some(&mask[..]), Some(&mut into[..]));
+        for ans in [in_place, into] {
+            for (i, &m) in mask.iter().enumerate() {
+                let range = i * nh * dh..(i + 1) * nh * dh;
+                let expected = if m == 0 {
+                    &t[range.clone()]
+                } else {
+                    &rotated[range.clone()]
+                };
+                assert_eq!(&ans[range], expected);
+            }
+        }
+
+        // 全部跳过时不修改张量
+        assert_eq!(launch(Some(&[0; NT][..]), None), t);
+        // 单词元路径
+        let mut t1 = t[..nh * dh].to_vec();
+        let args = Args::<Cpu>::builder(
+            TensorLayout::new_contiguous(ty::F32, &[1, nh, dh]),
+            t1.as_mut_ptr().cast(),
+            TensorLayout::new_contiguous(ty::U32, &[1]),
+            pos.as_ptr().cast(),
+            theta,
+        )
+        .mask(
+            TensorLayout::new_contiguous(ty::U8, &[1]),
+            mask[1..].as_ptr().cast(),
+        )
+        .build();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+        assert_eq!(t1, t[..nh * dh]);
+        // 掩码形状必须与位置向量相同
+        let mut t_ = t.clone();
+        let args = Args::<Cpu>::builder(
+            TensorLayout::new_contiguous(ty::F32, &[NT, nh, dh]),
+            t_.as_mut_ptr().cast(),
+            TensorLayout::new_contiguous(ty::U32, &[NT]),
+            pos.as_ptr().cast(),
+            theta,
+        )
+        .mask(
+            TensorLayout::new_contiguous(ty::U8, &[NT + 1]),
+            mask.as_ptr().cast(),
+        )
+        .build();
+        assert!(op.launch(&args, &mut [], &ThisThread).is_err());
+    }
+
+    #[test]
+    fn test_degenerate() {
+        let mut op = Operator::new(&Cpu);
+        let pos = [0u32; 4];
+        let mut t = [1f32; 64];
+        let args = |t_shape: &[usize], p_shape: &[usize], t: &mut [f32]| {
+            Args::<Cpu>::builder(
+                TensorLayout::new_contiguous(ty::F32, t_shape),
+                t.as_mut_ptr().cast(),
+                TensorLayout::new_contiguous(ty::U32, p_shape),
+                pos.as_ptr().cast(),
+                1e4,
+            )
+            .build()
+        };
+
+        // 头维度或头数为 0 时拒绝
+        for shape in [[4, 2, 0], [4, 0, 8]] {
+            let args = args(&shape, &[4], &mut t);
+            assert!(op.scheme(&args, 0).is_err());
+            assert!(op.launch(&args, &mut [], &ThisThread).is_err());
+        }
+        // 没有词元或没有批次时什么也不做
+        let args_ = args(&[0, 2, 8], &[0], &mut t);
+        op.scheme(&args_, 0).unwrap();
+        op.launch(&args_, &mut [], &ThisThread).unwrap();
+        let args_ = args(&[0, 4, 2, 8], &[0, 4], &mut t);
+        op.launch(&args_, &mut [], &ThisThread).unwrap();
+        assert!(t.iter().all(|&x| x == 1.));
+    }
+
+    #[test]
+    fn test_batched() {
+        const NB: usize = 3;
+        const NT: usize = 5;
+        let (nh, dh) = (2, 16);
+        let pos = [[0u32, 1, 2, 3, 4], [7, 8, 9, 10, 11], [4, 0, 31, 2, 2]];
+        let t = (0..NB * NT * nh * dh)
+            .map(|i| (i as f64 * 0.37).sin())
+            .collect::<Vec<_>>();
+        let op = Operator::new(&Cpu);
+
+        // 带批次维度一次发射
+        let mut t_batched = t.clone();
+        let args = Args::<Cpu>::builder(
+            TensorLayout::new_contiguous(ty::F64, &[NB, NT, nh, dh]),
+            t_batched.as_mut_ptr().cast(),
+            TensorLayout::new_contiguous(ty::U32, &[NB, NT]),
+            pos.as_ptr().cast(),
+            1e4,
+        )
+        .build();
+        op.launch(&args, &mut [], &ThisThread).unwrap();
+
+        // 逐个序列发射
+        let mut t_each = t;
+        for (b, pos) in pos.iter().enumerate() {
+            let seq = &mut t_each[b * NT * nh * dh..][..NT * nh * dh];
+            let args = Args::<Cpu>::builder(
+                TensorLayout::new_contiguous(ty::F64, &[NT, nh, dh]),
+                seq.as_mut_ptr().cast(),
+                TensorLayout::new_contiguous(ty::U32, &[NT]),
+                pos.as_ptr().cast(),
+                1e4,
+            )
+            .build();
+            op.launch(&args, &mut [], &ThisThread).unwrap();
+        }
+        assert_eq!(t_batched, t_each);
+    }
+
+    #[test]
+    fn test_error_category() {
+        use crate::LaunchErrorCategory;
+
+        let (nt, nh, dh) = (3, 2, 8);
+        let pos = [0u32; 3];
+        let mut t = vec![0f32; nt * nh * dh * 2];
+        // 元素不连续，CPU 实现不支持
+        let args = Args::<Cpu>::builder(
+            TensorLayout::new(
+                ty::F32,
+                &[nt, nh, dh],
+                &[(nh * dh * 8) as _, (dh * 8) as _, 8],
+            ),
+            t.as_mut_ptr().cast(),
+            TensorLayout::new_contiguous(ty::U32, &[nt]),
+            pos.as_ptr().cast(),
+            1e4,
+        )
+        .build();
+        let e = Operator::new(&Cpu)
+            .launch(&args, &mut [], &ThisThread)
+            .unwrap_err();
+        assert_eq!(e.category(), LaunchErrorCategory::Validation);
+    }
+
+    #[test]
+    fn test_unsupported_dtypes() {
+        let (nt, nh, dh) = (3, 2, 8);
+        let pos = [0u64; 3];
+        let mut t = vec![0f64; nt * nh * dh];
+        let mut op = Operator::new(&Cpu);
+        // 任何后端都不支持的组合在 meta 中即被拒绝，不会到达计算分支
+        for (dt_t, dt_p) in [(ty::BF16, ty::U32), (ty::F32, ty::U8), (ty::F32, ty::U16)] {
+            let args = Args::<Cpu>::builder(
+                TensorLayout::new_contiguous(dt_t, &[nt, nh, dh]),
+                t.as_mut_ptr().cast(),
+                TensorLayout::new_contiguous(dt_p, &[nt]),
+                pos.as_ptr().cast(),
+                1e4,
+            )
+            .build();
+            assert!(op.scheme(&args, 0).is_err());
+            assert!(op.launch(&args, &mut [], &ThisThread).is_err());
+        }
+    }
+}
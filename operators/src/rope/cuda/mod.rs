@@ -1,11 +1,13 @@
-use super::{args::Meta, fill_pos, Args, Rope, Seq, SinCosTable};
+use super::{args::Meta, fill_pos, Args, Rope, RopeScaling, Seq, SinCosTable};
 use crate::{
+    args_not_support,
     cuda::{Gpu, Handle, ModuleBox},
-    get_static, shape_not_support, strides_not_support, type_not_support, Blob, ByteOf,
-    LaunchError, QueueAlloc, SchemeError,
+    get_static, rank_not_support, shape_not_support, strides_not_support, type_not_support,
+    utils::debug_check_tensor,
+    Blob, ByteOf, LaunchError, QueueAlloc, SchemeError, SchemePlan,
 };
 use digit_layout::{types as ty, DigitLayout};
-use std::{ffi::CString, sync::Arc};
+use std::{ffi::CString, slice::from_raw_parts_mut, sync::Arc};
 
 pub struct Operator {
     _handle: Arc<Handle>,
@@ -29,6 +31,7 @@ impl Rope<Gpu> for Operator {
     {
         SinCosTable {
             nctx: 0,
+            theta: 1e4,
             mem: queue_alloc.alloc(0),
         }
     }
@@ -45,8 +48,14 @@ impl Rope<Gpu> for Operator {
     {
         let mut host = Blob::new(dt.nbytes() * nt);
         match dt {
-            ty::U32 => fill_pos(host.as_mut_ptr().cast::<u32>(), nt, iter),
-            ty::U64 => fill_pos(host.as_mut_ptr().cast::<u64>(), nt, iter),
+            ty::U32 => fill_pos(
+                unsafe { from_raw_parts_mut(host.as_mut_ptr().cast::<u32>(), nt) },
+                iter,
+            ),
+            ty::U64 => fill_pos(
+                unsafe { from_raw_parts_mut(host.as_mut_ptr().cast::<u64>(), nt) },
+                iter,
+            ),
             _ => todo!(),
         }
 
@@ -70,12 +79,30 @@ impl crate::Operator for Operator {
         }
     }
 
+    #[inline]
+    fn supported_dtypes() -> &'static [DigitLayout] {
+        &[ty::F16]
+    }
+
     fn scheme(
         &mut self,
-        _args: &Self::Args,
-        _max_workspace_size: usize,
+        args: &Self::Args,
+        max_workspace_size: usize,
     ) -> Result<usize, SchemeError> {
-        Ok(0)
+        crate::Operator::plan(self, args, max_workspace_size).map(|plan| plan.workspace_size)
+    }
+
+    fn plan(
+        &mut self,
+        args: &Self::Args,
+        _max_workspace_size: usize,
+    ) -> Result<SchemePlan, SchemeError> {
+        let _meta = args.meta()?;
+        Ok(SchemePlan {
+            workspace_size: 0,
+            align: 1,
+            algorithm: "cuda",
+        })
     }
 
     fn launch<QA>(
@@ -90,14 +117,32 @@ impl crate::Operator for Operator {
         let Meta {
             dt_t, dt_p, nt, dh, ..
         } = args.meta()?;
+        if args.t_layout.ndim() != 3 {
+            Err(rank_not_support("cuda: batched rope"))?;
+        }
+        if !args.theta_groups.is_empty() {
+            Err(args_not_support("cuda: theta groups"))?;
+        }
+        if args.scaling != RopeScaling::None {
+            Err(args_not_support("cuda: rope scaling"))?;
+        }
+        if args.sink.tokens > 0 {
+            Err(args_not_support("cuda: attention sink"))?;
+        }
+        if args.inverse {
+            Err(args_not_support("cuda: inverse rope"))?;
+        }
+        if args.mask.is_some() {
+            Err(args_not_support("cuda: rope mask"))?;
+        }
 
         if dt_t != ty::F16 {
-            return Err(type_not_support("").into());
+            Err(type_not_support(""))?;
         }
         let name = match dt_p {
             ty::U32 => POS_U32,
             ty::U64 => POS_U64,
-            _ => return Err(type_not_support("").into()),
+            _ => Err(type_not_support(""))?,
         };
 
         let Args {
@@ -123,10 +168,16 @@ impl crate::Operator for Operator {
             st sh sd
             sp
         }
+        debug_check_tensor("t", &args.t_layout, args.t_base);
+        debug_check_tensor("p", &args.p_layout, args.p_base);
+        // 没有词元时无事可做
+        if nt == 0 {
+            return Ok(());
+        }
 
         let unit = dt_t.nbytes() as isize;
         if sd != unit || sp != dt_p.nbytes() as isize {
-            return Err(strides_not_support("").into());
+            Err(strides_not_support(""))?;
         }
 
         let dh = dh / 2;
@@ -135,7 +186,7 @@ impl crate::Operator for Operator {
         let params = cuda::params![t_base, st, sh, p_base, theta];
 
         if self.max_threads_block % dh != 0 {
-            return Err(shape_not_support("").into());
+            Err(shape_not_support(""))?;
         }
 
         let max_nh_l = (self.max_threads_block / dh).min(nh);
@@ -183,7 +234,7 @@ extern "C" __global__ void {POS_U64}(
 
 #[cfg(test)]
 mod test {
-    use super::{Args, Gpu, Operator, POS_U32, POS_U64};
+    use super::{Args, Gpu, Operator, RopeScaling, POS_U32, POS_U64};
     use crate::{Hardware, Operator as _, TensorLayout};
     use digit_layout::{
         types::{F16, F64, U32},
@@ -203,6 +254,12 @@ mod test {
             cos_layout: TensorLayout::new_dyn(dt_t, &[dyn_(); 2], &[dyn_(); 2]),
             cos_base: null(),
             theta: 0.,
+            theta_groups: Vec::new(),
+            scaling: RopeScaling::None,
+            table_step: 1,
+            sink: Default::default(),
+            inverse: false,
+            mask: None,
         }
     }
 
@@ -227,6 +284,12 @@ mod test {
             cos_layout: TensorLayout::new_contiguous(dt_t, &[0, dh]),
             cos_base: null(),
             theta,
+            theta_groups: Vec::new(),
+            scaling: RopeScaling::None,
+            table_step: 1,
+            sink: Default::default(),
+            inverse: false,
+            mask: None,
         }
     }
 
@@ -254,6 +317,49 @@ mod test {
         })
     }
 
+    #[test]
+    fn test_module_reuse() {
+        use crate::cuda::cast_load;
+        use half::f16;
+        use std::sync::Arc;
+
+        let Some(gpu) = Gpu::init() else {
+            return;
+        };
+
+        // 模块按名字和计算能力缓存在句柄上，同一设备上的算子实例共享
+        let op = Operator::new(&gpu);
+        assert!(Arc::ptr_eq(&op.module, &Operator::new(&gpu).module));
+
+        const NT: usize = 3;
+        let (nh, dh) = (4, 64);
+        let t = vec![1.0f64; NT * nh * dh];
+        let p: [u32; NT] = [0, 1, 2];
+        gpu.apply(|ctx| {
+            let stream = ctx.stream();
+            #[cfg(use_nvidia)]
+            let rt = &stream;
+            #[cfg(use_iluvatar)]
+            let rt = ctx;
+            let mut t = cast_load(&t, f16::from_f64, &stream);
+            let p = rt.from_host(&p);
+            for _ in 0..4 {
+                let args = args(
+                    F16,
+                    U32,
+                    NT,
+                    nh,
+                    dh,
+                    1e4,
+                    t.as_mut_ptr().cast(),
+                    p.as_ptr().cast(),
+                );
+                op.launch(&args, &mut [], &stream).unwrap();
+                assert!(Arc::ptr_eq(&op.module, &Operator::new(&gpu).module));
+            }
+        });
+    }
+
     #[test]
     fn test_compute() {
         use super::super::common_cpu::Operator as RefOp;
@@ -1,9 +1,11 @@
-use super::{args::Meta, fill_pos, Args, Rope, Seq, SinCosTable};
+use super::{args::Meta, fill_pos, Args, Rope, RopeScaling, Seq, SinCosTable};
 use crate::{
-    get_static, infini::Device, Blob, ByteOf, LaunchError, QueueAlloc, SchemeError, Workspace,
+    args_not_support, get_static, infini::Device, rank_not_support, Blob, ByteOf, LaunchError,
+    QueueAlloc, SchemeError, SchemePlan, Workspace,
 };
 use digit_layout::{types as ty, DigitLayout};
 use infini_op::{infiniop, AsRaw, Descriptor};
+use std::slice::from_raw_parts_mut;
 
 pub struct Operator(Device);
 
@@ -39,7 +41,11 @@ impl Rope<Device> for Operator {
         let mut mem = queue_alloc.alloc(size_of_val(host.as_slice()));
         queue_alloc.queue().memcpy_h2d(&mut mem, &host);
         queue_alloc.queue().synchronize();
-        SinCosTable { nctx, mem }
+        SinCosTable {
+            nctx,
+            theta: 1e4,
+            mem,
+        }
     }
 
     fn build_pos<I, QA>(dt: DigitLayout, nt: usize, iter: I, queue_alloc: &QA) -> QA::DevMem
@@ -49,8 +55,14 @@ impl Rope<Device> for Operator {
     {
         let mut host = Blob::new(dt.nbytes() * nt);
         match dt {
-            ty::U32 => fill_pos(host.as_mut_ptr().cast::<u32>(), nt, iter),
-            ty::U64 => fill_pos(host.as_mut_ptr().cast::<u64>(), nt, iter),
+            ty::U32 => fill_pos(
+                unsafe { from_raw_parts_mut(host.as_mut_ptr().cast::<u32>(), nt) },
+                iter,
+            ),
+            ty::U64 => fill_pos(
+                unsafe { from_raw_parts_mut(host.as_mut_ptr().cast::<u64>(), nt) },
+                iter,
+            ),
             _ => todo!(),
         }
 
@@ -71,13 +83,31 @@ impl crate::Operator for Operator {
         Self(node.clone())
     }
 
+    #[inline]
+    fn supported_dtypes() -> &'static [DigitLayout] {
+        &[ty::F16]
+    }
+
     #[inline]
     fn scheme(
         &mut self,
-        _args: &Self::Args,
-        _max_workspace_size: usize,
+        args: &Self::Args,
+        max_workspace_size: usize,
     ) -> Result<usize, SchemeError> {
-        Ok(0)
+        crate::Operator::plan(self, args, max_workspace_size).map(|plan| plan.workspace_size)
+    }
+
+    fn plan(
+        &mut self,
+        args: &Self::Args,
+        _max_workspace_size: usize,
+    ) -> Result<SchemePlan, SchemeError> {
+        let _meta = args.meta()?;
+        Ok(SchemePlan {
+            workspace_size: 0,
+            align: 1,
+            algorithm: "infini",
+        })
     }
 
     fn launch<QA>(
@@ -90,6 +120,27 @@ impl crate::Operator for Operator {
         QA: QueueAlloc<Hardware = Self::Hardware>,
     {
         let Meta { dt_t, dt_p, .. } = args.meta()?;
+        if args.t_layout.ndim() != 3 {
+            Err(rank_not_support("infini: batched rope"))?;
+        }
+        if !args.theta_groups.is_empty() {
+            Err(args_not_support("infini: theta groups"))?;
+        }
+        if args.scaling != RopeScaling::None {
+            Err(args_not_support("infini: rope scaling"))?;
+        }
+        if args.table_step != 1 {
+            Err(args_not_support("infini: sin/cos table interpolation"))?;
+        }
+        if args.sink.tokens > 0 {
+            Err(args_not_support("infini: attention sink"))?;
+        }
+        if args.inverse {
+            Err(args_not_support("infini: inverse rope"))?;
+        }
+        if args.mask.is_some() {
+            Err(args_not_support("infini: rope mask"))?;
+        }
         let Args {
             t_layout,
             t_base,
@@ -123,6 +174,10 @@ impl crate::Operator for Operator {
             sns sds
             snc sdc
         }
+        // 没有词元时无事可做
+        if nctx == 0 {
+            return Ok(());
+        }
 
         let t = infini_op::Tensor::new(dt_t, [nctx, nh, dh], [ncs, nhs, dhs]);
         let p = infini_op::Tensor::new(dt_p, [nctx], [ps]);
@@ -165,7 +220,7 @@ impl crate::Operator for Operator {
 
 #[cfg(test)]
 mod test {
-    use super::{Args, Device, Operator};
+    use super::{Args, Device, Operator, RopeScaling};
     use crate::{rope::Rope, Hardware, Operator as _, TensorLayout};
     use digit_layout::{types as ty, DigitLayout};
     use std::ptr::null;
@@ -183,6 +238,12 @@ mod test {
             cos_layout: TensorLayout::new_dyn(ty::F32, &[dyn_(); 2], &[dyn_(); 2]),
             cos_base: null(),
             theta: 0.,
+            theta_groups: Vec::new(),
+            scaling: RopeScaling::None,
+            table_step: 1,
+            sink: Default::default(),
+            inverse: false,
+            mask: None,
         }
     }
 
@@ -217,6 +278,12 @@ mod test {
             cos_layout: TensorLayout::new_contiguous(ty::F32, &[nt, dh]),
             cos_base,
             theta,
+            theta_groups: Vec::new(),
+            scaling: RopeScaling::None,
+            table_step: 1,
+            sink: Default::default(),
+            inverse: false,
+            mask: None,
         }
     }
 
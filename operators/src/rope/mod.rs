@@ -8,51 +8,129 @@ pub mod infini;
 pub mod opencl;
 
 mod args;
-pub use args::Args;
+pub use args::{Args, ArgsBuilder, RopeScaling, Sink, ThetaGroup};
+
+use crate::{shape_mismatch, SchemeError};
 
 crate::op_trait! { Rope
-    /// 生成 sincos 表（[2, n, dh]）。
+    /// 生成 sincos 表（[2, n, dh]），rope 底数为 1e4。
     fn build_sincos<QA>(dt: digit_layout::DigitLayout, nctx: usize, dh: usize, queue_alloc: &QA) -> SinCosTable<QA::DevMem>
         where QA: crate::QueueAlloc<Hardware = Self::Hardware>;
     /// 为多个请求生成位置向量（[nt]）。
+    ///
+    /// 有符号位置类型的剩余位置填充为 -1，表示不旋转。
     fn build_pos<I, QA>(dt: digit_layout::DigitLayout, nt: usize, iter: I, queue_alloc: &QA) -> QA::DevMem
         where I: IntoIterator<Item = Seq>,
               QA: crate::QueueAlloc<Hardware = Self::Hardware>;
 }
 
+/// 一个请求在批次中占据的位置：从 `pos` 开始的 `len` 个连续位置。
+#[derive(Clone, Copy, Debug)]
 pub struct Seq {
     pub pos: usize,
     pub len: usize,
 }
 
+impl Seq {
+    /// 由累积序列长度 `cu_seqlens`（[nseq + 1]，从 0 开始单调不减，以 `nt` 结束）
+    /// 生成打包批次中的各个序列，每个序列的位置从 0 开始，与 [`Rope::build_pos`] 配合使用。
+    ///
+    /// 长度为 0 的序列不占据位置。`cu_seqlens` 不满足上述要求时返回错误。
+    pub fn packed(
+        cu_seqlens: &[usize],
+        nt: usize,
+    ) -> Result<impl Iterator<Item = Self> + '_, SchemeError> {
+        let (Some(&0), Some(&last)) = (cu_seqlens.first(), cu_seqlens.last()) else {
+            return Err(shape_mismatch(format!(
+                "cu_seqlens {cu_seqlens:?} must start with 0"
+            )));
+        };
+        if let Some(i) = cu_seqlens.windows(2).position(|w| w[0] > w[1]) {
+            return Err(shape_mismatch(format!(
+                "cu_seqlens must be non-decreasing, but cu_seqlens[{i}] = {} > cu_seqlens[{}] = {}",
+                cu_seqlens[i],
+                i + 1,
+                cu_seqlens[i + 1]
+            )));
+        }
+        if last != nt {
+            return Err(shape_mismatch(format!(
+                "cu_seqlens ends with {last}, but there are {nt} tokens"
+            )));
+        }
+        Ok(cu_seqlens.windows(2).map(|w| Self {
+            pos: 0,
+            len: w[1] - w[0],
+        }))
+    }
+}
+
+/// 预先计算的 sin/cos 表，sin 和 cos 各为 [nctx, dh]，依次连续存储。
+///
+/// 表在 launch 中只读，同一张表可以通过 [`ArgsBuilder::table`] 以引用传给多个算子的多次 launch，
+/// 例如同一层的 Q 和 K 或者所有层共用一张表。
 pub struct SinCosTable<Mem> {
     pub nctx: usize,
+    /// 生成表时使用的 rope 底数，使用表的参数必须使用相同的底数。
+    pub theta: f32,
     pub mem: Mem,
 }
 
-trait PosTy {
+impl<Mem> SinCosTable<Mem> {
+    /// 表覆盖的位置数量，位置必须小于此值。
+    ///
+    /// 以 [`ArgsBuilder::table_step`] 插值时第 `i` 行对应位置 `i * step`，
+    /// 可用的位置上限相应放大为 `(capacity - 1) * step`。
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.nctx
+    }
+}
+
+/// 位置向量的元素类型，实现了 `u32`、`u64`、`i32` 和 `i64`。
+pub trait PosTy {
+    /// 序列之外的填充位置。
+    const PADDING: Self;
     fn from_usize(p: usize) -> Self;
 }
 
-impl PosTy for u32 {
-    fn from_usize(p: usize) -> Self {
-        p as _
-    }
+macro_rules! impl_pos_ty {
+    ($( $ty:ty: $padding:literal )+) => {
+        $(
+            impl PosTy for $ty {
+                const PADDING: Self = $padding;
+                fn from_usize(p: usize) -> Self {
+                    p as _
+                }
+            }
+        )+
+    };
 }
 
-impl PosTy for u64 {
-    fn from_usize(p: usize) -> Self {
-        p as _
-    }
+impl_pos_ty! {
+    u32: 0
+    u64: 0
+    i32: -1
+    i64: -1
+}
+
+/// 计算 `nt` 个 `T` 类型位置占用的字节数，溢出时返回错误而不是 panic。
+fn pos_size<T>(nt: usize) -> Result<usize, crate::SchemeError> {
+    std::alloc::Layout::array::<T>(nt)
+        .map(|layout| layout.size())
+        .map_err(|_| crate::shape_not_support(format!("{nt} positions overflow")))
 }
 
-fn fill_pos<T, I>(ptr: *mut T, len: usize, iter: I)
+/// 在主机上为多个请求生成位置向量，依次写入每个序列的位置。
+///
+/// 这是 [`Rope::build_pos`] 的主机部分，不分配设备存储，便于自行上传。
+/// `out` 中超出所有序列总长的部分填充为 [`PosTy::PADDING`]，多出的位置被丢弃。
+pub fn fill_pos<T, I>(out: &mut [T], iter: I)
 where
     T: PosTy,
     I: IntoIterator<Item = Seq>,
 {
-    iter.into_iter()
-        .flat_map(|seq| seq.pos..seq.pos + seq.len)
-        .zip(unsafe { std::slice::from_raw_parts_mut(ptr, len) })
-        .for_each(|(pos, out)| *out = T::from_usize(pos))
+    let mut pos = iter.into_iter().flat_map(|seq| seq.pos..seq.pos + seq.len);
+    out.iter_mut()
+        .for_each(|out| *out = pos.next().map_or(T::PADDING, T::from_usize))
 }
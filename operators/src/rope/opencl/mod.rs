@@ -1,21 +1,48 @@
-﻿use super::{args::Meta, fill_pos, Args, Rope, Seq, SinCosTable};
+﻿use super::{
+    args::{Meta, Strides},
+    fill_pos, pos_size, Args, PosTy, Rope, RopeScaling, Seq, SinCosTable,
+};
 use crate::{
-    get_static,
-    opencl::{ClDevice, CodeGen, KernelCache, CL2_0},
-    shape_not_support, strides_not_support, ByteOf, LaunchError, QueueAlloc,
-    SchemeDiversity::Low as LowDiversity,
-    SchemeError,
+    args_not_support, execution_failed, get_static,
+    opencl::{event_duration, kernel_name, ClDevice, CodeGen, KernelCache, KernelWorkGroup, CL2_0},
+    shape_not_support, strides_not_support, type_not_support,
+    utils::debug_check_tensor,
+    Blob, ByteOf, LaunchError, QueueAlloc,
+    SchemeDiversity::{High as HighDiversity, Low as LowDiversity, Medium as MediumDiversity},
+    SchemeError, SchemePlan,
+};
+use clrt::{
+    bindings::{clReleaseEvent, clWaitForEvents, cl_event, cl_int, cl_uint},
+    CommandQueue, Context, Kernel, SvmBlob, SvmByte,
 };
-use clrt::{bindings::cl_int, Context};
 use digit_layout::{types as Ty, DigitLayout};
 use lru::LruCache;
 use std::sync::Mutex;
-use std::{alloc::Layout, iter::zip};
+use std::{
+    ffi::CStr,
+    fs, io,
+    mem::ManuallyDrop,
+    num::NonZeroUsize,
+    ops::Range,
+    path::Path,
+    ptr::null_mut,
+    slice::from_raw_parts,
+    time::{Duration, Instant},
+};
 
 pub struct Operator {
     ctx: Context,
     max_group_size: usize,
+    fp64: bool,
+    #[cfg(any(use_cpu, test))]
+    cpu_fallback: bool,
+    autotune: bool,
+    tuned: Mutex<LruCache<TuneKey, usize>>,
+    profiling: bool,
+    kernel_time: Mutex<Option<Duration>>,
     schemes: Mutex<LruCache<SchemeKey, KernelCache>>,
+    host_freq: bool,
+    freqs: Mutex<LruCache<FreqKey, SvmBlob>>,
 }
 
 impl Rope<ClDevice> for Operator {
@@ -30,56 +57,95 @@ impl Rope<ClDevice> for Operator {
     {
         SinCosTable {
             nctx: 0,
+            theta: 1e4,
             mem: _queue_alloc.alloc(0),
         }
     }
 
     fn build_pos<I, QA>(
-        _dt: digit_layout::DigitLayout,
-        _nt: usize,
-        _iter: I,
-        _queue_alloc: &QA,
+        dt: digit_layout::DigitLayout,
+        nt: usize,
+        iter: I,
+        queue_alloc: &QA,
     ) -> QA::DevMem
     where
         I: IntoIterator<Item = Seq>,
         QA: QueueAlloc<Hardware = Self::Hardware>,
     {
-        match _dt {
-            Ty::U32 => {
-                let mut blob = _queue_alloc.alloc(Layout::array::<u32>(_nt).unwrap().size());
-                let mut host = vec![0u32; _nt];
-                fill_pos(host.as_mut_ptr().cast::<u32>(), _nt, _iter);
-                let queue = _queue_alloc.queue();
-                let mut map = queue.map_mut(&mut blob, false);
-                let ([], mem, []) = (unsafe { map.align_to_mut::<u32>() }) else {
-                    panic!()
-                };
-                for (dst, src) in zip(mem, &host) {
-                    *dst = *src as _;
-                }
-                queue.unmap(map);
-                blob
-            }
-            Ty::U64 => {
-                let mut blob = _queue_alloc.alloc(Layout::array::<u64>(_nt).unwrap().size());
-                let mut host = vec![0u64; _nt];
-                fill_pos(host.as_mut_ptr().cast::<u32>(), _nt, _iter);
-                let queue = _queue_alloc.queue();
-                let mut map = queue.map_mut(&mut blob, false);
-                let ([], mem, []) = (unsafe { map.align_to_mut::<u64>() }) else {
-                    panic!()
-                };
-                for (dst, src) in zip(mem, &host) {
-                    *dst = *src as _;
-                }
-                queue.unmap(map);
-                blob
-            }
-            _ => panic!("Unsupported digit layout type"),
+        Self::try_build_pos(dt, nt, iter, queue_alloc).unwrap()
+    }
+}
+
+fn upload_pos<T, I, QA>(nt: usize, iter: I, queue_alloc: &QA) -> Result<QA::DevMem, SchemeError>
+where
+    T: PosTy,
+    I: IntoIterator<Item = Seq>,
+    QA: QueueAlloc<Hardware = ClDevice>,
+{
+    let mut blob = queue_alloc.alloc(pos_size::<T>(nt)?);
+    let queue = queue_alloc.queue();
+    let mut map = queue.map_mut(&mut blob, false);
+    let ([], mem, []) = (unsafe { map.align_to_mut::<T>() }) else {
+        panic!()
+    };
+    fill_pos(mem, iter);
+    queue.unmap(map);
+    Ok(blob)
+}
+
+fn fill_host<T, I>(nt: usize, iter: I) -> Result<Blob, SchemeError>
+where
+    T: PosTy,
+    I: IntoIterator<Item = Seq>,
+{
+    let mut host = Blob::new(pos_size::<T>(nt)?);
+    let ([], mem, []) = (unsafe { host.align_to_mut::<T>() }) else {
+        panic!()
+    };
+    fill_pos(mem, iter);
+    Ok(host)
+}
+
+/// [`Operator::build_pos_async`] 发起的位置向量上传。
+///
+/// 上传完成前主机上暂存的位置数据必须保持有效，因此由此结构体一并持有，
+/// 在 [`PosUpload::wait`] 或析构时等待完成后释放。
+pub struct PosUpload<M> {
+    blob: ManuallyDrop<M>,
+    host: ManuallyDrop<Blob>,
+    event: cl_event,
+}
+
+impl<M> PosUpload<M> {
+    /// 上传完成的事件，可加入其他命令的等待列表，所有权仍属于此结构体。
+    #[inline]
+    pub fn event(&self) -> cl_event {
+        self.event
+    }
+
+    /// 等待上传完成，取出位置向量。
+    pub fn wait(self) -> M {
+        let mut this = ManuallyDrop::new(self);
+        this.finish();
+        unsafe { ManuallyDrop::take(&mut this.blob) }
+    }
+
+    fn finish(&mut self) {
+        unsafe {
+            clWaitForEvents(1, &self.event);
+            clReleaseEvent(self.event);
+            ManuallyDrop::drop(&mut self.host)
         }
     }
 }
 
+impl<M> Drop for PosUpload<M> {
+    fn drop(&mut self) {
+        self.finish();
+        unsafe { ManuallyDrop::drop(&mut self.blob) }
+    }
+}
+
 impl crate::Operator for Operator {
     type Hardware = ClDevice;
     type TopoNode = ClDevice;
@@ -97,16 +163,49 @@ impl crate::Operator for Operator {
         Self {
             ctx,
             max_group_size,
+            fp64: node.support_fp64(),
+            #[cfg(any(use_cpu, test))]
+            cpu_fallback: false,
+            autotune: false,
+            tuned: node.new_cache(HighDiversity),
+            profiling: false,
+            kernel_time: Default::default(),
             schemes: node.new_cache(LowDiversity),
+            host_freq: false,
+            freqs: node.new_cache(MediumDiversity),
         }
     }
 
+    /// F64 还取决于设备是否支持双精度，不在此列出。
+    #[inline]
+    fn supported_dtypes() -> &'static [DigitLayout] {
+        &[Ty::F16, Ty::F32]
+    }
+
     fn scheme(
         &mut self,
-        _args: &Self::Args,
-        _max_workspace_size: usize,
+        args: &Self::Args,
+        max_workspace_size: usize,
     ) -> Result<usize, SchemeError> {
-        Ok(0)
+        crate::Operator::plan(self, args, max_workspace_size).map(|plan| plan.workspace_size)
+    }
+
+    fn plan(
+        &mut self,
+        args: &Self::Args,
+        _max_workspace_size: usize,
+    ) -> Result<SchemePlan, SchemeError> {
+        let Meta { dt_t, dt_p, .. } = args.meta()?;
+        self.check_dt(dt_t, dt_p)?;
+        Ok(SchemePlan {
+            workspace_size: 0,
+            align: 1,
+            algorithm: if self.autotune {
+                "opencl-autotune"
+            } else {
+                "opencl"
+            },
+        })
     }
 
     fn launch<QA>(
@@ -118,113 +217,815 @@ impl crate::Operator for Operator {
     where
         QA: QueueAlloc<Hardware = Self::Hardware>,
     {
+        self.launch_on(args, queue_alloc.queue())
+    }
+}
+
+impl Operator {
+    /// [`Rope::build_pos`] 的可失败版本，`nt` 过大或类型不支持时返回错误。
+    pub fn try_build_pos<I, QA>(
+        dt: DigitLayout,
+        nt: usize,
+        iter: I,
+        queue_alloc: &QA,
+    ) -> Result<QA::DevMem, SchemeError>
+    where
+        I: IntoIterator<Item = Seq>,
+        QA: QueueAlloc<Hardware = ClDevice>,
+    {
+        match dt {
+            Ty::U32 => upload_pos::<u32, _, _>(nt, iter, queue_alloc),
+            Ty::U64 => upload_pos::<u64, _, _>(nt, iter, queue_alloc),
+            Ty::I32 => upload_pos::<i32, _, _>(nt, iter, queue_alloc),
+            Ty::I64 => upload_pos::<i64, _, _>(nt, iter, queue_alloc),
+            _ => Err(type_not_support(format!("position type {dt}"))),
+        }
+    }
+
+    /// [`Rope::build_pos`] 的非阻塞版本，在主机上生成位置后发起上传即返回。
+    ///
+    /// 上传在分配器绑定的队列上执行，可与主机上的其他工作重叠，
+    /// 使用位置向量前需要 [`PosUpload::wait`]，或让后续命令等待 [`PosUpload::event`]。
+    pub fn build_pos_async<I, QA>(
+        dt: DigitLayout,
+        nt: usize,
+        iter: I,
+        queue_alloc: &QA,
+    ) -> Result<PosUpload<QA::DevMem>, SchemeError>
+    where
+        I: IntoIterator<Item = Seq>,
+        QA: QueueAlloc<Hardware = ClDevice>,
+    {
+        let host = match dt {
+            Ty::U32 => fill_host::<u32, _>(nt, iter),
+            Ty::U64 => fill_host::<u64, _>(nt, iter),
+            Ty::I32 => fill_host::<i32, _>(nt, iter),
+            Ty::I64 => fill_host::<i64, _>(nt, iter),
+            _ => Err(type_not_support(format!("position type {dt}"))),
+        }?;
+
+        let mut blob = queue_alloc.alloc(host.len());
+        let mut event = null_mut();
+        let src = unsafe { from_raw_parts(host.as_ptr().cast::<SvmByte>(), host.len()) };
+        queue_alloc
+            .queue()
+            .memcpy(&mut *blob, src, Some(&mut event));
+        Ok(PosUpload {
+            blob: ManuallyDrop::new(blob),
+            host: ManuallyDrop::new(host),
+            event,
+        })
+    }
+
+    /// 在指定的命令队列上发射，而不是分配器绑定的队列，以便与其他队列上的计算重叠。
+    pub fn launch_on(
+        &self,
+        args: &Args<ClDevice>,
+        queue: &CommandQueue,
+    ) -> Result<(), LaunchError> {
         let Meta {
-            dt_t, dt_p, nt, dh, ..
+            dt_t,
+            dt_p,
+            nb,
+            nt,
+            dh,
+            ..
         } = args.meta()?;
+        self.check_dt(dt_t, dt_p)?;
 
-        let Args {
-            t_layout,
-            t_base,
-            p_layout,
-            p_base,
-            theta,
-            ..
-        } = args;
-        let &[_, nh, _] = t_layout.shape() else {
-            unreachable!()
-        };
-        let &[st, sh, sd] = t_layout.strides() else {
-            unreachable!()
-        };
-        let &[sp] = p_layout.strides() else {
-            unreachable!()
-        };
+        let Args { t_base, p_base, .. } = args;
+        let Strides {
+            nh,
+            t: [sb, st, sh, sd],
+            p: [spb, sp],
+        } = args.strides();
 
         get_static! {
-            nt nh dh
-            st sh sd
-            sp
+            nb nt nh dh
+            sb st sh sd
+            spb sp
+        }
+        debug_check_tensor("t", &args.t_layout, args.t_base);
+        debug_check_tensor("p", &args.p_layout, args.p_base);
+        // 没有词元时无事可做
+        if nb == 0 || nt == 0 {
+            return Ok(());
         }
 
         let unit = dt_t.nbytes() as isize;
         if sd != unit || sp != dt_p.nbytes() as isize {
-            return Err(strides_not_support("").into());
+            return self.fallback(args, queue, strides_not_support(""));
+        };
+        // 提供了 sin/cos 表时按表旋转，theta 和缩放不再起作用
+        if has_table(args) {
+            return self.launch_table(args, queue, [nb, nt, nh, dh / 2], [sb, st, sh, spb]);
+        }
+        // 每组头单独发射，使用各自的 theta
+        let groups = args.theta_groups(nh)?;
+        // 核函数以 factor 为 0 表示不缩放
+        let scaling = match args.scaling {
+            RopeScaling::None => [0.; 4],
+            RopeScaling::Llama3 {
+                factor,
+                low_freq_factor,
+                high_freq_factor,
+                original_ctx,
+            } => [
+                factor,
+                low_freq_factor,
+                high_freq_factor,
+                original_ctx as f32,
+            ],
         };
 
         let dh = dh / 2;
+        let head = sh;
         let st = (st / unit / 2) as i32;
+        // 超出序列长度的汇聚词元数不影响结果，截断以免溢出
+        let sink_tokens = args.sink.tokens.min(nt) as cl_uint;
+        let sink_pos = args.sink.pos as f32;
+        let sign = args.sign();
+        let (mask_base, [smb, sm], use_mask) = mask_args(args)?;
         let sh = (sh / unit / 2) as i32;
 
+        if nt == 1 {
+            return self.launch_token(args, queue, &groups, scaling, [nb, dh], [sb, head, spb]);
+        }
         if self.max_group_size % dh != 0 {
-            return Err(shape_not_support("").into());
+            return self.fallback(args, queue, shape_not_support(""));
+        }
+
+        let name = kernel_name("rope", dt_t)?;
+        let key = self.cache_kernel(dt_t, dt_p)?;
+        let mut rope = self
+            .schemes
+            .lock()
+            .unwrap()
+            .get(&key)
+            .unwrap()
+            .take_guard(&name)
+            .ok_or_else(|| execution_failed(format!("opencl: kernel {name} not found")))?;
+        let (freqs, use_freq) = self.freq_args(args, queue, &groups, scaling, dt_t, dh);
+
+        // 每个批次单独发射，批次间的位置向量互不相关
+        let enqueue = |rope: &mut Kernel,
+                       t_base: *mut SvmByte,
+                       nh_l: usize,
+                       mut events: Option<&mut Vec<cl_event>>| {
+            for b in 0..nb as isize {
+                let p = unsafe { p_base.byte_offset(b * spb) };
+                let mask = unsafe { mask_base.byte_offset(b * smb) };
+                for ((heads, theta), freq) in groups.iter().zip(&freqs) {
+                    let t = unsafe { t_base.byte_offset(b * sb + heads.start as isize * head) };
+                    let nh_h = heads.len() / nh_l;
+                    let mut event = null_mut();
+                    rope.set_arg(0, &t)
+                        .set_arg(1, st as cl_int)
+                        .set_arg(2, sh as cl_int)
+                        .set_arg(3, &p)
+                        .set_arg(4, theta)
+                        .set_arg(5, scaling[0])
+                        .set_arg(6, scaling[1])
+                        .set_arg(7, scaling[2])
+                        .set_arg(8, scaling[3])
+                        .set_arg(9, sink_tokens)
+                        .set_arg(10, sink_pos)
+                        .set_arg(11, sign)
+                        .set_arg(12, &mask)
+                        .set_arg(13, sm as cl_int)
+                        .set_arg(14, use_mask)
+                        .set_arg(15, freq)
+                        .set_arg(16, use_freq)
+                        .launch(
+                            &[0, 0],
+                            &[nt * nh_l, nh_h * dh],
+                            &[nh_l, dh],
+                            queue,
+                            events.is_some().then_some(&mut event),
+                        );
+                    if let Some(events) = events.as_deref_mut() {
+                        events.push(event)
+                    }
+                }
+            }
+        };
+
+        // 每个工作组处理的头数必须整除每组的头数，且工作组不超过设备和核函数的上限
+        let group_size = rope
+            .work_group_size(&self.ctx)
+            .map_or(self.max_group_size, |n| n.min(self.max_group_size));
+        if group_size < dh {
+            return self.fallback(
+                args,
+                queue,
+                shape_not_support(format!("dh {dh} exceeds work-group limit {group_size}")),
+            );
+        }
+        let divides = |nh_l: usize| groups.iter().all(|(heads, _)| heads.len() % nh_l == 0);
+        let max_nh_l = (group_size / dh).min(nh);
+        let candidates = (1..=max_nh_l).rev().filter(|&nh_l| divides(nh_l));
+        let tune_key = TuneKey {
+            unit: unit as _,
+            nt,
+            nh,
+            dh,
+        };
+        let tuned = self
+            .tuned
+            .lock()
+            .unwrap()
+            .get(&tune_key)
+            .copied()
+            .filter(|&nh_l| divides(nh_l) && nh_l <= max_nh_l);
+        let nh_l = match tuned {
+            Some(nh_l) => nh_l,
+            None if self.autotune => {
+                // 在临时存储上试跑，避免多次旋转用户数据
+                let range = args.t_layout.byte_range().unwrap();
+                let mut scratch = self.ctx.malloc::<u8>((range.end - range.start) as _);
+                let base = unsafe { scratch.as_mut_ptr().byte_offset(-range.start) };
+                let nh_l = candidates
+                    .min_by_key(|&nh_l| time(queue, || enqueue(&mut rope, base, nh_l, None)))
+                    .unwrap();
+                self.tuned.lock().unwrap().put(tune_key, nh_l);
+                nh_l
+            }
+            None => candidates.max().unwrap(),
+        };
+        if self.profiling {
+            let mut events = Vec::with_capacity(nb * groups.len());
+            enqueue(&mut rope, *t_base, nh_l, Some(&mut events));
+            *self.kernel_time.lock().unwrap() = events.into_iter().map(event_duration).sum();
+        } else {
+            enqueue(&mut rope, *t_base, nh_l, None);
         }
+        Ok(())
+    }
 
-        let max_nh_l = (self.max_group_size / dh).min(nh);
-        let nh_l = (1..=max_nh_l).rev().find(|nhl| nh % nhl == 0).unwrap();
-        let nh_h = nh / nh_l;
+    /// 单个词元的快速路径，每组头展平为一维发射，不受工作组划分的限制，也无需调优。
+    ///
+    /// `dh` 为旋转对的数量，`head` 为头的字节步长。
+    fn launch_token(
+        &self,
+        args: &Args<ClDevice>,
+        queue: &CommandQueue,
+        groups: &[(Range<usize>, f32)],
+        scaling: [f32; 4],
+        [nb, dh]: [usize; 2],
+        [sb, head, spb]: [isize; 3],
+    ) -> Result<(), LaunchError> {
+        let Meta { dt_t, dt_p, .. } = args.meta()?;
+        let Args { t_base, p_base, .. } = args;
+        let unit = dt_t.nbytes() as isize;
+        let sh = (head / unit / 2) as cl_int;
+        let sink_tokens = args.sink.tokens.min(1) as cl_uint;
+        let sink_pos = args.sink.pos as f32;
+        let sign = args.sign();
+        let (mask_base, [smb, sm], use_mask) = mask_args(args)?;
 
-        let key = self.cache_kernel(dt_t, dt_p);
+        let name = kernel_name("rope_token", dt_t)?;
+        let key = self.cache_kernel(dt_t, dt_p)?;
         let mut rope = self
             .schemes
             .lock()
             .unwrap()
             .get(&key)
             .unwrap()
-            .take("rope")
-            .unwrap();
+            .take_guard(&name)
+            .ok_or_else(|| execution_failed(format!("opencl: kernel {name} not found")))?;
+        let group_size = rope
+            .work_group_size(&self.ctx)
+            .map_or(self.max_group_size, |n| n.min(self.max_group_size));
+        let (freqs, use_freq) = self.freq_args(args, queue, groups, scaling, dt_t, dh);
 
-        rope.set_arg(0, t_base)
-            .set_arg(1, st as cl_int)
-            .set_arg(2, sh as cl_int)
-            .set_arg(3, p_base)
-            .set_arg(4, theta)
-            .launch(
-                &[0, 0],
-                &[(nt * nh_l) as usize, (nh_h * dh) as usize],
-                &[nh_l as usize, dh as usize],
-                queue_alloc.queue(),
-                None,
+        let mut events = Vec::new();
+        for b in 0..nb as isize {
+            let p = unsafe { p_base.byte_offset(b * spb) };
+            let mask = unsafe { mask_base.byte_offset(b * smb) };
+            for ((heads, theta), freq) in groups.iter().zip(&freqs) {
+                let n = heads.len() * dh;
+                if n == 0 {
+                    continue;
+                }
+                let t = unsafe { t_base.byte_offset(b * sb + heads.start as isize * head) };
+                let local = group_size.min(n);
+                let mut event = null_mut();
+                rope.set_arg(0, &t)
+                    .set_arg(1, sh)
+                    .set_arg(2, dh as cl_int)
+                    .set_arg(3, n as cl_int)
+                    .set_arg(4, &p)
+                    .set_arg(5, theta)
+                    .set_arg(6, scaling[0])
+                    .set_arg(7, scaling[1])
+                    .set_arg(8, scaling[2])
+                    .set_arg(9, scaling[3])
+                    .set_arg(10, sink_tokens)
+                    .set_arg(11, sink_pos)
+                    .set_arg(12, sign)
+                    .set_arg(13, &mask)
+                    .set_arg(14, sm as cl_int)
+                    .set_arg(15, use_mask)
+                    .set_arg(16, freq)
+                    .set_arg(17, use_freq)
+                    .launch(
+                        &[0],
+                        &[n.div_ceil(local) * local],
+                        &[local],
+                        queue,
+                        self.profiling.then_some(&mut event),
+                    );
+                if self.profiling {
+                    events.push(event)
+                }
+            }
+        }
+        if self.profiling {
+            *self.kernel_time.lock().unwrap() = events.into_iter().map(event_duration).sum();
+        }
+        Ok(())
+    }
+
+    /// 按 sin/cos 表旋转，表的行、列步长可以任意，例如多个头共享且带填充的表。
+    ///
+    /// 只支持不插值的 f32 表，其他情况回退。`dh` 为旋转对的数量，步长以字节计。
+    fn launch_table(
+        &self,
+        args: &Args<ClDevice>,
+        queue: &CommandQueue,
+        [nb, nt, nh, dh]: [usize; 4],
+        [sb, st, sh, spb]: [isize; 4],
+    ) -> Result<(), LaunchError> {
+        let Meta {
+            dt_t, dt_p, dt_sc, ..
+        } = args.meta()?;
+        let Args {
+            t_base,
+            p_base,
+            sin_layout,
+            sin_base,
+            cos_layout,
+            cos_base,
+            table_step,
+            ..
+        } = args;
+        if dt_t == Ty::F64 || dt_sc != Ty::F32 {
+            return self.fallback(
+                args,
+                queue,
+                type_not_support(format!("opencl: {dt_t} rope with {dt_sc} sin/cos table")),
             );
+        }
+        if *table_step != 1 {
+            return self.fallback(
+                args,
+                queue,
+                args_not_support("opencl: interpolated sin/cos table"),
+            );
+        }
+        let &[ssn, ssd] = sin_layout.strides() else {
+            unreachable!()
+        };
+        let &[scn, scd] = cos_layout.strides() else {
+            unreachable!()
+        };
+        get_static! {
+            ssn ssd scn scd
+        }
+        let unit_sc = dt_sc.nbytes() as isize;
+        if [ssn, ssd, scn, scd].iter().any(|s| s % unit_sc != 0) {
+            return self.fallback(args, queue, strides_not_support("sin/cos table"));
+        }
+        debug_check_tensor("sin", sin_layout, *sin_base);
+        debug_check_tensor("cos", cos_layout, *cos_base);
+
+        let n = nt * nh * dh;
+        if n == 0 {
+            return Ok(());
+        }
+        let unit = dt_t.nbytes() as isize * 2;
+        let sink_tokens = args.sink.tokens.min(nt) as cl_uint;
+        let sink_pos = args.sink.pos as cl_uint;
+        let sign = args.sign();
+        let (mask_base, [smb, sm], use_mask) = mask_args(args)?;
 
-        let mut cache = self.schemes.lock().unwrap();
-        let program = cache.get(&key).unwrap();
-        program.put("rope", rope);
+        let name = kernel_name("rope_table", dt_t)?;
+        let key = self.cache_kernel(dt_t, dt_p)?;
+        let mut rope = self
+            .schemes
+            .lock()
+            .unwrap()
+            .get(&key)
+            .unwrap()
+            .take_guard(&name)
+            .ok_or_else(|| execution_failed(format!("opencl: kernel {name} not found")))?;
+        let group_size = rope
+            .work_group_size(&self.ctx)
+            .map_or(self.max_group_size, |n| n.min(self.max_group_size));
+        let local = group_size.min(n);
 
+        let mut events = Vec::new();
+        for b in 0..nb as isize {
+            let t = unsafe { t_base.byte_offset(b * sb) };
+            let p = unsafe { p_base.byte_offset(b * spb) };
+            let mask = unsafe { mask_base.byte_offset(b * smb) };
+            let mut event = null_mut();
+            rope.set_arg(0, &t)
+                .set_arg(1, (st / unit) as cl_int)
+                .set_arg(2, (sh / unit) as cl_int)
+                .set_arg(3, nh as cl_int)
+                .set_arg(4, dh as cl_int)
+                .set_arg(5, n as cl_int)
+                .set_arg(6, &p)
+                .set_arg(7, sin_base)
+                .set_arg(8, (ssn / unit_sc) as cl_int)
+                .set_arg(9, (ssd / unit_sc) as cl_int)
+                .set_arg(10, cos_base)
+                .set_arg(11, (scn / unit_sc) as cl_int)
+                .set_arg(12, (scd / unit_sc) as cl_int)
+                .set_arg(13, sink_tokens)
+                .set_arg(14, sink_pos)
+                .set_arg(15, sign)
+                .set_arg(16, &mask)
+                .set_arg(17, sm as cl_int)
+                .set_arg(18, use_mask)
+                .launch(
+                    &[0],
+                    &[n.div_ceil(local) * local],
+                    &[local],
+                    queue,
+                    self.profiling.then_some(&mut event),
+                );
+            if self.profiling {
+                events.push(event)
+            }
+        }
+        if self.profiling {
+            *self.kernel_time.lock().unwrap() = events.into_iter().map(event_duration).sum();
+        }
         Ok(())
     }
-}
 
-impl Operator {
-    fn cache_kernel(&self, dt_t: DigitLayout, dt_p: DigitLayout) -> SchemeKey {
-        let key = SchemeKey { dt_t, dt_p };
-        self.schemes.lock().unwrap().get_or_insert(key, || {
-            let dt_t = match dt_t {
-                Ty::F32 => "float2",
-                Ty::F16 => "half2",
-                _ => unimplemented!(),
-            };
-            let dt_p = match dt_p {
-                Ty::U64 => "unsigned long",
-                Ty::U32 => "unsigned int",
-                _ => unimplemented!(),
-            };
+    /// 设置是否记录每次发射的核函数在设备上的执行时间。
+    ///
+    /// 计时基于 OpenCL 事件，发射的队列需要以 `CL_QUEUE_PROFILING_ENABLE` 创建。
+    /// 开启后发射将等待核函数执行完成。
+    pub fn set_profiling(&mut self, enable: bool) {
+        self.profiling = enable
+    }
+
+    /// 最近一次发射的核函数在设备上的执行时间，多个批次时为各批次之和。
+    ///
+    /// 未开启计时或队列不支持计时时返回 `None`。
+    pub fn kernel_time(&self) -> Option<Duration> {
+        *self.kernel_time.lock().unwrap()
+    }
+
+    /// 设置是否对未调优过的形状自动调优。
+    ///
+    /// 调优时在临时存储上依次计时各候选工作组配置，选出最快的并按形状缓存。
+    /// 缓存容量有限，最久未用的形状先被淘汰。
+    pub fn set_autotune(&mut self, enable: bool) {
+        self.autotune = enable
+    }
+
+    /// 设置是否在主机上预先计算频率，默认关闭。
+    ///
+    /// 开启后每组头的 `dh / 2` 个频率以 f64 计算并缩放，转为 f32 上传，
+    /// 核函数只需乘以位置，不再逐元素计算幂。上传的频率按 `theta`、缩放和 `dh` 缓存，
+    /// 缓存容量有限，最久未用的频率在发射所用的队列上排队释放。双精度始终在核函数内计算。
+    pub fn set_host_freq(&mut self, enable: bool) {
+        self.host_freq = enable
+    }
+
+    /// 每组头的频率向量和是否使用。
+    ///
+    /// 未开启主机计算时以位置向量占位，核函数不会读取。`dh` 为旋转对的数量。
+    fn freq_args(
+        &self,
+        args: &Args<ClDevice>,
+        queue: &CommandQueue,
+        groups: &[(Range<usize>, f32)],
+        scaling: [f32; 4],
+        dt_t: DigitLayout,
+        dh: usize,
+    ) -> (Vec<*const SvmByte>, cl_uint) {
+        if !self.host_freq || dt_t == Ty::F64 {
+            return (vec![args.p_base; groups.len()], 0);
+        }
+        let mut freqs = self.freqs.lock().unwrap();
+        // 本次发射用到的频率都是最近使用的，容量不小于组数时不会互相淘汰
+        if freqs.cap().get() < groups.len() {
+            freqs.resize(NonZeroUsize::new(groups.len()).unwrap())
+        }
+        let ptrs = groups
+            .iter()
+            .map(|&(_, theta)| {
+                let key = FreqKey {
+                    theta: theta.to_bits(),
+                    scaling: scaling.map(f32::to_bits),
+                    dh,
+                };
+                if let Some(blob) = freqs.get(&key) {
+                    return blob.as_ptr();
+                }
+                let mut blob = self.ctx.malloc::<f32>(dh);
+                let mut map = queue.map_mut(&mut blob, false);
+                let ([], mem, []) = (unsafe { map.align_to_mut::<f32>() }) else {
+                    panic!()
+                };
+                for (i, freq) in mem.iter_mut().enumerate() {
+                    let inv = (theta as f64).powf(-(i as f64) / dh as f64);
+                    *freq = args.scaling.scale(inv) as f32
+                }
+                queue.unmap(map);
+                let ptr = blob.as_ptr();
+                // 被淘汰的频率可能仍被已提交的核函数读取，排在它们之后释放
+                if let Some((_, old)) = freqs.push(key, blob) {
+                    queue.free(old, None)
+                }
+                ptr
+            })
+            .collect();
+        (ptrs, 1)
+    }
+
+    /// 将调优结果写入文件，每行依次为 `unit nt nh dh nh_l`。
+    pub fn save_tuning(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut lines = self
+            .tuned
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(k, nh_l)| format!("{} {} {} {} {nh_l}\n", k.unit, k.nt, k.nh, k.dh))
+            .collect::<Vec<_>>();
+        lines.sort_unstable();
+        fs::write(path, lines.concat())
+    }
 
-            let src = match dt_t {
-                "float2" => CodeGen::new(include_str!("rope.cl"))
-                    .define("Tval", dt_t)
-                    .define("Tpos", dt_p)
-                    .to_string(),
-                "half2" => CodeGen::new(include_str!("rope.cl"))
-                    .define("Tval", dt_t)
-                    .define("Tpos", dt_p)
-                    .define("USE_HALF", true)
-                    .to_string(), // 只有 F16 类型时才定义 USE_HALF
-                _ => unimplemented!(),
+    /// 从文件加载调优结果，与已有的结果合并。
+    ///
+    /// 不适用于当前设备的配置在发射时被忽略。
+    pub fn load_tuning(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let text = fs::read_to_string(path)?;
+        let mut tuned = self.tuned.lock().unwrap();
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let vals = line
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<Vec<usize>, _>>()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let &[unit, nt, nh, dh, nh_l] = &*vals else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid tuning record: {line}"),
+                ));
             };
-            KernelCache::new(&self.ctx, &src, CL2_0)
-        });
-        key
+            tuned.put(TuneKey { unit, nt, nh, dh }, nh_l);
+        }
+        Ok(())
+    }
+
+    /// 设置遇到不支持的形状或步长时是否回退到 CPU 执行。
+    ///
+    /// 回退时将设备存储映射到主机，使用 CPU 实现计算后写回。
+    #[cfg(any(use_cpu, test))]
+    pub fn set_cpu_fallback(&mut self, enable: bool) {
+        self.cpu_fallback = enable
+    }
+
+    fn fallback(
+        &self,
+        args: &Args<ClDevice>,
+        queue: &CommandQueue,
+        err: SchemeError,
+    ) -> Result<(), LaunchError> {
+        #[cfg(any(use_cpu, test))]
+        if self.cpu_fallback {
+            return launch_cpu(args, queue);
+        }
+        let _ = (args, queue);
+        Err(err.into())
+    }
+
+    fn check_dt(&self, dt_t: DigitLayout, dt_p: DigitLayout) -> Result<(), SchemeError> {
+        match dt_t {
+            Ty::F16 | Ty::F32 => {}
+            Ty::F64 if self.fp64 => {}
+            Ty::F64 => Err(type_not_support("opencl: device does not support fp64"))?,
+            _ => Err(type_not_support(format!(
+                "opencl: rope does not support {dt_t}"
+            )))?,
+        }
+        match dt_p {
+            Ty::U32 | Ty::U64 | Ty::I32 | Ty::I64 => Ok(()),
+            _ => Err(type_not_support(format!(
+                "opencl: rope does not support pos type {dt_p}"
+            ))),
+        }
+    }
+
+    /// 指定类型组合下实际编译的程序源码（已展开宏定义）和编译选项，用于调试。
+    pub fn program_source(
+        dt_t: DigitLayout,
+        dt_p: DigitLayout,
+    ) -> Result<(String, &'static CStr), SchemeError> {
+        let name = kernel_name("rope", dt_t)?;
+        let token = kernel_name("rope_token", dt_t)?;
+        let table = kernel_name("rope_table", dt_t)?;
+        let dt_t = match dt_t {
+            Ty::F64 => "double2",
+            Ty::F32 => "float2",
+            Ty::F16 => "half2",
+            _ => Err(type_not_support(format!(
+                "opencl: rope does not support {dt_t}"
+            )))?,
+        };
+        let (dt_p, signed) = match dt_p {
+            Ty::U64 => ("unsigned long", false),
+            Ty::U32 => ("unsigned int", false),
+            Ty::I64 => ("long", true),
+            Ty::I32 => ("int", true),
+            _ => Err(type_not_support(format!(
+                "opencl: rope does not support pos type {dt_p}"
+            )))?,
+        };
+
+        let mut code = CodeGen::new(include_str!("rope.cl"));
+        code.define("Tpos", dt_p);
+        match dt_t {
+            "float2" => code
+                .define("Tval", dt_t)
+                .define("ROPE", name)
+                .define("ROPE_TOKEN", token)
+                .define("ROPE_TABLE", table),
+            // 只有 F16 类型时才定义 USE_HALF
+            "half2" => code
+                .define("Tval", dt_t)
+                .define("ROPE", name)
+                .define("ROPE_TOKEN", token)
+                .define("ROPE_TABLE", table)
+                .define("USE_HALF", true),
+            // 只有 F64 类型时才编译 rope_f64
+            "double2" => code.define("USE_DOUBLE", true),
+            _ => unreachable!(),
+        };
+        // 有符号位置的负值表示填充，不旋转
+        if signed {
+            code.define("SIGNED_POS", true);
+        }
+        Ok((code.to_string(), CL2_0))
+    }
+
+    fn cache_kernel(&self, dt_t: DigitLayout, dt_p: DigitLayout) -> Result<SchemeKey, SchemeError> {
+        let key = SchemeKey { dt_t, dt_p };
+        self.schemes.lock().unwrap().try_get_or_insert(key, || {
+            let (src, opts) = Self::program_source(dt_t, dt_p)?;
+            Ok::<_, SchemeError>(KernelCache::new(&self.ctx, &src, opts))
+        })?;
+        Ok(key)
+    }
+}
+
+/// 掩码的基址、批次和词元步长以及是否启用掩码。
+///
+/// 没有掩码时以位置向量占位，核函数不会读取。
+fn mask_args(args: &Args<ClDevice>) -> Result<(*const SvmByte, [isize; 2], cl_uint), SchemeError> {
+    match (&args.mask, args.mask_strides()) {
+        (Some((layout, base)), Some([smb, sm])) => {
+            get_static! {
+                smb sm
+            }
+            debug_check_tensor("mask", layout, *base);
+            Ok((*base, [smb, sm], 1))
+        }
+        _ => Ok((args.p_base, [0, 0], 0)),
+    }
+}
+
+/// 是否提供了非空的 sin/cos 表，与 CPU 实现的判断一致。
+fn has_table(args: &Args<ClDevice>) -> bool {
+    !args.sin_base.is_null()
+        && !args.cos_base.is_null()
+        && args.sin_layout.shape()[0].get_static() != Some(&0)
+}
+
+/// 调优时用于计时的轮数。
+const TUNE_ROUNDS: usize = 4;
+
+/// 预热一次后计时 `f` 执行 [TUNE_ROUNDS] 轮的总耗时。
+fn time(queue: &CommandQueue, mut f: impl FnMut()) -> Duration {
+    f();
+    queue.finish();
+    let time = Instant::now();
+    for _ in 0..TUNE_ROUNDS {
+        f()
+    }
+    queue.finish();
+    time.elapsed()
+}
+
+#[cfg(any(use_cpu, test))]
+fn launch_cpu(args: &Args<ClDevice>, queue: &CommandQueue) -> Result<(), LaunchError> {
+    use crate::TensorLayout;
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        dyn_not_support, Operator as _,
+    };
+    use std::{ptr::null, slice::from_raw_parts_mut};
+
+    let t_range = args
+        .t_layout
+        .byte_range()
+        .ok_or_else(|| dyn_not_support(""))?;
+    let p_range = args
+        .p_layout
+        .byte_range()
+        .ok_or_else(|| dyn_not_support(""))?;
+    let t = unsafe {
+        from_raw_parts_mut(
+            args.t_base.byte_offset(t_range.start),
+            (t_range.end - t_range.start) as _,
+        )
+    };
+    let p = unsafe {
+        from_raw_parts_mut(
+            args.p_base.cast_mut().byte_offset(p_range.start),
+            (p_range.end - p_range.start) as _,
+        )
+    };
+
+    // 表和掩码是可选的，只在提供时映射
+    let optional = |present: bool, layout: &TensorLayout, base: *const ByteOf<ClDevice>| {
+        if !present {
+            return Ok(None);
+        }
+        let range = layout.byte_range().ok_or_else(|| dyn_not_support(""))?;
+        let table = unsafe {
+            from_raw_parts(
+                base.byte_offset(range.start),
+                (range.end - range.start) as _,
+            )
+        };
+        Ok::<_, LaunchError>(Some((range.start, table)))
+    };
+    let sin = optional(has_table(args), &args.sin_layout, args.sin_base)?;
+    let cos = optional(has_table(args), &args.cos_layout, args.cos_base)?;
+    let mask = match &args.mask {
+        Some((layout, base)) => optional(true, layout, *base)?,
+        None => None,
+    };
+
+    let mut t_map = queue.map_mut(t, false);
+    let p_map = queue.map(p);
+    let sin_map = sin.map(|(start, table)| (start, queue.map(table)));
+    let cos_map = cos.map(|(start, table)| (start, queue.map(table)));
+    let (sin_base, cos_base) = match (&sin_map, &cos_map) {
+        (Some((sin_start, sin_map)), Some((cos_start, cos_map))) => unsafe {
+            (
+                sin_map.as_ptr().byte_offset(-sin_start),
+                cos_map.as_ptr().byte_offset(-cos_start),
+            )
+        },
+        _ => (null(), null()),
+    };
+    let mask_map = mask.map(|(start, mask)| (start, queue.map(mask)));
+    let cpu_mask = match (&args.mask, &mask_map) {
+        (Some((layout, _)), Some((start, map))) => {
+            Some((layout.clone(), unsafe { map.as_ptr().byte_offset(-start) }))
+        }
+        _ => None,
+    };
+    let cpu_args = Args::<Cpu> {
+        t_layout: args.t_layout.clone(),
+        t_base: unsafe { t_map.as_mut_ptr().byte_offset(-t_range.start) },
+        p_layout: args.p_layout.clone(),
+        p_base: unsafe { p_map.as_ptr().byte_offset(-p_range.start) },
+        sin_layout: args.sin_layout.clone(),
+        sin_base,
+        cos_layout: args.cos_layout.clone(),
+        cos_base,
+        theta: args.theta,
+        theta_groups: args.theta_groups.clone(),
+        scaling: args.scaling,
+        table_step: args.table_step,
+        sink: args.sink,
+        inverse: args.inverse,
+        mask: cpu_mask,
+    };
+    let ans = super::common_cpu::Operator::new(&Cpu).launch(&cpu_args, &mut [], &ThisThread);
+    if let Some((_, map)) = mask_map {
+        queue.unmap(map)
+    }
+    if let Some((_, map)) = cos_map {
+        queue.unmap(map)
     }
+    if let Some((_, map)) = sin_map {
+        queue.unmap(map)
+    }
+    queue.unmap(p_map);
+    queue.unmap(t_map);
+    ans
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
@@ -233,9 +1034,26 @@ struct SchemeKey {
     dt_p: DigitLayout,
 }
 
+/// 主机上计算的频率的键，浮点数按位比较，`dh` 为旋转对的数量。
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+struct FreqKey {
+    theta: u32,
+    scaling: [u32; 4],
+    dh: usize,
+}
+
+/// 调优结果的键，`dh` 为旋转对的数量。
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+struct TuneKey {
+    unit: usize,
+    nt: usize,
+    nh: usize,
+    dh: usize,
+}
+
 #[cfg(test)]
 mod test {
-    use super::Args;
+    use super::{Args, RopeScaling};
     use crate::{Hardware, TensorLayout};
     use digit_layout::{
         types::{F32, F64, U32},
@@ -255,6 +1073,12 @@ mod test {
             cos_layout: TensorLayout::new_dyn(dt_t, &[dyn_(); 2], &[dyn_(); 2]),
             cos_base: null(),
             theta: 0.,
+            theta_groups: Vec::new(),
+            scaling: RopeScaling::None,
+            table_step: 1,
+            sink: Default::default(),
+            inverse: false,
+            mask: None,
         }
     }
 
@@ -279,6 +1103,12 @@ mod test {
             cos_layout: TensorLayout::new_contiguous(dt_t, &[0, dh]),
             cos_base: null(),
             theta,
+            theta_groups: Vec::new(),
+            scaling: RopeScaling::None,
+            table_step: 1,
+            sink: Default::default(),
+            inverse: false,
+            mask: None,
         }
     }
 
@@ -286,15 +1116,13 @@ mod test {
     fn test_compute() {
         use super::{super::common_cpu::Operator as RefOp, Operator};
         use crate::{
-            common_cpu::{Cpu, ThisThread},
-            opencl::ClDevice,
-            test_utils::{Diff, ErrorCollector},
+            common_cpu::Cpu,
+            opencl::{write_from_slice, ClDevice},
+            test_utils::{assert_matches_cpu, ErrorCollector},
             Operator as _,
         };
         use clrt::Platform;
-        use digit_layout::types as ty;
         use rand::Rng;
-        use std::{iter::zip, time::Instant};
 
         let mut cpu_op = RefOp::new(&Cpu);
         for platform in Platform::all() {
@@ -317,30 +1145,289 @@ mod test {
                 let mut t_svm = context.malloc::<f32>(NT * nh * dh);
                 let mut p_svm = context.malloc::<u32>(7);
 
-                let mut map = queue.map_mut(&mut t_svm, false);
-                let ([], mem, []) = (unsafe { map.align_to_mut::<f32>() }) else {
-                    panic!()
-                };
-                for (dst, src) in zip(mem, &t) {
-                    *dst = *src as _;
-                }
-                queue.unmap(map);
+                let t_f32 = t.iter().map(|&x| x as f32).collect::<Vec<_>>();
+                write_from_slice(&mut t_svm, &t_f32, &queue);
 
-                let mut map = queue.map_mut(&mut p_svm, false);
-                let ([], mem, []) = (unsafe { map.align_to_mut::<u32>() }) else {
-                    panic!()
-                };
-                for (dst, src) in zip(mem, &p) {
-                    *dst = *src as _;
-                }
-                queue.unmap(map);
+                write_from_slice(&mut p_svm[..size_of_val(&p)], &p, &queue);
 
-                let time = Instant::now();
-                cl_op
+                let t_base = t_svm.as_mut_ptr();
+                let mut t_ref = t;
+                let t_ref_base = t_ref.as_mut_ptr();
+                assert_matches_cpu(
+                    &cl_op,
+                    || {
+                        args(
+                            F32,
+                            U32,
+                            NT,
+                            nh,
+                            dh,
+                            1e4,
+                            t_base.cast(),
+                            p_svm.as_ptr().cast(),
+                        )
+                    },
+                    &queue,
+                    || {
+                        let map = queue.map(&mut t_svm);
+                        let ([], y_ans, []) = (unsafe { map.align_to::<f32>() }) else {
+                            panic!()
+                        };
+                        let ans = y_ans.iter().map(|&x| x as f64).collect();
+                        queue.unmap(map);
+                        ans
+                    },
+                    &cpu_op,
+                    || {
+                        args(
+                            F64,
+                            U32,
+                            NT,
+                            nh,
+                            dh,
+                            1e4,
+                            t_ref_base.cast(),
+                            p.as_ptr().cast(),
+                        )
+                    },
+                    || t_ref.clone(),
+                    ErrorCollector::new(f32::EPSILON as f64, 1e-3),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_compute_batched() {
+        use super::{super::common_cpu::Operator as RefOp, Operator};
+        use crate::{
+            common_cpu::{Cpu, ThisThread},
+            opencl::{write_from_slice, ClDevice},
+            test_utils::{Diff, ErrorCollector},
+            Operator as _,
+        };
+        use clrt::Platform;
+        use rand::Rng;
+        use std::{
+            iter::zip,
+            ptr::{null, null_mut},
+        };
+
+        fn batched_args<H: Hardware>(
+            dt_t: DigitLayout,
+            shape: [usize; 4],
+            t_base: *mut H::Byte,
+            p_base: *const H::Byte,
+        ) -> Args<H> {
+            let [nb, nt, _, dh] = shape;
+            Args {
+                t_layout: TensorLayout::new_contiguous(dt_t, &shape),
+                t_base,
+                p_layout: TensorLayout::new_contiguous(U32, &[nb, nt]),
+                p_base,
+                sin_layout: TensorLayout::new_contiguous(dt_t, &[0, dh]),
+                sin_base: null(),
+                cos_layout: TensorLayout::new_contiguous(dt_t, &[0, dh]),
+                cos_base: null(),
+                theta: 1e4,
+                theta_groups: Vec::new(),
+                scaling: RopeScaling::None,
+                table_step: 1,
+                sink: Default::default(),
+                inverse: false,
+                mask: None,
+            }
+        }
+
+        let mut cpu_op = RefOp::new(&Cpu);
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let mut cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+
+                let shape = [2, 3, 8, 64];
+                let len = shape.iter().product::<usize>();
+                // 两个批次的位置向量不同
+                let p: [u32; 6] = [0, 1, 2, 17, 18, 19];
+
+                cpu_op
+                    .scheme(&batched_args::<Cpu>(F64, shape, null_mut(), null()), 0)
+                    .unwrap();
+                cl_op
+                    .scheme(&batched_args::<ClDevice>(F32, shape, null_mut(), null()), 0)
+                    .unwrap();
+
+                let mut t = vec![0.0f64; len];
+                rand::rng().fill(&mut t[..]);
+                let mut t_svm = context.malloc::<f32>(len);
+                let mut p_svm = context.malloc::<u32>(p.len());
+
+                let t_f32 = t.iter().map(|&x| x as f32).collect::<Vec<_>>();
+                write_from_slice(&mut t_svm, &t_f32, &queue);
+                write_from_slice(&mut p_svm, &p, &queue);
+
+                cl_op
+                    .launch(
+                        &batched_args::<ClDevice>(
+                            F32,
+                            shape,
+                            t_svm.as_mut_ptr().cast(),
+                            p_svm.as_ptr().cast(),
+                        ),
+                        &mut [],
+                        &queue,
+                    )
+                    .unwrap();
+                queue.finish();
+
+                cpu_op
+                    .launch(
+                        &batched_args::<Cpu>(F64, shape, t.as_mut_ptr().cast(), p.as_ptr().cast()),
+                        &mut [],
+                        &ThisThread,
+                    )
+                    .unwrap();
+
+                let map = queue.map(&mut t_svm);
+                let ([], y_ans, []) = (unsafe { map.align_to::<f32>() }) else {
+                    panic!()
+                };
+                let mut ec = ErrorCollector::new(f32::EPSILON as f64, 1e-3);
+                zip(&t, y_ans).for_each(|(a, b)| ec.push(Diff::new(*a, *b as _)));
+                queue.unmap(map);
+                println!("{ec}");
+
+                let (out, count) = ec.summary();
+                assert!(out * 1000 <= count);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cpu_fallback() {
+        use super::{super::common_cpu::Operator as RefOp, Operator};
+        use crate::{
+            common_cpu::{Cpu, ThisThread},
+            opencl::{write_from_slice, ClDevice},
+            Operator as _,
+        };
+        use clrt::Platform;
+        use rand::Rng;
+
+        let mut cpu_op = RefOp::new(&Cpu);
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let mut cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+                cl_op.set_cpu_fallback(true);
+                cpu_op.scheme(&dyn_args(F32, U32), 0).unwrap();
+                cl_op.scheme(&dyn_args(F32, U32), 0).unwrap();
+
+                const NT: usize = 5;
+                let nh = 8;
+                let dh = 64;
+
+                let mut t = vec![0.0f32; NT * nh * dh];
+                rand::rng().fill(&mut t[..]);
+                // 位置向量间隔存储，OpenCL 实现不支持这种步长
+                let p: [u32; NT * 2] = [3, 0, 1, 0, 4, 0, 1, 0, 5, 0];
+                let mut t_svm = context.malloc::<f32>(NT * nh * dh);
+                let mut p_svm = context.malloc::<u32>(NT * 2);
+
+                write_from_slice(&mut t_svm, &t, &queue);
+                write_from_slice(&mut p_svm, &p, &queue);
+
+                let mut cl_args = args::<ClDevice>(
+                    F32,
+                    U32,
+                    NT,
+                    nh,
+                    dh,
+                    1e4,
+                    t_svm.as_mut_ptr().cast(),
+                    p_svm.as_ptr().cast(),
+                );
+                cl_args.p_layout = TensorLayout::new(U32, &[NT], &[8]);
+                cl_op.launch(&cl_args, &mut [], &queue).unwrap();
+                queue.finish();
+
+                let mut cpu_args = args::<Cpu>(
+                    F32,
+                    U32,
+                    NT,
+                    nh,
+                    dh,
+                    1e4,
+                    t.as_mut_ptr().cast(),
+                    p.as_ptr().cast(),
+                );
+                cpu_args.p_layout = TensorLayout::new(U32, &[NT], &[8]);
+                cpu_op.launch(&cpu_args, &mut [], &ThisThread).unwrap();
+
+                let map = queue.map(&mut t_svm);
+                let ([], y_ans, []) = (unsafe { map.align_to::<f32>() }) else {
+                    panic!()
+                };
+                assert_eq!(y_ans, t);
+                queue.unmap(map);
+            }
+        }
+    }
+
+    #[test]
+    fn test_compute_f64() {
+        use super::{super::common_cpu::Operator as RefOp, Operator};
+        use crate::{
+            common_cpu::{Cpu, ThisThread},
+            opencl::{write_from_slice, ClDevice},
+            test_utils::{Diff, ErrorCollector},
+            Operator as _,
+        };
+        use clrt::Platform;
+        use rand::Rng;
+        use std::iter::zip;
+
+        let mut cpu_op = RefOp::new(&Cpu);
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                let context = device.context();
+                let node = ClDevice::new(context.clone(), Default::default());
+                if !node.support_fp64() {
+                    println!("device: {} (fp64 not supported, skipped)", device.name());
+                    continue;
+                }
+                println!("device: {}", device.name());
+
+                let queue = context.queue();
+                let mut cl_op = Operator::new(&node);
+                cpu_op.scheme(&dyn_args(F64, U32), 0).unwrap();
+                cl_op.scheme(&dyn_args(F64, U32), 0).unwrap();
+
+                const NT: usize = 7;
+                let nh = 32;
+                let dh = 64;
+
+                let mut t = vec![0.0f64; NT * nh * dh];
+                rand::rng().fill(&mut t[..]);
+                let p: [u32; NT] = [0, 1, 2, 3, 7, 100, 4095];
+                let mut t_svm = context.malloc::<f64>(NT * nh * dh);
+                let mut p_svm = context.malloc::<u32>(NT);
+
+                write_from_slice(&mut t_svm, &t, &queue);
+
+                write_from_slice(&mut p_svm, &p, &queue);
+
+                cl_op
                     .launch(
                         &args(
-                            ty::F32,
-                            ty::U32,
+                            F64,
+                            U32,
                             NT,
                             nh,
                             dh,
@@ -353,10 +1440,8 @@ mod test {
                     )
                     .unwrap();
                 queue.finish();
-                let cl_time = time.elapsed();
 
                 let mut t_ref = t;
-                let time = Instant::now();
                 cpu_op
                     .launch(
                         &args(
@@ -373,27 +1458,1211 @@ mod test {
                         &ThisThread,
                     )
                     .unwrap();
-                let cpu_time = time.elapsed();
 
                 let map = queue.map(&mut t_svm);
-
-                let ([], y_ans, []) = (unsafe { map.align_to::<f32>() }) else {
+                let ([], y_ans, []) = (unsafe { map.align_to::<f64>() }) else {
                     panic!()
                 };
+                let mut ec = ErrorCollector::new(1e-12, 1e-10);
+                zip(&t_ref, y_ans).for_each(|(a, b)| ec.push(Diff::new(*a, *b)));
+                queue.unmap(map);
+                println!("{ec}");
+
+                let (out, _) = ec.summary();
+                assert_eq!(out, 0);
+            }
+        }
+    }
+    #[test]
+    fn test_launch_on_queues() {
+        use super::{super::common_cpu::Operator as RefOp, Operator};
+        use crate::{
+            common_cpu::{Cpu, ThisThread},
+            opencl::{write_from_slice, ClDevice},
+            test_utils::{Diff, ErrorCollector},
+            Operator as _,
+        };
+        use clrt::Platform;
+        use rand::Rng;
+        use std::iter::zip;
+
+        const NT: usize = 7;
+        let nh = 32;
+        let dh = 64;
+        let p: [u32; NT] = [0, 1, 2, 3, 7, 8, 1];
+
+        let mut cpu_op = RefOp::new(&Cpu);
+        cpu_op.scheme(&dyn_args(F64, U32), 0).unwrap();
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queues = [context.queue(), context.queue()];
+                let mut cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+                cl_op.scheme(&dyn_args(F32, U32), 0).unwrap();
+
+                let mut p_svm = context.malloc::<u32>(NT);
+                write_from_slice(&mut p_svm, &p, &queues[0]);
+                queues[0].finish();
+
+                let mut t = [vec![0.0f64; NT * nh * dh], vec![0.0f64; NT * nh * dh]];
+                let mut t_svm = [
+                    context.malloc::<f32>(NT * nh * dh),
+                    context.malloc::<f32>(NT * nh * dh),
+                ];
+                for ((t, t_svm), queue) in zip(zip(&mut t, &mut t_svm), &queues) {
+                    rand::rng().fill(&mut t[..]);
+                    let t_f32 = t.iter().map(|&x| x as f32).collect::<Vec<_>>();
+                    write_from_slice(t_svm, &t_f32, queue);
+                }
+
+                // 两次发射分别进入两个队列，之后再分别等待
+                for (t_svm, queue) in zip(&mut t_svm, &queues) {
+                    cl_op
+                        .launch_on(
+                            &args(
+                                F32,
+                                U32,
+                                NT,
+                                nh,
+                                dh,
+                                1e4,
+                                t_svm.as_mut_ptr().cast(),
+                                p_svm.as_ptr().cast(),
+                            ),
+                            queue,
+                        )
+                        .unwrap();
+                }
+                queues.iter().for_each(|queue| queue.finish());
+
+                for ((mut t_ref, t_svm), queue) in zip(zip(t, &mut t_svm), &queues) {
+                    cpu_op
+                        .launch(
+                            &args(
+                                F64,
+                                U32,
+                                NT,
+                                nh,
+                                dh,
+                                1e4,
+                                t_ref.as_mut_ptr().cast(),
+                                p.as_ptr().cast(),
+                            ),
+                            &mut [],
+                            &ThisThread,
+                        )
+                        .unwrap();
+
+                    let map = queue.map(t_svm);
+                    let ([], y_ans, []) = (unsafe { map.align_to::<f32>() }) else {
+                        panic!()
+                    };
+                    let mut ec = ErrorCollector::new(f32::EPSILON as f64, 1e-3);
+                    zip(&t_ref, y_ans).for_each(|(a, b)| ec.push(Diff::new(*a, *b as _)));
+                    queue.unmap(map);
+                    println!("{ec}");
+
+                    let (out, count) = ec.summary();
+                    assert!(out * 1000 <= count);
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_autotune() {
+        use super::{super::common_cpu::Operator as RefOp, Operator};
+        use crate::{
+            common_cpu::{Cpu, ThisThread},
+            opencl::{write_from_slice, ClDevice},
+            test_utils::{Diff, ErrorCollector},
+            Operator as _,
+        };
+        use clrt::Platform;
+        use rand::Rng;
+        use std::iter::zip;
+
+        const NT: usize = 7;
+        let nh = 32;
+        let dh = 64;
+        let p: [u32; NT] = [0, 1, 2, 3, 7, 8, 1];
+        let path = std::env::temp_dir().join("operators_rope_tuning.txt");
+
+        let mut cpu_op = RefOp::new(&Cpu);
+        cpu_op.scheme(&dyn_args(F64, U32), 0).unwrap();
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let node = ClDevice::new(context.clone(), Default::default());
+                let mut cl_op = Operator::new(&node);
+                cl_op.set_autotune(true);
+                cl_op.scheme(&dyn_args(F32, U32), 0).unwrap();
+
+                let mut t = vec![0.0f64; NT * nh * dh];
+                rand::rng().fill(&mut t[..]);
+                let mut t_svm = context.malloc::<f32>(NT * nh * dh);
+                let mut p_svm = context.malloc::<u32>(NT);
+
+                let t_f32 = t.iter().map(|&x| x as f32).collect::<Vec<_>>();
+                write_from_slice(&mut t_svm, &t_f32, &queue);
+
+                write_from_slice(&mut p_svm, &p, &queue);
+
+                cl_op
+                    .launch(
+                        &args(
+                            F32,
+                            U32,
+                            NT,
+                            nh,
+                            dh,
+                            1e4,
+                            t_svm.as_mut_ptr().cast(),
+                            p_svm.as_ptr().cast(),
+                        ),
+                        &mut [],
+                        &queue,
+                    )
+                    .unwrap();
+                queue.finish();
 
-                let diff = t_ref
-                    .into_iter()
-                    .zip(y_ans)
-                    .map(|(a, b)| Diff::new(a, *b as _))
+                // 调优结果必须是合法的工作组配置
+                let tuned = cl_op
+                    .tuned
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|(k, v)| (*k, *v))
                     .collect::<Vec<_>>();
-                queue.unmap(map);
+                assert_eq!(tuned.len(), 1);
+                let &(key, nh_l) = tuned.first().unwrap();
+                assert_eq!((key.nt, key.nh, key.dh), (NT, nh, dh / 2));
+                assert_eq!(nh % nh_l, 0);
+                assert!(nh_l * dh / 2 <= cl_op.max_group_size);
+
+                let mut t_ref = t;
+                cpu_op
+                    .launch(
+                        &args(
+                            F64,
+                            U32,
+                            NT,
+                            nh,
+                            dh,
+                            1e4,
+                            t_ref.as_mut_ptr().cast(),
+                            p.as_ptr().cast(),
+                        ),
+                        &mut [],
+                        &ThisThread,
+                    )
+                    .unwrap();
 
+                let map = queue.map(&mut t_svm);
+                let ([], y_ans, []) = (unsafe { map.align_to::<f32>() }) else {
+                    panic!()
+                };
                 let mut ec = ErrorCollector::new(f32::EPSILON as f64, 1e-3);
-                diff.into_iter().for_each(|diff| ec.push(diff));
-                println!("cl: {cl_time:?} / cpu: {cpu_time:?}");
+                zip(&t_ref, y_ans).for_each(|(a, b)| ec.push(Diff::new(*a, *b as _)));
+                queue.unmap(map);
+                println!("{ec}");
 
                 let (out, count) = ec.summary();
                 assert!(out * 1000 <= count);
+
+                // 持久化后由新的算子实例加载
+                cl_op.save_tuning(&path).unwrap();
+                let loaded = Operator::new(&node);
+                loaded.load_tuning(&path).unwrap();
+                let loaded = loaded
+                    .tuned
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|(k, v)| (*k, *v))
+                    .collect::<Vec<_>>();
+                assert_eq!(loaded, tuned);
+            }
+        }
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_profiling() {
+        use super::Operator;
+        use crate::{opencl::ClDevice, Operator as _};
+        use clrt::Platform;
+        use std::time::{Duration, Instant};
+
+        const NT: usize = 64;
+        let nh = 32;
+        let dh = 128;
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                let context = device.context();
+                let queue = context.queue();
+                let mut cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+                cl_op.scheme(&dyn_args(F32, U32), 0).unwrap();
+                assert!(cl_op.kernel_time().is_none());
+                cl_op.set_profiling(true);
+
+                let mut t_svm = context.malloc::<f32>(NT * nh * dh);
+                let p_svm = context.malloc::<u32>(NT);
+                let args = args(
+                    F32,
+                    U32,
+                    NT,
+                    nh,
+                    dh,
+                    1e4,
+                    t_svm.as_mut_ptr().cast(),
+                    p_svm.as_ptr().cast(),
+                );
+                queue.finish();
+                let time = Instant::now();
+                cl_op.launch_on(&args, &queue).unwrap();
+                queue.finish();
+                let wall = time.elapsed();
+
+                // 队列未开启计时时没有设备时间
+                let Some(kernel) = cl_op.kernel_time() else {
+                    println!("device: {} profiling not available", device.name());
+                    continue;
+                };
+                println!("device: {} kernel {kernel:?} wall {wall:?}", device.name());
+                assert!(kernel > Duration::ZERO);
+                assert!(kernel < wall);
+            }
+        }
+    }
+
+    #[test]
+    fn test_supported_dtypes() {
+        use super::Operator;
+        use crate::Operator as _;
+
+        let dts = Operator::supported_dtypes();
+        assert!(dts.contains(&F32));
+        assert!(!dts.contains(&U32));
+    }
+
+    #[test]
+    fn test_pos_dtype() {
+        use super::Operator;
+        use crate::{opencl::ClDevice, LaunchErrorCategory, Operator as _};
+        use clrt::Platform;
+        use digit_layout::types::{U16, U8};
+
+        const NT: usize = 3;
+        let (nh, dh) = (2, 16);
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                let context = device.context();
+                let queue = context.queue();
+                let mut op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+                let mut t_svm = context.malloc::<f32>(NT * nh * dh);
+                let p_svm = context.malloc::<u32>(NT);
+
+                for dt_p in [U8, U16] {
+                    // 规划时即拒绝，而不是在编译程序时崩溃
+                    assert!(op.plan(&dyn_args(F32, dt_p), 0).is_err());
+                    let e = op
+                        .launch_on(
+                            &args(
+                                F32,
+                                dt_p,
+                                NT,
+                                nh,
+                                dh,
+                                1e4,
+                                t_svm.as_mut_ptr().cast(),
+                                p_svm.as_ptr().cast(),
+                            ),
+                            &queue,
+                        )
+                        .unwrap_err();
+                    assert_eq!(e.category(), LaunchErrorCategory::Validation);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_theta_groups() {
+        use super::{super::common_cpu::Operator as RefOp, Operator};
+        use crate::{
+            common_cpu::{Cpu, ThisThread},
+            opencl::{write_from_slice, ClDevice},
+            rope::ThetaGroup,
+            Operator as _,
+        };
+        use clrt::Platform;
+        use std::iter::zip;
+
+        const NT: usize = 5;
+        let (nh, dh) = (6, 32);
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f64 * 0.01).sin())
+            .collect::<Vec<_>>();
+        let p: [u32; NT] = [0, 3, 9, 1, 4];
+        let groups = vec![
+            ThetaGroup {
+                heads: 0..4,
+                theta: 1e4,
+            },
+            ThetaGroup {
+                heads: 4..6,
+                theta: 5e5,
+            },
+        ];
+
+        let mut t_ref = t.clone();
+        let mut ref_args = args(
+            F64,
+            U32,
+            NT,
+            nh,
+            dh,
+            0.,
+            t_ref.as_mut_ptr().cast(),
+            p.as_ptr().cast(),
+        );
+        ref_args.theta_groups = groups.clone();
+        RefOp::new(&Cpu)
+            .launch(&ref_args, &mut [], &ThisThread)
+            .unwrap();
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                let context = device.context();
+                let queue = context.queue();
+                let cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+
+                let mut t_svm = context.malloc::<f32>(NT * nh * dh);
+                let mut p_svm = context.malloc::<u32>(NT);
+                let t_f32 = t.iter().map(|&x| x as f32).collect::<Vec<_>>();
+                write_from_slice(&mut t_svm, &t_f32, &queue);
+                write_from_slice(&mut p_svm, &p, &queue);
+
+                let mut cl_args = args(
+                    F32,
+                    U32,
+                    NT,
+                    nh,
+                    dh,
+                    0.,
+                    t_svm.as_mut_ptr().cast(),
+                    p_svm.as_ptr().cast(),
+                );
+                cl_args.theta_groups = groups.clone();
+                cl_op.launch_on(&cl_args, &queue).unwrap();
+
+                let map = queue.map(&mut t_svm);
+                let ([], ans, []) = (unsafe { map.align_to::<f32>() }) else {
+                    panic!()
+                };
+                for (a, b) in zip(ans, &t_ref) {
+                    assert!((*a as f64 - b).abs() < 1e-3, "{a} vs {b}");
+                }
+                queue.unmap(map);
+            }
+        }
+    }
+
+    #[test]
+    fn test_program_source() {
+        use super::Operator;
+        use digit_layout::types::{F16, I32};
+
+        let (src, opts) = Operator::program_source(F32, U32).unwrap();
+        assert!(src.contains("#define ROPE rope_f32"));
+        assert!(src.contains("__kernel void ROPE("));
+        assert!(src.contains("#define ROPE_TOKEN rope_token_f32"));
+        assert!(src.contains("#define ROPE_TABLE rope_table_f32"));
+        assert!(!src.contains("#define USE_HALF"));
+        assert_eq!(opts.to_str(), Ok("-cl-std=CL2.0"));
+
+        let (src, _) = Operator::program_source(F16, I32).unwrap();
+        assert!(src.contains("#define ROPE rope_f16"));
+        assert!(src.contains("#define SIGNED_POS"));
+
+        assert!(Operator::program_source(U32, U32).is_err());
+    }
+
+    #[test]
+    fn test_llama3_scaling() {
+        use super::{super::common_cpu::Operator as RefOp, Operator};
+        use crate::{
+            common_cpu::{Cpu, ThisThread},
+            opencl::{write_from_slice, ClDevice},
+            Operator as _,
+        };
+        use clrt::Platform;
+        use std::iter::zip;
+
+        const NT: usize = 5;
+        let (nh, dh) = (2, 128);
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f64 * 0.01).sin())
+            .collect::<Vec<_>>();
+        let p: [u32; NT] = [0, 3, 17, 100, 60];
+        let scaling = RopeScaling::Llama3 {
+            factor: 8.,
+            low_freq_factor: 1.,
+            high_freq_factor: 4.,
+            original_ctx: 8192,
+        };
+
+        let mut t_ref = t.clone();
+        let mut ref_args = args(
+            F64,
+            U32,
+            NT,
+            nh,
+            dh,
+            5e5,
+            t_ref.as_mut_ptr().cast(),
+            p.as_ptr().cast(),
+        );
+        ref_args.scaling = scaling;
+        RefOp::new(&Cpu)
+            .launch(&ref_args, &mut [], &ThisThread)
+            .unwrap();
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                let context = device.context();
+                let queue = context.queue();
+                let cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+
+                let mut t_svm = context.malloc::<f32>(NT * nh * dh);
+                let mut p_svm = context.malloc::<u32>(NT);
+                let t_f32 = t.iter().map(|&x| x as f32).collect::<Vec<_>>();
+                write_from_slice(&mut t_svm, &t_f32, &queue);
+                write_from_slice(&mut p_svm, &p, &queue);
+
+                let mut cl_args = args(
+                    F32,
+                    U32,
+                    NT,
+                    nh,
+                    dh,
+                    5e5,
+                    t_svm.as_mut_ptr().cast(),
+                    p_svm.as_ptr().cast(),
+                );
+                cl_args.scaling = scaling;
+                cl_op.launch_on(&cl_args, &queue).unwrap();
+
+                let map = queue.map(&mut t_svm);
+                let ([], ans, []) = (unsafe { map.align_to::<f32>() }) else {
+                    panic!()
+                };
+                for (a, b) in zip(ans, &t_ref) {
+                    assert!((*a as f64 - b).abs() < 1e-3, "{a} vs {b}");
+                }
+                queue.unmap(map);
+            }
+        }
+    }
+
+    #[test]
+    fn test_shrink_work_group() {
+        use super::{super::common_cpu::Operator as RefOp, Operator};
+        use crate::{
+            common_cpu::{Cpu, ThisThread},
+            opencl::{write_from_slice, ClDevice},
+            Operator as _,
+        };
+        use clrt::Platform;
+        use std::iter::zip;
+
+        const NT: usize = 3;
+        let (nh, dh) = (64, 128);
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f64 * 0.01).cos())
+            .collect::<Vec<_>>();
+        let p: [u32; NT] = [0, 9, 4];
+
+        let mut t_ref = t.clone();
+        RefOp::new(&Cpu)
+            .launch(
+                &args(
+                    F64,
+                    U32,
+                    NT,
+                    nh,
+                    dh,
+                    1e4,
+                    t_ref.as_mut_ptr().cast(),
+                    p.as_ptr().cast(),
+                ),
+                &mut [],
+                &ThisThread,
+            )
+            .unwrap();
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let mut cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+                // 假装设备允许所有头放进一个工作组，迫使发射前按核函数上限缩小工作组
+                cl_op.max_group_size = nh * dh / 2;
+
+                let mut t_svm = context.malloc::<f32>(NT * nh * dh);
+                let mut p_svm = context.malloc::<u32>(NT);
+                let t_f32 = t.iter().map(|&x| x as f32).collect::<Vec<_>>();
+                write_from_slice(&mut t_svm, &t_f32, &queue);
+                write_from_slice(&mut p_svm, &p, &queue);
+
+                let cl_args = args(
+                    F32,
+                    U32,
+                    NT,
+                    nh,
+                    dh,
+                    1e4,
+                    t_svm.as_mut_ptr().cast(),
+                    p_svm.as_ptr().cast(),
+                );
+                cl_op.launch_on(&cl_args, &queue).unwrap();
+
+                let map = queue.map(&mut t_svm);
+                let ([], ans, []) = (unsafe { map.align_to::<f32>() }) else {
+                    panic!()
+                };
+                for (a, b) in zip(ans, &t_ref) {
+                    assert!((*a as f64 - b).abs() < 1e-3, "{a} vs {b}");
+                }
+                queue.unmap(map);
+            }
+        }
+    }
+
+    #[test]
+    fn test_single_token() {
+        use super::Operator;
+        use crate::opencl::{read_to_vec, write_from_slice, ClDevice};
+        use clrt::Platform;
+        use std::iter::zip;
+
+        const NT: usize = 4;
+        let (nh, dh) = (5, 64);
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f32 * 0.01).cos())
+            .collect::<Vec<_>>();
+        let p: [u32; NT] = [0, 9, 4, 1000];
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+
+                let mut t_svm = context.malloc::<f32>(t.len());
+                let mut p_svm = context.malloc::<u32>(NT);
+                write_from_slice(&mut p_svm, &p, &queue);
+
+                // 批量路径一次旋转所有词元
+                write_from_slice(&mut t_svm, &t, &queue);
+                let batched = args(
+                    F32,
+                    U32,
+                    NT,
+                    nh,
+                    dh,
+                    1e4,
+                    t_svm.as_mut_ptr(),
+                    p_svm.as_ptr(),
+                );
+                cl_op.launch_on(&batched, &queue).unwrap();
+                let t_batched = read_to_vec::<f32>(&mut t_svm, &queue);
+
+                // 单词元路径逐个旋转，使用相同的位置
+                write_from_slice(&mut t_svm, &t, &queue);
+                for i in 0..NT {
+                    let single = args(
+                        F32,
+                        U32,
+                        1,
+                        nh,
+                        dh,
+                        1e4,
+                        unsafe { t_svm.as_mut_ptr().byte_add(i * nh * dh * size_of::<f32>()) },
+                        unsafe { p_svm.as_ptr().byte_add(i * size_of::<u32>()) },
+                    );
+                    cl_op.launch_on(&single, &queue).unwrap();
+                }
+                let t_single = read_to_vec::<f32>(&mut t_svm, &queue);
+
+                for (a, b) in zip(t_single, t_batched) {
+                    assert!((a - b).abs() < 1e-6, "{a} vs {b}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_build_pos_async() {
+        use super::{super::Seq, Operator};
+        use crate::opencl::read_to_vec;
+        use clrt::Platform;
+        use digit_layout::types::{I32, I64, U64};
+
+        let seqs = [Seq { pos: 3, len: 5 }, Seq { pos: 0, len: 2 }];
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                for dt in [U32, U64, I32, I64] {
+                    let mut sync = Operator::try_build_pos(dt, 10, seqs, &queue).unwrap();
+                    let upload = Operator::build_pos_async(dt, 10, seqs, &queue).unwrap();
+                    let mut async_ = upload.wait();
+                    assert_eq!(
+                        read_to_vec::<u8>(&mut async_, &queue),
+                        read_to_vec::<u8>(&mut sync, &queue),
+                    )
+                }
+                assert!(Operator::build_pos_async(F32, 10, seqs, &queue).is_err())
+            }
+        }
+    }
+
+    #[test]
+    fn test_sink() {
+        use super::{super::Sink, Operator};
+        use crate::opencl::{read_to_vec, write_from_slice, ClDevice};
+        use clrt::Platform;
+        use std::iter::zip;
+
+        const NT: usize = 6;
+        const SINK: usize = 2;
+        let (nh, dh) = (4, 64);
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f32 * 0.03).sin())
+            .collect::<Vec<_>>();
+        let p: [u32; NT] = [50, 51, 52, 53, 54, 55];
+        // 汇聚词元的位置替换为固定位置，作为参考
+        let mut p_ref = p;
+        p_ref[..SINK].fill(3);
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+
+                let mut t_svm = context.malloc::<f32>(t.len());
+                let mut p_svm = context.malloc::<u32>(NT);
+                let mut p_ref_svm = context.malloc::<u32>(NT);
+                write_from_slice(&mut p_svm, &p, &queue);
+                write_from_slice(&mut p_ref_svm, &p_ref, &queue);
+
+                // 批量路径和单词元路径
+                for nt in [NT, 1] {
+                    write_from_slice(&mut t_svm, &t, &queue);
+                    let mut sink = args(
+                        F32,
+                        U32,
+                        nt,
+                        nh,
+                        dh,
+                        1e4,
+                        t_svm.as_mut_ptr(),
+                        p_svm.as_ptr(),
+                    );
+                    sink.sink = Sink {
+                        tokens: SINK,
+                        pos: 3,
+                    };
+                    cl_op.launch_on(&sink, &queue).unwrap();
+                    let t_sink = read_to_vec::<f32>(&mut t_svm, &queue);
+
+                    write_from_slice(&mut t_svm, &t, &queue);
+                    let reference = args(
+                        F32,
+                        U32,
+                        nt,
+                        nh,
+                        dh,
+                        1e4,
+                        t_svm.as_mut_ptr(),
+                        p_ref_svm.as_ptr(),
+                    );
+                    cl_op.launch_on(&reference, &queue).unwrap();
+                    let t_ref = read_to_vec::<f32>(&mut t_svm, &queue);
+
+                    for (a, b) in zip(t_sink, t_ref) {
+                        assert!((a - b).abs() < 1e-6, "{a} vs {b}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_strided_table() {
+        use super::{super::common_cpu::Operator as RefOp, Operator};
+        use crate::{
+            common_cpu::{Cpu, ThisThread},
+            opencl::{read_to_vec, write_from_slice, ClDevice},
+            Operator as _,
+        };
+        use clrt::Platform;
+        use std::iter::zip;
+
+        const NT: usize = 5;
+        const NCTX: usize = 64;
+        let (nh, dh) = (4, 32);
+        // sin 和 cos 共用一块缓冲，每行依次存放 sin、cos 和填充
+        let row = 2 * dh + 4;
+        let mut table = vec![0f32; NCTX * row];
+        for (pos, row) in table.chunks_exact_mut(row).enumerate() {
+            for k in 0..dh {
+                let angle = pos as f32 / 1e4f32.powf((k / 2 * 2) as f32 / dh as f32);
+                row[k] = angle.sin();
+                row[dh + k] = angle.cos();
+            }
+        }
+        let table_layout =
+            |dt| TensorLayout::new(dt, &[NCTX, dh], &[(row * size_of::<f32>()) as _, 4]);
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f32 * 0.07).cos())
+            .collect::<Vec<_>>();
+        let p: [u32; NT] = [0, 7, 3, 63, 20];
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+
+                let mut t_svm = context.malloc::<f32>(t.len());
+                let mut p_svm = context.malloc::<u32>(NT);
+                let mut table_svm = context.malloc::<f32>(table.len());
+                write_from_slice(&mut p_svm, &p, &queue);
+                write_from_slice(&mut table_svm, &table, &queue);
+
+                // 批量路径和单词元路径
+                for nt in [NT, 1] {
+                    let mut t_ref = t[..nt * nh * dh].to_vec();
+                    let mut ref_args = args::<Cpu>(
+                        F32,
+                        U32,
+                        nt,
+                        nh,
+                        dh,
+                        1e4,
+                        t_ref.as_mut_ptr().cast(),
+                        p.as_ptr().cast(),
+                    );
+                    ref_args.sin_layout = table_layout(F32);
+                    ref_args.sin_base = table.as_ptr().cast();
+                    ref_args.cos_layout = table_layout(F32);
+                    ref_args.cos_base = table[dh..].as_ptr().cast();
+                    RefOp::new(&Cpu)
+                        .launch(&ref_args, &mut [], &ThisThread)
+                        .unwrap();
+
+                    write_from_slice(&mut t_svm, &t, &queue);
+                    let mut cl_args = args(
+                        F32,
+                        U32,
+                        nt,
+                        nh,
+                        dh,
+                        1e4,
+                        t_svm.as_mut_ptr(),
+                        p_svm.as_ptr(),
+                    );
+                    cl_args.sin_layout = table_layout(F32);
+                    cl_args.sin_base = table_svm.as_ptr();
+                    cl_args.cos_layout = table_layout(F32);
+                    cl_args.cos_base = unsafe { table_svm.as_ptr().add(dh * size_of::<f32>()) };
+                    cl_op.launch_on(&cl_args, &queue).unwrap();
+
+                    let ans = read_to_vec::<f32>(&mut t_svm, &queue);
+                    for (a, b) in zip(&ans, &t_ref) {
+                        assert!((a - b).abs() < 1e-5, "{a} vs {b}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_inverse() {
+        use super::{super::Rope, Operator};
+        use crate::{
+            common_cpu::ThisThread,
+            opencl::{read_to_vec, write_from_slice, ClDevice},
+        };
+        use clrt::Platform;
+
+        const NT: usize = 5;
+        const NCTX: usize = 64;
+        let (nh, dh) = (4, 64);
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f32 * 0.11).sin())
+            .collect::<Vec<_>>();
+        let p: [u32; NT] = [0, 9, 2, 63, 30];
+        let table = super::super::common_cpu::Operator::build_sincos(F32, NCTX, dh, &ThisThread);
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+
+                let mut t_svm = context.malloc::<f32>(t.len());
+                let mut p_svm = context.malloc::<u32>(NT);
+                let mut table_svm = context.malloc::<u8>(table.mem.len());
+                write_from_slice(&mut p_svm, &p, &queue);
+                write_from_slice(&mut table_svm, &table.mem, &queue);
+                let cl_table = super::SinCosTable {
+                    nctx: NCTX,
+                    theta: table.theta,
+                    mem: &*table_svm,
+                };
+
+                // 批量、单词元和查表三条路径，正向再反向旋转后恢复原值
+                for (nt, use_table) in [(NT, false), (1, false), (NT, true)] {
+                    write_from_slice(&mut t_svm, &t, &queue);
+                    let builder = || {
+                        let builder = Args::<ClDevice>::builder(
+                            TensorLayout::new_contiguous(F32, &[nt, nh, dh]),
+                            t_svm.as_ptr().cast_mut(),
+                            TensorLayout::new_contiguous(U32, &[nt]),
+                            p_svm.as_ptr(),
+                            1e4,
+                        );
+                        if use_table {
+                            builder.table(&cl_table, F32).unwrap()
+                        } else {
+                            builder
+                        }
+                    };
+                    cl_op.launch_on(&builder().build(), &queue).unwrap();
+                    cl_op
+                        .launch_on(&builder().inverse().build(), &queue)
+                        .unwrap();
+
+                    let ans = read_to_vec::<f32>(&mut t_svm, &queue);
+                    for (a, b) in ans.iter().zip(&t).take(nt * nh * dh) {
+                        assert!((a - b).abs() < 1e-4, "{a} vs {b}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_complex_interleaved() {
+        use super::Operator;
+        use crate::opencl::{read_to_vec, write_from_slice, ClDevice};
+        use clrt::Platform;
+
+        const NT: usize = 5;
+        let (nh, dh) = (4, 64);
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f32 * 0.07).cos())
+            .collect::<Vec<_>>();
+        let p: [u32; NT] = [0, 3, 8, 21, 40];
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+
+                let mut t_complex = context.malloc::<f32>(t.len());
+                let mut t_real = context.malloc::<f32>(t.len());
+                let mut p_svm = context.malloc::<u32>(NT);
+                write_from_slice(&mut t_complex, &t, &queue);
+                write_from_slice(&mut t_real, &t, &queue);
+                write_from_slice(&mut p_svm, &p, &queue);
+
+                // [nt, nh, dh / 2, 2] 的复数交错存储与 [nt, nh, dh] 的实数对旋转一致
+                let builder = |t_layout, t_base| {
+                    Args::<ClDevice>::builder(
+                        t_layout,
+                        t_base,
+                        TensorLayout::new_contiguous(U32, &[NT]),
+                        p_svm.as_ptr(),
+                        1e4,
+                    )
+                };
+                let args = builder(
+                    TensorLayout::new_contiguous(F32, &[NT, nh, dh / 2, 2]),
+                    t_complex.as_mut_ptr(),
+                )
+                .complex_interleaved()
+                .unwrap()
+                .build();
+                cl_op.launch_on(&args, &queue).unwrap();
+                let args = builder(
+                    TensorLayout::new_contiguous(F32, &[NT, nh, dh]),
+                    t_real.as_mut_ptr(),
+                )
+                .build();
+                cl_op.launch_on(&args, &queue).unwrap();
+
+                let complex = read_to_vec::<f32>(&mut t_complex, &queue);
+                let real = read_to_vec::<f32>(&mut t_real, &queue);
+                assert_eq!(complex, real);
+                assert_ne!(complex, t);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mask() {
+        use super::{super::Rope, Operator};
+        use crate::{
+            common_cpu::ThisThread,
+            opencl::{read_to_vec, write_from_slice, ClDevice},
+        };
+        use clrt::Platform;
+        use digit_layout::types::U8;
+
+        const NT: usize = 5;
+        const NCTX: usize = 64;
+        let (nh, dh) = (4, 64);
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f32 * 0.13).cos())
+            .collect::<Vec<_>>();
+        let p: [u32; NT] = [4, 0, 17, 63, 8];
+        let mask: [u8; NT] = [0, 1, 1, 0, 1];
+        let table = super::super::common_cpu::Operator::build_sincos(F32, NCTX, dh, &ThisThread);
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let cl_op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+
+                let mut t_svm = context.malloc::<f32>(t.len());
+                let mut p_svm = context.malloc::<u32>(NT);
+                let mut mask_svm = context.malloc::<u8>(NT);
+                let mut table_svm = context.malloc::<u8>(table.mem.len());
+                write_from_slice(&mut p_svm, &p, &queue);
+                write_from_slice(&mut mask_svm, &mask, &queue);
+                write_from_slice(&mut table_svm, &table.mem, &queue);
+                let cl_table = super::SinCosTable {
+                    nctx: NCTX,
+                    theta: table.theta,
+                    mem: &*table_svm,
+                };
+
+                // 批量、单词元和查表三条路径，被掩码的词元保持原值，其余与不加掩码时一致
+                for (nt, use_table) in [(NT, false), (1, false), (NT, true)] {
+                    let mut launch = |masked: bool| {
+                        write_from_slice(&mut t_svm, &t, &queue);
+                        let mut builder = Args::<ClDevice>::builder(
+                            TensorLayout::new_contiguous(F32, &[nt, nh, dh]),
+                            t_svm.as_ptr().cast_mut(),
+                            TensorLayout::new_contiguous(U32, &[nt]),
+                            p_svm.as_ptr(),
+                            1e4,
+                        );
+                        if use_table {
+                            builder = builder.table(&cl_table, F32).unwrap()
+                        }
+                        if masked {
+                            builder = builder
+                                .mask(TensorLayout::new_contiguous(U8, &[nt]), mask_svm.as_ptr())
+                        }
+                        cl_op.launch_on(&builder.build(), &queue).unwrap();
+                        read_to_vec::<f32>(&mut t_svm, &queue)
+                    };
+                    let rotated = launch(false);
+                    let ans = launch(true);
+                    for (i, &m) in mask.iter().enumerate().take(nt) {
+                        let range = i * nh * dh..(i + 1) * nh * dh;
+                        let expected = if m == 0 {
+                            &t[range.clone()]
+                        } else {
+                            &rotated[range.clone()]
+                        };
+                        assert_eq!(&ans[range], expected);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_host_freq() {
+        use super::{super::common_cpu::Operator as RefOp, Operator};
+        use crate::{
+            common_cpu::{Cpu, ThisThread},
+            opencl::{read_to_vec, write_from_slice, ClDevice},
+            Operator as _, SchemeCacheSize,
+        };
+        use clrt::Platform;
+
+        const NT: usize = 5;
+        let (nh, dh, theta) = (4, 64, 1e4);
+        let t = (0..NT * nh * dh)
+            .map(|i| (i as f64 * 0.07).sin())
+            .collect::<Vec<_>>();
+        let p: [u32; NT] = [0, 7, 150, 200, 64];
+        let llama3 = RopeScaling::Llama3 {
+            factor: 8.,
+            low_freq_factor: 1.,
+            high_freq_factor: 4.,
+            original_ctx: 256,
+        };
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                println!("device: {}", device.name());
+
+                let context = device.context();
+                let queue = context.queue();
+                let node = ClDevice::new(context.clone(), Default::default());
+                let kernel_op = Operator::new(&node);
+                let mut host_op = Operator::new(&node);
+                host_op.set_host_freq(true);
+                // 频率缓存只有 1 项，每换一种缩放就淘汰前一种
+                let small = ClDevice::new(
+                    context.clone(),
+                    SchemeCacheSize {
+                        medium: 1,
+                        ..Default::default()
+                    },
+                );
+                let mut small_op = Operator::new(&small);
+                small_op.set_host_freq(true);
+
+                let t_f32 = t.iter().map(|&x| x as f32).collect::<Vec<_>>();
+                let mut t_svm = context.malloc::<f32>(t.len());
+                let mut p_svm = context.malloc::<u32>(NT);
+                write_from_slice(&mut p_svm, &p, &queue);
+
+                // 批量和单词元两条路径，不缩放和 Llama 3 缩放，主机频率与核函数内计算的结果一致
+                for (nt, scaling) in [(NT, RopeScaling::None), (NT, llama3), (1, llama3)] {
+                    let mut t_ref = t.clone();
+                    let mut ref_args = args(
+                        F64,
+                        U32,
+                        nt,
+                        nh,
+                        dh,
+                        theta,
+                        t_ref.as_mut_ptr().cast(),
+                        p.as_ptr().cast(),
+                    );
+                    ref_args.scaling = scaling;
+                    RefOp::new(&Cpu)
+                        .launch(&ref_args, &mut [], &ThisThread)
+                        .unwrap();
+
+                    let mut launch = |op: &Operator| {
+                        write_from_slice(&mut t_svm, &t_f32, &queue);
+                        let mut cl_args = args(
+                            F32,
+                            U32,
+                            nt,
+                            nh,
+                            dh,
+                            theta,
+                            t_svm.as_mut_ptr().cast(),
+                            p_svm.as_ptr().cast(),
+                        );
+                        cl_args.scaling = scaling;
+                        op.launch_on(&cl_args, &queue).unwrap();
+                        read_to_vec::<f32>(&mut t_svm, &queue)
+                    };
+                    let in_kernel = launch(&kernel_op);
+                    let on_host = launch(&host_op);
+                    let n = nt * nh * dh;
+                    for ((a, b), c) in on_host.iter().zip(&in_kernel).zip(&t_ref).take(n) {
+                        assert!((a - b).abs() < 1e-3, "{a} vs {b}");
+                        assert!((*a as f64 - c).abs() < 1e-3, "{a} vs {c}");
+                    }
+                    assert_eq!(launch(&small_op), on_host);
+                }
+                // 每种 theta 和缩放只上传一次频率
+                assert_eq!(host_op.freqs.lock().unwrap().len(), 2);
+                assert!(kernel_op.freqs.lock().unwrap().is_empty());
+                // 超出容量的频率被淘汰
+                assert_eq!(small_op.freqs.lock().unwrap().len(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_category() {
+        use super::{Operator, SchemeKey};
+        use crate::{
+            opencl::{ClDevice, KernelCache, CL2_0},
+            LaunchErrorCategory,
+        };
+        use clrt::Platform;
+
+        const NT: usize = 3;
+        let (nh, dh) = (2, 16);
+
+        for platform in Platform::all() {
+            for device in platform.devices() {
+                let context = device.context();
+                let queue = context.queue();
+                let op = Operator::new(&ClDevice::new(context.clone(), Default::default()));
+                let mut t_svm = context.malloc::<f32>(NT * nh * dh * 2);
+                let p_svm = context.malloc::<u32>(NT);
+                let mut args = || {
+                    args(
+                        F32,
+                        U32,
+                        NT,
+                        nh,
+                        dh,
+                        1e4,
+                        t_svm.as_mut_ptr().cast(),
+                        p_svm.as_ptr().cast(),
+                    )
+                };
+
+                // 元素不连续是参数错误，可以换用其他后端
+                let mut strided = args();
+                strided.t_layout =
+                    TensorLayout::new(F32, &[NT, nh, dh], &[(nh * dh * 8) as _, (dh * 8) as _, 8]);
+                let e = op.launch_on(&strided, &queue).unwrap_err();
+                assert_eq!(e.category(), LaunchErrorCategory::Validation);
+
+                // 设备上的程序缺少核函数是运行时错误
+                op.schemes.lock().unwrap().put(
+                    SchemeKey {
+                        dt_t: F32,
+                        dt_p: U32,
+                    },
+                    KernelCache::new(&context, "__kernel void noop() {}", CL2_0),
+                );
+                let e = op.launch_on(&args(), &queue).unwrap_err();
+                assert_eq!(e.category(), LaunchErrorCategory::Runtime);
             }
         }
     }
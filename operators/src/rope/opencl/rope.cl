@@ -9,6 +9,19 @@
 #define Tpos unsigned int
 #endif
 
+// 核函数名由 kernel_name 生成，与数据类型对应
+#ifndef ROPE
+#define ROPE rope_f32
+#endif
+
+#ifndef ROPE_TOKEN
+#define ROPE_TOKEN rope_token_f32
+#endif
+
+#ifndef ROPE_TABLE
+#define ROPE_TABLE rope_table_f32
+#endif
+
 #ifdef USE_HALF
 #define LOAD_DATA(ptr) vload_half2(0, (__global half *) ptr)
 #define STORE_DATA(ptr, val) vstore_half2(val, 0, (__global half *) ptr)
@@ -19,12 +32,65 @@
 
 typedef unsigned int Tidx;
 
-__kernel void rope(
+#define MASKED(it) (use_mask && !mask[(it) * mask_stride])
+
+// Llama 3 的分段频率缩放
+float llama3_freq(float freq, float factor, float low, float high, float ctx) {
+    float wavelen = 2 * M_PI_F / freq;
+    if (wavelen < ctx / high) return freq;
+    if (wavelen > ctx / low) return freq / factor;
+    float smooth = (ctx / wavelen - low) / (high - low);
+    return (1 - smooth) * freq / factor + smooth * freq;
+}
+
+// 旋转第 i 个旋转对，批量和单词元的核函数共用
+// sign 为 -1 时反向旋转，撤销同一位置的正向旋转
+// use_freq 不为 0 时频率已在主机上按 theta 和缩放算好，只需乘以位置
+float2 rotate(float2 data, float pos, Tidx i, Tidx dh, float theta,
+              float factor, float low_freq_factor, float high_freq_factor, float original_ctx,
+              float sign, global float const *freq, Tidx use_freq) {
+    float angle;
+    if (use_freq) {
+        angle = pos * freq[i];
+    } else if (factor > 0) {
+        angle = pos * llama3_freq(pow(theta, -(float) i / (float) dh),
+                                  factor, low_freq_factor, high_freq_factor, original_ctx);
+    } else {
+        angle = pos / pow(theta, (float) i / (float) dh);
+    }
+    angle *= sign;
+    float sin_val = native_sin(angle);
+    float cos_val = native_cos(angle);
+
+    float2 result;
+    result.x = data.x * cos_val - data.y * sin_val;
+    result.y = data.x * sin_val + data.y * cos_val;
+    return result;
+}
+
+__kernel void ROPE(
     __global Tval *t,
     int const stride_token,
     int const stride_head,
     __global Tpos const *pos,
-    float const theta) {
+    float const theta,
+    // factor 为 0 表示不缩放
+    float const factor,
+    float const low_freq_factor,
+    float const high_freq_factor,
+    float const original_ctx,
+    // 开头的 sink_tokens 个词元固定按 sink_pos 旋转
+    Tidx const sink_tokens,
+    float const sink_pos,
+    // 1 为正向旋转，-1 为反向旋转
+    float const sign,
+    // use_mask 不为 0 时，mask[it * mask_stride] 为 0 的词元保持不变
+    global uchar const *mask,
+    int const mask_stride,
+    Tidx const use_mask,
+    // use_freq 不为 0 时，第 i 个旋转对的频率为 freq[i]
+    global float const *freq,
+    Tidx const use_freq) {
 
     Tidx nh_l = get_local_size(0),
          dh = get_local_size(1),
@@ -34,15 +100,219 @@ __kernel void rope(
          ih = ih_h * nh_l + ih_l,
          i = get_local_id(1);
 
+    if (MASKED(it)) return;
+    bool const sink = it < sink_tokens;
+#ifdef SIGNED_POS
+    // 负位置表示填充，保持不旋转
+    if (!sink && pos[it] < 0) return;
+#endif
+
     __global Tval *t2 = t + it * stride_token + ih * stride_head + i;
 
-    float2 data = LOAD_DATA(t2);
-    float angle = (float) (pos[it]) / pow(theta, (float) i / (float) dh);
-    float sin_val = native_sin(angle);
-    float cos_val = native_cos(angle);
+    float2 result = rotate(LOAD_DATA(t2), sink ? sink_pos : (float) (pos[it]), i, dh, theta,
+                           factor, low_freq_factor, high_freq_factor, original_ctx, sign,
+                           freq, use_freq);
+    STORE_DATA(t2, result);
+}
+
+// 单个词元的一维发射，n = nh * dh，全局大小向上对齐到工作组
+__kernel void ROPE_TOKEN(
+    __global Tval *t,
+    int const stride_head,
+    int const dh,
+    int const n,
+    __global Tpos const *pos,
+    float const theta,
+    float const factor,
+    float const low_freq_factor,
+    float const high_freq_factor,
+    float const original_ctx,
+    // 唯一的词元是否为注意力汇聚，是则按 sink_pos 旋转
+    Tidx const sink_tokens,
+    float const sink_pos,
+    float const sign,
+    global uchar const *mask,
+    int const mask_stride,
+    Tidx const use_mask,
+    global float const *freq,
+    Tidx const use_freq) {
+
+    Tidx gid = get_global_id(0);
+    if (gid >= (Tidx) n) return;
+
+    if (MASKED(0)) return;
+    bool const sink = sink_tokens > 0;
+#ifdef SIGNED_POS
+    if (!sink && pos[0] < 0) return;
+#endif
+
+    Tidx ih = gid / dh,
+         i = gid % dh;
+    __global Tval *t2 = t + ih * stride_head + i;
+
+    float2 result = rotate(LOAD_DATA(t2), sink ? sink_pos : (float) (pos[0]), i, dh, theta,
+                           factor, low_freq_factor, high_freq_factor, original_ctx, sign,
+                           freq, use_freq);
+    STORE_DATA(t2, result);
+}
+
+// 从预先计算的 sin/cos 表读取，表为 [nctx, 2 * dh] 的 float，步长以元素计，第 i 个旋转对读第 2i 列
+// 一维发射，n = nt * nh * dh，全局大小向上对齐到工作组
+__kernel void ROPE_TABLE(
+    __global Tval *t,
+    int const stride_token,
+    int const stride_head,
+    int const nh,
+    int const dh,
+    int const n,
+    __global Tpos const *pos,
+    __global float const *sin_table,
+    int const sin_stride_row,
+    int const sin_stride_col,
+    __global float const *cos_table,
+    int const cos_stride_row,
+    int const cos_stride_col,
+    // 开头的 sink_tokens 个词元固定读取第 sink_pos 行
+    Tidx const sink_tokens,
+    Tidx const sink_pos,
+    float const sign,
+    global uchar const *mask,
+    int const mask_stride,
+    Tidx const use_mask) {
+
+    Tidx gid = get_global_id(0);
+    if (gid >= (Tidx) n) return;
 
+    Tidx it = gid / (nh * dh),
+         ih = gid / dh % nh,
+         i = gid % dh;
+
+    if (MASKED(it)) return;
+    bool const sink = it < sink_tokens;
+#ifdef SIGNED_POS
+    if (!sink && pos[it] < 0) return;
+#endif
+    long row = sink ? sink_pos : (long) pos[it];
+    float sin_val = sign * sin_table[row * sin_stride_row + 2 * i * sin_stride_col];
+    float cos_val = cos_table[row * cos_stride_row + 2 * i * cos_stride_col];
+
+    __global Tval *t2 = t + it * stride_token + ih * stride_head + i;
+    float2 data = LOAD_DATA(t2);
     float2 result;
     result.x = data.x * cos_val - data.y * sin_val;
     result.y = data.x * sin_val + data.y * cos_val;
     STORE_DATA(t2, result);
 }
+
+#ifdef USE_DOUBLE
+#pragma OPENCL EXTENSION cl_khr_fp64 : enable
+
+double llama3_freq_f64(double freq, double factor, double low, double high, double ctx) {
+    double wavelen = 2 * M_PI / freq;
+    if (wavelen < ctx / high) return freq;
+    if (wavelen > ctx / low) return freq / factor;
+    double smooth = (ctx / wavelen - low) / (high - low);
+    return (1 - smooth) * freq / factor + smooth * freq;
+}
+
+double2 rotate_f64(double2 data, double pos, Tidx i, Tidx dh, float theta,
+                   double factor, double low_freq_factor, double high_freq_factor, double original_ctx,
+                   float sign) {
+    double angle = pos / pow((double) theta, (double) i / (double) dh);
+    if (factor > 0) {
+        double freq = llama3_freq_f64(pow((double) theta, -(double) i / (double) dh),
+                                      factor, low_freq_factor, high_freq_factor, original_ctx);
+        angle = pos * freq;
+    }
+    angle *= sign;
+    double sin_val = sin(angle);
+    double cos_val = cos(angle);
+
+    double2 result;
+    result.x = data.x * cos_val - data.y * sin_val;
+    result.y = data.x * sin_val + data.y * cos_val;
+    return result;
+}
+
+__kernel void rope_f64(
+    __global double2 *t,
+    int const stride_token,
+    int const stride_head,
+    __global Tpos const *pos,
+    float const theta,
+    // factor 为 0 表示不缩放
+    float const factor,
+    float const low_freq_factor,
+    float const high_freq_factor,
+    float const original_ctx,
+    // 开头的 sink_tokens 个词元固定按 sink_pos 旋转
+    Tidx const sink_tokens,
+    float const sink_pos,
+    // 1 为正向旋转，-1 为反向旋转
+    float const sign,
+    global uchar const *mask,
+    int const mask_stride,
+    Tidx const use_mask,
+    // 双精度总是在核函数内计算频率，不读取 freq
+    global float const *freq,
+    Tidx const use_freq) {
+
+    Tidx nh_l = get_local_size(0),
+         dh = get_local_size(1),
+         it = get_group_id(0),
+         ih_h = get_group_id(1),
+         ih_l = get_local_id(0),
+         ih = ih_h * nh_l + ih_l,
+         i = get_local_id(1);
+
+    if (MASKED(it)) return;
+    bool const sink = it < sink_tokens;
+#ifdef SIGNED_POS
+    // 负位置表示填充，保持不旋转
+    if (!sink && pos[it] < 0) return;
+#endif
+
+    __global double2 *t2 = t + it * stride_token + ih * stride_head + i;
+
+    *t2 = rotate_f64(*t2, sink ? (double) sink_pos : (double) (pos[it]), i, dh, theta,
+                     factor, low_freq_factor, high_freq_factor, original_ctx, sign);
+}
+
+__kernel void rope_token_f64(
+    __global double2 *t,
+    int const stride_head,
+    int const dh,
+    int const n,
+    __global Tpos const *pos,
+    float const theta,
+    float const factor,
+    float const low_freq_factor,
+    float const high_freq_factor,
+    float const original_ctx,
+    // 唯一的词元是否为注意力汇聚，是则按 sink_pos 旋转
+    Tidx const sink_tokens,
+    float const sink_pos,
+    float const sign,
+    global uchar const *mask,
+    int const mask_stride,
+    Tidx const use_mask,
+    global float const *freq,
+    Tidx const use_freq) {
+
+    Tidx gid = get_global_id(0);
+    if (gid >= (Tidx) n) return;
+
+    if (MASKED(0)) return;
+    bool const sink = sink_tokens > 0;
+#ifdef SIGNED_POS
+    if (!sink && pos[0] < 0) return;
+#endif
+
+    Tidx ih = gid / dh,
+         i = gid % dh;
+    __global double2 *t2 = t + ih * stride_head + i;
+
+    *t2 = rotate_f64(*t2, sink ? (double) sink_pos : (double) (pos[0]), i, dh, theta,
+                     factor, low_freq_factor, high_freq_factor, original_ctx, sign);
+}
+#endif
@@ -0,0 +1,86 @@
+﻿use crate::{
+    type_not_support,
+    utils::{dim_distinct, rank_error, type_distinct},
+    ConstPtr, Hardware, MaybeDyn, MutPtr, SchemeError, TensorLayout,
+};
+use digit_layout::{types as ty, DigitLayout};
+use std::ptr::{null, null_mut};
+
+/// 将 `src` 的第 `i` 行累加到 `dst` 的第 `idx[i]` 行，`idx` 中可以有重复的索引。
+#[derive(Clone)]
+pub struct Args<H: Hardware> {
+    pub dst_layout: TensorLayout,
+    pub dst_base: MutPtr<H>,
+    pub src_layout: TensorLayout,
+    pub src_base: ConstPtr<H>,
+    pub idx_layout: TensorLayout,
+    pub idx_base: ConstPtr<H>,
+}
+
+impl<H: Hardware> Args<H> {
+    pub fn new_null(
+        dst_layout: TensorLayout,
+        src_layout: TensorLayout,
+        idx_layout: TensorLayout,
+    ) -> Self {
+        Self {
+            dst_layout,
+            dst_base: null_mut(),
+            src_layout,
+            src_base: null(),
+            idx_layout,
+            idx_base: null(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct Meta {
+    pub dt: DigitLayout,
+    pub dt_idx: DigitLayout,
+    pub k: MaybeDyn<usize>,
+    pub m: MaybeDyn<usize>,
+    pub n: MaybeDyn<usize>,
+}
+
+impl<H: Hardware> Args<H> {
+    pub(super) fn meta(&self) -> Result<Meta, SchemeError> {
+        let Self {
+            dst_layout: dst,
+            src_layout: src,
+            idx_layout: idx,
+            ..
+        } = self;
+
+        let dt = type_distinct(&[dst.dt(), src.dt()])?;
+        if !matches!(dt, ty::F16 | ty::F32 | ty::F64) {
+            return Err(type_not_support(format!(
+                "data type {dt} is not supported, must be f16, f32 or f64"
+            )));
+        }
+        let dt_idx = idx.dt();
+        if !matches!(dt_idx, ty::U32 | ty::U64) {
+            return Err(type_not_support(format!(
+                "index type {dt_idx} is not supported, must be u32 or u64"
+            )));
+        }
+
+        let &[k, n] = dst.shape() else {
+            return Err(rank_error("dst", 2, dst.ndim()));
+        };
+        let &[m, n_] = src.shape() else {
+            return Err(rank_error("src", 2, src.ndim()));
+        };
+        let &[m_] = idx.shape() else {
+            return Err(rank_error("idx", 1, idx.ndim()));
+        };
+
+        Ok(Meta {
+            dt,
+            dt_idx,
+            k,
+            m: dim_distinct(&[m, m_])?,
+            n: dim_distinct(&[n, n_])?,
+        })
+    }
+}
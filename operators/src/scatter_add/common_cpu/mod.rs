@@ -0,0 +1,341 @@
+use super::{args::Meta, Args, ScatterAdd};
+use crate::{
+    args_not_support, common_cpu::Cpu, get_static, ByteOf, LaunchError, QueueAlloc, SchemeError,
+    Unsigned,
+};
+use digit_layout::types as ty;
+use half::f16;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::{
+    ops::Add,
+    sync::atomic::{AtomicU16, AtomicU32, AtomicU64, Ordering::Relaxed},
+};
+
+pub struct Operator {
+    deterministic: bool,
+}
+
+impl ScatterAdd<Cpu> for Operator {}
+
+impl crate::Operator for Operator {
+    type Hardware = Cpu;
+    type TopoNode = Cpu;
+    type Args = Args<Cpu>;
+
+    fn new(_node: &Self::TopoNode) -> Self {
+        Self {
+            deterministic: false,
+        }
+    }
+
+    fn scheme(
+        &mut self,
+        args: &Self::Args,
+        _max_workspace_size: usize,
+    ) -> Result<usize, SchemeError> {
+        let _meta = args.meta()?;
+        Ok(0)
+    }
+
+    fn launch<QA>(
+        &self,
+        args: &Self::Args,
+        _workspace: &mut [ByteOf<Self::Hardware>],
+        _queue_alloc: &QA,
+    ) -> Result<(), LaunchError>
+    where
+        QA: QueueAlloc<Hardware = Self::Hardware>,
+    {
+        let Meta {
+            dt,
+            dt_idx,
+            k,
+            m,
+            n,
+        } = args.meta()?;
+        let Args {
+            dst_layout,
+            dst_base,
+            src_layout,
+            src_base,
+            idx_layout,
+            idx_base,
+        } = args;
+
+        let &[ksd, nsd] = dst_layout.strides() else {
+            unreachable!()
+        };
+        let &[mss, nss] = src_layout.strides() else {
+            unreachable!()
+        };
+        let &[msi] = idx_layout.strides() else {
+            unreachable!()
+        };
+
+        get_static! {
+            k   m   n
+            ksd nsd
+            mss nss
+            msi
+        }
+
+        let dst = *dst_base as usize;
+        let src = *src_base as usize;
+        let idx = *idx_base as usize;
+
+        macro_rules! calculate {
+            ($t:ty, $i:ty) => {{
+                let scheme = Scheme::<$t, $i> {
+                    dst: dst as _,
+                    src: src as _,
+                    idx: idx as _,
+                    k,
+                    m,
+                    n,
+                    ksd,
+                    nsd,
+                    mss,
+                    nss,
+                    msi,
+                };
+                scheme.check_idx()?;
+                if self.deterministic {
+                    scheme.calculate_ordered()
+                } else {
+                    scheme.calculate_atomic()
+                }
+            }};
+        }
+
+        match (dt, dt_idx) {
+            (ty::F16, ty::U32) => calculate!(f16, u32),
+            (ty::F32, ty::U32) => calculate!(f32, u32),
+            (ty::F64, ty::U32) => calculate!(f64, u32),
+            (ty::F16, ty::U64) => calculate!(f16, u64),
+            (ty::F32, ty::U64) => calculate!(f32, u64),
+            (ty::F64, ty::U64) => calculate!(f64, u64),
+            (_, _) => unreachable!(),
+        }
+        Ok(())
+    }
+}
+
+impl Operator {
+    /// 设置是否使用确定性的累加顺序，默认为 `false`。
+    ///
+    /// 默认实现以原子加并行累加，重复索引的累加顺序随调度变化，浮点结果可能逐次不同；
+    /// 确定性模式按目标行分组，每行按 `idx` 中的出现顺序依次累加，结果可复现但并行度较低。
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic
+    }
+}
+
+/// 以比较交换实现的浮点原子加。
+trait AtomicAdd: Add<Output = Self> + Copy {
+    unsafe fn atomic_add(ptr: *mut Self, val: Self);
+}
+
+macro_rules! impl_atomic_add {
+    ($( $t:ty => $atomic:ty )+) => {
+        $(
+            impl AtomicAdd for $t {
+                #[inline]
+                unsafe fn atomic_add(ptr: *mut Self, val: Self) {
+                    let atomic = unsafe { <$atomic>::from_ptr(ptr.cast()) };
+                    let _ = atomic.fetch_update(Relaxed, Relaxed, |bits| {
+                        Some((<$t>::from_bits(bits) + val).to_bits())
+                    });
+                }
+            }
+        )+
+    };
+}
+
+impl_atomic_add! {
+    f16 => AtomicU16
+    f32 => AtomicU32
+    f64 => AtomicU64
+}
+
+struct Scheme<T, I> {
+    dst: *mut T,
+    src: *const T,
+    idx: *const I,
+    k: usize,
+    m: usize,
+    n: usize,
+    ksd: isize,
+    nsd: isize,
+    mss: isize,
+    nss: isize,
+    msi: isize,
+}
+
+unsafe impl<T, I> Send for Scheme<T, I> {}
+unsafe impl<T, I> Sync for Scheme<T, I> {}
+
+impl<T, I> Scheme<T, I>
+where
+    T: AtomicAdd,
+    I: Unsigned + Copy,
+{
+    fn idx(&self, i: usize) -> usize {
+        unsafe { *self.idx.byte_offset(i as isize * self.msi) }.val()
+    }
+
+    /// 检查全部索引在 `[0, k)` 内，返回第一个越界的索引。
+    fn check_idx(&self) -> Result<(), LaunchError> {
+        for i in 0..self.m {
+            let idx = self.idx(i);
+            if idx >= self.k {
+                Err(args_not_support(format!(
+                    "scatter_add: idx[{i}] = {idx} out of range [0, {})",
+                    self.k
+                )))?
+            }
+        }
+        Ok(())
+    }
+
+    /// 将 `src` 的第 `i` 行累加到 `dst` 的第 `j` 行，`atomic` 决定是否使用原子加。
+    fn add_row(&self, j: usize, i: usize, atomic: bool) {
+        let dst = unsafe { self.dst.byte_offset(j as isize * self.ksd) };
+        let src = unsafe { self.src.byte_offset(i as isize * self.mss) };
+        for c in 0..self.n as isize {
+            unsafe {
+                let dst = dst.byte_offset(c * self.nsd);
+                let val = *src.byte_offset(c * self.nss);
+                if atomic {
+                    T::atomic_add(dst, val)
+                } else {
+                    *dst = *dst + val
+                }
+            }
+        }
+    }
+
+    fn calculate_atomic(&self) {
+        (0..self.m)
+            .into_par_iter()
+            .for_each(|i| self.add_row(self.idx(i), i, true))
+    }
+
+    fn calculate_ordered(&self) {
+        let mut rows = vec![Vec::new(); self.k];
+        for i in 0..self.m {
+            rows[self.idx(i)].push(i)
+        }
+        rows.into_par_iter().enumerate().for_each(|(j, rows)| {
+            for i in rows {
+                self.add_row(j, i, false)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Args, Operator};
+    use crate::{
+        common_cpu::{Cpu, ThisThread},
+        LaunchError, LaunchErrorKind, Operator as _, SchemeErrorKind, TensorLayout,
+    };
+    use digit_layout::types as ty;
+
+    fn try_scatter_add(
+        op: &Operator,
+        dst: &mut [f32],
+        src: &[f32],
+        idx: &[u32],
+        k: usize,
+        n: usize,
+    ) -> Result<(), LaunchError> {
+        let m = idx.len();
+        op.launch(
+            &Args::<Cpu> {
+                dst_layout: TensorLayout::new_contiguous(ty::F32, &[k, n]),
+                dst_base: dst.as_mut_ptr().cast(),
+                src_layout: TensorLayout::new_contiguous(ty::F32, &[m, n]),
+                src_base: src.as_ptr().cast(),
+                idx_layout: TensorLayout::new_contiguous(ty::U32, &[m]),
+                idx_base: idx.as_ptr().cast(),
+            },
+            &mut [],
+            &ThisThread,
+        )
+    }
+
+    fn scatter_add(op: &Operator, src: &[f32], idx: &[u32], k: usize, n: usize) -> Vec<f32> {
+        let mut dst = vec![0.0f32; k * n];
+        try_scatter_add(op, &mut dst, src, idx, k, n).unwrap();
+        dst
+    }
+
+    #[test]
+    fn test_invalid() {
+        let (k, n) = (4, 2);
+        let src = [1.0f32; 6];
+        let mut op = Operator::new(&Cpu);
+
+        // 越界的索引报错且不写入
+        for deterministic in [false, true] {
+            op.set_deterministic(deterministic);
+            let mut dst = vec![0.0f32; k * n];
+            let err = try_scatter_add(&op, &mut dst, &src, &[1, 4, 0], k, n).unwrap_err();
+            assert_eq!(
+                err.kind,
+                LaunchErrorKind::Scheme(SchemeErrorKind::ArgsNotSupport)
+            );
+            assert_eq!(err.info, "scatter_add: idx[1] = 4 out of range [0, 4)");
+            assert!(dst.iter().all(|&x| x == 0.));
+        }
+
+        // 不支持的数据类型和索引类型在 scheme 阶段报错
+        for (dt, dt_idx) in [(ty::F32, ty::U16), (ty::U32, ty::U32), (ty::BF16, ty::U64)] {
+            let args = Args::<Cpu>::new_null(
+                TensorLayout::new_contiguous(dt, &[k, n]),
+                TensorLayout::new_contiguous(dt, &[3, n]),
+                TensorLayout::new_contiguous(dt_idx, &[3]),
+            );
+            assert!(op.scheme(&args, 0).is_err());
+        }
+    }
+
+    #[test]
+    fn test_deterministic() {
+        use rand::Rng;
+
+        let (m, n, k) = (4096, 3, 2);
+        let mut rng = rand::rng();
+        // 数量级差异较大的数，累加顺序不同时结果容易不同
+        let src = (0..m * n)
+            .map(|_| rng.random_range(-1.0f32..1.) * 10f32.powi(rng.random_range(-4..4)))
+            .collect::<Vec<_>>();
+        let idx = (0..m)
+            .map(|_| rng.random_range(0..k as u32))
+            .collect::<Vec<_>>();
+
+        // 按 idx 顺序串行累加的参考结果
+        let mut expected = vec![0.0f32; k * n];
+        for (i, &j) in idx.iter().enumerate() {
+            for c in 0..n {
+                expected[j as usize * n + c] += src[i * n + c]
+            }
+        }
+
+        let mut op = Operator::new(&Cpu);
+        let fast = scatter_add(&op, &src, &idx, k, n);
+        for (a, b) in fast.iter().zip(&expected) {
+            assert!((a - b).abs() <= 1e-3 * b.abs().max(1.));
+        }
+
+        op.set_deterministic(true);
+        for _ in 0..8 {
+            let ans = scatter_add(&op, &src, &idx, k, n);
+            assert!(ans
+                .iter()
+                .zip(&expected)
+                .all(|(a, b)| a.to_bits() == b.to_bits()));
+        }
+    }
+}
@@ -0,0 +1,9 @@
+﻿//! dst[idx[i]] += src[i]
+
+#[cfg(any(use_cpu, test))]
+pub mod common_cpu;
+
+mod args;
+pub use args::Args;
+
+crate::op_trait!(ScatterAdd);
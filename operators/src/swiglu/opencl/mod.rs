@@ -99,7 +99,7 @@ impl crate::Operator for Operator {
             .unwrap()
             .get(&key)
             .unwrap()
-            .take("swiglu")
+            .take_guard("swiglu")
             .unwrap();
 
         swiglu
@@ -114,10 +114,6 @@ impl crate::Operator for Operator {
                 queue_alloc.queue(),
                 None,
             );
-
-        let mut cache = self.schemes.lock().unwrap();
-        let program = cache.get(&key).unwrap();
-        program.put("swiglu", swiglu);
         Ok(())
     }
 }
@@ -0,0 +1,40 @@
+//! 在 `no_std` + `alloc` 下编译重排方案的布局压缩。
+
+#![no_std]
+
+extern crate alloc;
+
+#[path = "../src/rearrange/compact.rs"]
+mod compact;
+
+use alloc::vec::Vec;
+use compact::{compact, Dim};
+
+#[test]
+fn test_transpose() {
+    // [3, 4] 的 f32 张量转置，dst 连续
+    let dims = [(3, 4, 16), (4, 12, 4)]
+        .into_iter()
+        .map(|(len, dst, src)| Dim { len, dst, src })
+        .collect::<Vec<_>>();
+    #[rustfmt::skip]
+    assert_eq!(
+        compact(4, dims),
+        [
+            4,
+            12, 3, 1,
+            12, 4,
+            4, 16,
+        ]
+    );
+}
+
+#[test]
+fn test_contiguous() {
+    // dst 和 src 都连续时全部并入 unit
+    let dims = [(3, 16, 16), (4, 4, 4)]
+        .into_iter()
+        .map(|(len, dst, src)| Dim { len, dst, src })
+        .collect::<Vec<_>>();
+    assert_eq!(compact(4, dims), [48, 1]);
+}